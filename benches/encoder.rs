@@ -0,0 +1,27 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use ruff::{EncodeBuffer, Farbfeld, Pixel};
+
+fn tiles() -> Vec<Farbfeld> {
+    (0..1000_u16)
+        .map(|i| Farbfeld::new(16, 16, vec![Pixel::new(i, i, i, 65535); 256]).unwrap())
+        .collect()
+}
+
+fn bench_encode_tiles_reuse(c: &mut Criterion) {
+    let tiles = tiles();
+    let mut buffer = EncodeBuffer::new();
+    c.bench_function("encode_tiles_reuse", |b| b.iter(|| for tile in &tiles {
+        buffer.encode(tile, &mut ::std::io::sink()).unwrap();
+    }));
+}
+
+fn bench_encode_tiles_save(c: &mut Criterion) {
+    let tiles = tiles();
+    c.bench_function("encode_tiles_save", |b| b.iter(|| for tile in &tiles {
+        tile.save(&mut ::std::io::sink()).unwrap();
+    }));
+}
+
+criterion_group!(benches, bench_encode_tiles_reuse, bench_encode_tiles_save);
+criterion_main!(benches);
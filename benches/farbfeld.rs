@@ -0,0 +1,100 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use ruff::{Farbfeld, Pixel};
+use ruff::pixel::Channel;
+
+/// The dimensions of the generated images each benchmark is run against.
+const SIZES: [(u32, u32); 3] = [(64, 64), (512, 512), (1920, 1080)];
+
+/// Returns a noisy image with channels from a xorshift generator, so every run uses the same
+/// pixels.
+fn image(width: u32, height: u32) -> Farbfeld {
+    let mut state = 0x2545_f491_u32;
+    let pixels = (0..width * height).map(|_| {
+        let mut channels = [0; 4];
+        for channel in channels.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *channel = (state >> 16) as u16;
+        }
+        Pixel::from(channels)
+    }).collect();
+    Farbfeld::new(width, height, pixels).unwrap()
+}
+
+fn bench_from_file(c: &mut Criterion) {
+    c.bench_function("from_file", |b| b.iter(|| Farbfeld::from_file("test.ff").unwrap()));
+}
+
+fn bench_from_bytes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("from_bytes");
+    for &(width, height) in SIZES.iter() {
+        let bytes = image(width, height).to_bytes();
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{}x{}", width, height)), &bytes,
+                               |b, bytes| b.iter(|| Farbfeld::from_bytes(bytes).unwrap()));
+    }
+    group.finish();
+}
+
+fn bench_save(c: &mut Criterion) {
+    let mut group = c.benchmark_group("save");
+    for &(width, height) in SIZES.iter() {
+        let farb = image(width, height);
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{}x{}", width, height)), &farb,
+                               |b, farb| b.iter(|| farb.save(&mut ::std::io::sink()).unwrap()));
+    }
+    group.finish();
+}
+
+fn bench_save_to_vec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("save_to_vec");
+    for &(width, height) in SIZES.iter() {
+        let farb = image(width, height);
+        let mut bytes = Vec::with_capacity(16 + farb.pixels().len() * 8);
+        group.bench_function(BenchmarkId::from_parameter(format!("{}x{}", width, height)), |b| b.iter(|| {
+            bytes.clear();
+            farb.save_to_vec(&mut bytes);
+        }));
+    }
+    group.finish();
+}
+
+fn bench_to_rgba8_vec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("to_rgba8_vec");
+    for &(width, height) in SIZES.iter() {
+        let farb = image(width, height);
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{}x{}", width, height)), &farb,
+                               |b, farb| b.iter(|| farb.to_rgba8_vec()));
+    }
+    group.finish();
+}
+
+fn bench_box_blur(c: &mut Criterion) {
+    let farb = image(1920, 1080);
+    let mut group = c.benchmark_group("box_blur");
+    group.sample_size(10);
+    for radius in [1, 16, 256].iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(radius), radius, |b, &radius| b.iter(|| farb.box_blur(radius)));
+    }
+    group.finish();
+}
+
+/// Compares the per-channel filters with the same filter written directly over the flat channel
+/// slice.
+fn bench_invert_channel(c: &mut Criterion) {
+    let mut farb = image(1024, 1024);
+    let mut group = c.benchmark_group("invert_channel");
+    group.bench_function("invert_channel_in_place", |b| b.iter(|| farb.invert_channel_in_place(Channel::Green)));
+    group.bench_function("map_channel", |b| b.iter(|| farb.map_channel(Channel::Green, |green| u16::MAX - green)));
+    group.bench_function("as_channels_mut", |b| b.iter(|| {
+        for green in farb.as_channels_mut().iter_mut().skip(1).step_by(4) {
+            *green = u16::MAX - *green;
+        }
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, bench_from_file, bench_from_bytes, bench_save, bench_save_to_vec, bench_to_rgba8_vec, bench_box_blur,
+                 bench_invert_channel);
+criterion_main!(benches);
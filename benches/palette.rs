@@ -0,0 +1,35 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use ruff::Pixel;
+use ruff::palette::PaletteIndex;
+
+/// Returns pseudo-random pixels from a xorshift generator.
+fn pixels(count: usize, mut state: u64) -> Vec<Pixel> {
+    (0..count).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        Pixel::new(state as u16, (state >> 16) as u16, (state >> 32) as u16, 65535)
+    }).collect()
+}
+
+/// Returns the index of the palette entry nearest the pixel by checking every entry.
+fn linear_scan(palette: &[Pixel], pixel: &Pixel) -> usize {
+    let distance = |entry: &Pixel| entry.iter().zip(pixel.iter()).take(3)
+        .map(|(&a, &b)| (a.abs_diff(b) as u64).pow(2))
+        .sum::<u64>();
+    (0..palette.len()).min_by_key(|&i| distance(&palette[i])).unwrap()
+}
+
+fn bench_nearest_256(c: &mut Criterion) {
+    let palette = pixels(256, 0x2545_f491_4f6c_dd1d);
+    let targets = pixels(10000, 7);
+    let index = PaletteIndex::new(&palette).unwrap();
+    let mut group = c.benchmark_group("nearest_256");
+    group.bench_function("palette_index", |b| b.iter(|| targets.iter().map(|&target| index.nearest(target).0).sum::<usize>()));
+    group.bench_function("linear_scan", |b| b.iter(|| targets.iter().map(|target| linear_scan(&palette, target)).sum::<usize>()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_nearest_256);
+criterion_main!(benches);
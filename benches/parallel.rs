@@ -0,0 +1,45 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use ruff::{Farbfeld, Pixel};
+use ruff::patterns::{linear_gradient, GradientDirection};
+
+/// The dimensions of the generated images each benchmark is run against, up to 8K.
+const SIZES: [(u32, u32); 3] = [(512, 512), (1920, 1080), (7680, 4320)];
+
+fn image(width: u32, height: u32) -> Farbfeld {
+    linear_gradient(width, height, Pixel::new(0_u16, 0, 0, 65535), Pixel::new(65535_u16, 40000, 20000, 65535),
+                    GradientDirection::Diagonal)
+}
+
+/// A colour transform with enough work per pixel to be limited by the processor.
+fn transform(pixel: &Pixel) -> Pixel {
+    let gamma = |value: u16| ((value as f32 / 65535.0).powf(1.0 / 2.2) * 65535.0 + 0.5) as u16;
+    Pixel::new(gamma(*pixel.red()), gamma(*pixel.green()), gamma(*pixel.blue()), *pixel.alpha())
+}
+
+fn bench_map_pixels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("map_pixels");
+    group.sample_size(10);
+    for &(width, height) in SIZES.iter() {
+        let farb = image(width, height);
+        let size = format!("{}x{}", width, height);
+        group.bench_with_input(BenchmarkId::new("serial", &size), &farb, |b, farb| b.iter(|| farb.map_pixels(transform)));
+        group.bench_with_input(BenchmarkId::new("parallel", &size), &farb, |b, farb| b.iter(|| farb.par_map_pixels(transform)));
+    }
+    group.finish();
+}
+
+fn bench_for_each_pixel_mut(c: &mut Criterion) {
+    let mut group = c.benchmark_group("for_each_pixel_mut");
+    group.sample_size(10);
+    for &(width, height) in SIZES.iter() {
+        let mut farb = image(width, height);
+        let size = format!("{}x{}", width, height);
+        group.bench_function(BenchmarkId::new("serial", &size), |b| b.iter(|| farb.for_each_pixel_mut(|pixel| *pixel = transform(pixel))));
+        group.bench_function(BenchmarkId::new("parallel", &size), |b| b.iter(|| farb.par_for_each_pixel_mut(|pixel| *pixel = transform(pixel))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_map_pixels, bench_for_each_pixel_mut);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use ruff::{Farbfeld, Pixel};
+use ruff::patterns::{linear_gradient, GradientDirection};
+
+/// The sizes of the generated square images each benchmark is run against.
+const SIZES: [u32; 3] = [256, 1024, 4096];
+
+fn image(size: u32) -> Farbfeld {
+    linear_gradient(size, size, Pixel::new(0_u16, 0, 0, 65535), Pixel::new(65535_u16, 40000, 20000, 65535),
+                    GradientDirection::Diagonal)
+}
+
+fn bench_rotate90(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rotate90");
+    group.sample_size(10);
+    for &size in SIZES.iter() {
+        let mut farb = image(size);
+        let size = format!("{}x{}", size, size);
+        group.bench_function(BenchmarkId::new("copy", &size), |b| b.iter(|| farb.rotate90()));
+        group.bench_function(BenchmarkId::new("in_place", &size), |b| b.iter(|| farb.rotate90_in_place().unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_rotate90);
+criterion_main!(benches);
@@ -0,0 +1,4 @@
+//! Builds the C interface of ruff as a shared library, `libruff`, with the functions from
+//! `ruff::ffi`.
+
+pub use ruff::ffi::*;
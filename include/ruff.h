@@ -0,0 +1,63 @@
+#ifndef RUFF_H
+#define RUFF_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of a call to a ruff function.
+typedef enum RuffError {
+  // The call succeeded.
+  RUFF_ERROR_OK = 0,
+  // A required pointer argument was null.
+  RUFF_ERROR_NULL_POINTER = 1,
+  // The path wasn't valid UTF-8.
+  RUFF_ERROR_INVALID_PATH = 2,
+  // Reading or writing failed.
+  RUFF_ERROR_IO = 3,
+  // The file isn't a valid Farbfeld image.
+  RUFF_ERROR_PARSE = 4,
+  // The dimensions don't fit in memory.
+  RUFF_ERROR_INVALID_DIMENSIONS = 5,
+  // ruff panicked. This is a bug in ruff.
+  RUFF_ERROR_PANIC = 6,
+} RuffError;
+
+// Loads the Farbfeld image at the given path, storing its dimensions in width and height and a
+// pointer to its pixels in pixels. The pixels contain width * height * 4 channels, and must be
+// freed with [ruff_free](fn.ruff_free.html). Nothing is stored if an error is returned.
+//
+// # Safety
+// path must be a null terminated string, and width, height and pixels must be valid for writes.
+enum RuffError ruff_load_path(const char *path,
+                              uint32_t *width,
+                              uint32_t *height,
+                              uint16_t **pixels);
+
+// Saves an image with the given dimensions and pixels to the given path. The pixels must contain
+// width * height * 4 channels.
+//
+// # Safety
+// path must be a null terminated string, and pixels must be valid for reads of
+// width * height * 4 channels.
+enum RuffError ruff_save_path(const char *path,
+                              uint32_t width,
+                              uint32_t height,
+                              const uint16_t *pixels);
+
+// Frees the pixels returned by [ruff_load_path](fn.ruff_load_path.html). Does nothing if pixels
+// is null.
+//
+// # Safety
+// pixels must be null or a pointer returned by ruff_load_path which hasn't already been freed.
+void ruff_free(uint16_t *pixels);
+
+// Returns the message of the last error returned by a ruff function on the calling thread, or
+// null if the last call succeeded. The message is valid until the next call to a ruff function
+// on the same thread.
+const char *ruff_last_error_message(void);
+
+#endif  /* RUFF_H */
@@ -0,0 +1,869 @@
+//! Colour adjustments applied to every pixel of a Farbfeld image. The alpha channel is left
+//! untouched unless stated otherwise.
+
+use alloc::vec::Vec;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::{Pixel, Channel, GrayscaleMethod};
+use crate::convolve::AlphaMode;
+use crate::histogram;
+#[cfg(feature = "std")]
+use crate::lut::ChannelLut;
+use crate::error::*;
+
+/// The midpoint of a channel, around which contrast is adjusted.
+const CONTRAST_PIVOT: f32 = 32767.5;
+
+impl Farbfeld {
+    /// Adds `delta` to the red, green and blue channels of every pixel, saturating at 0 and
+    /// 65535.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(100_u16, 65000, 0, 7)]).unwrap();
+    /// farb.adjust_brightness(1000);
+    ///
+    /// assert_eq!(&Pixel::new(1100_u16, 65535, 1000, 7), &farb[0]);
+    /// ```
+    pub fn adjust_brightness(&mut self, delta: i32) {
+        self.map_colors(|channel| (channel as i32 + delta).clamp(0, u16::MAX as i32) as u16);
+    }
+
+    /// Returns a copy of the image with its brightness adjusted as described by
+    /// [adjust_brightness](#method.adjust_brightness).
+    pub fn adjusted_brightness(&self, delta: i32) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.adjust_brightness(delta);
+        farb
+    }
+
+    /// Adds `delta_8` in 8 bit units, where 255 is full brightness, to the red, green and blue
+    /// channels of every pixel. Each unit is 257 16 bit units, as described by
+    /// [adjust_brightness](#method.adjust_brightness).
+    pub fn adjust_brightness_8(&mut self, delta_8: i16) {
+        self.adjust_brightness(delta_8 as i32 * 257);
+    }
+
+    /// Returns a copy of the image with its brightness adjusted as described by
+    /// [adjust_brightness_8](#method.adjust_brightness_8).
+    pub fn adjusted_brightness_8(&self, delta_8: i16) -> Farbfeld {
+        self.adjusted_brightness(delta_8 as i32 * 257)
+    }
+
+    /// Multiplies the red, green and blue channels of every pixel by `factor`, rounding to the
+    /// nearest value and clamping to 0 and 65535. A factor which isn't a number makes every
+    /// channel 0.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(3_u16, 40000, 65535, 7)]).unwrap();
+    /// farb.scale_brightness(0.5);
+    ///
+    /// assert_eq!(&Pixel::new(2_u16, 20000, 32768, 7), &farb[0]);
+    /// ```
+    pub fn scale_brightness(&mut self, factor: f32) {
+        // Casting saturates, mapping negative values and NaN to 0 and large values to 65535.
+        self.map_colors(|channel| (channel as f32 * factor + 0.5) as u16);
+    }
+
+    /// Returns a copy of the image with its brightness scaled as described by
+    /// [scale_brightness](#method.scale_brightness).
+    pub fn scaled_brightness(&self, factor: f32) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.scale_brightness(factor);
+        farb
+    }
+
+    /// Scales the distance of the red, green and blue channels of every pixel from the midpoint,
+    /// 32767.5, by `factor`, rounding to the nearest value and clamping to 0 and 65535. A factor
+    /// of 1 leaves the image unchanged, a factor of 0 makes every pixel mid-grey, and factors
+    /// above 1 increase the contrast.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(10000_u16, 40000, 60000, 7)]).unwrap();
+    /// farb.adjust_contrast(2.0);
+    ///
+    /// assert_eq!(&Pixel::new(0_u16, 47233, 65535, 7), &farb[0]);
+    /// ```
+    pub fn adjust_contrast(&mut self, factor: f32) {
+        // Casting saturates, mapping negative values and NaN to 0 and large values to 65535.
+        self.map_colors(|channel| ((channel as f32 - CONTRAST_PIVOT) * factor + CONTRAST_PIVOT + 0.5) as u16);
+    }
+
+    /// Returns a copy of the image with its contrast adjusted as described by
+    /// [adjust_contrast](#method.adjust_contrast).
+    pub fn adjusted_contrast(&self, factor: f32) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.adjust_contrast(factor);
+        farb
+    }
+
+    /// Adjusts the contrast by a percentage, where 100 leaves the image unchanged, as described by
+    /// [adjust_contrast](#method.adjust_contrast).
+    pub fn adjust_contrast_percent(&mut self, percent: u32) {
+        self.adjust_contrast(percent as f32 / 100.0);
+    }
+
+    /// Applies gamma correction to the red, green and blue channels of every pixel, mapping each
+    /// channel to `(channel / 65535) ^ (1 / gamma) * 65535` rounded to the nearest value. Gammas
+    /// above 1 brighten the image and gammas below 1 darken it. Requires the `std` feature.
+    ///
+    /// Every possible channel value is mapped once up front, so the cost doesn't depend on the
+    /// size of the image beyond a table lookup per channel.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidAdjustment](error/enum.Error.html) if `gamma` isn't a positive
+    /// number.
+    #[cfg(feature = "std")]
+    pub fn adjust_gamma(&mut self, gamma: f32) -> Result<()> {
+        if gamma.is_nan() || gamma <= 0.0 {
+            return Err(Error::InvalidAdjustment("gamma must be positive"));
+        }
+        let exponent = 1.0 / gamma as f64;
+        self.apply_lut_in_place(&ChannelLut::from_colour_fn(|channel|
+            ((channel as f64 / 65535.0).powf(exponent) * 65535.0 + 0.5) as u16));
+        Ok(())
+    }
+
+    /// Returns a copy of the image with gamma correction applied as described by
+    /// [adjust_gamma](#method.adjust_gamma).
+    ///
+    /// # Errors
+    /// Returns the errors of [adjust_gamma](#method.adjust_gamma).
+    #[cfg(feature = "std")]
+    pub fn adjusted_gamma(&self, gamma: f32) -> Result<Farbfeld> {
+        let mut farb = self.clone();
+        farb.adjust_gamma(gamma)?;
+        Ok(farb)
+    }
+
+    /// Linearly maps the red, green and blue channels of every pixel from the input range,
+    /// `input_black` to `input_white`, to the output range, `output_black` to `output_white`,
+    /// rounding to the nearest value. Channels at or below `input_black` become `output_black` and
+    /// channels at or above `input_white` become `output_white`. The output range may be
+    /// reversed to invert the image.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(1000_u16, 2000, 3000, 7)]).unwrap();
+    /// farb.adjust_levels(1000, 3000, 0, 65535).unwrap();
+    ///
+    /// assert_eq!(&Pixel::new(0_u16, 32768, 65535, 7), &farb[0]);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidAdjustment](error/enum.Error.html) if `input_black` isn't
+    /// below `input_white`.
+    pub fn adjust_levels(&mut self, input_black: u16, input_white: u16, output_black: u16, output_white: u16) -> Result<()> {
+        if input_black >= input_white {
+            return Err(Error::InvalidAdjustment("input black point must be below the white point"));
+        }
+        let scale = (output_white as f64 - output_black as f64) / (input_white - input_black) as f64;
+        self.map_colors(|channel| {
+            let channel = channel.clamp(input_black, input_white);
+            // The result is between the output points, so it's never negative.
+            (output_black as f64 + (channel - input_black) as f64 * scale + 0.5) as u16
+        });
+        Ok(())
+    }
+
+    /// Returns a copy of the image with its levels adjusted as described by
+    /// [adjust_levels](#method.adjust_levels).
+    ///
+    /// # Errors
+    /// Returns the errors of [adjust_levels](#method.adjust_levels).
+    pub fn adjusted_levels(&self, input_black: u16, input_white: u16, output_black: u16, output_white: u16) -> Result<Farbfeld> {
+        let mut farb = self.clone();
+        farb.adjust_levels(input_black, input_white, output_black, output_white)?;
+        Ok(farb)
+    }
+
+    /// Stretches the red, green and blue channels to the full range with
+    /// [adjust_levels](#method.adjust_levels), picking the input black point so `percentile`
+    /// percent of the channels are at or below it, and the white point so `percentile` percent
+    /// are at or above it, as found by the image's [histogram](#method.histogram). A percentile of
+    /// 0 stretches the darkest channel to black and the brightest to white. The image is left
+    /// unchanged if the points are equal, such as when every channel has the same value.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidAdjustment](error/enum.Error.html) if `percentile` isn't at
+    /// least 0 and below 50.
+    pub fn auto_levels(&mut self, percentile: f32) -> Result<()> {
+        if percentile.is_nan() || !(0.0..50.0).contains(&percentile) {
+            return Err(Error::InvalidAdjustment("percentile must be at least 0 and below 50"));
+        }
+        let histogram = self.histogram();
+        let counts: Vec<u64> = histogram.channel(Channel::Red).iter()
+            .zip(histogram.channel(Channel::Green))
+            .zip(histogram.channel(Channel::Blue))
+            .map(|((red, green), blue)| red + green + blue)
+            .collect();
+        let total = *histogram.total() * 3;
+        if let (Some(black), Some(white)) = (histogram::percentile_of(&counts, total, percentile),
+                                             histogram::percentile_of(&counts, total, 100.0 - percentile)) {
+            if black < white {
+                self.adjust_levels(black, white, 0, u16::MAX)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a copy of the image with the red, green and blue channels of every pixel reduced to
+    /// `levels` evenly spaced values, where 0 and 65535 are always included. Each channel is
+    /// moved to the nearest of the values `i * 65535 / (levels - 1)`, rounded to the nearest
+    /// integer, so 5 levels give 0, 16384, 32768, 49151 and 65535. 65536 or more levels leave the
+    /// image unchanged.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(1, 1, vec![Pixel::new(20000_u16, 32767, 65535, 7)]).unwrap();
+    ///
+    /// assert_eq!(&Pixel::new(16384_u16, 32768, 65535, 7), &farb.posterize(5).unwrap()[0]);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidAdjustment](error/enum.Error.html) if `levels` is less than 2.
+    pub fn posterize(&self, levels: u32) -> Result<Farbfeld> {
+        self.posterize_with(levels, AlphaMode::Preserve)
+    }
+
+    /// Returns a copy of the image posterized as described by [posterize](#method.posterize),
+    /// with the alpha channel also posterized if `alpha` is
+    /// [AlphaMode::Process](convolve/enum.AlphaMode.html).
+    ///
+    /// # Errors
+    /// Returns the errors of [posterize](#method.posterize).
+    pub fn posterize_with(&self, levels: u32, alpha: AlphaMode) -> Result<Farbfeld> {
+        if levels < 2 {
+            return Err(Error::InvalidAdjustment("posterizing needs at least 2 levels"));
+        }
+        let steps = (levels - 1).min(u16::MAX as u32) as u64;
+        let max = u16::MAX as u64;
+        let mut farb = self.clone();
+        farb.map_channels(|channel, value| {
+            if channel == Channel::Alpha && alpha == AlphaMode::Preserve {
+                return value;
+            }
+            // Both divisions round to the nearest value, with halves rounded up.
+            let step = (value as u64 * steps * 2 + max) / (max * 2);
+            ((step * max * 2 + steps) / (steps * 2)) as u16
+        });
+        Ok(farb)
+    }
+
+    /// Sets the red, green and blue channels of every pixel to its
+    /// [luminance](pixel/struct.Pixel.html#method.luminance).
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(0_u16, 65535, 0, 7)]).unwrap();
+    /// farb.grayscale_in_place();
+    ///
+    /// assert_eq!(&Pixel::new(46870_u16, 46870, 46870, 7), &farb[0]);
+    /// ```
+    pub fn grayscale_in_place(&mut self) {
+        self.grayscale_in_place_with(GrayscaleMethod::Luminance709);
+    }
+
+    /// Returns a copy of the image converted to greyscale as described by
+    /// [grayscale_in_place](#method.grayscale_in_place).
+    pub fn grayscale(&self) -> Farbfeld {
+        self.grayscale_with(GrayscaleMethod::Luminance709)
+    }
+
+    /// Sets the red, green and blue channels of every pixel to its
+    /// [grey value](pixel/struct.Pixel.html#method.grey) found by `method`.
+    pub fn grayscale_in_place_with(&mut self, method: GrayscaleMethod) {
+        for pixel in self[..].iter_mut() {
+            let grey = pixel.grey(method);
+            *pixel = Pixel::new(grey, grey, grey, *pixel.alpha());
+        }
+    }
+
+    /// Returns a copy of the image converted to greyscale as described by
+    /// [grayscale_in_place_with](#method.grayscale_in_place_with).
+    pub fn grayscale_with(&self, method: GrayscaleMethod) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.grayscale_in_place_with(method);
+        farb
+    }
+
+    /// [Inverts](pixel/struct.Pixel.html#method.invert) the red, green and blue channels of
+    /// every pixel.
+    pub fn invert_in_place(&mut self) {
+        for pixel in self[..].iter_mut() {
+            *pixel = pixel.invert();
+        }
+    }
+
+    /// Returns a copy of the image with its colours inverted as described by
+    /// [invert_in_place](#method.invert_in_place).
+    pub fn invert(&self) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.invert_in_place();
+        farb
+    }
+
+    /// Inverts only the given channel of every pixel, which may be alpha, so each value becomes
+    /// 65535 minus itself.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::pixel::Channel;
+    /// let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2, 3, 65535)]).unwrap();
+    /// farb.invert_channel_in_place(Channel::Alpha);
+    ///
+    /// assert_eq!(&Pixel::new(1_u16, 2, 3, 0), &farb[0]);
+    /// ```
+    pub fn invert_channel_in_place(&mut self, channel: Channel) {
+        for pixel in self[..].iter_mut() {
+            let value = pixel.channel_mut(channel);
+            *value = u16::MAX - *value;
+        }
+    }
+
+    /// Returns a copy of the image with one channel inverted as described by
+    /// [invert_channel_in_place](#method.invert_channel_in_place).
+    pub fn invert_channel(&self, channel: Channel) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.invert_channel_in_place(channel);
+        farb
+    }
+
+    /// Spreads the [luminance](pixel/struct.Pixel.html#method.luminance) of the pixels evenly
+    /// across the full range with histogram equalization. Each luminance is mapped to its position
+    /// in the cumulative distribution of luminances, so the darkest pixels become black and the
+    /// brightest white, and the red, green and blue channels are scaled by the ratio of the new
+    /// luminance to the old, saturating at 65535. Black pixels are left unchanged, as is the whole
+    /// image if every pixel has the same luminance.
+    pub fn equalize_in_place(&mut self) {
+        let table = match histogram::equalization_table(&self.luminance_counts()) {
+            Some(table) => table,
+            None => return
+        };
+        for pixel in self[..].iter_mut() {
+            let luminance = pixel.luminance() as u64;
+            if luminance == 0 {
+                continue;
+            }
+            let equalized = table[luminance as usize] as u64;
+            let scale = |channel: u16|
+                ((channel as u64 * equalized * 2 + luminance) / (luminance * 2)).min(u16::MAX as u64) as u16;
+            *pixel = Pixel::new(scale(*pixel.red()), scale(*pixel.green()), scale(*pixel.blue()), *pixel.alpha());
+        }
+    }
+
+    /// Returns a copy of the image equalized as described by
+    /// [equalize_in_place](#method.equalize_in_place).
+    pub fn equalize(&self) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.equalize_in_place();
+        farb
+    }
+
+    /// Equalizes the red, green and blue channels independently, as described by
+    /// [equalize_in_place](#method.equalize_in_place) for luminance. This spreads each channel
+    /// across the full range but may shift the colours. Channels where every pixel has the same
+    /// value are left unchanged.
+    pub fn equalize_per_channel_in_place(&mut self) {
+        let histogram = self.histogram();
+        for &channel in &[Channel::Red, Channel::Green, Channel::Blue] {
+            if let Some(table) = histogram::equalization_table(histogram.channel(channel)) {
+                for pixel in self[..].iter_mut() {
+                    let value = pixel.channel_mut(channel);
+                    *value = table[*value as usize];
+                }
+            }
+        }
+    }
+
+    /// Returns a copy of the image equalized as described by
+    /// [equalize_per_channel_in_place](#method.equalize_per_channel_in_place).
+    pub fn equalize_per_channel(&self) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.equalize_per_channel_in_place();
+        farb
+    }
+
+    /// Linearly stretches each of the red, green and blue channels so its smallest value becomes 0
+    /// and its largest 65535, rounding to the nearest value. Channels where every pixel has the
+    /// same value are left unchanged.
+    pub fn normalize_in_place(&mut self) {
+        self.normalize_clipped_in_place(0.0).expect("0 is a valid percentile");
+    }
+
+    /// Returns a copy of the image normalized as described by
+    /// [normalize_in_place](#method.normalize_in_place).
+    pub fn normalize(&self) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.normalize_in_place();
+        farb
+    }
+
+    /// Normalizes each of the red, green and blue channels as described by
+    /// [normalize_in_place](#method.normalize_in_place), but stretches the channel's `percent`
+    /// percentile to 0 and its `100 - percent` percentile to 65535, as found by the image's
+    /// [histogram](#method.histogram), so a few outlying pixels don't stop the rest being
+    /// stretched. Values beyond the percentiles are clamped.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidAdjustment](error/enum.Error.html) if `percent` isn't at least 0
+    /// and below 50.
+    pub fn normalize_clipped_in_place(&mut self, percent: f32) -> Result<()> {
+        if percent.is_nan() || !(0.0..50.0).contains(&percent) {
+            return Err(Error::InvalidAdjustment("percentile must be at least 0 and below 50"));
+        }
+        let histogram = self.histogram();
+        for &channel in &[Channel::Red, Channel::Green, Channel::Blue] {
+            if let (Some(black), Some(white)) = (histogram.percentile(channel, percent),
+                                                 histogram.percentile(channel, 100.0 - percent)) {
+                if black < white {
+                    for pixel in self[..].iter_mut() {
+                        let value = pixel.channel_mut(channel);
+                        *value = stretch(*value, black, white);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a copy of the image normalized as described by
+    /// [normalize_clipped_in_place](#method.normalize_clipped_in_place).
+    ///
+    /// # Errors
+    /// Returns the errors of [normalize_clipped_in_place](#method.normalize_clipped_in_place).
+    pub fn normalize_clipped(&self, percent: f32) -> Result<Farbfeld> {
+        let mut farb = self.clone();
+        farb.normalize_clipped_in_place(percent)?;
+        Ok(farb)
+    }
+
+    /// Linearly stretches the red, green and blue channels together so the smallest
+    /// [luminance](pixel/struct.Pixel.html#method.luminance) becomes 0 and the largest 65535,
+    /// clamping each channel and rounding to the nearest value. Unlike
+    /// [normalize_in_place](#method.normalize_in_place), this doesn't shift the colours. The image
+    /// is left unchanged if every pixel has the same luminance.
+    pub fn normalize_luminance_in_place(&mut self) {
+        let counts = self.luminance_counts();
+        let total = self.pixels().len() as u64;
+        if let (Some(black), Some(white)) = (histogram::percentile_of(&counts, total, 0.0),
+                                             histogram::percentile_of(&counts, total, 100.0)) {
+            if black < white {
+                self.map_colors(|channel| stretch(channel, black, white));
+            }
+        }
+    }
+
+    /// Returns a copy of the image normalized as described by
+    /// [normalize_luminance_in_place](#method.normalize_luminance_in_place).
+    pub fn normalize_luminance(&self) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.normalize_luminance_in_place();
+        farb
+    }
+
+    /// Multiplies the red, green and blue channels of every pixel by their respective gains,
+    /// rounding to the nearest value and saturating at 65535.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(40000_u16, 1000, 1000, 7)]).unwrap();
+    /// farb.white_balance_in_place(2.0, 1.0, 0.5).unwrap();
+    ///
+    /// assert_eq!(&Pixel::new(65535_u16, 1000, 500, 7), &farb[0]);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidAdjustment](error/enum.Error.html) if any gain isn't a positive
+    /// number.
+    pub fn white_balance_in_place(&mut self, red_gain: f32, green_gain: f32, blue_gain: f32) -> Result<()> {
+        let gains = [red_gain, green_gain, blue_gain];
+        if gains.iter().any(|gain| gain.is_nan() || *gain <= 0.0) {
+            return Err(Error::InvalidAdjustment("gains must be positive"));
+        }
+        let gains = gains.map(|gain| gain as f64);
+        for pixel in self[..].iter_mut() {
+            // Casting saturates, mapping large values to 65535.
+            let scale = |channel: u16, gain: f64| (channel as f64 * gain + 0.5) as u16;
+            *pixel = Pixel::new(scale(*pixel.red(), gains[0]), scale(*pixel.green(), gains[1]),
+                                scale(*pixel.blue(), gains[2]), *pixel.alpha());
+        }
+        Ok(())
+    }
+
+    /// Returns a copy of the image white balanced as described by
+    /// [white_balance_in_place](#method.white_balance_in_place).
+    ///
+    /// # Errors
+    /// Returns the errors of [white_balance_in_place](#method.white_balance_in_place).
+    pub fn white_balance(&self, red_gain: f32, green_gain: f32, blue_gain: f32) -> Result<Farbfeld> {
+        let mut farb = self.clone();
+        farb.white_balance_in_place(red_gain, green_gain, blue_gain)?;
+        Ok(farb)
+    }
+
+    /// White balances the image with the grey world assumption, that the average colour of a scene
+    /// is grey. Each of the red, green and blue channels is
+    /// [scaled](#method.white_balance_in_place) so its mean matches the mean
+    /// [luminance](pixel/struct.Pixel.html#method.luminance) of the image. Channels which are 0
+    /// in every pixel, and images which are black or empty, are left unchanged.
+    pub fn auto_white_balance_in_place(&mut self) {
+        let mut sums = [0_u64; 4];
+        for pixel in self.pixels() {
+            for (sum, value) in sums.iter_mut().zip([*pixel.red(), *pixel.green(), *pixel.blue(), pixel.luminance()]) {
+                *sum += value as u64;
+            }
+        }
+        let luminance = sums[3] as f64;
+        if luminance == 0.0 {
+            return;
+        }
+        let gains = [0, 1, 2].map(|i| if sums[i] == 0 { 1.0 } else { (luminance / sums[i] as f64) as f32 });
+        self.white_balance_in_place(gains[0], gains[1], gains[2]).expect("the gains are positive");
+    }
+
+    /// Returns a copy of the image white balanced as described by
+    /// [auto_white_balance_in_place](#method.auto_white_balance_in_place).
+    pub fn auto_white_balance(&self) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.auto_white_balance_in_place();
+        farb
+    }
+
+    /// Replaces the red, green and blue channels of every pixel with the result of `map`.
+    fn map_colors<F: Fn(u16) -> u16>(&mut self, map: F) {
+        for pixel in self[..].iter_mut() {
+            *pixel = Pixel::new(map(*pixel.red()), map(*pixel.green()), map(*pixel.blue()), *pixel.alpha());
+        }
+    }
+}
+
+/// Linearly maps `value` from `black` to `white` onto the full range, clamping values beyond them
+/// and rounding to the nearest value. `black` must be below `white`.
+fn stretch(value: u16, black: u16, white: u16) -> u16 {
+    let span = (white - black) as u64;
+    let value = (value.clamp(black, white) - black) as u64;
+    ((value * u16::MAX as u64 * 2 + span) / (span * 2)) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    fn image() -> Farbfeld {
+        Farbfeld::new(2, 1, vec![Pixel::new(0_u16, 3, 40000, 5), Pixel::new(65535_u16, 1, 2, 65535)]).unwrap()
+    }
+
+    #[test]
+    fn test_adjust_brightness_identity() {
+        assert_eq!(image(), image().adjusted_brightness(0));
+        assert_eq!(image(), image().adjusted_brightness_8(0));
+        assert_eq!(image(), image().scaled_brightness(1.0));
+    }
+
+    #[test]
+    fn test_adjust_brightness_saturates() {
+        let farb = image().adjusted_brightness(100000);
+        assert_eq!(&[Pixel::new(65535_u16, 65535, 65535, 5), Pixel::new(65535_u16, 65535, 65535, 65535)], farb.pixels());
+
+        let farb = image().adjusted_brightness_8(-255);
+        assert_eq!(&[Pixel::new(0_u16, 0, 0, 5), Pixel::new(0_u16, 0, 0, 65535)], farb.pixels());
+
+        let mut farb = image();
+        farb.adjust_brightness_8(1);
+        assert_eq!(&Pixel::new(257_u16, 260, 40257, 5), &farb[0]);
+    }
+
+    #[test]
+    fn test_scale_brightness() {
+        let farb = image().scaled_brightness(0.5);
+        assert_eq!(&[Pixel::new(0_u16, 2, 20000, 5), Pixel::new(32768_u16, 1, 1, 65535)], farb.pixels());
+
+        let farb = image().scaled_brightness(2.0);
+        assert_eq!(&[Pixel::new(0_u16, 6, 65535, 5), Pixel::new(65535_u16, 2, 4, 65535)], farb.pixels());
+
+        let mut farb = image();
+        farb.scale_brightness(-1.0);
+        assert_eq!(&[Pixel::new(0_u16, 0, 0, 5), Pixel::new(0_u16, 0, 0, 65535)], farb.pixels());
+    }
+
+    #[test]
+    fn test_adjust_contrast() {
+        assert_eq!(image(), image().adjusted_contrast(1.0));
+
+        let mut farb = image();
+        farb.adjust_contrast_percent(100);
+        assert_eq!(image(), farb);
+
+        let farb = image().adjusted_contrast(2.0);
+        assert_eq!(&[Pixel::new(0_u16, 0, 47233, 5), Pixel::new(65535_u16, 0, 0, 65535)], farb.pixels());
+
+        let farb = image().adjusted_contrast(0.0);
+        assert_eq!(&[Pixel::new(32768_u16, 32768, 32768, 5), Pixel::new(32768_u16, 32768, 32768, 65535)], farb.pixels());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_adjust_gamma() {
+        let farb = Farbfeld::new(256, 256, (0..=u16::MAX).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+        assert_eq!(farb, farb.adjusted_gamma(1.0).unwrap());
+
+        let farb = Farbfeld::new(3, 1, vec![
+            Pixel::new(0_u16, 0, 0, 0),
+            Pixel::new(65535_u16, 65535, 65535, 65535),
+            Pixel::new(32768_u16, 16384, 49152, 1)
+        ]).unwrap().adjusted_gamma(2.2).unwrap();
+        assert_eq!(&Pixel::new(0_u16, 0, 0, 0), &farb[0]);
+        assert_eq!(&Pixel::new(65535_u16, 65535, 65535, 65535), &farb[1]);
+        for (&channel, &original) in farb[2].iter().zip(&[32768_u16, 16384, 49152]) {
+            let expected = (original as f64 / 65535.0).powf(1.0 / 2.2) * 65535.0;
+            assert!((channel as f64 - expected).abs() <= 1.0, "{} isn't close to {}", channel, expected);
+        }
+        assert_eq!(&1, farb[2].alpha());
+    }
+
+    #[test]
+    fn test_posterize() {
+        let ramp = Farbfeld::new(256, 256, (0..=u16::MAX).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+        let binary = ramp.posterize(2).unwrap();
+        for (pixel, original) in binary.pixels().iter().zip(ramp.pixels()) {
+            let expected = if *original.red() < 32768 { 0 } else { 65535 };
+            assert_eq!(&Pixel::new(expected, expected, expected, *original.alpha()), pixel);
+        }
+        assert_eq!(ramp, ramp.posterize(65536).unwrap());
+        assert_eq!(ramp, ramp.posterize_with(u32::MAX, AlphaMode::Process).unwrap());
+
+        let farb = Farbfeld::new(1, 1, vec![Pixel::new(20000_u16, 40000, 8191, 30000)]).unwrap();
+        assert_eq!(&Pixel::new(16384_u16, 32768, 0, 30000), &farb.posterize(5).unwrap()[0]);
+        assert_eq!(&Pixel::new(16384_u16, 32768, 0, 32768), &farb.posterize_with(5, AlphaMode::Process).unwrap()[0]);
+        assert_eq!(&Pixel::new(21845_u16, 43690, 0, 30000), &farb.posterize(4).unwrap()[0]);
+
+        for &levels in &[0, 1] {
+            match farb.posterize(levels) {
+                Err(Error::InvalidAdjustment(_)) => {},
+                res => panic!("Unexpected result {:?}", res)
+            }
+        }
+    }
+
+    #[test]
+    fn test_grayscale() {
+        let grey = Farbfeld::new(3, 1, vec![
+            Pixel::new(0_u16, 0, 0, 1),
+            Pixel::new(12345_u16, 12345, 12345, 65535),
+            Pixel::new(65535_u16, 65535, 65535, 0)
+        ]).unwrap();
+        assert_eq!(grey, grey.grayscale());
+        assert_eq!(grey, grey.grayscale_with(GrayscaleMethod::Lightness));
+
+        let green = Farbfeld::new(1, 1, vec![Pixel::new(0_u16, 65535, 0, 9)]).unwrap().grayscale();
+        let expected = 0.7152 * 65535.0;
+        assert!((*green[0].red() as f64 - expected).abs() <= 1.0, "{:?} isn't close to {}", green[0], expected);
+        assert_eq!(&9, green[0].alpha());
+
+        let mut farb = image();
+        farb.grayscale_in_place_with(GrayscaleMethod::Average);
+        assert_eq!(&[Pixel::new(13334_u16, 13334, 13334, 5), Pixel::new(21846_u16, 21846, 21846, 65535)], farb.pixels());
+    }
+
+    #[test]
+    fn test_invert() {
+        let farb = image().invert();
+        assert_eq!(&[Pixel::new(65535_u16, 65532, 25535, 5), Pixel::new(0_u16, 65534, 65533, 65535)], farb.pixels());
+        assert_eq!(image(), farb.invert());
+
+        let mut farb = image();
+        farb.invert_in_place();
+        farb.invert_channel_in_place(Channel::Red);
+        assert_eq!(&[Pixel::new(0_u16, 65532, 25535, 5), Pixel::new(65535_u16, 65534, 65533, 65535)], farb.pixels());
+
+        let farb = image().invert_channel(Channel::Alpha);
+        assert_eq!(&[Pixel::new(0_u16, 3, 40000, 65530), Pixel::new(65535_u16, 1, 2, 0)], farb.pixels());
+    }
+
+    #[test]
+    fn test_equalize() {
+        let ramp = Farbfeld::new(256, 256, (0..=u16::MAX).map(|i| Pixel::new(i, i, i, 1)).collect()).unwrap();
+        assert_eq!(ramp, ramp.equalize());
+        assert_eq!(ramp, ramp.equalize_per_channel());
+
+        let low = Farbfeld::new(100, 3, (0..300).map(|i| Pixel::new(20000 + i * 10_u16, 20000 + i * 10, 20000 + i * 10, 7)).collect())
+            .unwrap();
+        for farb in &[low.equalize(), low.equalize_per_channel()] {
+            let (min, max) = farb.pixels().iter().fold((u16::MAX, 0), |(min, max), pixel| (min.min(*pixel.green()), max.max(*pixel.green())));
+            assert_eq!((0, 65535), (min, max));
+            assert!(farb.pixels().iter().all(|pixel| pixel.alpha() == &7 && pixel.red() == pixel.blue()));
+        }
+
+        let mut constant = Farbfeld::new(2, 2, vec![Pixel::new(5000_u16, 6000, 7000, 8); 4]).unwrap();
+        constant.equalize_in_place();
+        constant.equalize_per_channel_in_place();
+        assert_eq!(&[Pixel::new(5000_u16, 6000, 7000, 8); 4], constant.pixels());
+    }
+
+    #[test]
+    fn test_equalize_colour() {
+        let farb = Farbfeld::new(3, 1, vec![
+            Pixel::new(0_u16, 0, 0, 1),
+            Pixel::new(10000_u16, 5000, 0, 2),
+            Pixel::new(20000_u16, 20000, 20000, 3)
+        ]).unwrap();
+        let equalized = farb.equalize();
+        assert_eq!(&farb[0], &equalized[0]);
+        assert_eq!(&Pixel::new(65535_u16, 65535, 65535, 3), &equalized[2]);
+        assert_eq!(32768, equalized[1].luminance());
+        assert!(equalized[1].red().abs_diff(equalized[1].green() * 2) <= 1, "{:?} isn't twice as red as green", equalized[1]);
+        assert_eq!(&0, equalized[1].blue());
+
+        let per_channel = farb.equalize_per_channel();
+        assert_eq!(&[Pixel::new(0_u16, 0, 0, 1), Pixel::new(32768_u16, 32768, 0, 2), Pixel::new(65535_u16, 65535, 65535, 3)],
+                   per_channel.pixels());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let full = Farbfeld::new(3, 1, vec![
+            Pixel::new(0_u16, 0, 0, 1),
+            Pixel::new(123_u16, 456, 789, 2),
+            Pixel::new(65535_u16, 65535, 65535, 3)
+        ]).unwrap();
+        assert_eq!(full, full.normalize());
+        assert_eq!(full, full.normalize_luminance());
+
+        let half = Farbfeld::new(101, 1, (0..=100_u16).map(|i| Pixel::new(i * 300, 30000 - i * 300, 5000, 9)).collect())
+            .unwrap();
+        let normalized = half.normalize();
+        assert_eq!(&Pixel::new(0_u16, 65535, 5000, 9), &normalized[0]);
+        assert_eq!(&Pixel::new(65535_u16, 0, 5000, 9), &normalized[100]);
+        assert_eq!(&Pixel::new(32768_u16, 32768, 5000, 9), &normalized[50]);
+
+        let grey = Farbfeld::new(2, 1, vec![Pixel::new(10000_u16, 10000, 10000, 0), Pixel::new(30000_u16, 20000, 30000, 0)])
+            .unwrap();
+        let luminance = grey.normalize_luminance();
+        assert_eq!(&Pixel::new(0_u16, 0, 0, 0), &luminance[0]);
+        assert_eq!(22848, grey[1].luminance());
+        assert_eq!(&Pixel::new(65535_u16, 51008, 65535, 0), &luminance[1]);
+    }
+
+    #[test]
+    fn test_normalize_clipped() {
+        let mut pixels: Vec<Pixel> = (0..199_u16).map(|i| Pixel::new(10000 + i * 100, 0, 0, 65535)).collect();
+        pixels.push(Pixel::new(65535_u16, 0, 0, 65535));
+        let farb = Farbfeld::new(200, 1, pixels).unwrap();
+
+        let normalized = farb.normalize();
+        assert_eq!(&0, normalized[0].red());
+        assert!(normalized[198].red() < &30000);
+        let clipped = farb.normalize_clipped(1.0).unwrap();
+        assert_eq!(&0, clipped[..2].iter().map(|pixel| pixel.red()).max().unwrap());
+        assert_eq!(&65535, clipped[197..].iter().map(|pixel| pixel.red()).min().unwrap());
+        assert_eq!(&33102, clipped[100].red());
+
+        for &percent in &[-1.0, 50.0, f32::NAN] {
+            match farb.normalize_clipped(percent) {
+                Err(Error::InvalidAdjustment(_)) => {},
+                res => panic!("Unexpected result {:?}", res)
+            }
+        }
+    }
+
+    #[test]
+    fn test_white_balance() {
+        assert_eq!(image(), image().white_balance(1.0, 1.0, 1.0).unwrap());
+
+        let red = Farbfeld::new(2, 1, vec![Pixel::new(50000_u16, 100, 0, 1), Pixel::new(20000_u16, 0, 3, 2)]).unwrap();
+        let balanced = red.white_balance(2.0, 1.0, 1.0).unwrap();
+        assert_eq!(&[Pixel::new(65535_u16, 100, 0, 1), Pixel::new(40000_u16, 0, 3, 2)], balanced.pixels());
+
+        for gains in &[(0.0, 1.0, 1.0), (1.0, -2.0, 1.0), (1.0, 1.0, f32::NAN)] {
+            match image().white_balance(gains.0, gains.1, gains.2) {
+                Err(Error::InvalidAdjustment(_)) => {},
+                res => panic!("Unexpected result {:?}", res)
+            }
+        }
+    }
+
+    #[test]
+    fn test_auto_white_balance() {
+        let tinted = Farbfeld::new(64, 1, (0..64_u16).map(|i| Pixel::new(i * 700, i * 750, i * 950, 65535)).collect()).unwrap();
+        let balanced = tinted.auto_white_balance();
+        let means: Vec<f64> = [Channel::Red, Channel::Green, Channel::Blue].iter()
+            .map(|&channel| balanced.pixels().iter().map(|pixel| *pixel.channel(channel) as f64).sum::<f64>() / 64.0)
+            .collect();
+        for mean in &means {
+            assert!((mean - means[0]).abs() < 50.0, "{:?} aren't close", means);
+        }
+        for pixel in balanced.pixels() {
+            assert!(pixel.red().abs_diff(*pixel.blue()) <= 1, "{:?} isn't grey", pixel);
+        }
+
+        let mut black = Farbfeld::new(2, 1, vec![Pixel::new(0_u16, 0, 0, 5); 2]).unwrap();
+        black.auto_white_balance_in_place();
+        assert_eq!(&[Pixel::new(0_u16, 0, 0, 5); 2], black.pixels());
+        let red = Farbfeld::new(1, 1, vec![Pixel::new(30000_u16, 0, 0, 5)]).unwrap();
+        assert_eq!(&Pixel::new(6378_u16, 0, 0, 5), &red.auto_white_balance()[0]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_adjust_gamma_invalid() {
+        for &gamma in &[0.0, -1.0, f32::NAN] {
+            match image().adjust_gamma(gamma) {
+                Err(Error::InvalidAdjustment(_)) => {},
+                res => panic!("Unexpected result {:?}", res)
+            }
+        }
+    }
+
+    #[test]
+    fn test_adjust_levels() {
+        let farb = Farbfeld::new(5, 1, vec![
+            Pixel::new(0_u16, 1000, 1001, 1),
+            Pixel::new(2000_u16, 2999, 3000, 2),
+            Pixel::new(3001_u16, 65535, 1500, 3),
+            Pixel::new(0_u16, 0, 0, 4),
+            Pixel::new(0_u16, 0, 0, 5)
+        ]).unwrap();
+
+        let stretched = farb.adjusted_levels(1000, 3000, 0, 65535).unwrap();
+        assert_eq!(&Pixel::new(0_u16, 0, 33, 1), &stretched[0]);
+        assert_eq!(&Pixel::new(32768_u16, 65502, 65535, 2), &stretched[1]);
+        assert_eq!(&Pixel::new(65535_u16, 65535, 16384, 3), &stretched[2]);
+
+        let compressed = farb.adjusted_levels(0, 65535, 100, 200).unwrap();
+        assert_eq!(&Pixel::new(100_u16, 102, 102, 1), &compressed[0]);
+        assert_eq!(&Pixel::new(105_u16, 200, 102, 3), &compressed[2]);
+
+        let inverted = farb.adjusted_levels(0, 65535, 65535, 0).unwrap();
+        assert_eq!(&Pixel::new(65535_u16, 64535, 64534, 1), &inverted[0]);
+    }
+
+    #[test]
+    fn test_adjust_levels_invalid() {
+        for &(black, white) in &[(1000, 1000), (2000, 1000)] {
+            match image().adjust_levels(black, white, 0, 65535) {
+                Err(Error::InvalidAdjustment(_)) => {},
+                res => panic!("Unexpected result {:?}", res)
+            }
+        }
+    }
+
+    #[test]
+    fn test_auto_levels() {
+        let pixels = (0..100_u16).map(|i| Pixel::new(10000 + i * 100, 10000 + i * 100, 10000 + i * 100, 7)).collect();
+        let mut farb = Farbfeld::new(10, 10, pixels).unwrap();
+        farb.auto_levels(0.0).unwrap();
+        assert_eq!(&Pixel::new(0_u16, 0, 0, 7), &farb[0]);
+        assert_eq!(&Pixel::new(65535_u16, 65535, 65535, 7), &farb[99]);
+
+        let mut constant = Farbfeld::new(2, 1, vec![Pixel::new(5_u16, 5, 5, 5); 2]).unwrap();
+        constant.auto_levels(1.0).unwrap();
+        assert_eq!(&[Pixel::new(5_u16, 5, 5, 5); 2], constant.pixels());
+
+        assert!(image().auto_levels(50.0).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Rendering Farbfeld images as ANSI escape sequences for previewing them in a terminal.
+//!
+//! Each character cell shows two image rows using the upper half block character, `▀`, with the
+//! foreground colour set to the upper pixel and the background colour set to the lower pixel.
+
+use core::fmt::Write;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::{Pixel, composite_channel, narrow_channel};
+
+/// The sequence resetting all colours.
+const RESET: &str = "\x1b[0m";
+/// The light and dark channel values of the checkerboard background.
+const CHECKER: [u16; 2] = [0xcccc, 0x9999];
+
+/// The background transparent pixels are composited onto.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnsiBackground {
+    /// A light and dark grey checkerboard, as used by most image editors.
+    Checker,
+    /// The red, green and blue channels of the given pixel.
+    Color(Pixel)
+}
+
+/// The colours a terminal supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnsiColors {
+    /// 24 bit colour.
+    TrueColor,
+    /// The 6x6x6 colour cube of the 256 colour palette.
+    Ansi256
+}
+
+impl Farbfeld {
+    /// Renders the image for a terminal with 24 bit colour, at most max_width columns wide,
+    /// compositing transparent pixels onto a checkerboard. The image is downscaled to fit, keeping
+    /// its aspect ratio, though at least one column is always rendered. The output ends with a
+    /// sequence resetting the terminal's colours.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(2, 3, vec![Pixel::new(65535_u16, 0, 0, 65535); 6]).unwrap();
+    /// let preview = farb.render_ansi(80);
+    ///
+    /// assert_eq!(2, preview.lines().count());
+    /// assert!(preview.ends_with("\x1b[0m"));
+    /// ```
+    pub fn render_ansi(&self, max_width: u32) -> String {
+        self.render_ansi_with(max_width, AnsiBackground::Checker, AnsiColors::TrueColor)
+    }
+
+    /// Renders the image as described by [render_ansi](#method.render_ansi), for terminals with
+    /// only the 256 colour palette.
+    pub fn render_ansi_256(&self, max_width: u32) -> String {
+        self.render_ansi_with(max_width, AnsiBackground::Checker, AnsiColors::Ansi256)
+    }
+
+    /// Renders the image as described by [render_ansi](#method.render_ansi), with the given
+    /// background and colours.
+    pub fn render_ansi_with(&self, max_width: u32, background: AnsiBackground, colors: AnsiColors) -> String {
+        let (width, height, pixels) = self.downscale(max_width);
+        let color = |x: u32, y: u32| {
+            let pixel = pixels[y as usize * width as usize + x as usize];
+            let background = match background {
+                AnsiBackground::Checker => {
+                    let channel = CHECKER[((x / 2 + y / 2) % 2) as usize];
+                    Pixel::new(channel, channel, channel, 65535)
+                },
+                AnsiBackground::Color(background) => background
+            };
+            let composite = |channel, background| {
+                narrow_channel(composite_channel(channel, background, *pixel.alpha()))
+            };
+            [composite(*pixel.red(), *background.red()), composite(*pixel.green(), *background.green()),
+             composite(*pixel.blue(), *background.blue())]
+        };
+
+        let mut out = String::new();
+        for y in (0..height).step_by(2) {
+            if y > 0 {
+                out.push('\n');
+            }
+            for x in 0..width {
+                push_color(&mut out, 38, color(x, y), colors);
+                if y + 1 < height {
+                    push_color(&mut out, 48, color(x, y + 1), colors);
+                } else {
+                    // The last row of an odd height image is shown on the terminal's background.
+                    out.push_str("\x1b[49m");
+                }
+                out.push('▀');
+            }
+            out.push_str(RESET);
+        }
+        if out.is_empty() {
+            out.push_str(RESET);
+        }
+        out
+    }
+
+    /// Downscales the image by averaging blocks of pixels, so it is at most max_width pixels wide.
+    /// Colours are weighted by their alpha, so transparent pixels don't darken their neighbours.
+    fn downscale(&self, max_width: u32) -> (u32, u32, Vec<Pixel>) {
+        let (width, height) = (*self.width(), *self.height());
+        if width <= max_width {
+            return (width, height, self.pixels().to_vec());
+        }
+        let scale = width.div_ceil(max_width.max(1));
+        let (out_width, out_height) = (width.div_ceil(scale), height.div_ceil(scale));
+        let mut pixels = Vec::with_capacity(out_width as usize * out_height as usize);
+        for out_y in 0..out_height {
+            for out_x in 0..out_width {
+                let mut sums = [0_u64; 4];
+                let mut count = 0_u64;
+                for y in out_y * scale..((out_y + 1) * scale).min(height) {
+                    for x in out_x * scale..((out_x + 1) * scale).min(width) {
+                        let pixel = &self.pixels()[y as usize * width as usize + x as usize];
+                        let alpha = *pixel.alpha() as u64;
+                        sums[0] += *pixel.red() as u64 * alpha;
+                        sums[1] += *pixel.green() as u64 * alpha;
+                        sums[2] += *pixel.blue() as u64 * alpha;
+                        sums[3] += alpha;
+                        count += 1;
+                    }
+                }
+                let channel = |sum: u64| sum.checked_div(sums[3]).unwrap_or(0) as u16;
+                pixels.push(Pixel::new(channel(sums[0]), channel(sums[1]), channel(sums[2]), (sums[3] / count) as u16));
+            }
+        }
+        (out_width, out_height, pixels)
+    }
+}
+
+/// Pushes the sequence setting the foreground (38) or background (48) colour.
+fn push_color(out: &mut String, target: u8, [r, g, b]: [u8; 3], colors: AnsiColors) {
+    match colors {
+        AnsiColors::TrueColor => write!(out, "\x1b[{};2;{};{};{}m", target, r, g, b),
+        AnsiColors::Ansi256 => {
+            let cube = |channel: u8| (channel as u16 * 5 + 127) / 255;
+            write!(out, "\x1b[{};5;{}m", target, 16 + 36 * cube(r) + 6 * cube(g) + cube(b))
+        }
+    }.expect("writing to a String can't fail");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    fn image(width: u32, height: u32) -> Farbfeld {
+        Farbfeld::new(width, height, vec![Pixel::new(65535_u16, 0, 0, 65535); (width * height) as usize]).unwrap()
+    }
+
+    #[test]
+    fn test_structure() {
+        let preview = image(3, 4).render_ansi(80);
+        assert_eq!(2, preview.lines().count());
+        assert_eq!(6, preview.matches('▀').count());
+        assert!(preview.contains("\x1b[38;2;255;0;0m"));
+        assert!(preview.contains("\x1b[48;2;255;0;0m"));
+        assert!(preview.lines().all(|line| line.ends_with(RESET)));
+        assert!(preview.ends_with(RESET));
+    }
+
+    #[test]
+    fn test_odd_height() {
+        let preview = image(2, 3).render_ansi(80);
+        let lines: Vec<&str> = preview.lines().collect();
+        assert_eq!(2, lines.len());
+        assert!(!lines[0].contains("\x1b[49m"));
+        assert_eq!(2, lines[1].matches("\x1b[49m").count());
+        assert!(preview.ends_with(RESET));
+    }
+
+    #[test]
+    fn test_downscale() {
+        let preview = image(100, 10).render_ansi(30);
+        let first = preview.lines().next().unwrap();
+        assert_eq!(25, first.matches('▀').count());
+        assert_eq!(2, preview.lines().count());
+    }
+
+    #[test]
+    fn test_background() {
+        let farb = Farbfeld::new(1, 2, vec![Pixel::new(65535_u16, 0, 0, 0); 2]).unwrap();
+        let preview = farb.render_ansi_with(80, AnsiBackground::Color(Pixel::new(0_u16, 0, 65535, 0)),
+                                            AnsiColors::TrueColor);
+        assert_eq!("\x1b[38;2;0;0;255m\x1b[48;2;0;0;255m▀\x1b[0m", preview);
+        assert!(farb.render_ansi(80).contains("\x1b[38;2;204;204;204m"));
+    }
+
+    #[test]
+    fn test_ansi_256() {
+        let preview = image(1, 2).render_ansi_256(80);
+        assert_eq!("\x1b[38;5;196m\x1b[48;5;196m▀\x1b[0m", preview);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(RESET, image(0, 0).render_ansi(80));
+        assert_eq!(1, image(3, 4).render_ansi(0).lines().next().unwrap().matches('▀').count());
+    }
+}
@@ -0,0 +1,311 @@
+//! Asynchronous parsing and writing of Farbfeld images using [tokio](https://tokio.rs). Only
+//! available with the `tokio` feature.
+//!
+//! The header and pixels are parsed by the same parsers as the synchronous API, so both produce
+//! identical results for identical input.
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::pixel::Pixel;
+use crate::farbfeld::{self, Farbfeld};
+use crate::error::*;
+use crate::parser;
+
+/// An asynchronous decoder producing one row of a Farbfeld image at a time, without ever holding
+/// more than a single row in memory. The row buffer grows as data arrives, so a header claiming a
+/// huge width can't make the decoder allocate more than the reader provides.
+#[derive(Debug)]
+pub struct AsyncFarbfeldDecoder<R> {
+    reader: R,
+    width: u32,
+    height: u32,
+    rows_read: u32,
+    buff: Vec<u8>
+}
+
+/// An asynchronous encoder writing a Farbfeld image one row at a time.
+#[derive(Debug)]
+pub struct AsyncFarbfeldEncoder<W> {
+    writer: W,
+    width: u32,
+    height: u32,
+    rows_written: u32,
+    bytes_written: u64,
+    buff: Vec<u8>
+}
+
+/// Converts an error from `read_exact` into a crate error, reporting early EOF the same way as the
+/// synchronous parser. The number of missing bytes isn't known here.
+fn read_error(err: ::std::io::Error) -> Error {
+    if err.kind() == ::std::io::ErrorKind::UnexpectedEof {
+        Error::UnexpectedEof { needed: None }
+    } else {
+        Error::IoError(err)
+    }
+}
+
+impl Farbfeld {
+    /// Asynchronously parses the entire of the given AsyncRead into a Farbfeld object.
+    ///
+    /// # Errors
+    /// Returns the same errors as [from_read](struct.Farbfeld.html#method.from_read).
+    pub async fn from_async_read<R: AsyncRead + Unpin>(mut read: R) -> Result<Farbfeld> {
+        let mut buff = Vec::new();
+        read.read_to_end(&mut buff).await.map_err(Error::IoError)?;
+        parser::parse_farb(&buff)
+    }
+
+    /// Asynchronously writes the image to the given AsyncWrite according to the
+    /// [spec](http://tools.suckless.org/farbfeld/), flushing it once the image has been written.
+    /// Returns the number of bytes written, including the header.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+    ///     std IoError during write.</li>
+    /// </ul>
+    pub async fn save_async<W: AsyncWrite + Unpin>(&self, write: W) -> Result<u64> {
+        let mut encoder = AsyncFarbfeldEncoder::new(write, *self.width(), *self.height()).await?;
+        for row in 0..*self.height() {
+            encoder.write_row(self.row(row).expect("row within image height")).await?;
+        }
+        encoder.finish().await.map(|(_, written)| written)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncFarbfeldDecoder<R> {
+    /// Creates a new decoder, reading and parsing the header of the image from the given reader.
+    ///
+    /// # Errors
+    /// Returns an [Error::UnexpectedEof](../error/enum.Error.html) if the reader ends
+    /// before the header is complete, an [Error::InvalidMagic](../error/enum.Error.html)
+    /// if the header is malformed or an [Error::IoError](../error/enum.Error.html) if the
+    /// header cannot be read.
+    pub async fn new(mut reader: R) -> Result<AsyncFarbfeldDecoder<R>> {
+        let mut header = [0_u8; 16];
+        reader.read_exact(&mut header).await.map_err(read_error)?;
+        let (width, height) = parser::parse_header(&header)?;
+        Ok(AsyncFarbfeldDecoder {
+            reader,
+            width,
+            height,
+            rows_read: 0,
+            buff: Vec::new()
+        })
+    }
+
+    /// Returns the width of the image being decoded, as defined in its header.
+    pub fn width(&self) -> &u32 {
+        &self.width
+    }
+
+    /// Returns the height of the image being decoded, as defined in its header.
+    pub fn height(&self) -> &u32 {
+        &self.height
+    }
+
+    /// Reads and parses the next row of the image, returning None once every row has been read.
+    ///
+    /// # Errors
+    /// Returns an [Error::UnexpectedEof](../error/enum.Error.html) if the reader ends
+    /// before the row is complete, or an [Error::IoError](../error/enum.Error.html) if the
+    /// row cannot be read.
+    pub async fn read_row(&mut self) -> Result<Option<Vec<Pixel>>> {
+        if self.rows_read >= self.height {
+            return Ok(None);
+        }
+        let len = self.width as u64 * 8;
+        self.buff.clear();
+        (&mut self.reader).take(len).read_to_end(&mut self.buff).await.map_err(Error::IoError)?;
+        if (self.buff.len() as u64) < len {
+            return Err(Error::UnexpectedEof { needed: Some(len - self.buff.len() as u64) });
+        }
+        self.rows_read += 1;
+        parser::parse_pixels(&self.buff).map(Some)
+    }
+
+    /// Consumes the decoder, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncFarbfeldEncoder<W> {
+    /// Creates a new encoder for an image with the given dimensions, writing the header
+    /// immediately.
+    ///
+    /// # Errors
+    /// Returns an [Error::IoError](../error/enum.Error.html) if the header cannot be
+    /// written.
+    pub async fn new(mut writer: W, width: u32, height: u32) -> Result<AsyncFarbfeldEncoder<W>> {
+        writer.write_all(&farbfeld::header_bytes(width, height)).await.map_err(Error::IoError)?;
+        Ok(AsyncFarbfeldEncoder {
+            writer,
+            width,
+            height,
+            rows_written: 0,
+            bytes_written: 16,
+            buff: Vec::with_capacity(width as usize * 8)
+        })
+    }
+
+    /// Writes the next row of the image.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li>Returns an <a href="../error/enum.Error.html">Error::InvalidRowLength</a> if
+    ///     the row doesn't contain exactly width pixels.</li>
+    ///     <li>Returns an <a href="../error/enum.Error.html">Error::InvalidRowCount</a> if
+    ///     every row of the image has already been written.</li>
+    ///     <li>Returns an <a href="../error/enum.Error.html">Error::IoError</a> if the write
+    ///     fails.</li>
+    /// </ul>
+    pub async fn write_row(&mut self, row: &[Pixel]) -> Result<()> {
+        if row.len() != self.width as usize {
+            return Err(Error::InvalidRowLength(self.rows_written, self.width, row.len()));
+        }
+        if self.rows_written >= self.height {
+            return Err(Error::InvalidRowCount(self.height, self.rows_written as u64 + 1));
+        }
+        self.buff.clear();
+        farbfeld::extend_row_bytes(&mut self.buff, row);
+        self.writer.write_all(&self.buff).await.map_err(Error::IoError)?;
+        self.rows_written += 1;
+        self.bytes_written += self.buff.len() as u64;
+        Ok(())
+    }
+
+    /// Flushes the writer once every row has been written, returning it along with the total
+    /// number of bytes written, including the header.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidRowCount](../error/enum.Error.html) if fewer than height
+    /// rows were written, or an [Error::IoError](../error/enum.Error.html) if the flush
+    /// fails.
+    pub async fn finish(mut self) -> Result<(W, u64)> {
+        if self.rows_written != self.height {
+            return Err(Error::InvalidRowCount(self.height, self.rows_written as u64));
+        }
+        self.writer.flush().await.map_err(Error::IoError)?;
+        Ok((self.writer, self.bytes_written))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::io::duplex;
+
+    fn gradient(width: u32, height: u32) -> Farbfeld {
+        let pixels = (0..width * height)
+            .map(|i| Pixel::new(i as u16, (i * 3) as u16, (i * 7) as u16, 65535 - i as u16))
+            .collect();
+        Farbfeld::new(width, height, pixels).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let farb = gradient(13, 7);
+        // A tiny buffer forces both sides to repeatedly wait on each other.
+        let (writer, reader) = duplex(3);
+        let (saved, parsed) = tokio::join!(
+            farb.save_async(writer),
+            Farbfeld::from_async_read(reader)
+        );
+        assert_eq!(16 + 13 * 7 * 8, saved.unwrap());
+        let parsed = parsed.unwrap();
+
+        assert_eq!(farb.width(), parsed.width());
+        assert_eq!(farb.height(), parsed.height());
+        assert_eq!(farb.pixels(), parsed.pixels());
+    }
+
+    #[tokio::test]
+    async fn test_matches_sync_save() {
+        let farb = gradient(5, 4);
+        let mut sync = Vec::new();
+        farb.save(&mut sync).unwrap();
+        let mut async_ = Vec::new();
+        farb.save_async(&mut async_).await.unwrap();
+
+        assert_eq!(sync, async_);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_rows() {
+        let farb = gradient(9, 6);
+        let (writer, reader) = duplex(5);
+        let write = async {
+            let mut encoder = AsyncFarbfeldEncoder::new(writer, 9, 6).await?;
+            for row in 0..6 {
+                encoder.write_row(farb.row(row).unwrap()).await?;
+            }
+            encoder.finish().await
+        };
+        let read = async {
+            let mut decoder = AsyncFarbfeldDecoder::new(reader).await?;
+            let mut rows = Vec::new();
+            while let Some(row) = decoder.read_row().await? {
+                rows.push(row);
+            }
+            Ok::<_, Error>((*decoder.width(), *decoder.height(), rows))
+        };
+        let (written, read) = tokio::join!(write, read);
+        written.unwrap();
+        let (width, height, rows) = read.unwrap();
+
+        assert_eq!((9, 6), (width, height));
+        assert_eq!(6, rows.len());
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(farb.row(i as u32).unwrap(), &row[..]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_truncated_row() {
+        let mut data = Vec::new();
+        gradient(4, 4).save(&mut data).unwrap();
+        data.truncate(16 + 4 * 8 + 3);
+        let mut decoder = AsyncFarbfeldDecoder::new(&data[..]).await.unwrap();
+
+        assert!(decoder.read_row().await.unwrap().is_some());
+        match decoder.read_row().await {
+            Err(Error::UnexpectedEof { needed: Some(29) }) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_huge_width() {
+        let mut data = b"farbfeld\xff\xff\xff\xff\0\0\0\x01".to_vec();
+        data.extend_from_slice(&[0; 24]);
+        let mut decoder = AsyncFarbfeldDecoder::new(&data[..]).await.unwrap();
+
+        assert_eq!((u32::MAX, 1), (*decoder.width(), *decoder.height()));
+        match decoder.read_row().await {
+            Err(Error::UnexpectedEof { needed: Some(missing) }) => assert_eq!(u32::MAX as u64 * 8 - 24, missing),
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encoder_row_errors() {
+        let mut encoder = AsyncFarbfeldEncoder::new(Vec::new(), 2, 1).await.unwrap();
+        match encoder.write_row(&[Pixel::default()]).await {
+            Err(Error::InvalidRowLength(0, 2, 1)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        encoder.write_row(&[Pixel::default(); 2]).await.unwrap();
+        match encoder.write_row(&[Pixel::default(); 2]).await {
+            Err(Error::InvalidRowCount(1, 2)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+
+        let encoder = AsyncFarbfeldEncoder::new(Vec::new(), 2, 1).await.unwrap();
+        match encoder.finish().await {
+            Err(Error::InvalidRowCount(1, 0)) => {},
+            res => panic!("Unexpected result {:?}", res.map(|_| ()))
+        }
+    }
+}
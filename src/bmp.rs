@@ -0,0 +1,156 @@
+//! Writing Farbfeld images as uncompressed BMP images, which can be opened by almost any image
+//! viewer. Channels are narrowed to 8 bits, rounding to the nearest value.
+
+use std::io::Write;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::{composite_channel, narrow_channel};
+use crate::error::*;
+
+/// The size of the BMP file header.
+const FILE_HEADER_SIZE: u32 = 14;
+/// The size of a BITMAPINFOHEADER, used for 24 bit images.
+const INFO_HEADER_SIZE: u32 = 40;
+/// The size of a BITMAPV4HEADER, used for 32 bit images so the alpha channel can be described.
+const V4_HEADER_SIZE: u32 = 108;
+/// About 72 DPI, in pixels per metre.
+const PIXELS_PER_METRE: u32 = 2835;
+
+/// The pixel format of a written BMP image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BmpFormat {
+    /// 32 bits per pixel, storing the blue, green, red and alpha channels.
+    Bgra32,
+    /// 24 bits per pixel, storing the blue, green and red channels after compositing each pixel
+    /// onto a white background.
+    Bgr24
+}
+
+impl Farbfeld {
+    /// Writes the image to the given write as a 32 bit BMP image with an alpha channel.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">ImageTooLarge</a> if the image is
+    ///     too large to be stored as a BMP image.</li>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+    ///     std IoError during write.</li>
+    /// </ul>
+    pub fn to_bmp<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.to_bmp_with_format(write, BmpFormat::Bgra32)
+    }
+
+    /// Writes the image to the given write as a BMP image with the given pixel format.
+    ///
+    /// # Errors
+    /// Returns the errors of [to_bmp](#method.to_bmp).
+    pub fn to_bmp_with_format<W: Write>(&self, write: &mut W, format: BmpFormat) -> Result<()> {
+        let (bytes_per_pixel, info_size) = match format {
+            BmpFormat::Bgra32 => (4, V4_HEADER_SIZE),
+            BmpFormat::Bgr24 => (3, INFO_HEADER_SIZE)
+        };
+        // Rows are padded to a multiple of 4 bytes.
+        let row_size = (*self.width() as u64 * bytes_per_pixel).div_ceil(4) * 4;
+        let image_size = row_size * *self.height() as u64;
+        let offset = FILE_HEADER_SIZE + info_size;
+        let file_size = offset as u64 + image_size;
+        if *self.width() > i32::MAX as u32 || *self.height() > i32::MAX as u32 || file_size > u32::MAX as u64 {
+            return Err(Error::ImageTooLarge("BMP"));
+        }
+
+        let mut buff = Vec::with_capacity(file_size as usize);
+        buff.extend_from_slice(b"BM");
+        for field in &[file_size as u32, 0, offset, info_size, *self.width(), *self.height()] {
+            buff.extend_from_slice(&field.to_le_bytes());
+        }
+        // One plane, then the bits per pixel.
+        buff.extend_from_slice(&1_u16.to_le_bytes());
+        buff.extend_from_slice(&(bytes_per_pixel as u16 * 8).to_le_bytes());
+        // BI_BITFIELDS for 32 bit images, BI_RGB for 24 bit images.
+        let compression = if format == BmpFormat::Bgra32 { 3_u32 } else { 0 };
+        for field in &[compression, image_size as u32, PIXELS_PER_METRE, PIXELS_PER_METRE, 0, 0] {
+            buff.extend_from_slice(&field.to_le_bytes());
+        }
+        if format == BmpFormat::Bgra32 {
+            // The red, green, blue and alpha masks, then the sRGB colour space.
+            for field in &[0x00ff_0000_u32, 0x0000_ff00, 0x0000_00ff, 0xff00_0000, 0x7352_4742] {
+                buff.extend_from_slice(&field.to_le_bytes());
+            }
+            // The unused CIE endpoints and gamma values.
+            buff.resize(offset as usize, 0);
+        }
+
+        // BMP rows are stored bottom to top.
+        for row in (0..*self.height()).rev() {
+            let start = buff.len();
+            for pixel in self.row(row).unwrap_or(&[]) {
+                let channels = [*pixel.blue(), *pixel.green(), *pixel.red()];
+                match format {
+                    BmpFormat::Bgra32 => {
+                        buff.extend(channels.iter().map(|&channel| narrow_channel(channel)));
+                        buff.push(narrow_channel(*pixel.alpha()));
+                    },
+                    BmpFormat::Bgr24 => buff.extend(channels.iter()
+                        .map(|&channel| narrow_channel(composite_channel(channel, 65535, *pixel.alpha()))))
+                }
+            }
+            buff.resize(start + row_size as usize, 0);
+        }
+        write.write_all(&buff).map_err(Error::IoError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::pixel::Pixel;
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+    }
+
+    fn image() -> Farbfeld {
+        Farbfeld::new(3, 2, vec![Pixel::new(65535_u16, 0, 0, 65535), Pixel::new(0_u16, 65535, 0, 65535),
+                                 Pixel::new(0_u16, 0, 65535, 0), Pixel::new(257_u16, 514, 771, 32768),
+                                 Pixel::new(0_u16, 0, 0, 65535), Pixel::new(65535_u16, 65535, 65535, 65535)])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_bgra32() {
+        let mut bmp = Vec::new();
+        image().to_bmp(&mut bmp).unwrap();
+
+        assert_eq!(b"BM", &bmp[..2]);
+        assert_eq!(14 + 108 + 2 * 12, u32_at(&bmp, 2));
+        assert_eq!(bmp.len() as u32, u32_at(&bmp, 2));
+        assert_eq!(14 + 108, u32_at(&bmp, 10));
+        assert_eq!((3, 2), (u32_at(&bmp, 18), u32_at(&bmp, 22)));
+        assert_eq!(&[1, 0, 32, 0], &bmp[26..30]);
+
+        // The bottom row comes first.
+        assert_eq!(&[3, 2, 1, 128], &bmp[122..126]);
+        assert_eq!(&[255, 255, 255, 255], &bmp[130..134]);
+        assert_eq!(&[0, 0, 255, 255], &bmp[134..138]);
+        assert_eq!(&[255, 0, 0, 0], &bmp[142..146]);
+    }
+
+    #[test]
+    fn test_bgr24() {
+        let mut bmp = Vec::new();
+        image().to_bmp_with_format(&mut bmp, BmpFormat::Bgr24).unwrap();
+
+        // Each row of 9 bytes is padded to 12 bytes.
+        assert_eq!(14 + 40 + 2 * 12, u32_at(&bmp, 2));
+        assert_eq!(bmp.len() as u32, u32_at(&bmp, 2));
+        assert_eq!(14 + 40, u32_at(&bmp, 10));
+        assert_eq!((3, 2), (u32_at(&bmp, 18), u32_at(&bmp, 22)));
+        assert_eq!(&[1, 0, 24, 0], &bmp[26..30]);
+
+        assert_eq!(&[129, 128, 128], &bmp[54..57]);
+        assert_eq!(&[0, 0, 0], &bmp[63..66]);
+        assert_eq!(&[0, 0, 255], &bmp[66..69]);
+        assert_eq!(&[255, 255, 255], &bmp[72..75]);
+    }
+}
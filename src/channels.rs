@@ -0,0 +1,187 @@
+//! Splitting Farbfeld images into separate planes for each channel, and merging them back.
+
+use alloc::vec::Vec;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::{Pixel, Channel};
+use crate::error::*;
+
+/// The channels of an image stored as four planes, each holding the values of a single channel
+/// for every pixel in row-major order, created by
+/// [Farbfeld::split_channels](../struct.Farbfeld.html#method.split_channels).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Channels {
+    width: u32,
+    height: u32,
+    planes: [Vec<u16>; 4]
+}
+
+impl Channels {
+    /// Returns the width of each plane.
+    pub fn width(&self) -> &u32 {
+        &self.width
+    }
+
+    /// Returns the height of each plane.
+    pub fn height(&self) -> &u32 {
+        &self.height
+    }
+
+    /// Returns the plane of the given channel.
+    pub fn channel(&self, channel: Channel) -> &[u16] {
+        &self.planes[channel as usize]
+    }
+
+    /// Returns the plane of the red channel.
+    pub fn red(&self) -> &[u16] {
+        self.channel(Channel::Red)
+    }
+
+    /// Returns the plane of the green channel.
+    pub fn green(&self) -> &[u16] {
+        self.channel(Channel::Green)
+    }
+
+    /// Returns the plane of the blue channel.
+    pub fn blue(&self) -> &[u16] {
+        self.channel(Channel::Blue)
+    }
+
+    /// Returns the plane of the alpha channel.
+    pub fn alpha(&self) -> &[u16] {
+        self.channel(Channel::Alpha)
+    }
+
+    /// Consumes the channels, returning the red, green, blue and alpha planes in that order.
+    pub fn into_planes(self) -> [Vec<u16>; 4] {
+        self.planes
+    }
+
+    /// Merges the planes back into an image.
+    pub fn merge(&self) -> Farbfeld {
+        let [red, green, blue, alpha] = &self.planes;
+        Farbfeld::merge_channels(self.width, self.height, red, green, blue, Some(alpha))
+            .expect("planes created by split_channels match their dimensions")
+    }
+}
+
+impl Farbfeld {
+    /// Splits the image into a plane for each channel in a single pass over the pixels.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2, 3, 4), Pixel::new(5_u16, 6, 7, 8)]).unwrap();
+    /// let channels = farb.split_channels();
+    ///
+    /// assert_eq!(&[1, 5], channels.red());
+    /// assert_eq!(&[4, 8], channels.alpha());
+    /// ```
+    pub fn split_channels(&self) -> Channels {
+        let len = self.pixels().len();
+        let mut planes = [Vec::with_capacity(len), Vec::with_capacity(len), Vec::with_capacity(len), Vec::with_capacity(len)];
+        for pixel in self.pixels() {
+            for (plane, channel) in planes.iter_mut().zip(pixel.iter()) {
+                plane.push(*channel);
+            }
+        }
+        Channels {
+            width: *self.width(),
+            height: *self.height(),
+            planes
+        }
+    }
+
+    /// Creates an image with the given dimensions from a plane for each channel, each holding the
+    /// values of that channel for every pixel in row-major order. Without an alpha plane, every
+    /// pixel is opaque.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::merge_channels(2, 1, &[1, 5], &[2, 6], &[3, 7], None).unwrap();
+    ///
+    /// assert_eq!(&[Pixel::new(1_u16, 2, 3, 65535), Pixel::new(5_u16, 6, 7, 65535)], farb.pixels());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidPlaneLength](error/enum.Error.html) naming the first plane
+    /// which doesn't contain width * height values.
+    pub fn merge_channels(width: u32, height: u32, red: &[u16], green: &[u16], blue: &[u16], alpha: Option<&[u16]>)
+        -> Result<Farbfeld> {
+        let len = width as u64 * height as u64;
+        let planes = [(Channel::Red, Some(red)), (Channel::Green, Some(green)), (Channel::Blue, Some(blue)),
+                      (Channel::Alpha, alpha)];
+        for &(channel, plane) in planes.iter() {
+            if let Some(plane) = plane {
+                if plane.len() as u64 != len {
+                    return Err(Error::InvalidPlaneLength(channel, len, plane.len()));
+                }
+            }
+        }
+        let pixels = match alpha {
+            Some(alpha) => red.iter().zip(green).zip(blue).zip(alpha)
+                .map(|(((&red, &green), &blue), &alpha)| Pixel::new(red, green, blue, alpha))
+                .collect(),
+            None => red.iter().zip(green).zip(blue)
+                .map(|((&red, &green), &blue)| Pixel::new(red, green, blue, u16::MAX))
+                .collect()
+        };
+        Farbfeld::new(width, height, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    fn image() -> Farbfeld {
+        Farbfeld::new(2, 2, vec![
+            Pixel::new(1_u16, 2, 3, 4),
+            Pixel::new(10_u16, 20, 30, 40),
+            Pixel::new(100_u16, 200, 300, 400),
+            Pixel::new(1000_u16, 2000, 3000, 4000)
+        ]).unwrap()
+    }
+
+    #[test]
+    fn test_split_channels() {
+        let channels = image().split_channels();
+
+        assert_eq!((&2, &2), (channels.width(), channels.height()));
+        assert_eq!(&[1, 10, 100, 1000], channels.red());
+        assert_eq!(&[2, 20, 200, 2000], channels.green());
+        assert_eq!(&[3, 30, 300, 3000], channels.blue());
+        assert_eq!(&[4, 40, 400, 4000], channels.alpha());
+        assert_eq!(channels.blue(), channels.channel(Channel::Blue));
+
+        let [red, _, _, alpha] = channels.into_planes();
+        assert_eq!(vec![1, 10, 100, 1000], red);
+        assert_eq!(vec![4, 40, 400, 4000], alpha);
+    }
+
+    #[test]
+    fn test_merge_channels_round_trip() {
+        let channels = image().split_channels();
+        assert_eq!(image(), channels.merge());
+
+        let merged = Farbfeld::merge_channels(2, 2, channels.red(), channels.green(), channels.blue(),
+                                              Some(channels.alpha())).unwrap();
+        assert_eq!(image(), merged);
+
+        let opaque = Farbfeld::merge_channels(2, 2, channels.red(), channels.green(), channels.blue(), None).unwrap();
+        assert!(opaque.pixels().iter().all(|pixel| pixel.alpha() == &65535));
+    }
+
+    #[test]
+    fn test_merge_channels_mismatch() {
+        match Farbfeld::merge_channels(2, 1, &[1, 2], &[1], &[1], Some(&[1, 2])) {
+            Err(Error::InvalidPlaneLength(Channel::Green, 2, 1)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        match Farbfeld::merge_channels(2, 1, &[1, 2], &[1, 2], &[1, 2], Some(&[1, 2, 3])) {
+            Err(Error::InvalidPlaneLength(Channel::Alpha, 2, 3)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Transforming the colours of Farbfeld images with matrices, such as sepia toning, saturation
+//! and hue rotation.
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+
+/// The Rec. 709 luminance coefficients of red, green and blue, which SVG rounds to 0.213, 0.715
+/// and 0.072.
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// A 4 by 5 matrix transforming the red, green, blue and alpha channels of a pixel, as used by
+/// SVG's `feColorMatrix`. Each row produces one channel, in that order, from the sum of the four
+/// channels scaled to 0 to 1 and multiplied by the first four entries, plus the fifth entry as an
+/// offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorMatrix {
+    rows: [[f32; 5]; 4]
+}
+
+impl ColorMatrix {
+    /// Creates a matrix from its rows.
+    pub fn new(rows: [[f32; 5]; 4]) -> ColorMatrix {
+        ColorMatrix { rows }
+    }
+
+    /// Creates a matrix which leaves pixels unchanged.
+    pub fn identity() -> ColorMatrix {
+        ColorMatrix::new([
+            [1.0, 0.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0, 0.0]
+        ])
+    }
+
+    /// Creates a matrix which tones pixels sepia with the coefficients of CSS's `sepia()` filter.
+    /// White becomes 100%, 100% and 93.7% red, green and blue.
+    pub fn sepia() -> ColorMatrix {
+        ColorMatrix::from_colours([
+            [0.393, 0.769, 0.189],
+            [0.349, 0.686, 0.168],
+            [0.272, 0.534, 0.131]
+        ])
+    }
+
+    /// Creates a matrix which replaces the red, green and blue channels of pixels with their
+    /// Rec. 709 luminance, the same as [saturate(0.0)](#method.saturate).
+    pub fn grayscale() -> ColorMatrix {
+        ColorMatrix::saturate(0.0)
+    }
+
+    /// Creates a matrix which scales the saturation of pixels by `amount`, where 0 is greyscale, 1
+    /// leaves pixels unchanged and larger amounts oversaturate. Colours are desaturated towards
+    /// their Rec. 709 luminance.
+    pub fn saturate(amount: f32) -> ColorMatrix {
+        let mut colours = [LUMINANCE; 3];
+        for (i, row) in colours.iter_mut().enumerate() {
+            for (j, weight) in row.iter_mut().enumerate() {
+                let identity = if i == j { 1.0 } else { 0.0 };
+                *weight += (identity - *weight) * amount;
+            }
+        }
+        ColorMatrix::from_colours(colours)
+    }
+
+    /// Creates a matrix which rotates the hue of pixels by `degrees` while approximately
+    /// preserving their luminance, as described by SVG's `hueRotate`. Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn hue_rotate(degrees: f32) -> ColorMatrix {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let [red, green, blue] = LUMINANCE;
+        // The directions the colours move in as the hue rotates, each summing to 0 so greys are
+        // unchanged.
+        let rotation = [
+            [-red, -green, 1.0 - blue],
+            [0.143, 0.140, -0.283],
+            [red - 1.0, green, blue]
+        ];
+        let mut colours = [LUMINANCE; 3];
+        for (i, row) in colours.iter_mut().enumerate() {
+            for (j, weight) in row.iter_mut().enumerate() {
+                let identity = if i == j { 1.0 } else { 0.0 };
+                *weight += (identity - *weight) * cos + rotation[i][j] * sin;
+            }
+        }
+        ColorMatrix::from_colours(colours)
+    }
+
+    /// Creates a matrix which mixes the red, green and blue channels with the given weights,
+    /// without an offset, and leaves alpha unchanged.
+    fn from_colours(colours: [[f32; 3]; 3]) -> ColorMatrix {
+        let mut matrix = ColorMatrix::identity();
+        for (row, colours) in matrix.rows.iter_mut().zip(colours.iter()) {
+            row[..3].copy_from_slice(colours);
+        }
+        matrix
+    }
+
+    /// Returns the rows of the matrix.
+    pub fn rows(&self) -> &[[f32; 5]; 4] {
+        &self.rows
+    }
+
+    /// Transforms the pixel, clamping each channel to 0 and 65535 and rounding to the nearest
+    /// value.
+    pub fn apply(&self, pixel: &Pixel) -> Pixel {
+        let mut input = [0.0; 4];
+        for (input, &value) in input.iter_mut().zip(pixel.iter()) {
+            *input = value as f32 / 65535.0;
+        }
+        let mut output = [0_u16; 4];
+        for (output, row) in output.iter_mut().zip(self.rows.iter()) {
+            let value = row.iter().zip(input.iter()).map(|(weight, input)| weight * input).sum::<f32>() + row[4];
+            // Casting saturates, mapping NaN to 0.
+            *output = (value.clamp(0.0, 1.0) * 65535.0 + 0.5) as u16;
+        }
+        Pixel::from(output)
+    }
+}
+
+impl Default for ColorMatrix {
+    fn default() -> ColorMatrix {
+        ColorMatrix::identity()
+    }
+}
+
+impl Farbfeld {
+    /// Transforms every pixel with the matrix as described by
+    /// [ColorMatrix::apply](color_matrix/struct.ColorMatrix.html#method.apply).
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::color_matrix::ColorMatrix;
+    /// let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(65535_u16, 65535, 65535, 7)]).unwrap();
+    /// farb.apply_color_matrix_in_place(&ColorMatrix::sepia());
+    ///
+    /// assert_eq!(&Pixel::new(65535_u16, 65535, 61406, 7), &farb[0]);
+    /// ```
+    pub fn apply_color_matrix_in_place(&mut self, matrix: &ColorMatrix) {
+        for pixel in self[..].iter_mut() {
+            *pixel = matrix.apply(pixel);
+        }
+    }
+
+    /// Returns a copy of the image transformed as described by
+    /// [apply_color_matrix_in_place](#method.apply_color_matrix_in_place).
+    pub fn apply_color_matrix(&self, matrix: &ColorMatrix) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.apply_color_matrix_in_place(matrix);
+        farb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::patterns::{linear_gradient, GradientDirection};
+
+    fn image() -> Farbfeld {
+        let from = Pixel::new(65535_u16, 1000, 0, 0);
+        let to = Pixel::new(3_u16, 50000, 65535, 65535);
+        linear_gradient(97, 13, from, to, GradientDirection::Diagonal)
+    }
+
+    #[test]
+    fn test_identity() {
+        let ramp = Farbfeld::new(256, 256, (0..=u16::MAX).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+        assert_eq!(ramp, ramp.apply_color_matrix(&ColorMatrix::identity()));
+        assert_eq!(ramp, ramp.apply_color_matrix(&ColorMatrix::saturate(1.0)));
+        assert_eq!(image(), image().apply_color_matrix(&ColorMatrix::default()));
+    }
+
+    #[test]
+    fn test_sepia() {
+        let white = Pixel::new(65535_u16, 65535, 65535, 65535);
+        assert_eq!(Pixel::new(65535_u16, 65535, 61406, 65535), ColorMatrix::sepia().apply(&white));
+        let black = Pixel::new(0_u16, 0, 0, 3);
+        assert_eq!(black, ColorMatrix::sepia().apply(&black));
+    }
+
+    #[test]
+    fn test_saturate() {
+        let grey = image().apply_color_matrix(&ColorMatrix::grayscale());
+        assert_eq!(grey, image().apply_color_matrix(&ColorMatrix::saturate(0.0)));
+        for (pixel, expected) in grey.pixels().iter().zip(image().grayscale().pixels()) {
+            for (&channel, &expected) in pixel.iter().zip(expected.iter()) {
+                assert!(channel.abs_diff(expected) <= 1, "{:?} isn't close to {:?}", pixel, expected);
+            }
+        }
+
+        let saturated = ColorMatrix::saturate(2.0).apply(&Pixel::new(40000_u16, 30000, 30000, 65535));
+        assert_eq!(&65535, saturated.alpha());
+        assert!(saturated.red() > &40000 && saturated.green() < &30000);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_hue_rotate() {
+        let ramp = Farbfeld::new(256, 1, (0..256_u16).map(|i| Pixel::new(i * 257, i * 257, i * 257, i)).collect()).unwrap();
+        let rotated = ramp.apply_color_matrix(&ColorMatrix::hue_rotate(123.0));
+        for (pixel, expected) in rotated.pixels().iter().zip(ramp.pixels()) {
+            for (&channel, &expected) in pixel.iter().zip(expected.iter()) {
+                assert!(channel.abs_diff(expected) <= 1, "{:?} isn't close to {:?}", pixel, expected);
+            }
+        }
+        for (pixel, expected) in image().apply_color_matrix(&ColorMatrix::hue_rotate(360.0)).pixels().iter().zip(image().pixels()) {
+            for (&channel, &expected) in pixel.iter().zip(expected.iter()) {
+                assert!(channel.abs_diff(expected) <= 1, "{:?} isn't close to {:?}", pixel, expected);
+            }
+        }
+
+        let red = ColorMatrix::hue_rotate(120.0).apply(&Pixel::new(65535_u16, 0, 0, 65535));
+        assert!(red.green() > red.red() && red.green() > red.blue(), "{:?} isn't mostly green", red);
+    }
+}
@@ -0,0 +1,203 @@
+//! Compositing whole Farbfeld images over each other.
+
+use crate::farbfeld::Farbfeld;
+use crate::patterns;
+use crate::pixel::Pixel;
+use crate::error::*;
+
+impl Farbfeld {
+    /// Returns this image composited over the backdrop, combining each pixel with the pixel at the
+    /// same position in the backdrop with [Pixel::over](pixel/struct.Pixel.html#method.over).
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(1, 1, vec![Pixel::new(65535_u16, 65535, 65535, 0)]).unwrap();
+    /// let backdrop = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2, 3, 65535)]).unwrap();
+    ///
+    /// assert_eq!(backdrop, farb.composite_over(&backdrop).unwrap());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::DimensionMismatch](error/enum.Error.html) containing both sizes if
+    /// the backdrop isn't the same size as the image.
+    pub fn composite_over(&self, backdrop: &Farbfeld) -> Result<Farbfeld> {
+        let mut composited = self.clone();
+        composited.composite_over_in_place(backdrop)?;
+        Ok(composited)
+    }
+
+    /// Composites this image over the backdrop in place, as described by
+    /// [composite_over](#method.composite_over).
+    ///
+    /// # Errors
+    /// Returns an [Error::DimensionMismatch](error/enum.Error.html) containing both sizes if
+    /// the backdrop isn't the same size as the image.
+    pub fn composite_over_in_place(&mut self, backdrop: &Farbfeld) -> Result<()> {
+        for (pixel, backdrop) in self.zip_pixels_mut(backdrop)? {
+            *pixel = pixel.over(*backdrop);
+        }
+        Ok(())
+    }
+
+    /// Returns the image composited over a solid background with
+    /// [Pixel::over](pixel/struct.Pixel.html#method.over), leaving every pixel opaque, such as
+    /// before saving to a format without transparency. The background's alpha is ignored, so it's
+    /// always treated as opaque.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(1, 1, vec![Pixel::new(65535_u16, 0, 0, 32768)]).unwrap();
+    /// let flat = farb.flatten(Pixel::new(0_u16, 0, 65535, 65535));
+    ///
+    /// assert_eq!(&[Pixel::new(32768_u16, 0, 32767, 65535)], flat.pixels());
+    /// ```
+    pub fn flatten(&self, background: Pixel) -> Farbfeld {
+        let mut flat = self.clone();
+        flat.flatten_in_place(background);
+        flat
+    }
+
+    /// Composites the image over a solid background in place, as described by
+    /// [flatten](#method.flatten).
+    pub fn flatten_in_place(&mut self, mut background: Pixel) {
+        *background.alpha_mut() = u16::MAX;
+        for pixel in self[..].iter_mut() {
+            *pixel = pixel.over(background);
+        }
+    }
+
+    /// Returns the image composited over a checkerboard of square cells `cell` pixels wide as
+    /// described by [composite_over](#method.composite_over), leaving every pixel opaque, such as
+    /// to preview transparency. The checkerboard starts with a cell of `light` in the top left
+    /// corner, as described by [checkerboard](patterns/fn.checkerboard.html), and the alpha of
+    /// `light` and `dark` is ignored, so they're always treated as opaque.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let (light, dark) = (Pixel::new(65535_u16, 65535, 65535, 65535), Pixel::new(0_u16, 0, 0, 65535));
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2, 3, 65535), Pixel::default()]).unwrap();
+    ///
+    /// assert_eq!(&[Pixel::new(1_u16, 2, 3, 65535), dark], farb.render_on_checker(1, light, dark).unwrap().pixels());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidAdjustment](error/enum.Error.html) if `cell` is 0.
+    pub fn render_on_checker(&self, cell: u32, mut light: Pixel, mut dark: Pixel) -> Result<Farbfeld> {
+        if cell == 0 {
+            return Err(Error::InvalidAdjustment("checkerboard cells must be at least 1 pixel wide"));
+        }
+        *light.alpha_mut() = u16::MAX;
+        *dark.alpha_mut() = u16::MAX;
+        self.composite_over(&patterns::checkerboard(*self.width(), *self.height(), cell, light, dark))
+    }
+
+    /// Returns the image composited over the checkerboard image viewers commonly show behind
+    /// transparent images, with cells 8 pixels wide alternating between light grey (#cccccc) and
+    /// dark grey (#999999), as described by [render_on_checker](#method.render_on_checker).
+    pub fn render_on_checker_default(&self) -> Farbfeld {
+        let (light, dark) = (Pixel::new(0xcccc_u16, 0xcccc, 0xcccc, 0xffff), Pixel::new(0x9999_u16, 0x9999, 0x9999, 0xffff));
+        self.render_on_checker(8, light, dark).expect("the cells aren't empty")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    fn solid(pixel: Pixel) -> Farbfeld {
+        patterns::solid(2, 2, pixel)
+    }
+
+    fn checker() -> Farbfeld {
+        let (white, black) = (Pixel::new(65535_u16, 65535, 65535, 32768), Pixel::new(0_u16, 0, 0, 32768));
+        patterns::checkerboard(2, 2, 1, white, black)
+    }
+
+    #[test]
+    fn test_opaque_backdrop() {
+        let composited = checker().composite_over(&solid(Pixel::new(1_u16, 2, 3, 65535))).unwrap();
+        assert!(composited.pixels().iter().all(|pixel| pixel.alpha() == &65535));
+    }
+
+    #[test]
+    fn test_transparent_source() {
+        let backdrop = Farbfeld::new(2, 2, vec![
+            Pixel::new(1_u16, 2, 3, 4), Pixel::new(5_u16, 6, 7, 65535),
+            Pixel::new(8_u16, 9, 10, 0), Pixel::new(65535_u16, 0, 65535, 30000)
+        ]).unwrap();
+        let composited = solid(Pixel::new(65535_u16, 40000, 1, 0)).composite_over(&backdrop).unwrap();
+        assert_eq!(backdrop, composited);
+    }
+
+    #[test]
+    fn test_checker() {
+        let mut farb = checker();
+        farb.composite_over_in_place(&solid(Pixel::new(65535_u16, 0, 0, 65535))).unwrap();
+        let (light, dark) = (Pixel::new(65535_u16, 32768, 32768, 65535), Pixel::new(32767_u16, 0, 0, 65535));
+        assert_eq!(&[light, dark, dark, light], farb.pixels());
+    }
+
+    #[test]
+    fn test_flatten_opaque() {
+        let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2, 3, 65535), Pixel::new(4_u16, 5, 6, 65535)]).unwrap();
+        assert_eq!(farb, farb.flatten(Pixel::new(65535_u16, 65535, 65535, 65535)));
+    }
+
+    #[test]
+    fn test_flatten_transparent() {
+        let mut farb = solid(Pixel::new(1_u16, 2, 3, 0));
+        farb.flatten_in_place(Pixel::new(100_u16, 200, 300, 0));
+        assert_eq!(solid(Pixel::new(100_u16, 200, 300, 65535)), farb);
+    }
+
+    #[test]
+    fn test_flatten_matches_over() {
+        let background = Pixel::new(10000_u16, 20000, 30000, 65535);
+        let flat = checker().flatten(background);
+        for (pixel, flat) in checker().pixels().iter().zip(flat.pixels()) {
+            assert_eq!(&pixel.over(background), flat);
+            assert_eq!(&65535, flat.alpha());
+        }
+    }
+
+    #[test]
+    fn test_render_on_checker() {
+        let (light, dark) = (Pixel::new(60000_u16, 60000, 60000, 65535), Pixel::new(20000_u16, 20000, 20000, 0));
+        let opaque = patterns::checkerboard(7, 5, 3, Pixel::new(1_u16, 2, 3, 65535), Pixel::new(65535_u16, 0, 9, 65535));
+        assert_eq!(opaque, opaque.render_on_checker(2, light, dark).unwrap());
+
+        let transparent = patterns::solid(7, 5, Pixel::new(65535_u16, 0, 0, 0));
+        let opaque_dark = Pixel::new(20000_u16, 20000, 20000, 65535);
+        assert_eq!(patterns::checkerboard(7, 5, 2, light, opaque_dark), transparent.render_on_checker(2, light, dark).unwrap());
+
+        let half = patterns::solid(4, 1, Pixel::new(65535_u16, 0, 0, 32768)).render_on_checker(2, light, dark).unwrap();
+        assert_eq!(&[Pixel::new(62768_u16, 30000, 30000, 65535); 2], &half.pixels()[..2]);
+        assert_eq!(&[Pixel::new(42768_u16, 10000, 10000, 65535); 2], &half.pixels()[2..]);
+
+        match opaque.render_on_checker(0, light, dark) {
+            Err(Error::InvalidAdjustment(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_render_on_checker_default() {
+        let preview = patterns::solid(17, 9, Pixel::default()).render_on_checker_default();
+        let (light, dark) = (Pixel::new(52428_u16, 52428, 52428, 65535), Pixel::new(39321_u16, 39321, 39321, 65535));
+        assert_eq!(patterns::checkerboard(17, 9, 8, light, dark), preview);
+    }
+
+    #[test]
+    fn test_dimension_mismatch() {
+        let backdrop = Farbfeld::new(4, 1, vec![Pixel::default(); 4]).unwrap();
+        match checker().composite_over(&backdrop) {
+            Err(Error::DimensionMismatch(2, 2, 4, 1)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        let mut farb = checker();
+        assert!(farb.composite_over_in_place(&backdrop).is_err());
+        assert_eq!(checker(), farb);
+    }
+}
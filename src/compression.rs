@@ -0,0 +1,277 @@
+//! Reading and writing compressed Farbfeld images. Gzip support requires the `flate2` feature and
+//! bzip2 support requires the `bzip2` feature.
+//!
+//! Compressed input is detected by sniffing the magic bytes at the start of the data, so files
+//! are decompressed correctly regardless of their extension.
+
+use std::path::Path;
+use std::fs::File;
+use std::io::{self, Read, Cursor, Write, BufWriter};
+
+#[cfg(feature = "flate2")]
+use flate2::{self, read::MultiGzDecoder, write::GzEncoder};
+#[cfg(feature = "bzip2")]
+use bzip2::{self, read::MultiBzDecoder, write::BzEncoder};
+
+use crate::farbfeld::Farbfeld;
+use crate::error::*;
+
+/// The magic bytes at the start of a gzip stream.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+/// The magic bytes at the start of a bzip2 stream.
+const BZIP2_MAGIC: &[u8] = b"BZh";
+
+/// A compression format and level used when writing compressed images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Gzip compression with the given level, from 0 (none) to 9 (best).
+    #[cfg(feature = "flate2")]
+    Gzip(u32),
+    /// Bzip2 compression with the given level, from 1 (fastest) to 9 (best).
+    #[cfg(feature = "bzip2")]
+    Bzip2(u32)
+}
+
+/// Wraps the given read in a decompressor if its data starts with gzip or bzip2 magic bytes,
+/// otherwise the data is passed through unchanged. The result can be given to any of the reading
+/// APIs, such as [FarbfeldStream](../stream/struct.FarbfeldStream.html), so large compressed
+/// images never need to be fully buffered.
+///
+/// # Errors
+/// <ul>
+///     <li>Returns an <a href="../error/enum.Error.html">Error::UnsupportedCompression</a>
+///     if the data is compressed with a format whose feature isn't enabled.</li>
+///     <li>Returns an <a href="../error/enum.Error.html">Error::IoError</a> if the start of
+///     the data cannot be read.</li>
+/// </ul>
+pub fn decompress<'a, R: Read + 'a>(mut read: R) -> Result<Box<dyn Read + 'a>> {
+    let mut magic = [0_u8; 3];
+    let len = read_magic(&mut read, &mut magic)?;
+    let read = Cursor::new(magic[..len].to_vec()).chain(read);
+    if magic[..len].starts_with(GZIP_MAGIC) {
+        gzip_reader(read)
+    } else if magic[..len].starts_with(BZIP2_MAGIC) {
+        bzip2_reader(read)
+    } else {
+        Ok(Box::new(read))
+    }
+}
+
+/// Reads into `magic` until it is full or the read reaches EOF, returning the number of bytes read.
+fn read_magic<R: Read>(read: &mut R, magic: &mut [u8]) -> Result<usize> {
+    let mut len = 0;
+    while len < magic.len() {
+        match read.read(&mut magic[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+            Err(err) => return Err(Error::IoError(err))
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(feature = "flate2")]
+fn gzip_reader<'a, R: Read + 'a>(read: R) -> Result<Box<dyn Read + 'a>> {
+    Ok(Box::new(MultiGzDecoder::new(read)))
+}
+
+#[cfg(not(feature = "flate2"))]
+fn gzip_reader<'a, R: Read + 'a>(_read: R) -> Result<Box<dyn Read + 'a>> {
+    Err(Error::UnsupportedCompression("gzip"))
+}
+
+#[cfg(feature = "bzip2")]
+fn bzip2_reader<'a, R: Read + 'a>(read: R) -> Result<Box<dyn Read + 'a>> {
+    Ok(Box::new(MultiBzDecoder::new(read)))
+}
+
+#[cfg(not(feature = "bzip2"))]
+fn bzip2_reader<'a, R: Read + 'a>(_read: R) -> Result<Box<dyn Read + 'a>> {
+    Err(Error::UnsupportedCompression("bzip2"))
+}
+
+/// A write counting the bytes passed through it.
+struct CountWrite<W> {
+    inner: W,
+    count: u64
+}
+
+impl<W: Write> Write for CountWrite<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Farbfeld {
+    /// Parses the file at the given path into a Farbfeld object, decompressing it first if it is
+    /// compressed, as described by [decompress](compression/fn.decompress.html). Uncompressed
+    /// files are parsed exactly like [from_file](#method.from_file).
+    ///
+    /// # Errors
+    /// Returns the errors of [decompress](compression/fn.decompress.html) and
+    /// [from_read](#method.from_read).
+    pub fn from_file_auto<T: AsRef<Path>>(path: T) -> Result<Farbfeld> {
+        File::open(path)
+            .map_err(Error::IoError)
+            .and_then(Farbfeld::from_read_auto)
+    }
+
+    /// Parses the entire of the given Read into a Farbfeld object, decompressing it first if it is
+    /// compressed, as described by [decompress](compression/fn.decompress.html).
+    ///
+    /// # Errors
+    /// Returns the errors of [decompress](compression/fn.decompress.html) and
+    /// [from_read](#method.from_read).
+    pub fn from_read_auto<T: Read>(read: T) -> Result<Farbfeld> {
+        decompress(read).and_then(Farbfeld::from_read)
+    }
+
+    /// Writes the image to the given write, compressed with the given compression. Returns the
+    /// number of compressed bytes written.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+    ///     std IoError during write.</li>
+    /// </ul>
+    pub fn save_compressed<W: Write>(&self, write: W, compression: Compression) -> Result<u64> {
+        let mut write = CountWrite { inner: write, count: 0 };
+        match compression {
+            #[cfg(feature = "flate2")]
+            Compression::Gzip(level) => {
+                let mut encoder = GzEncoder::new(&mut write, flate2::Compression::new(level));
+                self.save(&mut encoder)?;
+                encoder.finish().map_err(Error::IoError)?;
+            },
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2(level) => {
+                let mut encoder = BzEncoder::new(&mut write, bzip2::Compression::new(level));
+                self.save(&mut encoder)?;
+                encoder.finish().map_err(Error::IoError)?;
+            }
+        }
+        write.flush().map_err(Error::IoError)?;
+        Ok(write.count)
+    }
+
+    /// Writes the image to the file at the given path, compressed with the given compression. The
+    /// File is created if it doesn't exist or truncated if it does. Returns the number of
+    /// compressed bytes written.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the file cannot be
+    ///     created or written.</li>
+    /// </ul>
+    pub fn save_to_file_compressed<T: AsRef<Path>>(&self, path: T, compression: Compression) -> Result<u64> {
+        File::create(path)
+            .map(BufWriter::new)
+            .map_err(Error::IoError)
+            .and_then(|w| self.save_compressed(w, compression))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use crate::stream::FarbfeldStream;
+
+    /// One of the enabled compressions, for tests which work with either.
+    #[cfg(feature = "flate2")]
+    fn compression() -> Compression {
+        Compression::Gzip(1)
+    }
+
+    #[cfg(not(feature = "flate2"))]
+    fn compression() -> Compression {
+        Compression::Bzip2(1)
+    }
+
+    fn assert_same(org: &Farbfeld, test: &Farbfeld) {
+        assert_eq!(org.width(), test.width());
+        assert_eq!(org.height(), test.height());
+        assert_eq!(org.pixels(), test.pixels());
+    }
+
+    #[test]
+    fn test_from_file_auto_uncompressed() {
+        assert_same(&Farbfeld::from_file("test.ff").unwrap(), &Farbfeld::from_file_auto("test.ff").unwrap());
+    }
+
+    #[test]
+    fn test_decompress_short_input() {
+        let mut data = Vec::new();
+        decompress(&b"fa"[..]).unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(b"fa".to_vec(), data);
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_gzip_round_trip() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut data = Vec::new();
+        let written = farb.save_compressed(&mut data, Compression::Gzip(6)).unwrap();
+
+        assert_eq!(data.len() as u64, written);
+        assert_eq!(GZIP_MAGIC, &data[..2]);
+        assert_same(&farb, &Farbfeld::from_read_auto(&data[..]).unwrap());
+    }
+
+    #[test]
+    fn test_compressed_stream() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut data = Vec::new();
+        farb.save_compressed(&mut data, compression()).unwrap();
+        farb.save_compressed(&mut data, compression()).unwrap();
+
+        let frames = FarbfeldStream::new(decompress(&data[..]).unwrap())
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(2, frames.len());
+        assert_same(&farb, &frames[1]);
+    }
+
+    #[test]
+    fn test_compressed_file() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut path = env::temp_dir();
+        path.push(format!("ruff-compressed-{}.ff.z", process::id()));
+        let written = farb.save_to_file_compressed(&path, compression()).unwrap();
+
+        assert_eq!(fs::metadata(&path).unwrap().len(), written);
+        assert_same(&farb, &Farbfeld::from_file_auto(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "bzip2")]
+    #[test]
+    fn test_bzip2_round_trip() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut data = Vec::new();
+        farb.save_compressed(&mut data, Compression::Bzip2(6)).unwrap();
+
+        assert_eq!(BZIP2_MAGIC, &data[..3]);
+        assert_same(&farb, &Farbfeld::from_read_auto(&data[..]).unwrap());
+    }
+
+    #[cfg(not(feature = "bzip2"))]
+    #[test]
+    fn test_bzip2_unsupported() {
+        match decompress(&b"BZh91AY&SY"[..]) {
+            Err(Error::UnsupportedCompression("bzip2")) => {},
+            res => panic!("Unexpected result {:?}", res.map(|_| ()))
+        }
+    }
+}
@@ -0,0 +1,790 @@
+//! Convolving Farbfeld images with arbitrary kernels, such as blurs, sharpens and embosses.
+
+use alloc::borrow::Cow;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+use crate::error::*;
+
+/// A matrix of weights with an odd width and height, centred on the pixel being convolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+    width: u32,
+    height: u32,
+    weights: Cow<'static, [f32]>
+}
+
+impl Kernel {
+    /// Leaves the image unchanged.
+    pub const IDENTITY: Kernel = Kernel::const_3x3(&[
+        0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0
+    ]);
+
+    /// Averages each pixel with its 8 neighbours.
+    pub const BOX_3X3: Kernel = Kernel::const_3x3(&[
+        1.0 / 9.0, 1.0 / 9.0, 1.0 / 9.0,
+        1.0 / 9.0, 1.0 / 9.0, 1.0 / 9.0,
+        1.0 / 9.0, 1.0 / 9.0, 1.0 / 9.0
+    ]);
+
+    /// Exaggerates the difference between each pixel and its direct neighbours.
+    pub const SHARPEN_3X3: Kernel = Kernel::const_3x3(&[
+        0.0, -1.0, 0.0,
+        -1.0, 5.0, -1.0,
+        0.0, -1.0, 0.0
+    ]);
+
+    /// Highlights edges running from the top right to the bottom left, as if lit from the top
+    /// left.
+    pub const EMBOSS_3X3: Kernel = Kernel::const_3x3(&[
+        -2.0, -1.0, 0.0,
+        -1.0, 1.0, 1.0,
+        0.0, 1.0, 2.0
+    ]);
+
+    /// Creates a 3 by 3 kernel from a constant array of weights in row-major order.
+    const fn const_3x3(weights: &'static [f32; 9]) -> Kernel {
+        Kernel {
+            width: 3,
+            height: 3,
+            weights: Cow::Borrowed(weights)
+        }
+    }
+
+    /// Creates a kernel with the given dimensions from its weights in row-major order.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidKernel](../error/enum.Error.html) if either dimension is even
+    /// or width * height != weights.len().
+    pub fn new(width: u32, height: u32, weights: Vec<f32>) -> Result<Kernel> {
+        if width.is_multiple_of(2) || height.is_multiple_of(2) {
+            return Err(Error::InvalidKernel("dimensions must be odd"));
+        }
+        if width as u64 * height as u64 != weights.len() as u64 {
+            return Err(Error::InvalidKernel("weight count doesn't match the dimensions"));
+        }
+        Ok(Kernel {
+            width,
+            height,
+            weights: Cow::Owned(weights)
+        })
+    }
+
+    /// Creates a 3 by 3 kernel from its weights in row-major order.
+    pub fn from_3x3(weights: [f32; 9]) -> Kernel {
+        Kernel::new(3, 3, weights.to_vec()).expect("3x3 kernels are valid")
+    }
+
+    /// Creates a 5 by 5 kernel from its weights in row-major order.
+    pub fn from_5x5(weights: [f32; 25]) -> Kernel {
+        Kernel::new(5, 5, weights.to_vec()).expect("5x5 kernels are valid")
+    }
+
+    /// Returns the width of the kernel.
+    pub fn width(&self) -> &u32 {
+        &self.width
+    }
+
+    /// Returns the height of the kernel.
+    pub fn height(&self) -> &u32 {
+        &self.height
+    }
+
+    /// Returns the weights of the kernel in row-major order.
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+}
+
+/// How pixels outside the image are sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeMode {
+    /// Use the nearest pixel at the edge of the image.
+    Clamp,
+    /// Wrap around to the opposite edge, as if the image were tiled.
+    Wrap,
+    /// Reflect back into the image without repeating the edge pixel, so the pixel one past the
+    /// edge is the pixel one before it.
+    Mirror,
+    /// Use the given pixel.
+    Fill(Pixel)
+}
+
+/// How the alpha channel is treated when processing an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Process the alpha channel the same way as the other channels.
+    Process,
+    /// Leave the alpha channel unchanged.
+    Preserve
+}
+
+/// The largest radius used by [Farbfeld::box_blur](../struct.Farbfeld.html#method.box_blur), so
+/// the sums of a window of channels always fit in a u64.
+const MAX_BOX_RADIUS: u32 = 1 << 23;
+
+/// Sums the window of `2 * radius + 1` values around each index of a line of `len` values,
+/// clamping indices outside the line to its ends, and passes each sum to `emit` in order.
+/// `len` must be at least 1.
+fn sliding_sums<S, E>(len: usize, radius: u64, sample: S, mut emit: E)
+    where S: Fn(usize) -> [u64; 4], E: FnMut([u64; 4]) {
+    let last = len - 1;
+    let add = |sums: &mut [u64; 4], values: [u64; 4], times: u64| {
+        for (sum, value) in sums.iter_mut().zip(values.iter()) {
+            *sum += value * times;
+        }
+    };
+    let mut sums = [0; 4];
+    add(&mut sums, sample(0), radius);
+    for i in 0..=(radius.min(last as u64) as usize) {
+        add(&mut sums, sample(i), 1);
+    }
+    add(&mut sums, sample(last), radius.saturating_sub(last as u64));
+    emit(sums);
+    for i in 1..len {
+        // Adding before subtracting keeps the sums from underflowing.
+        add(&mut sums, sample((i as u64 + radius).min(last as u64) as usize), 1);
+        let leaving = sample((i as u64).saturating_sub(radius + 1) as usize);
+        for (sum, value) in sums.iter_mut().zip(leaving.iter()) {
+            *sum -= value;
+        }
+        emit(sums);
+    }
+}
+
+/// Returns the weights of a normalised gaussian kernel with the given positive, finite standard
+/// deviation, truncated at 3 standard deviations either side of the centre or `max_radius`,
+/// whichever is smaller. Returns None if the kernel wouldn't change the image, such as when the
+/// standard deviation is so small that its variance underflows to 0.
+#[cfg(feature = "std")]
+fn gaussian_weights(sigma: f32, max_radius: u32) -> Option<Vec<f32>> {
+    let variance = 2.0 * sigma * sigma;
+    let radius = ((3.0 * sigma).ceil() as i64).min(max_radius as i64);
+    if variance == 0.0 || radius == 0 {
+        return None;
+    }
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|x| (-(x as f32).powi(2) / variance).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    Some(weights.into_iter().map(|weight| weight / total).collect())
+}
+
+/// Convolves each line of `len` values with the 1D kernel, clamping indices outside the line to its
+/// ends, and passes each result to `emit` in order. `len` must be at least 1.
+#[cfg(feature = "std")]
+fn convolve_line<S, E>(len: usize, weights: &[f32], sample: S, mut emit: E)
+    where S: Fn(usize) -> [f32; 4], E: FnMut([f32; 4]) {
+    let radius = (weights.len() / 2) as i64;
+    for i in 0..len as i64 {
+        let mut sums = [0_f32; 4];
+        for (offset, weight) in weights.iter().enumerate() {
+            let index = (i + offset as i64 - radius).clamp(0, len as i64 - 1) as usize;
+            for (sum, value) in sums.iter_mut().zip(sample(index).iter()) {
+                *sum += value * weight;
+            }
+        }
+        emit(sums);
+    }
+}
+
+/// The horizontal and vertical gradients of the luminance of an image, stored as planes holding a
+/// value for every pixel in row-major order, created by
+/// [Farbfeld::sobel_xy](../struct.Farbfeld.html#method.sobel_xy). Gradients are positive where the
+/// luminance increases to the right or downwards, and range from -262140 to 262140.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gradients {
+    width: u32,
+    height: u32,
+    x: Vec<i32>,
+    y: Vec<i32>
+}
+
+impl Gradients {
+    /// Returns the width of each plane.
+    pub fn width(&self) -> &u32 {
+        &self.width
+    }
+
+    /// Returns the height of each plane.
+    pub fn height(&self) -> &u32 {
+        &self.height
+    }
+
+    /// Returns the plane of horizontal gradients.
+    pub fn x(&self) -> &[i32] {
+        &self.x
+    }
+
+    /// Returns the plane of vertical gradients.
+    pub fn y(&self) -> &[i32] {
+        &self.y
+    }
+
+    /// Consumes the gradients, returning the horizontal and vertical planes in that order.
+    pub fn into_planes(self) -> (Vec<i32>, Vec<i32>) {
+        (self.x, self.y)
+    }
+}
+
+/// The largest gradient magnitude the sobel kernels can produce, found when both gradients are
+/// 4 * 65535.
+const MAX_SOBEL_MAGNITUDE: u64 = 370723;
+
+/// Maps a coordinate which may be outside `0..len` into it, or returns None if the edge mode
+/// fills it.
+pub(crate) fn edge_coordinate(coordinate: i64, len: u32, edge: EdgeMode) -> Option<u32> {
+    let len = len as i64;
+    if (0..len).contains(&coordinate) {
+        return Some(coordinate as u32);
+    }
+    match edge {
+        EdgeMode::Clamp => Some(coordinate.clamp(0, len - 1) as u32),
+        EdgeMode::Wrap => Some(coordinate.rem_euclid(len) as u32),
+        EdgeMode::Mirror if len == 1 => Some(0),
+        EdgeMode::Mirror => {
+            let period = 2 * (len - 1);
+            let coordinate = coordinate.rem_euclid(period);
+            Some(if coordinate < len { coordinate } else { period - coordinate } as u32)
+        },
+        EdgeMode::Fill(_) => None
+    }
+}
+
+impl Farbfeld {
+    /// Returns the pixel at the given coordinates, which may be anywhere, sampled as described by
+    /// `edge`. Returns transparent black for coordinates outside an empty image, unless `edge`
+    /// fills them with another pixel.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::convolve::EdgeMode;
+    /// let farb = Farbfeld::new(3, 1, (0..3_u16).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+    ///
+    /// assert_eq!(farb[1], farb.get_pixel_edge(-1, 0, EdgeMode::Mirror));
+    /// assert_eq!(Pixel::new(9_u16, 9, 9, 9), farb.get_pixel_edge(3, 0, EdgeMode::Fill(Pixel::new(9_u16, 9, 9, 9))));
+    /// ```
+    pub fn get_pixel_edge(&self, x: i64, y: i64, edge: EdgeMode) -> Pixel {
+        match (edge, self.pixels().is_empty()) {
+            (EdgeMode::Fill(pixel), true) => return pixel,
+            (_, true) => return Pixel::default(),
+            _ => {}
+        }
+        match (edge_coordinate(x, *self.width(), edge), edge_coordinate(y, *self.height(), edge), edge) {
+            (Some(x), Some(y), _) => self[y as usize * *self.width() as usize + x as usize],
+            (_, _, EdgeMode::Fill(pixel)) => pixel,
+            _ => unreachable!("only filling leaves coordinates outside the image")
+        }
+    }
+
+    /// Returns the pixel at the given coordinates, which may be anywhere, moving coordinates
+    /// outside the image to the nearest edge. Returns transparent black if the image is empty.
+    pub fn get_pixel_clamped(&self, x: i64, y: i64) -> Pixel {
+        self.get_pixel_edge(x, y, EdgeMode::Clamp)
+    }
+
+    /// Returns the pixel at the given coordinates, which may be anywhere, wrapping coordinates
+    /// outside the image around to the opposite edge as if the image were tiled. Each coordinate
+    /// is taken modulo the dimension with the result always positive, so -1 is the last column or
+    /// row. Returns transparent black if the image is empty.
+    pub fn get_pixel_wrapped(&self, x: i64, y: i64) -> Pixel {
+        self.get_pixel_edge(x, y, EdgeMode::Wrap)
+    }
+
+    /// Returns the pixel at the given coordinates, which may be anywhere, reflecting coordinates
+    /// outside the image back into it without repeating the edge pixel, so -1 is the second column
+    /// or row and the reflections repeat every `2 * (dimension - 1)` pixels. Every coordinate of a
+    /// dimension of 1 is 0. Returns transparent black if the image is empty.
+    pub fn get_pixel_mirrored(&self, x: i64, y: i64) -> Pixel {
+        self.get_pixel_edge(x, y, EdgeMode::Mirror)
+    }
+
+    /// Convolves the red, green and blue channels of the image with the kernel, leaving alpha
+    /// unchanged, as described by [convolve_with](#method.convolve_with).
+    pub fn convolve(&self, kernel: &Kernel, edge: EdgeMode) -> Farbfeld {
+        self.convolve_with(kernel, edge, AlphaMode::Preserve)
+    }
+
+    /// Returns a copy of the image where each channel of each pixel is the sum of the channels of
+    /// the pixels around it multiplied by the corresponding weights of the kernel, rounded to the
+    /// nearest value and clamped to 0 and 65535. Pixels outside the image are sampled as described
+    /// by `edge`, and the alpha channel is convolved or left unchanged as described by `alpha`.
+    ///
+    /// Convolving the alpha channel of an image with transparency can darken the edges of opaque
+    /// areas, as the colours of transparent pixels are mixed in at full weight.
+    pub fn convolve_with(&self, kernel: &Kernel, edge: EdgeMode, alpha: AlphaMode) -> Farbfeld {
+        if self.pixels().is_empty() {
+            return self.clone();
+        }
+        let (half_width, half_height) = ((kernel.width / 2) as i64, (kernel.height / 2) as i64);
+        let mut pixels = Vec::with_capacity(self.pixels().len());
+        for y in 0..*self.height() as i64 {
+            for x in 0..*self.width() as i64 {
+                let mut sums = [0_f32; 4];
+                for (i, weight) in kernel.weights().iter().enumerate() {
+                    let kernel_x = (i % kernel.width as usize) as i64 - half_width;
+                    let kernel_y = (i / kernel.width as usize) as i64 - half_height;
+                    let pixel = self.get_pixel_edge(x + kernel_x, y + kernel_y, edge);
+                    for (sum, channel) in sums.iter_mut().zip(pixel.iter()) {
+                        *sum += *channel as f32 * weight;
+                    }
+                }
+                // Casting saturates, clamping the channels to 0 and 65535.
+                let [red, green, blue, alpha_sum] = sums.map(|sum| (sum + 0.5) as u16);
+                let alpha_channel = match alpha {
+                    AlphaMode::Process => alpha_sum,
+                    AlphaMode::Preserve => *self[(y * *self.width() as i64 + x) as usize].alpha()
+                };
+                pixels.push(Pixel::new(red, green, blue, alpha_channel));
+            }
+        }
+        Farbfeld::new(*self.width(), *self.height(), pixels).expect("convolving keeps the dimensions")
+    }
+
+    /// Returns a copy of the image where every channel of each pixel, including alpha, is the
+    /// average of the square of pixels extending `radius` pixels around it, clamping pixels outside
+    /// the image to its edges. A radius of 0 leaves the image unchanged, and radii above 2^23 are
+    /// treated as 2^23.
+    ///
+    /// This produces the same image as [convolve_with](#method.convolve_with) with a square kernel
+    /// of equal weights, but keeps a running sum of each row and column, so the time taken doesn't
+    /// depend on the radius.
+    pub fn box_blur(&self, radius: u32) -> Farbfeld {
+        if radius == 0 || self.pixels().is_empty() {
+            return self.clone();
+        }
+        let radius = radius.min(MAX_BOX_RADIUS) as u64;
+        let (width, height) = (*self.width() as usize, *self.height() as usize);
+        let mut rows = Vec::with_capacity(self.pixels().len());
+        for row in self.pixels().chunks(width) {
+            sliding_sums(width, radius, |x| <[u16; 4]>::from(row[x]).map(u64::from), |sums| rows.push(sums));
+        }
+        let area = (2 * radius + 1) * (2 * radius + 1);
+        let mut pixels = vec![Pixel::default(); self.pixels().len()];
+        for x in 0..width {
+            let mut y = 0;
+            sliding_sums(height, radius, |y| rows[y * width + x], |sums| {
+                pixels[y * width + x] = sums.map(|sum| ((sum + area / 2) / area) as u16).into();
+                y += 1;
+            });
+        }
+        Farbfeld::new(*self.width(), *self.height(), pixels).expect("blurring keeps the dimensions")
+    }
+
+    /// Blurs every channel of the image, including alpha, as described by
+    /// [gaussian_blur_with](#method.gaussian_blur_with). Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn gaussian_blur(&self, sigma: f32) -> Farbfeld {
+        self.gaussian_blur_with(sigma, AlphaMode::Process)
+    }
+
+    /// Returns a copy of the image convolved with a gaussian kernel with a standard deviation of
+    /// `sigma` pixels, truncated at 3 standard deviations, clamping pixels outside the image to its
+    /// edges. The alpha channel is blurred or left unchanged as described by `alpha`. A sigma which
+    /// isn't positive and finite leaves the image unchanged, and the kernel never extends further
+    /// than the larger dimension of the image. Requires the `std` feature.
+    ///
+    /// The image is blurred horizontally then vertically, so the time taken grows with sigma
+    /// rather than its square. Channels are blurred independently of alpha, so the colours of
+    /// transparent pixels bleed into their neighbours, which can leave dark halos around opaque
+    /// areas next to transparent black.
+    #[cfg(feature = "std")]
+    pub fn gaussian_blur_with(&self, sigma: f32, alpha: AlphaMode) -> Farbfeld {
+        if !sigma.is_finite() || sigma <= 0.0 || self.pixels().is_empty() {
+            return self.clone();
+        }
+        let weights = match gaussian_weights(sigma, *self.width().max(self.height())) {
+            Some(weights) => weights,
+            None => return self.clone()
+        };
+        let (width, height) = (*self.width() as usize, *self.height() as usize);
+        let mut rows = Vec::with_capacity(self.pixels().len());
+        for row in self.pixels().chunks(width) {
+            convolve_line(width, &weights, |x| <[u16; 4]>::from(row[x]).map(f32::from), |sums| rows.push(sums));
+        }
+        let mut pixels = self.pixels().to_vec();
+        for x in 0..width {
+            let mut y = 0;
+            convolve_line(height, &weights, |y| rows[y * width + x], |sums| {
+                // Casting saturates, clamping the channels to 0 and 65535.
+                let [red, green, blue, alpha_sum] = sums.map(|sum| (sum + 0.5) as u16);
+                let pixel = &mut pixels[y * width + x];
+                *pixel = match alpha {
+                    AlphaMode::Process => Pixel::new(red, green, blue, alpha_sum),
+                    AlphaMode::Preserve => Pixel::new(red, green, blue, *pixel.alpha())
+                };
+                y += 1;
+            });
+        }
+        Farbfeld::new(*self.width(), *self.height(), pixels).expect("blurring keeps the dimensions")
+    }
+
+    /// Sharpens the red, green and blue channels of the image with an unsharp mask, leaving alpha
+    /// unchanged. Each channel becomes `channel + amount * (channel - blurred)`, rounded to the
+    /// nearest value and clamped to 0 and 65535, where `blurred` is the channel after a
+    /// [gaussian_blur](#method.gaussian_blur) of `sigma`. Channels which differ from their blurred
+    /// value by `threshold` or less are left unchanged, so noise in flat areas isn't amplified.
+    /// Requires the `std` feature.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(4, 1, vec![
+    ///     Pixel::new(16384_u16, 16384, 16384, 65535), Pixel::new(16384_u16, 16384, 16384, 65535),
+    ///     Pixel::new(49152_u16, 49152, 49152, 65535), Pixel::new(49152_u16, 49152, 49152, 65535)
+    /// ]).unwrap();
+    /// let sharpened = farb.sharpen(1.0, 1.0, 0);
+    ///
+    /// assert!(sharpened[1].red() < &16384);
+    /// assert!(sharpened[2].red() > &49152);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn sharpen(&self, amount: f32, sigma: f32, threshold: u16) -> Farbfeld {
+        let blurred = self.gaussian_blur_with(sigma, AlphaMode::Preserve);
+        let pixels = self.pixels().iter().zip(blurred.pixels()).map(|(pixel, blurred)| {
+            let mut channels: [u16; 4] = (*pixel).into();
+            for (channel, blurred) in channels[..3].iter_mut().zip(blurred.iter()) {
+                let difference = *channel as f32 - *blurred as f32;
+                if difference.abs() > threshold as f32 {
+                    // Casting saturates, clamping the channel to 0 and 65535.
+                    *channel = (*channel as f32 + amount * difference + 0.5) as u16;
+                }
+            }
+            channels.into()
+        }).collect();
+        Farbfeld::new(*self.width(), *self.height(), pixels).expect("sharpening keeps the dimensions")
+    }
+
+    /// Finds the horizontal and vertical gradients of the luminance of each pixel using the 3 by 3
+    /// sobel kernels, clamping pixels outside the image to its edges.
+    pub fn sobel_xy(&self) -> Gradients {
+        let (width, height) = (*self.width() as i64, *self.height() as i64);
+        let luminance: Vec<i32> = self.pixels().iter().map(|pixel| pixel.luminance() as i32).collect();
+        let at = |x: i64, y: i64| luminance[(y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize];
+        let mut gradients = Gradients {
+            width: *self.width(),
+            height: *self.height(),
+            x: Vec::with_capacity(luminance.len()),
+            y: Vec::with_capacity(luminance.len())
+        };
+        for y in 0..height {
+            for x in 0..width {
+                gradients.x.push(at(x + 1, y - 1) + 2 * at(x + 1, y) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1) - 2 * at(x - 1, y) - at(x - 1, y + 1));
+                gradients.y.push(at(x - 1, y + 1) + 2 * at(x, y + 1) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1) - 2 * at(x, y - 1) - at(x + 1, y - 1));
+            }
+        }
+        gradients
+    }
+
+    /// Returns an opaque grayscale image of the magnitude of the
+    /// [sobel_xy](#method.sobel_xy) gradients of each pixel, scaled so the largest possible
+    /// magnitude is 65535. Edges are bright and flat areas are black.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let black = Pixel::new(0_u16, 0, 0, 65535);
+    /// let white = Pixel::new(65535_u16, 65535, 65535, 65535);
+    /// let farb = Farbfeld::new(4, 1, vec![black, black, white, white]).unwrap();
+    /// let edges = farb.sobel();
+    ///
+    /// assert_eq!(&0, edges[0].red());
+    /// assert!(edges[1].red() > &40000);
+    /// ```
+    pub fn sobel(&self) -> Farbfeld {
+        let gradients = self.sobel_xy();
+        let pixels = gradients.x.iter().zip(&gradients.y).map(|(&x, &y)| {
+            let magnitude = (x as i64 * x as i64 + y as i64 * y as i64).unsigned_abs().isqrt();
+            let value = ((magnitude * u16::MAX as u64 + MAX_SOBEL_MAGNITUDE / 2) / MAX_SOBEL_MAGNITUDE) as u16;
+            Pixel::new(value, value, value, u16::MAX)
+        }).collect();
+        Farbfeld::new(*self.width(), *self.height(), pixels).expect("edge detection keeps the dimensions")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn impulse() -> Farbfeld {
+        let mut pixels = vec![Pixel::new(0_u16, 0, 0, 65535); 16];
+        pixels[0] = Pixel::new(65535_u16, 65535, 65535, 65535);
+        Farbfeld::new(4, 4, pixels).unwrap()
+    }
+
+    fn red_at(farb: &Farbfeld, x: usize, y: usize) -> u16 {
+        *farb[y * *farb.width() as usize + x].red()
+    }
+
+    #[test]
+    fn test_identity() {
+        let farb = Farbfeld::new(3, 2, (0..6_u16).map(|i| Pixel::new(i * 1000, i, 65535 - i, i * 7)).collect()).unwrap();
+        for &edge in &[EdgeMode::Clamp, EdgeMode::Wrap, EdgeMode::Mirror, EdgeMode::Fill(Pixel::default())] {
+            assert_eq!(farb, farb.convolve(&Kernel::IDENTITY, edge));
+            assert_eq!(farb, farb.convolve_with(&Kernel::IDENTITY, edge, AlphaMode::Process));
+        }
+    }
+
+    #[test]
+    fn test_get_pixel_edges() {
+        let farb = Farbfeld::new(3, 2, (0..6_u16).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+        let at = |x: u16, y: u16| Pixel::new(y * 3 + x, y * 3 + x, y * 3 + x, y * 3 + x);
+        // The coordinates, followed by the pixels found when clamping, wrapping and mirroring.
+        let cases = [
+            ((0, 0), (0, 0), (0, 0), (0, 0)),
+            ((2, 1), (2, 1), (2, 1), (2, 1)),
+            ((-1, 0), (0, 0), (2, 0), (1, 0)),
+            ((3, 1), (2, 1), (0, 1), (1, 1)),
+            ((4, -1), (2, 0), (1, 1), (0, 1)),
+            ((-5, 2), (0, 1), (1, 0), (1, 0)),
+            ((-7, -8), (0, 0), (2, 0), (1, 0)),
+            ((i64::MIN, i64::MAX), (0, 1), (1, 1), (0, 1)),
+            ((1_000_000_000_000_000, -1_000_000_000_000_001), (2, 0), (1, 1), (0, 1))
+        ];
+        for &((x, y), clamped, wrapped, mirrored) in cases.iter() {
+            assert_eq!(at(clamped.0, clamped.1), farb.get_pixel_clamped(x, y), "clamping ({}, {})", x, y);
+            assert_eq!(at(wrapped.0, wrapped.1), farb.get_pixel_wrapped(x, y), "wrapping ({}, {})", x, y);
+            assert_eq!(at(mirrored.0, mirrored.1), farb.get_pixel_mirrored(x, y), "mirroring ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn test_get_pixel_degenerate() {
+        let column = Farbfeld::new(1, 3, (0..3_u16).map(|i| Pixel::new(i, 0, 0, 0)).collect()).unwrap();
+        let row = Farbfeld::new(3, 1, (0..3_u16).map(|i| Pixel::new(i, 0, 0, 0)).collect()).unwrap();
+        for &(x, y) in &[(-2, 0), (5, 0), (i64::MIN, 0), (i64::MAX, 0)] {
+            assert_eq!(row.get_pixel_clamped(x, y), column.get_pixel_clamped(y, x));
+            assert_eq!(row.get_pixel_wrapped(x, y), column.get_pixel_wrapped(y, x));
+            assert_eq!(row.get_pixel_mirrored(x, y), column.get_pixel_mirrored(y, x));
+            assert_eq!(row.get_pixel_mirrored(x, 0), row.get_pixel_mirrored(x, y + 17));
+        }
+        assert_eq!(Pixel::new(1_u16, 0, 0, 0), row.get_pixel_mirrored(5, -4));
+        assert_eq!(Pixel::new(2_u16, 0, 0, 0), column.get_pixel_wrapped(9, -1));
+
+        let empty = Farbfeld::new(0, 4, Vec::new()).unwrap();
+        assert_eq!(Pixel::default(), empty.get_pixel_clamped(0, 0));
+        assert_eq!(Pixel::default(), empty.get_pixel_wrapped(-1, 3));
+        let fill = Pixel::new(1_u16, 2, 3, 4);
+        assert_eq!(fill, empty.get_pixel_edge(1, 1, EdgeMode::Fill(fill)));
+    }
+
+    #[test]
+    fn test_box_clamp() {
+        let blurred = impulse().convolve(&Kernel::BOX_3X3, EdgeMode::Clamp);
+        assert_eq!(29127, red_at(&blurred, 0, 0));
+        assert_eq!(14563, red_at(&blurred, 1, 0));
+        assert_eq!(14563, red_at(&blurred, 0, 1));
+        assert_eq!(7282, red_at(&blurred, 1, 1));
+        assert_eq!(0, red_at(&blurred, 2, 2));
+        assert_eq!(0, red_at(&blurred, 3, 3));
+        assert!(blurred.pixels().iter().all(|pixel| pixel.alpha() == &65535));
+    }
+
+    #[test]
+    fn test_box_wrap() {
+        let blurred = impulse().convolve(&Kernel::BOX_3X3, EdgeMode::Wrap);
+        for &(x, y) in &[(0, 0), (1, 0), (3, 0), (0, 3), (3, 3), (1, 1)] {
+            assert_eq!(7282, red_at(&blurred, x, y));
+        }
+        assert_eq!(0, red_at(&blurred, 2, 2));
+        assert_eq!(0, red_at(&blurred, 2, 0));
+    }
+
+    #[test]
+    fn test_fill_and_mirror() {
+        let filled = impulse().convolve(&Kernel::BOX_3X3, EdgeMode::Fill(Pixel::new(65535_u16, 0, 0, 0)));
+        assert_eq!(43690, red_at(&filled, 0, 0));
+
+        assert_eq!(Some(1), edge_coordinate(-1, 4, EdgeMode::Mirror));
+        assert_eq!(Some(2), edge_coordinate(4, 4, EdgeMode::Mirror));
+        assert_eq!(Some(0), edge_coordinate(6, 4, EdgeMode::Mirror));
+        assert_eq!(Some(0), edge_coordinate(-3, 1, EdgeMode::Mirror));
+        assert_eq!(Some(3), edge_coordinate(-1, 4, EdgeMode::Wrap));
+    }
+
+    /// Blurs the image by averaging every pixel in the window around each pixel.
+    fn brute_force_box_blur(farb: &Farbfeld, radius: i64) -> Farbfeld {
+        let area = ((2 * radius + 1) * (2 * radius + 1)) as u64;
+        let mut pixels = Vec::new();
+        for y in 0..*farb.height() as i64 {
+            for x in 0..*farb.width() as i64 {
+                let mut sums = [0_u64; 4];
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let pixel = farb.get_pixel_edge(x + dx, y + dy, EdgeMode::Clamp);
+                        for (sum, channel) in sums.iter_mut().zip(pixel.iter()) {
+                            *sum += *channel as u64;
+                        }
+                    }
+                }
+                pixels.push(sums.map(|sum| ((sum + area / 2) / area) as u16).into());
+            }
+        }
+        Farbfeld::new(*farb.width(), *farb.height(), pixels).unwrap()
+    }
+
+    #[test]
+    fn test_box_blur() {
+        let farb = Farbfeld::new(7, 5, (0..35_u32)
+            .map(|i| Pixel::new((i * 7919 % 65536) as u16, (i * 104729 % 65536) as u16, (i * 31 % 65536) as u16, 65535 - i as u16))
+            .collect()).unwrap();
+        assert_eq!(farb, farb.box_blur(0));
+        for radius in 1..10 {
+            assert_eq!(brute_force_box_blur(&farb, radius), farb.box_blur(radius as u32), "radius {}", radius);
+        }
+
+        let constant = Farbfeld::new(3, 1, vec![Pixel::new(65535_u16, 65535, 65535, 65535); 3]).unwrap();
+        assert_eq!(constant, constant.box_blur(u32::MAX));
+        let empty = Farbfeld::new(0, 0, Vec::new()).unwrap();
+        assert_eq!(empty, empty.box_blur(3));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_gaussian_blur_impulse() {
+        let mut pixels = vec![Pixel::new(0_u16, 0, 0, 65535); 225];
+        pixels[112] = Pixel::new(65535_u16, 65535, 65535, 0);
+        let farb = Farbfeld::new(15, 15, pixels).unwrap();
+        let blurred = farb.gaussian_blur_with(1.5, AlphaMode::Preserve);
+
+        for y in 0..15 {
+            for x in 0..15 {
+                assert_eq!(red_at(&blurred, x, y), red_at(&blurred, 14 - x, y));
+                assert_eq!(red_at(&blurred, x, y), red_at(&blurred, x, 14 - y));
+                assert_eq!(red_at(&blurred, x, y), red_at(&blurred, y, x));
+            }
+        }
+        assert!(red_at(&blurred, 7, 7) > red_at(&blurred, 6, 7));
+        assert!(red_at(&blurred, 6, 7) > red_at(&blurred, 5, 7));
+        let total: i64 = blurred.pixels().iter().map(|pixel| *pixel.red() as i64).sum();
+        assert!((total - 65535).abs() < 113, "total {}", total);
+        assert_eq!(farb.pixels().iter().map(Pixel::alpha).collect::<Vec<_>>(),
+                   blurred.pixels().iter().map(Pixel::alpha).collect::<Vec<_>>());
+
+        assert!(*farb.gaussian_blur(1.5)[112].alpha() < 65535);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_gaussian_blur_identity() {
+        let constant = Farbfeld::new(9, 4, vec![Pixel::new(12345_u16, 65535, 0, 54321); 36]).unwrap();
+        for &sigma in &[0.3, 1.0, 2.5, 10.0] {
+            assert_eq!(constant, constant.gaussian_blur(sigma));
+        }
+        let farb = impulse();
+        assert_eq!(farb, farb.gaussian_blur(0.0));
+        assert_eq!(farb, farb.gaussian_blur(-1.0));
+        assert_eq!(farb, farb.gaussian_blur(f32::NAN));
+        assert_eq!(farb, farb.gaussian_blur(f32::INFINITY));
+        assert_eq!(farb, farb.gaussian_blur(1e-30));
+        assert_eq!(constant, constant.gaussian_blur(1e-30));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_gaussian_blur_huge_sigma() {
+        let farb = impulse();
+        assert_eq!(Some(2 * 5 + 1), gaussian_weights(1e30, 5).map(|weights| weights.len()));
+        let blurred = farb.gaussian_blur(f32::MAX);
+        assert!(blurred.pixels().iter().all(|pixel| pixel.alpha() == &65535));
+        assert!(red_at(&blurred, 0, 0) < 65535 && red_at(&blurred, 3, 3) > 0);
+    }
+
+    #[cfg(feature = "std")]
+    fn step_edge() -> Farbfeld {
+        Farbfeld::new(8, 2, (0..16)
+            .map(|i| if i % 8 < 4 { 16384_u16 } else { 49152 })
+            .map(|value| Pixel::new(value, value, value, 65535))
+            .collect()).unwrap()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_sharpen_edge() {
+        let farb = step_edge();
+        assert_eq!(farb, farb.sharpen(0.0, 1.0, 0));
+
+        let sharpened = farb.sharpen(1.0, 1.0, 0);
+        assert_eq!(16384, red_at(&sharpened, 0, 0));
+        assert!(red_at(&sharpened, 2, 0) < 16384);
+        assert!(red_at(&sharpened, 3, 0) < red_at(&sharpened, 2, 0));
+        assert!(red_at(&sharpened, 4, 0) > red_at(&sharpened, 5, 0));
+        assert!(red_at(&sharpened, 5, 0) > 49152);
+        assert_eq!(49152, red_at(&sharpened, 7, 1));
+        assert!(sharpened.pixels().iter().all(|pixel| pixel.alpha() == &65535));
+
+        let stronger = farb.sharpen(100.0, 1.0, 0);
+        assert_eq!(0, red_at(&stronger, 3, 0));
+        assert_eq!(65535, red_at(&stronger, 4, 0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_sharpen_threshold() {
+        let noisy = Farbfeld::new(6, 6, (0..36_u16)
+            .map(|i| 30000 + i % 3 * 20)
+            .map(|value| Pixel::new(value, value, value, 65535))
+            .collect()).unwrap();
+        assert_eq!(noisy, noisy.sharpen(2.0, 1.0, 100));
+        assert_ne!(noisy, noisy.sharpen(2.0, 1.0, 0));
+
+        let farb = step_edge();
+        assert_ne!(farb, farb.sharpen(1.0, 1.0, 100));
+    }
+
+    fn vertical_edge() -> Farbfeld {
+        Farbfeld::new(8, 3, (0..24)
+            .map(|i| if i % 8 < 4 { 0_u16 } else { 65535 })
+            .map(|value| Pixel::new(value, value, value, 65535))
+            .collect()).unwrap()
+    }
+
+    #[test]
+    fn test_sobel_xy() {
+        let gradients = vertical_edge().sobel_xy();
+        assert_eq!((&8, &3), (gradients.width(), gradients.height()));
+        for y in 0..3 {
+            for x in 0..8 {
+                let expected = if x == 3 || x == 4 { 262140 } else { 0 };
+                assert_eq!(expected, gradients.x()[y * 8 + x], "({}, {})", x, y);
+            }
+        }
+        assert!(gradients.y().iter().all(|&gradient| gradient == 0));
+
+        let (x, _) = vertical_edge().sobel_xy().into_planes();
+        assert_eq!(gradients.x(), &x[..]);
+    }
+
+    #[test]
+    fn test_sobel() {
+        let edges = vertical_edge().sobel();
+        for y in 0..3 {
+            for x in 0..8 {
+                let expected = if x == 3 || x == 4 { 46340 } else { 0 };
+                assert_eq!(expected, red_at(&edges, x, y), "({}, {})", x, y);
+            }
+        }
+        assert!(edges.pixels().iter().all(|pixel| pixel.red() == pixel.blue() && pixel.alpha() == &65535));
+
+        let constant = Farbfeld::new(5, 5, vec![Pixel::new(1234_u16, 40000, 65535, 0); 25]).unwrap();
+        assert!(constant.sobel().pixels().iter().all(|pixel| *pixel == Pixel::new(0_u16, 0, 0, 65535)));
+    }
+
+    #[test]
+    fn test_invalid_kernel() {
+        match Kernel::new(2, 3, vec![0.0; 6]) {
+            Err(Error::InvalidKernel(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        match Kernel::new(3, 3, vec![0.0; 8]) {
+            Err(Error::InvalidKernel(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        let kernel = Kernel::from_5x5([0.5; 25]);
+        assert_eq!((&5, &5, 25), (kernel.width(), kernel.height(), kernel.weights().len()));
+    }
+}
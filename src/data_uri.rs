@@ -0,0 +1,100 @@
+//! Encoding Farbfeld images as base64 and data URIs, for embedding small images in text formats
+//! such as JSON and HTML. Requires the `base64` feature.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::farbfeld::Farbfeld;
+use crate::error::*;
+
+/// The prefix of a Farbfeld data URI.
+const DATA_URI_PREFIX: &str = "data:image/farbfeld;base64,";
+
+impl Farbfeld {
+    /// Returns the Farbfeld file of this image encoded as standard, padded base64.
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.to_bytes())
+    }
+
+    /// Parses a Farbfeld file encoded as standard base64. Whitespace within the input is ignored.
+    ///
+    /// # Errors
+    /// Returns an [Error::Base64Error](error/enum.Error.html) if the input isn't valid
+    /// base64, or the errors of [from_read](#method.from_read) if the decoded data isn't a valid
+    /// Farbfeld file.
+    pub fn from_base64(input: &str) -> Result<Farbfeld> {
+        let input: Vec<u8> = input.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+        let data = STANDARD.decode(input)?;
+        Farbfeld::from_read(&data[..])
+    }
+
+    /// Returns a data URI containing the Farbfeld file of this image, of the form
+    /// `data:image/farbfeld;base64,...`.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(0, 0, Vec::new()).unwrap();
+    ///
+    /// assert_eq!("data:image/farbfeld;base64,ZmFyYmZlbGQAAAAAAAAAAA==", farb.to_data_uri());
+    /// ```
+    pub fn to_data_uri(&self) -> String {
+        format!("{}{}", DATA_URI_PREFIX, self.to_base64())
+    }
+
+    /// Parses a data URI of the form produced by [to_data_uri](#method.to_data_uri). Whitespace
+    /// around the URI and within the base64 data is ignored.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidDataUri](error/enum.Error.html) if the URI doesn't start
+    /// with `data:image/farbfeld;base64,`, or the errors of [from_base64](#method.from_base64).
+    pub fn from_data_uri(uri: &str) -> Result<Farbfeld> {
+        match uri.trim_start().strip_prefix(DATA_URI_PREFIX) {
+            Some(data) => Farbfeld::from_base64(data),
+            None => Err(Error::InvalidDataUri)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_round_trip() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        assert_eq!(farb.to_bytes(), Farbfeld::from_base64(&farb.to_base64()).unwrap().to_bytes());
+    }
+
+    #[test]
+    fn test_data_uri_round_trip() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let uri = farb.to_data_uri();
+        assert!(uri.starts_with("data:image/farbfeld;base64,"));
+        assert_eq!(farb.to_bytes(), Farbfeld::from_data_uri(&uri).unwrap().to_bytes());
+
+        // Wrapped lines, as often found in HTML.
+        let (prefix, data) = uri.split_at(DATA_URI_PREFIX.len());
+        let wrapped: Vec<&str> = data.as_bytes().chunks(76).map(|line| ::std::str::from_utf8(line).unwrap()).collect();
+        let uri = format!("\n  {}\n{}\n", prefix, wrapped.join("\r\n  "));
+        assert_eq!(farb.to_bytes(), Farbfeld::from_data_uri(&uri).unwrap().to_bytes());
+    }
+
+    #[test]
+    fn test_invalid_prefix() {
+        let farb = Farbfeld::new(1, 1, vec![Default::default()]).unwrap();
+        for prefix in ["data:image/png;base64,", "data:image/farbfeld,", "image/farbfeld;base64,", ""].iter() {
+            match Farbfeld::from_data_uri(&format!("{}{}", prefix, farb.to_base64())) {
+                Err(Error::InvalidDataUri) => {},
+                res => panic!("Unexpected result {:?}", res)
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_base64() {
+        match Farbfeld::from_base64("ZmFyYmZlbGQ!") {
+            Err(Error::Base64Error(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! Comparing two Farbfeld images, such as a render against a known good reference.
+
+use alloc::vec::Vec;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::{Pixel, Channel};
+use crate::error::*;
+
+/// The width and height of the windows compared by
+/// [Farbfeld::ssim](../struct.Farbfeld.html#method.ssim).
+const SSIM_WINDOW: usize = 8;
+
+/// The differences between two images of the same size, created by
+/// [Farbfeld::diff](../struct.Farbfeld.html#method.diff).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffResult {
+    image: Farbfeld,
+    differing_pixels: u64,
+    max_difference: u16,
+    mean_difference: f64
+}
+
+impl DiffResult {
+    /// Returns an opaque image where the red, green and blue channels of each pixel are the
+    /// absolute differences between those of the compared pixels. Identical pixels are black.
+    pub fn image(&self) -> &Farbfeld {
+        &self.image
+    }
+
+    /// Consumes the result, returning the difference image.
+    pub fn into_image(self) -> Farbfeld {
+        self.image
+    }
+
+    /// Returns the number of pixels which differ in at least one channel, including alpha.
+    pub fn differing_pixels(&self) -> &u64 {
+        &self.differing_pixels
+    }
+
+    /// Returns the largest absolute difference between any channel of the compared pixels,
+    /// including alpha.
+    pub fn max_difference(&self) -> &u16 {
+        &self.max_difference
+    }
+
+    /// Returns the mean absolute difference between every channel of the compared pixels,
+    /// including alpha, or 0 if the images are empty.
+    pub fn mean_difference(&self) -> &f64 {
+        &self.mean_difference
+    }
+
+    /// Returns the difference image with each difference multiplied by `factor` and clamped to
+    /// 65535, so small differences are visible.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(1, 1, vec![Pixel::new(100_u16, 200, 300, 65535)]).unwrap();
+    /// let other = Farbfeld::new(1, 1, vec![Pixel::new(101_u16, 200, 0, 65535)]).unwrap();
+    /// let diff = farb.diff(&other).unwrap();
+    ///
+    /// assert_eq!(&[Pixel::new(1000_u16, 0, 65535, 65535)], diff.amplified(1000).pixels());
+    /// ```
+    pub fn amplified(&self, factor: u32) -> Farbfeld {
+        let mut amplified = self.image.clone();
+        amplified.map_channels(|channel, value| match channel {
+            Channel::Alpha => value,
+            _ => (value as u64 * factor as u64).min(u16::MAX as u64) as u16
+        });
+        amplified
+    }
+}
+
+impl Farbfeld {
+    /// Compares this image with another of the same size, finding the difference of each channel
+    /// of each pixel.
+    ///
+    /// # Errors
+    /// Returns an [Error::DimensionMismatch](error/enum.Error.html) containing both sizes if
+    /// the other image isn't the same size as this one.
+    pub fn diff(&self, other: &Farbfeld) -> Result<DiffResult> {
+        let (mut differing_pixels, mut max_difference, mut total) = (0, 0, 0_u64);
+        let pixels = self.zip_pixels(other)?.map(|(_, _, pixel, other)| {
+            let mut differences = [0_u16; 4];
+            for ((difference, channel), other) in differences.iter_mut().zip(pixel.iter()).zip(other.iter()) {
+                *difference = channel.abs_diff(*other);
+                max_difference = max_difference.max(*difference);
+                total += *difference as u64;
+            }
+            if differences != [0; 4] {
+                differing_pixels += 1;
+            }
+            Pixel::new(differences[0], differences[1], differences[2], u16::MAX)
+        }).collect();
+        let channels = self.pixels().len() as f64 * 4.0;
+        Ok(DiffResult {
+            image: Farbfeld::new(*self.width(), *self.height(), pixels)?,
+            differing_pixels,
+            max_difference,
+            mean_difference: if channels > 0.0 { total as f64 / channels } else { 0.0 }
+        })
+    }
+
+    /// Returns the peak signal-to-noise ratio in decibels between the red, green and blue channels
+    /// of this image and another of the same size, treating 65535 as the peak. Higher is more
+    /// similar, and identical images, including empty ones, have a ratio of infinity. Requires the
+    /// `std` feature.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let black = Farbfeld::new(1, 1, vec![Pixel::new(0_u16, 0, 0, 65535)]).unwrap();
+    /// let white = Farbfeld::new(1, 1, vec![Pixel::new(65535_u16, 65535, 65535, 65535)]).unwrap();
+    ///
+    /// assert_eq!(0.0, black.psnr(&white).unwrap());
+    /// assert_eq!(f64::INFINITY, black.psnr(&black).unwrap());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::DimensionMismatch](error/enum.Error.html) containing both sizes if
+    /// the other image isn't the same size as this one.
+    #[cfg(feature = "std")]
+    pub fn psnr(&self, other: &Farbfeld) -> Result<f64> {
+        let squared_error: u128 = self.zip_pixels(other)?
+            .flat_map(|(_, _, pixel, other)| pixel.iter().zip(other.iter()).take(3))
+            .map(|(channel, other)| (channel.abs_diff(*other) as u128).pow(2))
+            .sum();
+        if squared_error == 0 {
+            return Ok(f64::INFINITY);
+        }
+        let mean_squared_error = squared_error as f64 / (self.pixels().len() as f64 * 3.0);
+        let peak = u16::MAX as f64;
+        Ok(10.0 * (peak * peak / mean_squared_error).log10())
+    }
+
+    /// Returns the structural similarity index between the
+    /// [luminance](pixel/struct.Pixel.html#method.luminance) of this image and another of the same
+    /// size, averaged over every 8 by 8 window of pixels, or over the whole image if it's smaller
+    /// than a window. 1 means the images are identical, and lower values are less similar. Empty
+    /// images have an index of 1.
+    ///
+    /// # Errors
+    /// Returns an [Error::DimensionMismatch](error/enum.Error.html) containing both sizes if
+    /// the other image isn't the same size as this one.
+    pub fn ssim(&self, other: &Farbfeld) -> Result<f64> {
+        self.check_same_size(other)?;
+        if self.pixels().is_empty() {
+            return Ok(1.0);
+        }
+        let (width, height) = (*self.width() as usize, *self.height() as usize);
+        let luminance = |farb: &Farbfeld| farb.pixels().iter().map(|pixel| pixel.luminance() as f64).collect::<Vec<_>>();
+        let (first, second) = (luminance(self), luminance(other));
+        let (window_width, window_height) = (SSIM_WINDOW.min(width), SSIM_WINDOW.min(height));
+        let count = (window_width * window_height) as f64;
+        let peak = u16::MAX as f64;
+        let (c1, c2) = ((0.01 * peak) * (0.01 * peak), (0.03 * peak) * (0.03 * peak));
+        let mut total = 0.0;
+        for top in 0..=height - window_height {
+            for left in 0..=width - window_width {
+                let window = (top..top + window_height)
+                    .flat_map(|y| (left..left + window_width).map(move |x| y * width + x));
+                let (mut sum_first, mut sum_second) = (0.0, 0.0);
+                for i in window.clone() {
+                    sum_first += first[i];
+                    sum_second += second[i];
+                }
+                let (mean_first, mean_second) = (sum_first / count, sum_second / count);
+                let (mut variance_first, mut variance_second, mut covariance) = (0.0, 0.0, 0.0);
+                for i in window {
+                    let (a, b) = (first[i] - mean_first, second[i] - mean_second);
+                    variance_first += a * a;
+                    variance_second += b * b;
+                    covariance += a * b;
+                }
+                let (variance_first, variance_second, covariance) =
+                    (variance_first / count, variance_second / count, covariance / count);
+                total += ((2.0 * mean_first * mean_second + c1) * (2.0 * covariance + c2))
+                    / ((mean_first * mean_first + mean_second * mean_second + c1) * (variance_first + variance_second + c2));
+            }
+        }
+        Ok(total / ((height - window_height + 1) * (width - window_width + 1)) as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    fn image() -> Farbfeld {
+        Farbfeld::new(3, 2, (0..6_u16).map(|i| Pixel::new(i * 1000, i * 2000, 65535 - i, 65535)).collect()).unwrap()
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let diff = image().diff(&image()).unwrap();
+        assert_eq!((&0, &0, &0.0), (diff.differing_pixels(), diff.max_difference(), diff.mean_difference()));
+        assert!(diff.image().pixels().iter().all(|&pixel| pixel == Pixel::new(0_u16, 0, 0, 65535)));
+        assert_eq!(diff.image(), &diff.amplified(u32::MAX));
+    }
+
+    #[test]
+    fn test_diff_single_channel() {
+        let mut other = image();
+        *other[4].green_mut() = 0;
+        let diff = image().diff(&other).unwrap();
+
+        assert_eq!(&1, diff.differing_pixels());
+        assert_eq!(&8000, diff.max_difference());
+        assert_eq!(&(8000.0 / 24.0), diff.mean_difference());
+        let differing: Vec<_> = diff.image().pixels().iter().enumerate()
+            .filter(|(_, pixel)| pixel.iter().take(3).any(|&channel| channel != 0))
+            .map(|(i, _)| (i % 3, i / 3))
+            .collect();
+        assert_eq!(vec![(1, 1)], differing);
+        assert_eq!(&Pixel::new(0_u16, 8000, 0, 65535), &diff.into_image()[4]);
+    }
+
+    #[test]
+    fn test_diff_alpha() {
+        let mut other = image();
+        *other[0].alpha_mut() = 65000;
+        let diff = image().diff(&other).unwrap();
+        assert_eq!((&1, &535), (diff.differing_pixels(), diff.max_difference()));
+        assert!(diff.image().pixels().iter().all(|&pixel| pixel == Pixel::new(0_u16, 0, 0, 65535)));
+    }
+
+    #[test]
+    fn test_diff_amplified() {
+        let mut other = image();
+        *other[1].red_mut() += 3;
+        *other[2].blue_mut() -= 40000;
+        let amplified = image().diff(&other).unwrap().amplified(1000);
+        assert_eq!(&Pixel::new(3000_u16, 0, 0, 65535), &amplified[1]);
+        assert_eq!(&Pixel::new(0_u16, 0, 65535, 65535), &amplified[2]);
+    }
+
+    /// Returns a 16 by 16 image with a smooth gradient in each channel.
+    fn gradient() -> Farbfeld {
+        Farbfeld::new(16, 16, (0..256_u32)
+            .map(|i| Pixel::new((i % 16 * 4000) as u16, (i / 16 * 4000) as u16, (i * 250) as u16, 65535))
+            .collect()).unwrap()
+    }
+
+    /// Returns the image with every other pixel replaced by a bright or dark value.
+    fn corrupted(farb: &Farbfeld) -> Farbfeld {
+        let pixels = farb.pixels().iter().enumerate()
+            .map(|(i, &pixel)| match i % 4 {
+                0 => Pixel::new(65535_u16, 65535, 65535, 65535),
+                2 => Pixel::new(0_u16, 0, 0, 65535),
+                _ => pixel
+            })
+            .collect();
+        Farbfeld::new(*farb.width(), *farb.height(), pixels).unwrap()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_psnr() {
+        assert_eq!(f64::INFINITY, gradient().psnr(&gradient()).unwrap());
+        let empty = Farbfeld::new(0, 0, Vec::new()).unwrap();
+        assert_eq!(f64::INFINITY, empty.psnr(&empty).unwrap());
+
+        let mut off_by_one = image();
+        off_by_one.map_channels(|channel, value| if channel == Channel::Alpha { 0 } else { value ^ 1 });
+        let psnr = image().psnr(&off_by_one).unwrap();
+        assert!((psnr - 20.0 * 65535_f64.log10()).abs() < 1e-9, "psnr {}", psnr);
+        assert!(gradient().psnr(&corrupted(&gradient())).unwrap() < 10.0);
+    }
+
+    #[test]
+    fn test_ssim() {
+        assert_eq!(1.0, gradient().ssim(&gradient()).unwrap());
+        assert_eq!(1.0, image().ssim(&image()).unwrap());
+        let empty = Farbfeld::new(0, 0, Vec::new()).unwrap();
+        assert_eq!(1.0, empty.ssim(&empty).unwrap());
+
+        let ssim = gradient().ssim(&corrupted(&gradient())).unwrap();
+        assert!(ssim < 0.5, "ssim {}", ssim);
+        let mut slightly_brighter = gradient();
+        slightly_brighter.map_channels(|channel, value| if channel == Channel::Alpha { value } else { value / 100 * 99 + 500 });
+        let ssim = gradient().ssim(&slightly_brighter).unwrap();
+        assert!(ssim > 0.99 && ssim < 1.0, "ssim {}", ssim);
+    }
+
+    #[test]
+    fn test_dimension_mismatch() {
+        let other = Farbfeld::new(2, 3, vec![Pixel::default(); 6]).unwrap();
+        match image().diff(&other) {
+            Err(Error::DimensionMismatch(3, 2, 2, 3)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        match image().ssim(&other) {
+            Err(Error::DimensionMismatch(3, 2, 2, 3)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        #[cfg(feature = "std")]
+        match image().psnr(&other) {
+            Err(Error::DimensionMismatch(3, 2, 2, 3)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+}
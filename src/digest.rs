@@ -0,0 +1,283 @@
+//! Computing checksums of Farbfeld images while they are written. SHA-256 support requires the
+//! `sha2` feature and CRC32 support requires the `crc32fast` feature. The
+//! [content_hash](../struct.Farbfeld.html#method.content_hash) of an image requires the `digest`
+//! feature.
+//!
+//! The checksum is computed over exactly the bytes passed to the underlying write, so it matches
+//! a checksum of the written data without needing to read it back.
+
+#[cfg(feature = "digest")]
+use alloc::string::String;
+use std::io::{self, Write};
+
+#[cfg(any(feature = "sha2", feature = "digest"))]
+use sha2::{Sha256, Digest};
+#[cfg(feature = "crc32fast")]
+use crc32fast;
+
+use crate::farbfeld::Farbfeld;
+use crate::error::*;
+
+/// A checksum which can be updated incrementally with the bytes of a
+/// [TeeDigestWriter](struct.TeeDigestWriter.html).
+pub trait Checksum {
+    /// The final checksum value.
+    type Output;
+
+    /// Adds the given bytes to the checksum.
+    fn update(&mut self, bytes: &[u8]);
+
+    /// Consumes the checksum, returning its value.
+    fn finalize(self) -> Self::Output;
+}
+
+#[cfg(any(feature = "sha2", feature = "digest"))]
+impl Checksum for Sha256 {
+    type Output = [u8; 32];
+
+    fn update(&mut self, bytes: &[u8]) {
+        Digest::update(self, bytes);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        Digest::finalize(self).into()
+    }
+}
+
+#[cfg(feature = "crc32fast")]
+impl Checksum for crc32fast::Hasher {
+    type Output = u32;
+
+    fn update(&mut self, bytes: &[u8]) {
+        crc32fast::Hasher::update(self, bytes);
+    }
+
+    fn finalize(self) -> u32 {
+        crc32fast::Hasher::finalize(self)
+    }
+}
+
+/// A write which passes all bytes through to an inner write, adding the bytes accepted by the
+/// inner write to a checksum.
+///
+/// ```
+/// # #[cfg(feature = "crc32fast")] {
+/// # use ::ruff::digest::TeeDigestWriter;
+/// use std::io::Write;
+///
+/// let mut write = TeeDigestWriter::new(Vec::new(), crc32fast::Hasher::new());
+/// write.write_all(b"farbfeld").unwrap();
+/// let (data, crc) = write.finish();
+///
+/// assert_eq!(crc32fast::hash(&data), crc);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TeeDigestWriter<W, C> {
+    inner: W,
+    checksum: C
+}
+
+impl<W: Write, C: Checksum> TeeDigestWriter<W, C> {
+    /// Creates a new TeeDigestWriter wrapping the given write, updating the given checksum.
+    pub fn new(inner: W, checksum: C) -> TeeDigestWriter<W, C> {
+        TeeDigestWriter {
+            inner,
+            checksum
+        }
+    }
+
+    /// Returns a reference to the inner write.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Consumes the TeeDigestWriter, returning the inner write and the value of the checksum.
+    /// The inner write isn't flushed.
+    pub fn finish(self) -> (W, C::Output) {
+        (self.inner, self.checksum.finalize())
+    }
+}
+
+impl<W: Write, C: Checksum> Write for TeeDigestWriter<W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.checksum.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Farbfeld {
+    /// Writes the image to the given write like [save](#method.save), returning the checksum of
+    /// the written bytes computed with the given checksum.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+    ///     std IoError during write.</li>
+    /// </ul>
+    pub fn save_with_checksum<W: Write, C: Checksum>(&self, write: &mut W, checksum: C) -> Result<C::Output> {
+        let mut write = TeeDigestWriter::new(write, checksum);
+        self.save(&mut write)?;
+        Ok(write.finish().1)
+    }
+
+    /// Writes the image to the given write like [save](#method.save), returning the SHA-256 digest
+    /// of the written bytes.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+    ///     std IoError during write.</li>
+    /// </ul>
+    #[cfg(feature = "sha2")]
+    pub fn save_with_digest<W: Write>(&self, write: &mut W) -> Result<[u8; 32]> {
+        self.save_with_checksum(write, Sha256::new())
+    }
+
+    /// Returns the SHA-256 digest of the image's contents, for use as a cache key or to find
+    /// duplicate images.
+    ///
+    /// The digest is computed over the image as [save](#method.save) writes it: the magic value
+    /// `farbfeld`, the width and height as big-endian u32s, then the red, green, blue and alpha
+    /// channels of each pixel in row-major order as big-endian u16s. This layout is stable, so the
+    /// digest only depends on the dimensions and pixels, not on how the image was created, and
+    /// matches the digest of a file written by save even if the file it was read from had
+    /// trailing data.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2, 3, 4)]).unwrap();
+    /// let mut other = farb.clone();
+    /// *other[0].alpha_mut() = 5;
+    ///
+    /// assert_eq!(farb.content_hash(), Farbfeld::from_bytes(&farb.to_bytes()).unwrap().content_hash());
+    /// assert_ne!(farb.content_hash(), other.content_hash());
+    /// ```
+    #[cfg(feature = "digest")]
+    pub fn content_hash(&self) -> [u8; 32] {
+        self.save_with_checksum(&mut io::sink(), Sha256::new()).expect("writing to a sink can't fail")
+    }
+
+    /// Returns the [content_hash](#method.content_hash) of the image as 64 lowercase hexadecimal
+    /// digits.
+    #[cfg(feature = "digest")]
+    pub fn content_hash_hex(&self) -> String {
+        const DIGITS: &[u8; 16] = b"0123456789abcdef";
+        let mut hex = String::with_capacity(64);
+        for byte in self.content_hash().iter() {
+            hex.push(DIGITS[(byte >> 4) as usize] as char);
+            hex.push(DIGITS[(byte & 0xf) as usize] as char);
+        }
+        hex
+    }
+
+    /// Writes the image to the given write like [save](#method.save), returning the CRC32 of the
+    /// written bytes.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+    ///     std IoError during write.</li>
+    /// </ul>
+    #[cfg(feature = "crc32fast")]
+    pub fn save_with_crc32<W: Write>(&self, write: &mut W) -> Result<u32> {
+        self.save_with_checksum(write, crc32fast::Hasher::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "digest")]
+    use crate::pixel::Pixel;
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn test_save_with_digest() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut data = Vec::new();
+        let digest = farb.save_with_digest(&mut data).unwrap();
+        let expected: [u8; 32] = Sha256::digest(farb.to_bytes()).into();
+        assert_eq!(expected, digest);
+        assert_eq!(farb.to_bytes(), data);
+    }
+
+    #[cfg(feature = "digest")]
+    #[test]
+    fn test_content_hash() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let rebuilt = Farbfeld::new(*farb.width(), *farb.height(), farb.pixels().to_vec()).unwrap();
+        let expected: [u8; 32] = Sha256::digest(farb.to_bytes()).into();
+        assert_eq!(expected, farb.content_hash());
+        assert_eq!(farb.content_hash(), rebuilt.content_hash());
+        assert_eq!(farb.content_hash_hex(), rebuilt.content_hash_hex());
+
+        let mut changed = rebuilt.clone();
+        let middle = changed.pixels().len() / 2;
+        *changed[middle].green_mut() ^= 1;
+        assert_ne!(farb.content_hash(), changed.content_hash());
+        assert_ne!(farb.content_hash_hex(), changed.content_hash_hex());
+
+        let tall = Farbfeld::new(1, 2, vec![Pixel::default(); 2]).unwrap();
+        let wide = Farbfeld::new(2, 1, vec![Pixel::default(); 2]).unwrap();
+        assert_ne!(tall.content_hash(), wide.content_hash());
+
+        let empty = Farbfeld::new(0, 0, Vec::new()).unwrap();
+        assert_eq!("3293a75a4a6487c7512dee0582adf88ffe241d21cac1d4bd3417472d75989d50", empty.content_hash_hex());
+    }
+
+    #[cfg(feature = "crc32fast")]
+    #[test]
+    fn test_save_with_crc32() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut data = Vec::new();
+        let crc = farb.save_with_crc32(&mut data).unwrap();
+        assert_eq!(crc32fast::hash(&farb.to_bytes()), crc);
+        assert_eq!(farb.to_bytes(), data);
+    }
+
+    /// A write accepting at most 3 bytes per call.
+    struct ShortWrite(Vec<u8>);
+
+    impl Write for ShortWrite {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let len = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A checksum collecting every byte it is given.
+    struct Collect(Vec<u8>);
+
+    impl Checksum for Collect {
+        type Output = Vec<u8>;
+
+        fn update(&mut self, bytes: &[u8]) {
+            self.0.extend_from_slice(bytes);
+        }
+
+        fn finalize(self) -> Vec<u8> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_short_writes_checksum_written_bytes() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut write = ShortWrite(Vec::new());
+        let bytes = farb.save_with_checksum(&mut write, Collect(Vec::new())).unwrap();
+        assert_eq!(write.0, bytes);
+        assert_eq!(farb.to_bytes(), bytes);
+    }
+}
@@ -0,0 +1,631 @@
+//! Drawing shapes onto Farbfeld images, clipped to the bounds of the image.
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+
+/// How a drawn pixel is combined with the pixel already in the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Composite the drawn pixel over the image with [Pixel::over](../struct.Pixel.html#method.over),
+    /// so partially transparent pixels let the image show through.
+    Blend,
+    /// Overwrite the image with the drawn pixel, including its alpha.
+    Replace
+}
+
+/// Clips the span of values from `start` up to `end` to `0..bound`, returning the start and end of
+/// the visible part, or None if none of it is visible.
+fn clip_span(start: i128, end: i128, bound: u32) -> Option<(usize, usize)> {
+    let (start, end) = (start.max(0), end.min(bound as i128));
+    if start < end {
+        Some((start as usize, end as usize))
+    } else {
+        None
+    }
+}
+
+/// Returns `value * factor / divisor` rounded down and the remainder, for non-negative values with
+/// `factor` at most `divisor`, without overflowing even when the product doesn't fit in an i128.
+fn mul_div(value: i128, factor: i128, divisor: i128) -> (i128, i128) {
+    if let Some(product) = value.checked_mul(factor) {
+        return (product / divisor, product % divisor);
+    }
+    // Long multiplication one bit of value at a time, keeping the remainder below divisor.
+    let (mut quotient, mut remainder) = (0, 0);
+    for bit in (0..128 - value.leading_zeros()).rev() {
+        quotient *= 2;
+        remainder *= 2;
+        if remainder >= divisor {
+            remainder -= divisor;
+            quotient += 1;
+        }
+        if value >> bit & 1 == 1 {
+            remainder += factor;
+            if remainder >= divisor {
+                remainder -= divisor;
+                quotient += 1;
+            }
+        }
+    }
+    (quotient, remainder)
+}
+
+/// Returns the steps along a line's major axis, starting at `start` and moving `len` pixels in the
+/// direction of `sign`, which land within `0..bound`.
+fn visible_steps(start: i64, len: i128, sign: i128, bound: u32) -> core::ops::RangeInclusive<i128> {
+    let (start, bound) = (start as i128, bound as i128);
+    let (first, last) = match sign {
+        1 => (-start, bound - 1 - start),
+        -1 => (start - bound + 1, start),
+        _ if (0..bound).contains(&start) => (0, 0),
+        _ => (1, 0)
+    };
+    first.max(0)..=last.min(len)
+}
+
+impl Farbfeld {
+    /// Draws the pixel over the pixels of row `y` from `start` up to `end`, which must be within
+    /// the image.
+    fn draw_span(&mut self, y: usize, start: usize, end: usize, pixel: Pixel, mode: BlendMode) {
+        let row = y * *self.width() as usize;
+        let span = &mut self[row + start..row + end];
+        match (mode, *pixel.alpha()) {
+            (BlendMode::Replace, _) | (BlendMode::Blend, u16::MAX) => span.fill(pixel),
+            (BlendMode::Blend, 0) => {},
+            (BlendMode::Blend, _) => for backdrop in span {
+                *backdrop = pixel.over(*backdrop);
+            }
+        }
+    }
+
+    /// Blends the pixel over the pixel at the given coordinates, if they're within the image.
+    fn draw_point(&mut self, x: i128, y: i128, pixel: Pixel) {
+        if (0..*self.width() as i128).contains(&x) && (0..*self.height() as i128).contains(&y) {
+            self.draw_span(y as usize, x as usize, x as usize + 1, pixel, BlendMode::Blend);
+        }
+    }
+
+    /// Walks the line between the points one pixel at a time along its longer axis, skipping steps
+    /// outside the image. For each step, `draw` is given a function returning the coordinates of
+    /// the pixel the given number of pixels past the line along its shorter axis, rounding towards
+    /// the start, and how far past that pixel the line is, as a remainder out of a length.
+    fn trace_line<F>(&mut self, (x0, y0): (i64, i64), (x1, y1): (i64, i64), mut draw: F)
+        where F: FnMut(&mut Farbfeld, &dyn Fn(i128) -> (i128, i128), i128, i128) {
+        let (dx, dy) = (x1 as i128 - x0 as i128, y1 as i128 - y0 as i128);
+        let x_major = dx.abs() >= dy.abs();
+        let (major, minor, major_len, minor_len, major_sign, minor_sign, bound) = if x_major {
+            (x0, y0, dx.abs(), dy.abs(), dx.signum(), dy.signum(), *self.width())
+        } else {
+            (y0, x0, dy.abs(), dx.abs(), dy.signum(), dx.signum(), *self.height())
+        };
+        for step in visible_steps(major, major_len, major_sign, bound) {
+            let (offset, remainder) = match major_len {
+                0 => (0, 0),
+                len => mul_div(step, minor_len, len)
+            };
+            let position = major as i128 + major_sign * step;
+            let point = |past: i128| {
+                let minor = minor as i128 + minor_sign * (offset + past);
+                if x_major { (position, minor) } else { (minor, position) }
+            };
+            draw(self, &point, remainder, major_len);
+        }
+    }
+
+    /// Draws a line one pixel wide between the given points, including both ends, blending the
+    /// pixel over the image. Each step along the longer axis of the line lights the pixel nearest
+    /// the line, as found by Bresenham's algorithm, rounding half way points away from the start.
+    /// Only the part of the line within the image is drawn, and the time taken depends on the size
+    /// of the image rather than the length of the line.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(3, 3, vec![Pixel::default(); 9]).unwrap();
+    /// let red = Pixel::new(65535_u16, 0, 0, 65535);
+    /// farb.draw_line(-1, -1, 5, 5, red);
+    ///
+    /// assert_eq!(&red, &farb[4]);
+    /// assert_eq!(&Pixel::default(), &farb[1]);
+    /// ```
+    pub fn draw_line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, pixel: Pixel) {
+        self.trace_line((x0, y0), (x1, y1), |farb, point, remainder, len| {
+            let (x, y) = point(if remainder * 2 >= len && len > 0 { 1 } else { 0 });
+            farb.draw_point(x, y, pixel);
+        });
+    }
+
+    /// Draws an anti-aliased line between the given points with Wu's algorithm, blending the pixel
+    /// over the image. Each step along the longer axis of the line splits the pixel's alpha
+    /// between the two pixels either side of the line, in proportion to how close each is to it.
+    /// Horizontal, vertical and diagonal lines are drawn the same as by
+    /// [draw_line](#method.draw_line).
+    pub fn draw_line_aa(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, pixel: Pixel) {
+        let alpha = *pixel.alpha() as i128;
+        self.trace_line((x0, y0), (x1, y1), |farb, point, remainder, len| {
+            let far_alpha = if len > 0 { (alpha * remainder + len / 2) / len } else { 0 };
+            let mut covered = pixel;
+            for &(past, alpha) in &[(0, alpha - far_alpha), (1, far_alpha)] {
+                if alpha > 0 {
+                    *covered.alpha_mut() = alpha as u16;
+                    let (x, y) = point(past);
+                    farb.draw_point(x, y, covered);
+                }
+            }
+        });
+    }
+
+    /// Draws a rectangle filled with the pixel, blending it over the image as described by
+    /// [draw_filled_rect_with](#method.draw_filled_rect_with).
+    pub fn draw_filled_rect(&mut self, x: i64, y: i64, width: u32, height: u32, pixel: Pixel) {
+        self.draw_filled_rect_with(x, y, width, height, pixel, BlendMode::Blend);
+    }
+
+    /// Draws a rectangle filled with the pixel, with its top left corner at the given coordinates,
+    /// combining it with the image as described by `mode`. Only the part of the rectangle within
+    /// the image is drawn, so the coordinates may be negative.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(3, 2, vec![Pixel::default(); 6]).unwrap();
+    /// let red = Pixel::new(65535_u16, 0, 0, 65535);
+    /// farb.draw_filled_rect(-1, 1, 3, 5, red);
+    ///
+    /// assert_eq!(&[Pixel::default(); 3], &farb[..3]);
+    /// assert_eq!(&[red, red, Pixel::default()], &farb[3..]);
+    /// ```
+    pub fn draw_filled_rect_with(&mut self, x: i64, y: i64, width: u32, height: u32, pixel: Pixel, mode: BlendMode) {
+        let (x, y) = (x as i128, y as i128);
+        self.fill_area((x, x + width as i128), (y, y + height as i128), pixel, mode);
+    }
+
+    /// Fills the area of the image between the start and end of each range, which may be outside
+    /// the image, with the pixel.
+    fn fill_area(&mut self, (left, right): (i128, i128), (top, bottom): (i128, i128), pixel: Pixel, mode: BlendMode) {
+        if let (Some((left, right)), Some((top, bottom))) =
+            (clip_span(left, right, *self.width()), clip_span(top, bottom, *self.height())) {
+            for row in top..bottom {
+                self.draw_span(row, left, right, pixel, mode);
+            }
+        }
+    }
+
+    /// Draws the outline of a rectangle, blending it over the image as described by
+    /// [draw_rect_outline_with](#method.draw_rect_outline_with).
+    pub fn draw_rect_outline(&mut self, x: i64, y: i64, width: u32, height: u32, thickness: u32, pixel: Pixel) {
+        self.draw_rect_outline_with(x, y, width, height, thickness, pixel, BlendMode::Blend);
+    }
+
+    /// Draws the outline of a rectangle with its top left corner at the given coordinates, made of
+    /// lines `thickness` pixels wide inside its edges, combining it with the image as described by
+    /// `mode`. Each pixel is drawn at most once, and an outline too thick to leave a gap in the
+    /// middle fills the whole rectangle. Only the part of the outline within the image is drawn.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_rect_outline_with(&mut self, x: i64, y: i64, width: u32, height: u32, thickness: u32, pixel: Pixel,
+                                  mode: BlendMode) {
+        if thickness == 0 {
+            return;
+        }
+        if thickness as u64 * 2 >= width as u64 || thickness as u64 * 2 >= height as u64 {
+            return self.draw_filled_rect_with(x, y, width, height, pixel, mode);
+        }
+        let (inner_height, far) = (height - 2 * thickness, thickness as i64);
+        self.draw_filled_rect_with(x, y, width, thickness, pixel, mode);
+        self.draw_filled_rect_with(x, y + height as i64 - far, width, thickness, pixel, mode);
+        self.draw_filled_rect_with(x, y + far, thickness, inner_height, pixel, mode);
+        self.draw_filled_rect_with(x + width as i64 - far, y + far, thickness, inner_height, pixel, mode);
+    }
+
+    /// Returns whether any of the rectangle of pixels extending the given radii around the centre
+    /// is within the image.
+    fn overlaps(&self, (cx, cy): (i128, i128), (rx, ry): (i128, i128)) -> bool {
+        cx + rx >= 0 && cy + ry >= 0 && cx - rx < *self.width() as i128 && cy - ry < *self.height() as i128
+    }
+
+    /// Blends the pixel over each distinct point, offset from the centre, within the image.
+    fn draw_points(&mut self, (cx, cy): (i128, i128), points: &[(i128, i128)], pixel: Pixel) {
+        for (i, &(x, y)) in points.iter().enumerate() {
+            if !points[..i].contains(&(x, y)) {
+                self.draw_point(cx + x, cy + y, pixel);
+            }
+        }
+    }
+
+    /// Draws the outline of a circle one pixel wide around the given centre, blending the pixel
+    /// over the image. The outline is found with the midpoint circle algorithm, so it lights the
+    /// pixel nearest the circle in each row of the top and bottom and each column of the sides. A
+    /// radius of 0 draws a single pixel. Only the part of the circle within the image is drawn,
+    /// and each pixel is drawn at most once.
+    pub fn draw_circle(&mut self, cx: i64, cy: i64, radius: u32, pixel: Pixel) {
+        let (centre, radius) = ((cx as i128, cy as i128), radius as i128);
+        if !self.overlaps(centre, (radius, radius)) {
+            return;
+        }
+        let (mut x, mut y, mut decision) = (0, radius, 1 - radius);
+        while x <= y {
+            self.draw_points(centre, &[(x, y), (y, x), (-x, y), (-y, x), (x, -y), (y, -x), (-x, -y), (-y, -x)], pixel);
+            x += 1;
+            if decision < 0 {
+                decision += 2 * x + 1;
+            } else {
+                y -= 1;
+                decision += 2 * (x - y) + 1;
+            }
+        }
+    }
+
+    /// Draws a circle filled with the pixel around the given centre, blending it over the image.
+    /// The circle covers every pixel whose offset from the centre satisfies
+    /// `x * x + y * y <= radius * (radius + 1)`, which includes every pixel of the matching
+    /// [draw_circle](#method.draw_circle). Each row is filled as a single span.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(5, 5, vec![Pixel::default(); 25]).unwrap();
+    /// let red = Pixel::new(65535_u16, 0, 0, 65535);
+    /// farb.draw_filled_circle(2, 2, 1, red);
+    ///
+    /// assert_eq!(9, farb.pixels().iter().filter(|&&pixel| pixel == red).count());
+    /// ```
+    pub fn draw_filled_circle(&mut self, cx: i64, cy: i64, radius: u32, pixel: Pixel) {
+        let ((cx, cy), radius) = ((cx as i128, cy as i128), radius as i128);
+        if !self.overlaps((cx, cy), (radius, radius)) {
+            return;
+        }
+        let rows = (-radius).max(-cy)..=radius.min(*self.height() as i128 - 1 - cy);
+        for y in rows {
+            let half = (radius * (radius + 1) - y * y).isqrt();
+            self.fill_area((cx - half, cx + half + 1), (cy + y, cy + y + 1), pixel, BlendMode::Blend);
+        }
+    }
+
+    /// Walks the outline of the top right quarter of an axis-aligned ellipse with the midpoint
+    /// ellipse algorithm, from the top to the right, calling `visit` with the offset of each point
+    /// from the centre. Both radii must be positive.
+    fn trace_ellipse<F: FnMut(i128, i128)>(rx: i128, ry: i128, mut visit: F) {
+        let (rx2, ry2) = (rx * rx, ry * ry);
+        let (mut x, mut y) = (0, ry);
+        // The decision variables and gradients are scaled by 4 to stay whole numbers.
+        let (mut gradient_x, mut gradient_y) = (0, 2 * rx2 * y);
+        let mut decision = 4 * ry2 - 4 * rx2 * ry + rx2;
+        while gradient_x < gradient_y {
+            visit(x, y);
+            x += 1;
+            gradient_x += 2 * ry2;
+            if decision < 0 {
+                decision += 4 * (gradient_x + ry2);
+            } else {
+                y -= 1;
+                gradient_y -= 2 * rx2;
+                decision += 4 * (gradient_x - gradient_y + ry2);
+            }
+        }
+        let mut decision = ry2 * (2 * x + 1) * (2 * x + 1) + 4 * rx2 * (y - 1) * (y - 1) - 4 * rx2 * ry2;
+        while y >= 0 {
+            visit(x, y);
+            y -= 1;
+            gradient_y -= 2 * rx2;
+            if decision > 0 {
+                decision += 4 * (rx2 - gradient_y);
+            } else {
+                x += 1;
+                gradient_x += 2 * ry2;
+                decision += 4 * (gradient_x - gradient_y + rx2);
+            }
+        }
+    }
+
+    /// Draws the outline of an axis-aligned ellipse one pixel wide around the given centre, with
+    /// horizontal radius `rx` and vertical radius `ry`, blending the pixel over the image. The
+    /// outline is found with the midpoint ellipse algorithm, and an ellipse with a radius of 0 is a
+    /// straight line. Only the part of the ellipse within the image is drawn, and each pixel is
+    /// drawn at most once.
+    pub fn draw_ellipse(&mut self, cx: i64, cy: i64, rx: u32, ry: u32, pixel: Pixel) {
+        let (centre, rx, ry) = ((cx as i128, cy as i128), rx as i128, ry as i128);
+        if !self.overlaps(centre, (rx, ry)) {
+            return;
+        }
+        if rx == 0 || ry == 0 {
+            return self.draw_filled_ellipse(cx, cy, rx as u32, ry as u32, pixel);
+        }
+        Farbfeld::trace_ellipse(rx, ry,
+                                |x, y| self.draw_points(centre, &[(x, y), (-x, y), (x, -y), (-x, -y)], pixel));
+    }
+
+    /// Draws an axis-aligned ellipse filled with the pixel around the given centre, blending it
+    /// over the image. Each row is filled as a single span out to the outline drawn by
+    /// [draw_ellipse](#method.draw_ellipse).
+    pub fn draw_filled_ellipse(&mut self, cx: i64, cy: i64, rx: u32, ry: u32, pixel: Pixel) {
+        let ((cx, cy), rx, ry) = ((cx as i128, cy as i128), rx as i128, ry as i128);
+        if !self.overlaps((cx, cy), (rx, ry)) {
+            return;
+        }
+        if rx == 0 || ry == 0 {
+            return self.fill_area((cx - rx, cx + rx + 1), (cy - ry, cy + ry + 1), pixel, BlendMode::Blend);
+        }
+        let mut fill_rows = |half: i128, y: i128| {
+            self.fill_area((cx - half, cx + half + 1), (cy - y, cy - y + 1), pixel, BlendMode::Blend);
+            if y > 0 {
+                self.fill_area((cx - half, cx + half + 1), (cy + y, cy + y + 1), pixel, BlendMode::Blend);
+            }
+        };
+        // The outline moves outwards as it moves down, so the last point of each row is widest.
+        let mut row = (0, ry);
+        Farbfeld::trace_ellipse(rx, ry, |x, y| {
+            if y != row.1 {
+                fill_rows(row.0, row.1);
+            }
+            row = (x, y);
+        });
+        fill_rows(row.0, row.1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn white() -> Pixel {
+        Pixel::new(65535_u16, 65535, 65535, 65535)
+    }
+
+    fn canvas() -> Farbfeld {
+        Farbfeld::new(6, 4, vec![Pixel::default(); 24]).unwrap()
+    }
+
+    fn lit(farb: &Farbfeld) -> Vec<(usize, usize)> {
+        farb.pixels().iter().enumerate()
+            .filter(|(_, pixel)| **pixel != Pixel::default())
+            .map(|(i, _)| (i % *farb.width() as usize, i / *farb.width() as usize))
+            .collect()
+    }
+
+    #[test]
+    fn test_filled_rect_outside() {
+        let mut farb = canvas();
+        farb.draw_filled_rect(6, 0, 3, 3, white());
+        farb.draw_filled_rect(-3, 0, 3, 3, white());
+        farb.draw_filled_rect(0, -5, 3, 5, white());
+        farb.draw_filled_rect(i64::MAX, i64::MIN, u32::MAX, u32::MAX, white());
+        farb.draw_filled_rect(1, 1, 0, 3, white());
+        farb.draw_rect_outline(-10, -10, 5, 5, 1, white());
+        assert_eq!(canvas(), farb);
+    }
+
+    #[test]
+    fn test_filled_rect_clipped() {
+        let mut farb = canvas();
+        farb.draw_filled_rect(-2, 2, 4, 10, white());
+        assert_eq!(vec![(0, 2), (1, 2), (0, 3), (1, 3)], lit(&farb));
+
+        let mut farb = canvas();
+        farb.draw_filled_rect(i64::MIN, i64::MIN, u32::MAX, u32::MAX, white());
+        assert_eq!(canvas(), farb);
+        farb.draw_filled_rect(-(u32::MAX as i64) + 1, -1, u32::MAX, 2, white());
+        assert_eq!(vec![(0, 0)], lit(&farb));
+    }
+
+    #[test]
+    fn test_line() {
+        let mut farb = canvas();
+        farb.draw_line(0, 1, 3, 1, white());
+        assert_eq!(vec![(0, 1), (1, 1), (2, 1), (3, 1)], lit(&farb));
+
+        let mut farb = canvas();
+        farb.draw_line(4, 3, 4, 0, white());
+        assert_eq!(vec![(4, 0), (4, 1), (4, 2), (4, 3)], lit(&farb));
+
+        let mut farb = canvas();
+        farb.draw_line(0, 0, 3, 3, white());
+        assert_eq!(vec![(0, 0), (1, 1), (2, 2), (3, 3)], lit(&farb));
+
+        let mut farb = canvas();
+        farb.draw_line(0, 0, 1, 3, white());
+        assert_eq!(vec![(0, 0), (0, 1), (1, 2), (1, 3)], lit(&farb));
+        let mut reversed = canvas();
+        reversed.draw_line(1, 3, 0, 0, white());
+        assert_eq!(farb, reversed);
+
+        let mut farb = canvas();
+        farb.draw_line(5, 0, 0, 2, white());
+        assert_eq!(vec![(4, 0), (5, 0), (2, 1), (3, 1), (0, 2), (1, 2)], lit(&farb));
+
+        let mut farb = canvas();
+        farb.draw_line(2, 2, 2, 2, white());
+        assert_eq!(vec![(2, 2)], lit(&farb));
+    }
+
+    #[test]
+    fn test_line_clipped() {
+        let mut farb = canvas();
+        farb.draw_line(-5, -1, -1, -10, white());
+        farb.draw_line(10, 0, 20, 5, white());
+        farb.draw_line(-3, 0, 3, -6, white());
+        farb.draw_line(i64::MIN, -1, i64::MAX, -1, white());
+        farb.draw_line_aa(-5, 5, 10, 5, white());
+        assert_eq!(canvas(), farb);
+
+        farb.draw_line(i64::MIN, 2, i64::MAX, 2, white());
+        assert_eq!((0..6).map(|x| (x, 2)).collect::<Vec<_>>(), lit(&farb));
+
+        let mut farb = canvas();
+        farb.draw_line(-2, 0, 8, 10, white());
+        assert_eq!(vec![(0, 2), (1, 3)], lit(&farb));
+
+        let diagonal = vec![(0, 0), (1, 1), (2, 2), (3, 3)];
+        for &(start, end) in &[(i64::MIN, i64::MAX), (i64::MAX, i64::MIN)] {
+            let mut farb = canvas();
+            farb.draw_line(start, start, end, end, white());
+            assert_eq!(diagonal, lit(&farb));
+            let mut aa = canvas();
+            aa.draw_line_aa(start, start, end, end, white());
+            assert_eq!(farb, aa);
+        }
+        let mut farb = canvas();
+        farb.draw_line(i64::MIN, i64::MIN + 2, i64::MAX - 2, i64::MAX, white());
+        assert_eq!(vec![(0, 2), (1, 3)], lit(&farb));
+        farb.draw_line_aa(i64::MAX, i64::MIN, i64::MIN + 7, i64::MAX, white());
+    }
+
+    #[test]
+    fn test_mul_div() {
+        assert_eq!((7, 2), mul_div(5, 6, 4));
+        let big = 1_i128 << 65;
+        assert_eq!((big - 2, 1), mul_div(big - 1, big - 1, big));
+        assert_eq!((big - 1, 0), mul_div(big - 1, big - 1, big - 1));
+        assert_eq!((2, big - 3), mul_div(big - 1, 3, big));
+    }
+
+    #[test]
+    fn test_line_aa() {
+        let mut exact = canvas();
+        exact.draw_line(0, 0, 3, 3, white());
+        let mut aa = canvas();
+        aa.draw_line_aa(0, 0, 3, 3, white());
+        assert_eq!(exact, aa);
+
+        let mut farb = canvas();
+        farb.draw_line_aa(0, 0, 5, 2, white());
+        for x in 0..6 {
+            let column: u32 = (0..4).map(|y| *farb[y * 6 + x].alpha() as u32).sum();
+            assert!((65534..=65536).contains(&column), "column {} has alpha {}", x, column);
+        }
+        assert_eq!(65535, *farb[0].alpha());
+        assert_eq!(26214, *farb[6 + 1].alpha());
+        assert_eq!(39321, *farb[1].alpha());
+        assert!(farb.pixels().iter().all(|pixel| pixel.alpha() == &0 || pixel.red() == &65535));
+    }
+
+    /// Returns the offsets from the centre of the lit pixels.
+    fn offsets(farb: &Farbfeld, (cx, cy): (i64, i64)) -> Vec<(i64, i64)> {
+        lit(farb).into_iter().map(|(x, y)| (x as i64 - cx, y as i64 - cy)).collect()
+    }
+
+    #[test]
+    fn test_circle() {
+        for radius in 0..7 {
+            let mut farb = Farbfeld::new(15, 15, vec![Pixel::default(); 225]).unwrap();
+            farb.draw_circle(7, 7, radius, white());
+            let points = offsets(&farb, (7, 7));
+            for &(x, y) in &points {
+                for reflected in [(x, y), (y, x), (-x, y), (-y, x), (x, -y), (y, -x), (-x, -y), (-y, -x)].iter() {
+                    assert!(points.contains(reflected), "radius {} is missing {:?}", radius, reflected);
+                }
+            }
+            let radius = radius as i64;
+            assert!(points.contains(&(radius, 0)) && points.contains(&(0, -radius)));
+
+            let mut filled = Farbfeld::new(15, 15, vec![Pixel::default(); 225]).unwrap();
+            filled.draw_filled_circle(7, 7, radius as u32, white());
+            let filled = offsets(&filled, (7, 7));
+            assert!(points.iter().all(|point| filled.contains(point)));
+            let brute_force = (-radius..=radius)
+                .flat_map(|y| (-radius..=radius).map(move |x| (x, y)))
+                .filter(|(x, y)| x * x + y * y <= radius * (radius + 1))
+                .count();
+            assert_eq!(brute_force, filled.len(), "radius {}", radius);
+        }
+
+        let mut farb = canvas();
+        farb.draw_circle(2, 2, 0, white());
+        assert_eq!(vec![(2, 2)], lit(&farb));
+    }
+
+    #[test]
+    fn test_circle_clipped() {
+        let mut farb = canvas();
+        farb.draw_circle(-20, 2, 5, white());
+        farb.draw_filled_circle(2, 20, 5, white());
+        farb.draw_circle(i64::MAX, i64::MIN, u32::MAX, white());
+        farb.draw_ellipse(-5, -5, 4, 4, white());
+        farb.draw_filled_ellipse(13, 1, 7, 0, white());
+        assert_eq!(canvas(), farb);
+
+        for &(shape, radius) in &[(0, 3), (1, 3), (2, 4), (3, 4)] {
+            let mut clipped = canvas();
+            let mut whole = Farbfeld::new(20, 20, vec![Pixel::default(); 400]).unwrap();
+            for (farb, cx) in [(&mut clipped, 1), (&mut whole, 10)].iter_mut() {
+                match shape {
+                    0 => farb.draw_circle(*cx, 0, radius, white()),
+                    1 => farb.draw_filled_circle(*cx, 0, radius, white()),
+                    2 => farb.draw_ellipse(*cx, 0, radius, radius / 2, white()),
+                    _ => farb.draw_filled_ellipse(*cx, 0, radius, radius / 2, white())
+                }
+            }
+            let visible: Vec<_> = offsets(&whole, (10, 0)).into_iter()
+                .filter(|&(x, y)| (0..6).contains(&(x + 1)) && (0..4).contains(&y))
+                .collect();
+            assert_eq!(visible, offsets(&clipped, (1, 0)), "shape {}", shape);
+        }
+    }
+
+    #[test]
+    fn test_ellipse() {
+        let mut farb = Farbfeld::new(21, 11, vec![Pixel::default(); 231]).unwrap();
+        farb.draw_ellipse(10, 5, 8, 3, white());
+        let points = offsets(&farb, (10, 5));
+        for &(x, y) in &points {
+            for reflected in [(-x, y), (x, -y), (-x, -y)].iter() {
+                assert!(points.contains(reflected), "missing {:?}", reflected);
+            }
+        }
+        for point in [(8, 0), (-8, 0), (0, 3), (0, -3)].iter() {
+            assert!(points.contains(point), "missing {:?}", point);
+        }
+        assert!(points.iter().all(|&(x, y)| x.abs() <= 8 && y.abs() <= 3));
+
+        let mut filled = Farbfeld::new(21, 11, vec![Pixel::default(); 231]).unwrap();
+        filled.draw_filled_ellipse(10, 5, 8, 3, white());
+        let filled = offsets(&filled, (10, 5));
+        assert!(points.iter().all(|point| filled.contains(point)));
+        assert!(filled.contains(&(0, 0)) && filled.contains(&(7, 1)));
+
+        let mut line = canvas();
+        line.draw_ellipse(2, 1, 2, 0, white());
+        assert_eq!(vec![(0, 1), (1, 1), (2, 1), (3, 1), (4, 1)], lit(&line));
+    }
+
+    #[test]
+    fn test_circle_blends_once() {
+        let mut farb = Farbfeld::new(15, 15, vec![Pixel::new(0_u16, 0, 0, 65535); 225]).unwrap();
+        let half = Pixel::new(65535_u16, 65535, 65535, 32768);
+        farb.draw_circle(7, 7, 6, half);
+        farb.draw_ellipse(7, 7, 6, 2, half);
+        let once = half.over(Pixel::new(0_u16, 0, 0, 65535));
+        let twice = half.over(once);
+        assert!(farb.pixels().iter().all(|&pixel| pixel == once || pixel == twice || pixel.red() == &0));
+        assert!(farb.pixels().contains(&once) && farb.pixels().contains(&twice));
+    }
+
+    #[test]
+    fn test_filled_rect_blend() {
+        let mut farb = Farbfeld::new(2, 1, vec![Pixel::new(0_u16, 0, 65535, 65535); 2]).unwrap();
+        let half_red = Pixel::new(65535_u16, 0, 0, 32768);
+        farb.draw_filled_rect(0, 0, 1, 1, half_red);
+        farb.draw_filled_rect_with(1, 0, 1, 1, half_red, BlendMode::Replace);
+        assert_eq!(half_red.over(Pixel::new(0_u16, 0, 65535, 65535)), farb[0]);
+        assert_eq!(half_red, farb[1]);
+    }
+
+    #[test]
+    fn test_rect_outline() {
+        let mut farb = canvas();
+        farb.draw_rect_outline(0, 0, 6, 4, 1, white());
+        let mut expected = canvas();
+        expected.draw_filled_rect(0, 0, 6, 4, white());
+        expected.draw_filled_rect_with(1, 1, 4, 2, Pixel::default(), BlendMode::Replace);
+        assert_eq!(expected, farb);
+
+        let mut thick = canvas();
+        thick.draw_rect_outline(-1, 0, 5, 4, 2, white());
+        let mut filled = canvas();
+        filled.draw_filled_rect(-1, 0, 5, 4, white());
+        assert_eq!(filled, thick);
+
+        let mut blended = Farbfeld::new(6, 4, vec![Pixel::new(0_u16, 0, 0, 65535); 24]).unwrap();
+        blended.draw_rect_outline(0, 0, 6, 4, 1, Pixel::new(65535_u16, 65535, 65535, 32768));
+        assert!(blended.pixels().iter().all(|pixel| pixel.red() == &0 || pixel.red() == &32768));
+    }
+}
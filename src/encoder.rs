@@ -0,0 +1,575 @@
+//! Writing Farbfeld images one row at a time, without holding the whole image in memory.
+
+use std::io::{self, Read, Write, Seek, SeekFrom};
+
+use crate::pixel::{self, Pixel};
+use crate::farbfeld::{self, Farbfeld};
+use crate::endian;
+use crate::error::*;
+
+/// Patches the height in a header which has already been written, given the position of the header.
+type PatchHeight<W> = fn(&mut W, u64, u32) -> io::Result<()>;
+
+/// An encoder writing a Farbfeld image one row at a time. The header is written when the encoder
+/// is created, then each row is written as it is given to the encoder.
+///
+/// # Examples
+/// ```
+/// # use ::ruff::*;
+/// let mut encoder = FarbfeldEncoder::new(Vec::new(), 2, 2).unwrap();
+/// for _ in 0..2 {
+///     encoder.write_row(&[Pixel::new(1_u16, 2_u16, 3_u16, 4_u16); 2]).unwrap();
+/// }
+/// let (data, written) = encoder.finish().unwrap();
+///
+/// assert_eq!(16 + 2 * 2 * 8, data.len());
+/// assert_eq!(data.len() as u64, written);
+/// ```
+#[derive(Debug)]
+pub struct FarbfeldEncoder<W> {
+    writer: W,
+    width: u32,
+    height: u32,
+    rows_written: u32,
+    bytes_written: u64,
+    buff: Vec<u8>,
+    patch_height: Option<(u64, PatchHeight<W>)>
+}
+
+impl<W: Write> FarbfeldEncoder<W> {
+    /// Creates a new encoder for an image with the given dimensions, writing the header
+    /// immediately.
+    ///
+    /// # Errors
+    /// Returns an [Error::IoError](../error/enum.Error.html) if the header cannot be
+    /// written.
+    pub fn new(mut writer: W, width: u32, height: u32) -> Result<FarbfeldEncoder<W>> {
+        farbfeld::write_header(&mut writer, width, height)?;
+        Ok(FarbfeldEncoder {
+            writer,
+            width,
+            height,
+            rows_written: 0,
+            bytes_written: 16,
+            buff: Vec::new(),
+            patch_height: None
+        })
+    }
+
+    /// Returns the width of the image being written.
+    pub fn width(&self) -> &u32 {
+        &self.width
+    }
+
+    /// Returns the height of the image being written. For an encoder created with
+    /// [new_unknown_height](#method.new_unknown_height) this is the maximum number of rows which
+    /// can be written.
+    pub fn height(&self) -> &u32 {
+        &self.height
+    }
+
+    /// Returns the number of rows written so far.
+    pub fn rows_written(&self) -> &u32 {
+        &self.rows_written
+    }
+
+    /// Returns the number of bytes written so far, including the header.
+    pub fn bytes_written(&self) -> &u64 {
+        &self.bytes_written
+    }
+
+    /// Writes the next row of the image.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li>Returns an <a href="../error/enum.Error.html">Error::InvalidRowLength</a> if
+    ///     the row doesn't contain exactly width pixels.</li>
+    ///     <li>Returns an <a href="../error/enum.Error.html">Error::InvalidRowCount</a> if
+    ///     every row of the image has already been written.</li>
+    ///     <li>Returns an <a href="../error/enum.Error.html">Error::IoError</a> if the write
+    ///     fails.</li>
+    /// </ul>
+    pub fn write_row(&mut self, row: &[Pixel]) -> Result<()> {
+        if row.len() != self.width as usize {
+            return Err(Error::InvalidRowLength(self.rows_written, self.width, row.len()));
+        }
+        if self.rows_written >= self.height {
+            return Err(Error::InvalidRowCount(self.height, self.rows_written as u64 + 1));
+        }
+        self.buff.clear();
+        farbfeld::extend_row_bytes(&mut self.buff, row);
+        self.writer.write_all(&self.buff).map_err(Error::IoError)?;
+        self.rows_written += 1;
+        self.bytes_written += self.buff.len() as u64;
+        Ok(())
+    }
+
+    /// Flushes the writer once every row has been written, returning it along with the total
+    /// number of bytes written, including the header.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidRowCount](../error/enum.Error.html) if fewer than height
+    /// rows were written, or if rows were written to a zero-width encoder created with
+    /// [new_unknown_height](#method.new_unknown_height). Returns an
+    /// [Error::IoError](../error/enum.Error.html) if the flush fails.
+    pub fn finish(mut self) -> Result<(W, u64)> {
+        if let Some((header_pos, patch_height)) = self.patch_height {
+            if self.width == 0 && self.rows_written != 0 {
+                return Err(Error::InvalidRowCount(0, self.rows_written as u64));
+            }
+            patch_height(&mut self.writer, header_pos, self.rows_written).map_err(Error::IoError)?;
+        } else if self.rows_written != self.height {
+            return Err(Error::InvalidRowCount(self.height, self.rows_written as u64));
+        }
+        self.writer.flush().map_err(Error::IoError)?;
+        Ok((self.writer, self.bytes_written))
+    }
+}
+
+impl<W: Write + Seek> FarbfeldEncoder<W> {
+    /// Creates a new encoder for an image with the given width and a height which isn't known
+    /// yet, writing the header immediately with a placeholder height. Any number of rows can then
+    /// be written, and [finish](#method.finish) seeks back to patch the header with the number of
+    /// rows written, before seeking back to the end of the image.
+    ///
+    /// Rows must still contain exactly width pixels, as with [new](#method.new). Empty rows hold no
+    /// data, so the height of a zero-width image can't be recovered from its pixels, and
+    /// [finish](#method.finish) refuses to patch the header if any were written.
+    ///
+    /// # Errors
+    /// Returns an [Error::IoError](../error/enum.Error.html) if the position of the
+    /// writer cannot be found or the header cannot be written.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ::ruff::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut encoder = FarbfeldEncoder::new_unknown_height(Cursor::new(Vec::new()), 2).unwrap();
+    /// for _ in 0..3 {
+    ///     encoder.write_row(&[Pixel::default(); 2]).unwrap();
+    /// }
+    /// let (data, _) = encoder.finish().unwrap();
+    ///
+    /// assert_eq!(&3, Farbfeld::from_read(&data.into_inner()[..]).unwrap().height());
+    /// ```
+    pub fn new_unknown_height(mut writer: W, width: u32) -> Result<FarbfeldEncoder<W>> {
+        let header_pos = writer.stream_position().map_err(Error::IoError)?;
+        let mut encoder = FarbfeldEncoder::new(writer, width, 0)?;
+        encoder.height = u32::MAX;
+        encoder.patch_height = Some((header_pos, write_height::<W>));
+        Ok(encoder)
+    }
+}
+
+/// Overwrites the height of the header starting at header_pos, leaving the write positioned at
+/// the end of the image.
+fn write_height<W: Write + Seek>(write: &mut W, header_pos: u64, height: u32) -> io::Result<()> {
+    let end = write.stream_position()?;
+    write.seek(SeekFrom::Start(header_pos + 12))?;
+    write.write_all(&height.to_be_bytes())?;
+    write.seek(SeekFrom::Start(end))?;
+    Ok(())
+}
+
+/// A reusable encoder for writing many images, such as tiles. The image is serialized into an
+/// internal buffer which is kept between calls, growing to fit the largest image encoded, so
+/// encoding many images doesn't allocate for each one.
+///
+/// # Examples
+/// ```
+/// # use ::ruff::*;
+/// let tile = Farbfeld::new(4, 4, vec![Pixel::default(); 16]).unwrap();
+/// let mut buffer = EncodeBuffer::new();
+/// let mut out = Vec::new();
+/// for _ in 0..10 {
+///     buffer.encode(&tile, &mut out).unwrap();
+/// }
+///
+/// assert_eq!(10 * (16 + 16 * 8), out.len());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct EncodeBuffer {
+    buff: Vec<u8>
+}
+
+impl EncodeBuffer {
+    /// Creates a new EncodeBuffer with an empty buffer.
+    pub fn new() -> EncodeBuffer {
+        EncodeBuffer {
+            buff: Vec::new()
+        }
+    }
+
+    /// Returns the current capacity of the internal buffer in bytes.
+    pub fn capacity(&self) -> usize {
+        self.buff.capacity()
+    }
+
+    /// Releases the memory held by the internal buffer.
+    pub fn shrink(&mut self) {
+        self.buff = Vec::new();
+    }
+
+    /// Writes the image to the given write according to the
+    /// [spec](http://tools.suckless.org/farbfeld/), using a single write of the internal buffer.
+    /// Returns the number of bytes written, including the header.
+    ///
+    /// # Errors
+    /// Returns an [Error::IoError](../error/enum.Error.html) if the write fails.
+    pub fn encode<W: Write>(&mut self, image: &Farbfeld, write: &mut W) -> Result<u64> {
+        self.buff.clear();
+        image.save_to_vec(&mut self.buff);
+        write.write_all(&self.buff)
+            .map(|_| self.buff.len() as u64)
+            .map_err(Error::IoError)
+    }
+
+    /// Serializes the image into the start of the given slice, returning the number of bytes
+    /// written. The internal buffer isn't used.
+    ///
+    /// # Errors
+    /// Returns an [Error::BufferTooSmall](../error/enum.Error.html) if the slice is smaller
+    /// than 16 + 8 bytes per pixel.
+    pub fn encode_to_slice(&self, image: &Farbfeld, out: &mut [u8]) -> Result<usize> {
+        let len = 16 + image.pixels().len() as u64 * 8;
+        if (out.len() as u64) < len {
+            return Err(Error::BufferTooSmall(len, out.len()));
+        }
+        out[..16].copy_from_slice(&farbfeld::header_bytes(*image.width(), *image.height()));
+        endian::encode_channels(pixel::as_channels(image.pixels()), &mut out[16..len as usize]);
+        Ok(len as usize)
+    }
+}
+
+/// A Read producing the encoded bytes of a Farbfeld image on demand, without serializing the whole
+/// image up front. Created by [Farbfeld::as_read](../struct.Farbfeld.html#method.as_read).
+///
+/// # Examples
+/// ```
+/// # use ::ruff::*;
+/// use std::io::Read;
+///
+/// let farb = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2_u16, 3_u16, 4_u16)]).unwrap();
+/// let mut data = Vec::new();
+/// farb.as_read().read_to_end(&mut data).unwrap();
+///
+/// assert_eq!(farb.to_bytes(), data);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FarbfeldBytes<'a> {
+    image: &'a Farbfeld,
+    header: [u8; 16],
+    pos: u64
+}
+
+impl Farbfeld {
+    /// Creates a Read producing the encoded bytes of this image, as produced by
+    /// [to_bytes](#method.to_bytes), encoding pixels as they are read.
+    pub fn as_read(&self) -> FarbfeldBytes<'_> {
+        FarbfeldBytes {
+            image: self,
+            header: farbfeld::header_bytes(*self.width(), *self.height()),
+            pos: 0
+        }
+    }
+}
+
+impl<'a> FarbfeldBytes<'a> {
+    /// Returns the total number of bytes in the encoded image.
+    pub fn encoded_len(&self) -> u64 {
+        16 + self.image.pixels().len() as u64 * 8
+    }
+
+    /// Returns the number of bytes which haven't been read yet.
+    pub fn remaining(&self) -> u64 {
+        self.encoded_len() - self.pos
+    }
+}
+
+impl<'a> Read for FarbfeldBytes<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut written = 0;
+        while written < buf.len() && self.pos < self.encoded_len() {
+            let out = &mut buf[written..];
+            let copied = if self.pos < 16 {
+                copy_from(&self.header[self.pos as usize..], out)
+            } else {
+                let offset = self.pos - 16;
+                let pixels = &self.image.pixels()[(offset / 8) as usize..];
+                let within = (offset % 8) as usize;
+                let whole = (out.len() / 8).min(pixels.len());
+                if within == 0 && whole > 0 {
+                    endian::encode_channels(pixel::as_channels(&pixels[..whole]), &mut out[..whole * 8]);
+                    whole * 8
+                } else {
+                    copy_from(&farbfeld::pixel_bytes(&pixels[0])[within..], out)
+                }
+            };
+            written += copied;
+            self.pos += copied as u64;
+        }
+        Ok(written)
+    }
+}
+
+/// Copies as much of `src` into `dst` as fits, returning the number of bytes copied.
+fn copy_from(src: &[u8], dst: &mut [u8]) -> usize {
+    let len = src.len().min(dst.len());
+    dst[..len].copy_from_slice(&src[..len]);
+    len
+}
+
+/// Writes a Farbfeld image with the given dimensions, writing the header then each row as it is
+/// produced by `rows`. Only one row is held in memory at a time, so this suits pipelines rendering
+/// rows on demand. Returns the number of bytes written, including the header.
+///
+/// # Errors
+/// Returns the same errors as [FarbfeldEncoder](struct.FarbfeldEncoder.html). Errors for rows of
+/// the wrong length contain the index of the offending row, and an
+/// [Error::InvalidRowCount](../error/enum.Error.html) is returned if `rows` doesn't
+/// produce exactly height rows.
+///
+/// # Examples
+/// ```
+/// # use ::ruff::*;
+/// let mut data = Vec::new();
+/// save_rows(&mut data, 3, 2, (0..2_u16).map(|y| vec![Pixel::new(y, y, y, 65535); 3])).unwrap();
+///
+/// assert_eq!(16 + 3 * 2 * 8, data.len());
+/// ```
+pub fn save_rows<W, I>(write: W, width: u32, height: u32, rows: I) -> Result<u64>
+    where W: Write, I: IntoIterator, I::Item: AsRef<[Pixel]> {
+    let mut encoder = FarbfeldEncoder::new(write, width, height)?;
+    for row in rows {
+        encoder.write_row(row.as_ref())?;
+    }
+    encoder.finish().map(|(_, written)| written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_as_read_small_buffer() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut read = farb.as_read();
+        let mut data = Vec::new();
+        let mut buff = [0_u8; 3];
+        assert_eq!(read.encoded_len(), read.remaining());
+        loop {
+            match read.read(&mut buff).unwrap() {
+                0 => break,
+                n => data.extend_from_slice(&buff[..n])
+            }
+        }
+
+        assert_eq!(0, read.remaining());
+        assert_eq!(farb.to_bytes(), data);
+    }
+
+    #[test]
+    fn test_as_read_straddling_header() {
+        let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2_u16, 3_u16, 4_u16),
+                                            Pixel::new(5_u16, 6_u16, 7_u16, 8_u16)]).unwrap();
+        let mut read = farb.as_read();
+        let mut first = [0_u8; 13];
+        let mut second = [0_u8; 7];
+        let mut rest = Vec::new();
+        read.read_exact(&mut first).unwrap();
+        read.read_exact(&mut second).unwrap();
+        read.read_to_end(&mut rest).unwrap();
+
+        let bytes = farb.to_bytes();
+        assert_eq!(&bytes[..13], &first[..]);
+        assert_eq!(&bytes[13..20], &second[..]);
+        assert_eq!(&bytes[20..], &rest[..]);
+    }
+
+    #[test]
+    fn test_as_read_whole_and_partial_pixels() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut read = farb.as_read();
+        let mut data = Vec::new();
+        let mut buff = [0_u8; 21];
+        loop {
+            match read.read(&mut buff).unwrap() {
+                0 => break,
+                n => data.extend_from_slice(&buff[..n])
+            }
+        }
+
+        assert_eq!(farb.to_bytes(), data);
+    }
+
+    #[test]
+    fn test_encode_buffer_reuse() {
+        let small = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2_u16, 3_u16, 4_u16)]).unwrap();
+        let large = Farbfeld::from_file("test.ff").unwrap();
+        let mut buffer = EncodeBuffer::new();
+
+        let mut out = Vec::new();
+        assert_eq!(16 + large.pixels().len() as u64 * 8, buffer.encode(&large, &mut out).unwrap());
+        assert_eq!(large.to_bytes(), out);
+        let capacity = buffer.capacity();
+
+        out.clear();
+        buffer.encode(&small, &mut out).unwrap();
+        assert_eq!(small.to_bytes(), out);
+        assert_eq!(capacity, buffer.capacity());
+
+        buffer.shrink();
+        assert_eq!(0, buffer.capacity());
+    }
+
+    #[test]
+    fn test_encode_to_slice() {
+        let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2_u16, 3_u16, 4_u16),
+                                            Pixel::new(5_u16, 6_u16, 7_u16, 8_u16)]).unwrap();
+        let mut out = [0xff_u8; 40];
+        assert_eq!(32, EncodeBuffer::new().encode_to_slice(&farb, &mut out).unwrap());
+        assert_eq!(&farb.to_bytes()[..], &out[..32]);
+        assert_eq!(&[0xff_u8; 8], &out[32..]);
+
+        match EncodeBuffer::new().encode_to_slice(&farb, &mut out[..31]) {
+            Err(Error::BufferTooSmall(32, 31)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_eq_save() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut encoder = FarbfeldEncoder::new(Vec::new(), *farb.width(), *farb.height()).unwrap();
+        for row in 0..*farb.height() {
+            encoder.write_row(farb.row(row).unwrap()).unwrap();
+        }
+        let mut saved = Vec::new();
+        farb.save(&mut saved).unwrap();
+
+        let (data, written) = encoder.finish().unwrap();
+        assert_eq!(saved, data);
+        assert_eq!(16 + farb.pixels().len() as u64 * 8, written);
+    }
+
+    #[test]
+    fn test_wrong_row_length() {
+        let mut encoder = FarbfeldEncoder::new(Vec::new(), 2, 1).unwrap();
+        match encoder.write_row(&[Pixel::default(); 3]) {
+            Err(Error::InvalidRowLength(0, 2, 3)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        assert_eq!(&0, encoder.rows_written());
+    }
+
+    #[test]
+    fn test_too_many_rows() {
+        let mut encoder = FarbfeldEncoder::new(Vec::new(), 1, 1).unwrap();
+        encoder.write_row(&[Pixel::default()]).unwrap();
+        match encoder.write_row(&[Pixel::default()]) {
+            Err(Error::InvalidRowCount(1, 2)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        assert_eq!(16 + 8, encoder.finish().unwrap().0.len());
+    }
+
+    #[test]
+    fn test_too_few_rows() {
+        let mut encoder = FarbfeldEncoder::new(Vec::new(), 1, 3).unwrap();
+        encoder.write_row(&[Pixel::default()]).unwrap();
+        match encoder.finish() {
+            Err(Error::InvalidRowCount(3, 1)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_unknown_height() {
+        let mut state = 0x9e37_79b9_u32;
+        let counts = (0..8).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state % 128
+        });
+        for rows in core::iter::once(0).chain(counts) {
+            let mut encoder = FarbfeldEncoder::new_unknown_height(io::Cursor::new(Vec::new()), 3).unwrap();
+            for row in 0..rows {
+                encoder.write_row(&[Pixel::new(row as u16, 0_u16, 0_u16, 65535_u16); 3]).unwrap();
+            }
+            let (cursor, written) = encoder.finish().unwrap();
+            assert_eq!(written, cursor.position());
+
+            let farb = Farbfeld::from_read(&cursor.into_inner()[..]).unwrap();
+            assert_eq!((&3, &rows), (farb.width(), farb.height()));
+            for row in 0..rows {
+                assert_eq!(&[Pixel::new(row as u16, 0_u16, 0_u16, 65535_u16); 3], farb.row(row).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_height_zero_width() {
+        let encoder = FarbfeldEncoder::new_unknown_height(io::Cursor::new(Vec::new()), 0).unwrap();
+        let (cursor, _) = encoder.finish().unwrap();
+        assert_eq!(Farbfeld::new(0, 0, Vec::new()).unwrap().to_bytes(), cursor.into_inner());
+
+        let mut encoder = FarbfeldEncoder::new_unknown_height(io::Cursor::new(Vec::new()), 0).unwrap();
+        encoder.write_row(&[]).unwrap();
+        encoder.write_row(&[]).unwrap();
+        match encoder.finish() {
+            Err(Error::InvalidRowCount(0, 2)) => {},
+            res => panic!("Unexpected result {:?}", res.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn test_unknown_height_after_existing_data() {
+        let mut cursor = io::Cursor::new(b"prefix".to_vec());
+        cursor.seek(SeekFrom::End(0)).unwrap();
+        let mut encoder = FarbfeldEncoder::new_unknown_height(cursor, 1).unwrap();
+        encoder.write_row(&[Pixel::default()]).unwrap();
+        match encoder.write_row(&[Pixel::default(); 2]) {
+            Err(Error::InvalidRowLength(1, 1, 2)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        let (cursor, _) = encoder.finish().unwrap();
+        let data = cursor.into_inner();
+
+        assert_eq!(b"prefix", &data[..6]);
+        assert_eq!(Farbfeld::new(1, 1, vec![Pixel::default()]).unwrap().to_bytes(), &data[6..]);
+    }
+
+    #[test]
+    fn test_save_rows_eq_save() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut data = Vec::new();
+        let written = save_rows(&mut data, *farb.width(), *farb.height(),
+                                (0..*farb.height()).map(|row| farb.row(row).unwrap())).unwrap();
+
+        assert_eq!(farb.to_bytes(), data);
+        assert_eq!(data.len() as u64, written);
+    }
+
+    #[test]
+    fn test_save_rows_wrong_length() {
+        let rows = (0..4).map(|row| vec![Pixel::default(); if row == 2 { 1 } else { 2 }]);
+        match save_rows(Vec::new(), 2, 4, rows) {
+            Err(Error::InvalidRowLength(2, 2, 1)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_save_rows_wrong_count() {
+        match save_rows(Vec::new(), 1, 2, vec![[Pixel::default()]; 3]) {
+            Err(Error::InvalidRowCount(2, 3)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        match save_rows(Vec::new(), 1, 2, vec![[Pixel::default()]; 1]) {
+            Err(Error::InvalidRowCount(2, 1)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+}
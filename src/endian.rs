@@ -0,0 +1,109 @@
+//! Converting between the big endian channels of Farbfeld files and native endian channels.
+//!
+//! Swapping the bytes of each channel is the bulk of the work of parsing and saving images, so
+//! the four channels of a pixel are converted together by swapping the bytes within each u16 of a
+//! u64, which the compiler turns into vector instructions where they're available. On big endian
+//! targets no swapping is needed and the bytes are copied. A scalar conversion handles any
+//! channels left over at the end.
+
+use core::convert::TryInto;
+use core::slice;
+
+/// Selects the low byte of every u16 in a u64.
+const LOW_BYTES: u64 = 0x00ff_00ff_00ff_00ff;
+
+/// Swaps between big endian and native endian u16s in the given bytes, writing the result to
+/// `output`, which must be the same length as `input`. Both lengths must be even.
+fn swap_channels(input: &[u8], output: &mut [u8]) {
+    debug_assert_eq!(input.len(), output.len());
+    if cfg!(target_endian = "big") {
+        output.copy_from_slice(input);
+        return;
+    }
+    let mut inputs = input.chunks_exact(8);
+    let mut outputs = output.chunks_exact_mut(8);
+    for (input, output) in (&mut inputs).zip(&mut outputs) {
+        let word = u64::from_ne_bytes(input.try_into().expect("the chunk is 8 bytes"));
+        let swapped = ((word & LOW_BYTES) << 8) | ((word >> 8) & LOW_BYTES);
+        output.copy_from_slice(&swapped.to_ne_bytes());
+    }
+    for (input, output) in inputs.remainder().chunks_exact(2).zip(outputs.into_remainder().chunks_exact_mut(2)) {
+        output.copy_from_slice(&u16::from_be_bytes([input[0], input[1]]).to_ne_bytes());
+    }
+}
+
+/// Returns the bytes of the channels, without copying.
+fn channel_bytes_mut(channels: &mut [u16]) -> &mut [u8] {
+    // u8 has no alignment requirement, and every byte of a u16 is a valid u8 and vice versa.
+    unsafe { slice::from_raw_parts_mut(channels.as_mut_ptr() as *mut u8, channels.len() * 2) }
+}
+
+/// Returns the bytes of the channels, without copying.
+fn channel_bytes(channels: &[u16]) -> &[u8] {
+    // The layout is the same as for channel_bytes_mut.
+    unsafe { slice::from_raw_parts(channels.as_ptr() as *const u8, channels.len() * 2) }
+}
+
+/// Decodes the big endian channels in `bytes` into `channels`, which must hold half as many
+/// values as there are bytes.
+pub(crate) fn decode_channels(bytes: &[u8], channels: &mut [u16]) {
+    swap_channels(bytes, channel_bytes_mut(channels));
+}
+
+/// Encodes the channels as big endian values into `bytes`, which must be twice as long as
+/// `channels`.
+pub(crate) fn encode_channels(channels: &[u16], bytes: &mut [u8]) {
+    swap_channels(channel_bytes(channels), bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Returns `len` bytes from a xorshift generator.
+    fn random_bytes(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state >> 24) as u8
+        }).collect()
+    }
+
+    #[test]
+    fn test_decode_matches_scalar() {
+        for len in (0..70).chain([1 << 12, (1 << 12) + 6]) {
+            let bytes = random_bytes(len * 2, len as u32 + 1);
+            let expected: Vec<u16> = bytes.chunks_exact(2).map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]])).collect();
+            let mut channels = vec![0; len];
+            decode_channels(&bytes, &mut channels);
+            assert_eq!(expected, channels, "{} channels", len);
+        }
+    }
+
+    #[test]
+    fn test_encode_matches_scalar() {
+        for len in (0..70).chain([1 << 12, (1 << 12) + 6]) {
+            let channels: Vec<u16> = random_bytes(len * 2, len as u32 + 7).chunks_exact(2)
+                .map(|chunk| u16::from_ne_bytes([chunk[0], chunk[1]]))
+                .collect();
+            let expected: Vec<u8> = channels.iter().flat_map(|channel| channel.to_be_bytes()).collect();
+            let mut bytes = vec![0; len * 2];
+            encode_channels(&channels, &mut bytes);
+            assert_eq!(expected, bytes, "{} channels", len);
+
+            let mut decoded = vec![0; len];
+            decode_channels(&bytes, &mut decoded);
+            assert_eq!(channels, decoded, "{} channels", len);
+        }
+        assert_eq!([0x12, 0x34, 0xab, 0xcd], {
+            let mut bytes = [0; 4];
+            encode_channels(&[0x1234, 0xabcd], &mut bytes);
+            bytes
+        });
+    }
+}
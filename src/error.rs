@@ -1,28 +1,267 @@
-//! Errors produced by ruff. Generated by error-chain.
+//! Errors produced by ruff.
+//!
+//! Parsing failures are reported using ruff's own errors, so matching on them doesn't require
+//! depending on nom. Failures are mapped as follows:
+//! <ul>
+//!     <li><a href="enum.Error.html">Error::InvalidMagic</a> if the input doesn't start
+//!     with <code>farbfeld</code>.</li>
+//!     <li><a href="enum.Error.html">Error::UnexpectedEof</a> if the input ends part way
+//!     through the header or a pixel, together with the number of missing bytes if known.</li>
+//!     <li><a href="enum.Error.html">Error::InvalidFarbfeldDimensions</a> if the number of
+//!     parsed pixels doesn't match the header.</li>
+//!     <li><a href="enum.Error.html">Error::ParseFailure</a> for any other parsing
+//!     failure, together with the offset into the input where parsing failed.</li>
+//! </ul>
+//!
+//! Earlier versions exposed nom's errors directly through `ErrorKind::NomError` and
+//! `ErrorKind::NotEnoughDataError`. These are replaced by `ParseFailure` and `UnexpectedEof`
+//! respectively.
+//!
+//! # Migrating from error-chain
+//! Earlier versions generated their errors with error-chain, so every error was an `Error`
+//! struct wrapping an `ErrorKind` and a backtrace. `Error` is now a plain enum with the variants
+//! of the old `ErrorKind`, which is removed. It implements `std::error::Error`, with the errors
+//! from std and other crates available through `source()`, and is `Send + Sync + 'static`, so it
+//! can be converted into a `Box<dyn Error + Send + Sync>` with `?`.
+//!
+//! Matches are updated by dropping the wrapper, so `Err(Error(ErrorKind::InvalidMagic, _))`
+//! becomes `Err(Error::InvalidMagic)`:
+//!
+//! ```
+//! # use ruff::Farbfeld;
+//! # use ruff::error::Error;
+//! match Farbfeld::from_bytes(b"farbfelx") {
+//!     Err(Error::InvalidMagic) => {},
+//!     res => panic!("Unexpected result {:?}", res)
+//! }
+//! ```
+//!
+//! `Error` is `#[non_exhaustive]`, so matches must include a wildcard arm. Without the `std`
+//! feature, the variants wrapping errors from std or from optional dependencies which themselves
+//! require std are unavailable.
 
-use nom::Needed;
-use nom::Err;
+use alloc::boxed::Box;
+use core::fmt;
 
-error_chain! {
-    foreign_links {
-        NomError(Err) #[doc="A wrapper for a Nom error."];
-        IoError(::std::io::Error) #[doc="A wrapper for std's IO error."];
-    }
+use crate::pixel::Channel;
+use crate::resize::ResizeFilter;
+
+/// An error produced by ruff.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A wrapper for std's IO error.
+    #[cfg(feature = "std")]
+    IoError(::std::io::Error),
+
+    /// A wrapper for png's decoding error.
+    #[cfg(feature = "png")]
+    PngDecodingError(::png::DecodingError),
+
+    /// A wrapper for png's encoding error.
+    #[cfg(feature = "png")]
+    PngEncodingError(::png::EncodingError),
+
+    /// A wrapper for base64's decoding error.
+    #[cfg(feature = "base64")]
+    Base64Error(::base64::DecodeError),
+
+    /// Produced when the input doesn't start with the Farbfeld magic value, `farbfeld`.
+    InvalidMagic,
+
+    /// Produced when the input ends before parsing is finished.
+    UnexpectedEof {
+        /// The number of additional bytes needed to continue parsing, if known.
+        needed: Option<u64>
+    },
+
+    /// Produced when parsing fails for any reason other than a bad magic value or a lack of
+    /// data.
+    ParseFailure {
+        /// The offset in bytes into the input where parsing failed.
+        offset: u64
+    },
+
+    /// Produced when reading data compressed with a format whose feature isn't enabled.
+    /// Contains the name of the format.
+    UnsupportedCompression(&'static str),
+
+    /// Produced when the dimensions specified in the image header file don't match the number
+    /// of pixels parsed.
+    InvalidFarbfeldDimensions,
+
+    /// Produced when a data URI doesn't start with `data:image/farbfeld;base64,`.
+    InvalidDataUri,
+
+    /// Produced when an image is too large to be written in another format. Contains the name
+    /// of the format.
+    ImageTooLarge(&'static str),
+
+    /// Produced when the header of a netpbm image is malformed or describes an image which
+    /// isn't supported. Contains a description of the problem.
+    InvalidNetpbmHeader(&'static str),
+
+    /// Produced when a rectangle doesn't fit within an image. Contains the x and y coordinates
+    /// of the rectangle's top left corner, followed by its width and height.
+    RegionOutOfBounds(u32, u32, u32, u32),
+
+    /// Produced when a row given to an encoder doesn't contain exactly one pixel per column of
+    /// the image. Contains the index of the offending row, the expected number of pixels and
+    /// the number found.
+    InvalidRowLength(u32, u32, usize),
+
+    /// Produced when a buffer is too small to hold an encoded image. Contains the number of
+    /// bytes needed and the size of the buffer.
+    BufferTooSmall(u64, usize),
+
+    /// Produced when an encoder is given more or fewer rows than the height of the image.
+    InvalidRowCount(u32, u64),
+
+    /// Produced when the parameters of an adjustment are out of range. Contains a description of
+    /// the problem.
+    InvalidAdjustment(&'static str),
+
+    /// Produced when a plane doesn't contain exactly one value per pixel of the image. Contains
+    /// the channel of the plane, the expected number of values and the number found.
+    InvalidPlaneLength(Channel, u64, usize),
+
+    /// Produced when two images which must be the same size aren't. Contains the expected width
+    /// and height, followed by the width and height found.
+    DimensionMismatch(u32, u32, u32, u32),
+
+    /// Produced when a convolution kernel is malformed. Contains a description of the problem.
+    InvalidKernel(&'static str),
 
-    errors {
-        /// Produced when a parser doesn't get enough data to finish parsing.
-        NotEnoughDataError(needed: Needed) {
-            description("Not enough data to parse!")
-            display("Need {} more bytes to successfully parse!", match *needed {
-                Needed::Unknown => "Unknown".to_string(),
-                Needed::Size(size) => size.to_string()
-            })
+    /// Produced when a palette, or the number of colours requested for one, is unusable. Contains
+    /// a description of the problem.
+    InvalidPalette(&'static str),
+
+    /// Produced when a step of a [Pipeline](../pipeline/struct.Pipeline.html) fails. Contains the
+    /// name of the step and its error.
+    TransformFailed(&'static str, Box<Error>),
+
+    /// Produced when an operation which only supports square images is given an image which
+    /// isn't square. Contains the width and height of the image.
+    NotSquare(u32, u32),
+
+    /// Produced when an operation can't sample an image with the given
+    /// [ResizeFilter](../resize/enum.ResizeFilter.html). Contains the filter.
+    UnsupportedFilter(ResizeFilter),
+}
+
+/// Shorthand for a Result containing ruff's Error.
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            #[cfg(feature = "std")]
+            Error::IoError(ref err) => fmt::Display::fmt(err, f),
+            #[cfg(feature = "png")]
+            Error::PngDecodingError(ref err) => fmt::Display::fmt(err, f),
+            #[cfg(feature = "png")]
+            Error::PngEncodingError(ref err) => fmt::Display::fmt(err, f),
+            #[cfg(feature = "base64")]
+            Error::Base64Error(ref err) => fmt::Display::fmt(err, f),
+            Error::InvalidMagic => write!(f, "Input doesn't start with the farbfeld magic value!"),
+            Error::UnexpectedEof { needed: None } => write!(f, "Need Unknown more bytes to successfully parse!"),
+            Error::UnexpectedEof { needed: Some(size) } => write!(f, "Need {} more bytes to successfully parse!", size),
+            Error::ParseFailure { offset } => write!(f, "Failed to parse at byte {}!", offset),
+            Error::UnsupportedCompression(format) =>
+                write!(f, "Data is compressed with {}, which isn't enabled!", format),
+            Error::InvalidFarbfeldDimensions => write!(f, "Pixel count doesn't match image dimensions!"),
+            Error::InvalidDataUri => write!(f, "Data URI doesn't start with data:image/farbfeld;base64,!"),
+            Error::ImageTooLarge(format) => write!(f, "Image is too large to be written as {}!", format),
+            Error::InvalidNetpbmHeader(reason) => write!(f, "Invalid netpbm header: {}!", reason),
+            Error::RegionOutOfBounds(x, y, width, height) =>
+                write!(f, "Region of {}x{} at ({}, {}) doesn't fit within the image!", width, height, x, y),
+            Error::InvalidRowLength(row, expected, found) =>
+                write!(f, "Expected row {} to contain {} pixels, found {} pixels!", row, expected, found),
+            Error::BufferTooSmall(needed, found) =>
+                write!(f, "Need a buffer of {} bytes, found {} bytes!", needed, found),
+            Error::InvalidRowCount(expected, found) =>
+                write!(f, "Expected {} rows, found {} rows!", expected, found),
+            Error::InvalidAdjustment(reason) => write!(f, "Invalid adjustment: {}!", reason),
+            Error::InvalidPlaneLength(channel, expected, found) =>
+                write!(f, "Expected the {} plane to contain {} values, found {} values!", channel, expected, found),
+            Error::DimensionMismatch(width, height, found_width, found_height) =>
+                write!(f, "Expected an image of {}x{}, found an image of {}x{}!", width, height, found_width, found_height),
+            Error::InvalidKernel(reason) => write!(f, "Invalid kernel: {}!", reason),
+            Error::InvalidPalette(reason) => write!(f, "Invalid palette: {}!", reason),
+            Error::TransformFailed(name, ref err) => write!(f, "The {} step failed: {}", name, err),
+            Error::NotSquare(width, height) =>
+                write!(f, "Expected a square image, found an image of {}x{}!", width, height),
+            Error::UnsupportedFilter(filter) => write!(f, "The {:?} filter isn't supported here!", filter),
         }
+    }
+}
 
-        /// Produced when the dimensions specified in the image header file don't match the number
-        /// of pixels parsed.
-        InvalidFarbfeldDimensions {
-            description("Pixel count doesn't match image dimensions!")
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match *self {
+            #[cfg(feature = "std")]
+            Error::IoError(ref err) => Some(err),
+            #[cfg(feature = "png")]
+            Error::PngDecodingError(ref err) => Some(err),
+            #[cfg(feature = "png")]
+            Error::PngEncodingError(ref err) => Some(err),
+            #[cfg(feature = "base64")]
+            Error::Base64Error(ref err) => Some(err),
+            Error::TransformFailed(_, ref err) => Some(&**err),
+            _ => None
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "std")]
+impl From<::std::io::Error> for Error {
+    fn from(err: ::std::io::Error) -> Error {
+        Error::IoError(err)
+    }
+}
+
+#[cfg(feature = "png")]
+impl From<::png::DecodingError> for Error {
+    fn from(err: ::png::DecodingError) -> Error {
+        Error::PngDecodingError(err)
+    }
+}
+
+#[cfg(feature = "png")]
+impl From<::png::EncodingError> for Error {
+    fn from(err: ::png::EncodingError) -> Error {
+        Error::PngEncodingError(err)
+    }
+}
+
+#[cfg(feature = "base64")]
+impl From<::base64::DecodeError> for Error {
+    fn from(err: ::base64::DecodeError) -> Error {
+        Error::Base64Error(err)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    use std::error::Error as StdError;
+    use std::io;
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<Error>();
+
+        let boxed: Box<dyn StdError + Send + Sync> = Box::new(Error::InvalidMagic);
+        assert_eq!("Input doesn't start with the farbfeld magic value!", boxed.to_string());
+    }
+
+    #[test]
+    fn test_source() {
+        let err = Error::from(io::Error::other("broken"));
+        assert_eq!("broken", err.to_string());
+        assert_eq!("broken", err.source().unwrap().to_string());
+        assert!(Error::InvalidFarbfeldDimensions.source().is_none());
+    }
+}
@@ -1,29 +1,82 @@
+#[cfg(feature = "std")]
 use std::path::Path;
-use std::io::{Read, BufReader, Write, BufWriter};
-use std::fs::File;
-use std::ops::{Index, IndexMut, RangeFull, RangeFrom, RangeTo, Range};
+#[cfg(feature = "std")]
+use std::io::{self, Read, BufReader, Write, BufWriter};
+#[cfg(feature = "std")]
+use std::fs::{self, File, OpenOptions};
+#[cfg(feature = "std")]
+use std::ffi::OsString;
+#[cfg(feature = "std")]
+use std::process;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use core::mem::ManuallyDrop;
+use core::ops::{Index, IndexMut, RangeFull, RangeFrom, RangeTo, Range};
 
-use byteorder::{WriteBytesExt, BigEndian};
+#[cfg(feature = "serde")]
+use alloc::string::String;
+use alloc::vec;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 
-use pixel::Pixel;
-use error::*;
-use parser;
+use crate::pixel::{self, Pixel, Channel, composite_channel, narrow_channel};
+use crate::error::*;
+use crate::parser;
+use crate::endian;
+
+/// Every channel, in the order they are stored.
+const CHANNELS: [Channel; 4] = [Channel::Red, Channel::Green, Channel::Blue, Channel::Alpha];
 
 /// A Farbfeld image as defined by the [spec](http://tools.suckless.org/farbfeld/) by Suckless.
-#[derive(Debug)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+///
+/// With the `serde` feature, deserializing checks the number of pixels matches the dimensions.
+///
+/// Clones share their pixels until one of them is changed, at which point the changed image
+/// copies its pixels first, so cloning takes constant time however large the image is. This
+/// makes keeping earlier versions of an image, such as for undo, cheap when most of them are
+/// never changed. Reading pixels never copies them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(try_from = "FarbfeldFields"))]
 pub struct Farbfeld {
+    pixels: Arc<Vec<Pixel>>,
+    width: u32,
+    height: u32
+}
+
+/// The fields of a Farbfeld image before they are validated during deserialization.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct FarbfeldFields {
     pixels: Vec<Pixel>,
     width: u32,
     height: u32
 }
 
+#[cfg(feature = "serde")]
+impl ::core::convert::TryFrom<FarbfeldFields> for Farbfeld {
+    type Error = String;
+
+    fn try_from(fields: FarbfeldFields) -> ::core::result::Result<Farbfeld, String> {
+        let expected = fields.width as u64 * fields.height as u64;
+        if expected != fields.pixels.len() as u64 {
+            Err(format!("invalid Farbfeld dimensions: a {}x{} image needs {} pixels, found {} pixels",
+                        fields.width, fields.height, expected, fields.pixels.len()))
+        } else {
+            Ok(Farbfeld {
+                width: fields.width,
+                height: fields.height,
+                pixels: Arc::new(fields.pixels)
+            })
+        }
+    }
+}
+
 impl Farbfeld {
     /// Creates a new Farbfeld object, with the given dimensions and pixels.
     ///
     /// # Errors
-    /// Returns an [ErrorKind::InvalidFarbfeldDimensions](error/enum.ErrorKind.html) wrapped in an
-    /// [Error](error/struct.Error.html) if width * height != pixels.len().
+    /// Returns an [Error::InvalidFarbfeldDimensions](error/enum.Error.html)
+    /// if width * height != pixels.len().
     ///
     /// ```
     /// # use ::ruff::Farbfeld;
@@ -32,13 +85,13 @@ impl Farbfeld {
     /// ```
     ///
     pub fn new(width: u32, height: u32, pixels: Vec<Pixel>) -> Result<Farbfeld> {
-        if ((width * height) as usize) != pixels.len() {
-            Err(Error::from(ErrorKind::InvalidFarbfeldDimensions))
+        if (width as u64) * (height as u64) != pixels.len() as u64 {
+            Err(Error::InvalidFarbfeldDimensions)
         } else {
             Ok(Farbfeld {
                 width,
                 height,
-                pixels
+                pixels: Arc::new(pixels)
             })
         }
 
@@ -47,19 +100,22 @@ impl Farbfeld {
     /// Parses the file at the given path into a Farbfeld object.
     ///
     /// # Errors
-    /// Returns one of the following errors wrapped in an [Error](error/struct.Error.html).
+    /// Returns one of the following errors.
     /// <ul>
-    ///     <li><a href="error/enum.ErrorKing.html">ErrorKind::IoError</a> if the file cannot be opened or read, containing the
+    ///     <li><a href="error/enum.Error.html">Error::IoError</a> if the file cannot be opened or read, containing the
     ///     error produced by std.</li>
-    ///     <li><a href="error/enum.ErrorKind.html">ErrorKind::InvalidFarbfeldDimensions</a>
+    ///     <li><a href="error/enum.Error.html">Error::InvalidFarbfeldDimensions</a>
     ///     if the file's header's specified dimensions multiplied together do not equal the number
     ///     of parsed pixels.</li>
-    ///     <li><a href="error/enum.ErrorKind.html">ErrorKind::NomError</a> if something
-    ///     went wrong during parsing.</li>
+    ///     <li><a href="error/enum.Error.html">Error::InvalidMagic</a>,
+    ///     <a href="error/enum.Error.html">Error::UnexpectedEof</a> or
+    ///     <a href="error/enum.Error.html">Error::ParseFailure</a> if something went wrong
+    ///     during parsing, as described in the <a href="error/index.html">error module</a>.</li>
     /// </ul>
+    #[cfg(feature = "std")]
     pub fn from_file<T: AsRef<Path>>(path: T) -> Result<Farbfeld> {
         File::open(path)
-            .map_err(|err| Error::from(ErrorKind::IoError(err)))
+            .map_err(Error::IoError)
             .map(BufReader::new)
             .and_then(Farbfeld::from_read)
     }
@@ -67,20 +123,248 @@ impl Farbfeld {
     /// Parses the entire of the given Read into a Farbfeld object.
     ///
     /// # Errors
-    /// Returns one of the following errors wrapped in an [Error](error/struct.Error.html).
+    /// Returns one of the following errors.
     /// <ul>
-    ///     <li><a href="error/enum.ErrorKing.html">ErrorKind::IoError</a> if the reader cannot be
+    ///     <li><a href="error/enum.Error.html">Error::IoError</a> if the reader cannot be
     ///     read to the end, containing the error produced by std.</li>
-    ///     <li><a href="error/enum.ErrorKind.html">ErrorKind::InvalidFarbfeldDimensions</a>
+    ///     <li><a href="error/enum.Error.html">Error::InvalidFarbfeldDimensions</a>
     ///     if the reader's header's specified dimensions multiplied together do not equal the number
     ///     of parsed pixels.</li>
-    ///     <li><a href="error/enum.ErrorKind.html">ErrorKind::NomError</a> if something
-    ///     went wrong during parsing.</li>
+    ///     <li><a href="error/enum.Error.html">Error::InvalidMagic</a>,
+    ///     <a href="error/enum.Error.html">Error::UnexpectedEof</a> or
+    ///     <a href="error/enum.Error.html">Error::ParseFailure</a> if something went wrong
+    ///     during parsing, as described in the <a href="error/index.html">error module</a>.</li>
     /// </ul>
+    #[cfg(feature = "std")]
     pub fn from_read<T: Read>(mut read: T) -> Result<Farbfeld> {
         let mut buff = Vec::new();
-        read.read_to_end(&mut buff).map_err(ErrorKind::IoError)?;
-        parser::i_to_res(parser::parse_farb(&buff))
+        read.read_to_end(&mut buff).map_err(Error::IoError)?;
+        Farbfeld::from_bytes(&buff)
+    }
+
+    /// Parses a complete Farbfeld file held in memory. Unlike
+    /// [from_read](struct.Farbfeld.html#method.from_read) this is available without the `std`
+    /// feature.
+    ///
+    /// # Errors
+    /// Returns one of the following errors.
+    /// <ul>
+    ///     <li><a href="error/enum.Error.html">Error::InvalidFarbfeldDimensions</a>
+    ///     if the header's specified dimensions multiplied together do not equal the number
+    ///     of parsed pixels.</li>
+    ///     <li><a href="error/enum.Error.html">Error::InvalidMagic</a>,
+    ///     <a href="error/enum.Error.html">Error::UnexpectedEof</a> or
+    ///     <a href="error/enum.Error.html">Error::ParseFailure</a> if something went wrong
+    ///     during parsing, as described in the <a href="error/index.html">error module</a>.</li>
+    /// </ul>
+    pub fn from_bytes(data: &[u8]) -> Result<Farbfeld> {
+        parser::parse_farb(data)
+    }
+
+    /// Parses the file at the given path into this image, replacing its dimensions and pixels, as
+    /// described by [reload_from_read](#method.reload_from_read).
+    ///
+    /// # Errors
+    /// Returns the errors of [from_file](#method.from_file), in which case the image is left
+    /// unchanged.
+    #[cfg(feature = "std")]
+    pub fn reload_from_file<T: AsRef<Path>>(&mut self, path: T) -> Result<()> {
+        let file = File::open(path).map_err(Error::IoError)?;
+        self.reload_from_read(BufReader::new(file))
+    }
+
+    /// Parses the entire of the given Read into this image, replacing its dimensions and pixels,
+    /// and producing the same image as [from_read](#method.from_read). The existing allocation
+    /// for the pixels is reused if it can hold the new image, such as when playing back frames of
+    /// the same size, and grown otherwise. If a clone of the image shares its pixels, the new pixels
+    /// are parsed into a new allocation instead, leaving the clone unchanged.
+    ///
+    /// # Errors
+    /// Returns the errors of [from_read](#method.from_read), in which case the image is left
+    /// unchanged.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(2, 1, vec![Pixel::default(); 2]).unwrap();
+    /// let frame = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2, 3, 4)]).unwrap();
+    ///
+    /// farb.reload_from_read(&frame.to_bytes()[..]).unwrap();
+    /// assert_eq!(frame, farb);
+    /// assert!(farb.reload_from_read(&b"farbfeld"[..]).is_err());
+    /// assert_eq!(frame, farb);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn reload_from_read<T: Read>(&mut self, mut read: T) -> Result<()> {
+        let mut buff = Vec::new();
+        read.read_to_end(&mut buff).map_err(Error::IoError)?;
+        self.reload_from_bytes(&buff)
+    }
+
+    /// Parses a complete Farbfeld file held in memory into this image, replacing its dimensions
+    /// and pixels, as described by [reload_from_read](#method.reload_from_read). Unlike
+    /// reload_from_read this is available without the `std` feature.
+    ///
+    /// # Errors
+    /// Returns the errors of [from_bytes](#method.from_bytes), in which case the image is left
+    /// unchanged.
+    pub fn reload_from_bytes(&mut self, data: &[u8]) -> Result<()> {
+        let (width, height) = match Arc::get_mut(&mut self.pixels) {
+            Some(pixels) => parser::parse_farb_into(data, pixels)?,
+            None => {
+                let mut pixels = Vec::new();
+                let dimensions = parser::parse_farb_into(data, &mut pixels)?;
+                self.pixels = Arc::new(pixels);
+                dimensions
+            }
+        };
+        self.width = width;
+        self.height = height;
+        Ok(())
+    }
+
+    /// Creates a new Farbfeld object from the given dimensions and interleaved red, green, blue
+    /// and alpha channels in row-major order. The channels are native endian values, unrelated to
+    /// the big endian format used by Farbfeld files. The data isn't copied if its capacity is a
+    /// multiple of 4.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidFarbfeldDimensions](error/enum.Error.html)
+    /// if width * height * 4 != data.len().
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::from_raw_u16(2, 1, vec![1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+    ///
+    /// assert_eq!(&[Pixel::new(1_u16, 2, 3, 4), Pixel::new(5_u16, 6, 7, 8)], farb.pixels());
+    /// assert!(Farbfeld::from_raw_u16(2, 1, vec![1, 2, 3, 4]).is_err());
+    /// ```
+    pub fn from_raw_u16(width: u32, height: u32, data: Vec<u16>) -> Result<Farbfeld> {
+        if (width as u64) * (height as u64) * 4 != data.len() as u64 {
+            return Err(Error::InvalidFarbfeldDimensions);
+        }
+        let pixels = if data.capacity().is_multiple_of(4) {
+            let mut data = ManuallyDrop::new(data);
+            // Pixel is a repr(C) struct of four u16 channels, so it has the same alignment as u16
+            // and the allocation holds exactly capacity / 4 pixels.
+            unsafe { Vec::from_raw_parts(data.as_mut_ptr() as *mut Pixel, data.len() / 4, data.capacity() / 4) }
+        } else {
+            data.chunks(4).map(|chunk| Pixel::new(chunk[0], chunk[1], chunk[2], chunk[3])).collect()
+        };
+        Farbfeld::new(width, height, pixels)
+    }
+
+    /// Consumes the image, returning its width, height and interleaved red, green, blue and alpha
+    /// channels in row-major order, without copying. The channels are native endian values,
+    /// unrelated to the big endian format used by Farbfeld files. The channels are only copied if
+    /// a clone of the image shares its pixels.
+    pub fn into_raw_u16(self) -> (u32, u32, Vec<u16>) {
+        let pixels = Arc::try_unwrap(self.pixels).unwrap_or_else(|pixels| (*pixels).clone());
+        let mut pixels = ManuallyDrop::new(pixels);
+        // Pixel is a repr(C) struct of four u16 channels, so the allocation holds exactly four
+        // times as many u16s as pixels, with the same alignment.
+        let data = unsafe {
+            Vec::from_raw_parts(pixels.as_mut_ptr() as *mut u16, pixels.len() * 4, pixels.capacity() * 4)
+        };
+        (self.width, self.height, data)
+    }
+
+    /// Returns a copy of the image's interleaved red, green, blue and alpha channels in row-major
+    /// order, as described by [into_raw_u16](#method.into_raw_u16).
+    pub fn as_raw_u16(&self) -> Vec<u16> {
+        self.as_channels().to_vec()
+    }
+
+    /// Creates a new Farbfeld object from the given dimensions and interleaved 8 bit red, green,
+    /// blue and alpha channels in row-major order, expanding each channel as described by
+    /// [Pixel::from_rgba8](pixel/struct.Pixel.html#method.from_rgba8).
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidFarbfeldDimensions](error/enum.Error.html)
+    /// if width * height * 4 != data.len().
+    pub fn from_rgba8(width: u32, height: u32, data: &[u8]) -> Result<Farbfeld> {
+        if (width as u64) * (height as u64) * 4 != data.len() as u64 {
+            return Err(Error::InvalidFarbfeldDimensions);
+        }
+        let pixels = data.chunks_exact(4)
+            .map(|chunk| Pixel::from_rgba8([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        Farbfeld::new(width, height, pixels)
+    }
+
+    /// Returns the image's interleaved 8 bit red, green, blue and alpha channels in row-major
+    /// order, rounding each channel as described by
+    /// [Pixel::to_rgba8](pixel/struct.Pixel.html#method.to_rgba8).
+    pub fn to_rgba8_vec(&self) -> Vec<u8> {
+        let mut data = vec![0_u8; self.pixels.len() * 4];
+        for (out, pixel) in data.chunks_exact_mut(4).zip(self.pixels.iter()) {
+            out.copy_from_slice(&pixel.to_rgba8());
+        }
+        data
+    }
+
+    /// Returns the image's pixels in row-major order, each packed into a u32 as 8 bit alpha, red,
+    /// green and blue channels from the most to least significant byte, such as `0xAARRGGBB`.
+    /// Channels are rounded as described by
+    /// [Pixel::to_rgba8](pixel/struct.Pixel.html#method.to_rgba8).
+    ///
+    /// The pixels are packed integers, not bytes, so their layout in memory depends on the
+    /// platform's endianness. On little endian platforms the bytes are in blue, green, red, alpha
+    /// order.
+    pub fn to_argb32(&self) -> Vec<u32> {
+        self.pixels.iter()
+            .map(|pixel| {
+                let [r, g, b, a] = pixel.to_rgba8();
+                u32::from_be_bytes([a, r, g, b])
+            })
+            .collect()
+    }
+
+    /// Returns the image's pixels in row-major order, composited onto the red, green and blue
+    /// channels of the given background and packed into a u32 as `0x00RRGGBB`, the format used by
+    /// framebuffer crates such as [minifb](https://crates.io/crates/minifb) and
+    /// [softbuffer](https://crates.io/crates/softbuffer). As with
+    /// [to_argb32](#method.to_argb32), these are packed integers rather than bytes.
+    ///
+    /// # Examples
+    /// Previewing an image in a minifb window.
+    ///
+    /// ```ignore
+    /// let farb = Farbfeld::from_file("image.ff")?;
+    /// let buffer = farb.to_0rgb32(Pixel::new(65535_u16, 65535, 65535, 65535));
+    /// let mut window = minifb::Window::new("Preview", *farb.width() as usize, *farb.height() as usize,
+    ///                                      minifb::WindowOptions::default())?;
+    /// while window.is_open() && !window.is_key_down(minifb::Key::Escape) {
+    ///     window.update_with_buffer(&buffer, *farb.width() as usize, *farb.height() as usize)?;
+    /// }
+    /// ```
+    pub fn to_0rgb32(&self, background: Pixel) -> Vec<u32> {
+        self.pixels.iter()
+            .map(|pixel| {
+                let composite = |channel, background| {
+                    narrow_channel(composite_channel(channel, background, *pixel.alpha()))
+                };
+                u32::from_be_bytes([0, composite(*pixel.red(), *background.red()),
+                                    composite(*pixel.green(), *background.green()),
+                                    composite(*pixel.blue(), *background.blue())])
+            })
+            .collect()
+    }
+
+    /// Creates a new Farbfeld object from the given dimensions and pixels packed as `0xAARRGGBB`,
+    /// as produced by [to_argb32](#method.to_argb32), expanding each channel as described by
+    /// [Pixel::from_rgba8](pixel/struct.Pixel.html#method.from_rgba8).
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidFarbfeldDimensions](error/enum.Error.html)
+    /// if width * height != data.len().
+    pub fn from_argb32(width: u32, height: u32, data: &[u32]) -> Result<Farbfeld> {
+        let pixels = data.iter()
+            .map(|pixel| {
+                let [a, r, g, b] = pixel.to_be_bytes();
+                Pixel::from_rgba8([r, g, b, a])
+            })
+            .collect();
+        Farbfeld::new(width, height, pixels)
     }
 
     /// Returns all the pixels in the image in row-major order.
@@ -88,6 +372,90 @@ impl Farbfeld {
         &self.pixels
     }
 
+    /// Returns all the pixels in the image in row-major order, allowing them to be changed in
+    /// place. If a clone of the image shares its pixels, they're copied first so the clone is
+    /// left unchanged.
+    pub fn pixels_mut(&mut self) -> &mut [Pixel] {
+        Arc::make_mut(&mut self.pixels).as_mut_slice()
+    }
+
+    /// Returns the interleaved red, green, blue and alpha channels of every pixel in row-major
+    /// order, without copying, for passing to code which works on raw channel buffers. The
+    /// channels are native endian values, unrelated to the big endian format used by Farbfeld
+    /// files, and the slice is always `width * height * 4` values long.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2, 3, 4), Pixel::new(5_u16, 6, 7, 8)]).unwrap();
+    ///
+    /// assert_eq!(&[1, 2, 3, 4, 5, 6, 7, 8], farb.as_channels());
+    /// ```
+    pub fn as_channels(&self) -> &[u16] {
+        pixel::as_channels(&self.pixels)
+    }
+
+    /// Returns the interleaved channels of every pixel as described by
+    /// [as_channels](#method.as_channels), allowing them to be changed in place. The dimensions
+    /// can't be changed through the slice.
+    pub fn as_channels_mut(&mut self) -> &mut [u16] {
+        pixel::as_channels_mut(self.pixels_mut())
+    }
+
+    /// Returns true if any pixel isn't fully opaque.
+    pub fn has_transparency(&self) -> bool {
+        self.pixels.iter().any(|pixel| *pixel.alpha() != u16::MAX)
+    }
+
+    /// Replaces the given channel of every pixel with the result of `map`, leaving the other
+    /// channels untouched.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::pixel::Channel;
+    /// let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2, 3, 4)]).unwrap();
+    /// farb.map_channel(Channel::Green, |green| green * 10);
+    ///
+    /// assert_eq!(&Pixel::new(1_u16, 20, 3, 4), &farb[0]);
+    /// ```
+    pub fn map_channel<F: FnMut(u16) -> u16>(&mut self, channel: Channel, mut map: F) {
+        for pixel in self.pixels_mut() {
+            let value = pixel.channel_mut(channel);
+            *value = map(*value);
+        }
+    }
+
+    /// Replaces every channel of every pixel with the result of `map`, which is given the channel
+    /// being replaced and its value.
+    pub fn map_channels<F: FnMut(Channel, u16) -> u16>(&mut self, mut map: F) {
+        for pixel in self.pixels_mut() {
+            for &channel in CHANNELS.iter() {
+                let value = pixel.channel_mut(channel);
+                *value = map(channel, *value);
+            }
+        }
+    }
+
+    /// Returns a copy of the image with every pixel replaced by the result of `map`.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2, 3, 4)]).unwrap();
+    ///
+    /// assert_eq!(&[Pixel::new(3_u16, 2, 1, 4)], farb.map_pixels(|pixel| Pixel::new(*pixel.blue(), *pixel.green(), *pixel.red(), *pixel.alpha())).pixels());
+    /// ```
+    pub fn map_pixels<F: FnMut(&Pixel) -> Pixel>(&self, map: F) -> Farbfeld {
+        Farbfeld {
+            width: self.width,
+            height: self.height,
+            pixels: Arc::new(self.pixels.iter().map(map).collect())
+        }
+    }
+
+    /// Calls `f` with every pixel of the image in row-major order, so it can change them in place.
+    pub fn for_each_pixel_mut<F: FnMut(&mut Pixel)>(&mut self, f: F) {
+        self.pixels_mut().iter_mut().for_each(f);
+    }
+
     /// Tries to return the specified row of pixels from the image. The first row is row 0.
     ///
     /// # Errors
@@ -96,11 +464,100 @@ impl Farbfeld {
         if row >= self.height {
             None
         } else {
-            let offset = (row * self.width) as usize;
+            let offset = row as usize * self.width as usize;
             Some(&self.pixels[offset..offset + self.width as usize])
         }
     }
 
+    /// Creates a new image from the rectangle of this image with its top left corner at (x, y)
+    /// and the given dimensions.
+    ///
+    /// # Errors
+    /// Returns an [Error::RegionOutOfBounds](error/enum.Error.html) if the rectangle
+    /// doesn't fit within the image.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(2, 2, (0..4_u16).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+    /// let crop = farb.crop(1, 0, 1, 2).unwrap();
+    ///
+    /// assert_eq!(&[Pixel::new(1_u16, 1, 1, 1), Pixel::new(3_u16, 3, 3, 3)], crop.pixels());
+    /// assert!(farb.crop(1, 1, 2, 1).is_err());
+    /// ```
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Farbfeld> {
+        self.check_region(x, y, width, height)?;
+        let pixels = (y..y + height)
+            .flat_map(|row| self.region_row(row, x, width).iter().cloned())
+            .collect();
+        Ok(Farbfeld {
+            width,
+            height,
+            pixels: Arc::new(pixels)
+        })
+    }
+
+    /// Checks the rectangle with its top left corner at (x, y) and the given dimensions fits
+    /// within the image.
+    pub(crate) fn check_region(&self, x: u32, y: u32, width: u32, height: u32) -> Result<()> {
+        if x as u64 + width as u64 > self.width as u64 || y as u64 + height as u64 > self.height as u64 {
+            Err(Error::RegionOutOfBounds(x, y, width, height))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns an error unless the other image is the same size as this one.
+    pub(crate) fn check_same_size(&self, other: &Farbfeld) -> Result<()> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return Err(Error::DimensionMismatch(self.width, self.height, other.width, other.height));
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over the pixels of this image and another of the same size together,
+    /// in row-major order, producing the x and y coordinates of each pair of pixels followed by
+    /// the pixel from this image and the pixel from the other.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 1, 1, 1), Pixel::new(2_u16, 2, 2, 2)]).unwrap();
+    /// let other = Farbfeld::new(2, 1, vec![Pixel::new(3_u16, 3, 3, 3), Pixel::new(4_u16, 4, 4, 4)]).unwrap();
+    /// let mut iter = farb.zip_pixels(&other).unwrap();
+    ///
+    /// assert_eq!(Some((1, 0, &farb[1], &other[1])), iter.nth(1));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::DimensionMismatch](error/enum.Error.html) containing both sizes if
+    /// the other image isn't the same size as this one.
+    pub fn zip_pixels<'a>(&'a self, other: &'a Farbfeld)
+                          -> Result<impl ExactSizeIterator<Item = (u32, u32, &'a Pixel, &'a Pixel)>> {
+        self.check_same_size(other)?;
+        let width = (self.width as usize).max(1);
+        Ok(self.pixels.iter().zip(other.pixels.iter()).enumerate()
+            .map(move |(i, (pixel, other))| ((i % width) as u32, (i / width) as u32, pixel, other)))
+    }
+
+    /// Returns an iterator over the pixels of this image and another of the same size together,
+    /// in row-major order, producing a mutable reference to the pixel from this image and the
+    /// pixel from the other, such as to combine them in place.
+    ///
+    /// # Errors
+    /// Returns an [Error::DimensionMismatch](error/enum.Error.html) containing both sizes if
+    /// the other image isn't the same size as this one.
+    pub fn zip_pixels_mut<'a>(&'a mut self, other: &'a Farbfeld)
+                              -> Result<impl ExactSizeIterator<Item = (&'a mut Pixel, &'a Pixel)>> {
+        self.check_same_size(other)?;
+        Ok(self.pixels_mut().iter_mut().zip(other.pixels.iter()))
+    }
+
+    /// Returns the `width` pixels of the given row starting from column x. The region must already
+    /// have been checked by check_region.
+    fn region_row(&self, row: u32, x: u32, width: u32) -> &[Pixel] {
+        let offset = row as usize * self.width as usize + x as usize;
+        &self.pixels[offset..offset + width as usize]
+    }
+
     /// Returns the width of the image. This is defined in the header of the image.
     pub fn width(&self) -> &u32 {
         &self.width
@@ -111,47 +568,313 @@ impl Farbfeld {
         &self.height
     }
 
+    /// Serializes the image into a new Vec according to the
+    /// [spec](http://tools.suckless.org/farbfeld/). The Vec is allocated with exactly the
+    /// capacity required, 16 + 8 bytes per pixel.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2_u16, 3_u16, 4_u16); 2]).unwrap();
+    /// assert_eq!(32, farb.to_bytes().len());
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.save_to_vec(&mut bytes);
+        bytes
+    }
+
+    /// Appends the serialized image to the end of the given Vec, reserving exactly the space
+    /// required before writing. This is useful for building up several images in one buffer.
+    pub fn save_to_vec(&self, bytes: &mut Vec<u8>) {
+        bytes.reserve_exact(16 + self.pixels.len() * 8);
+        bytes.extend_from_slice(&header_bytes(self.width, self.height));
+        extend_row_bytes(bytes, &self.pixels);
+    }
+
     /// Writes the image to the given write according to the [spec](http://tools.suckless.org/farbfeld/).
+    /// Every byte of the image is written, even if the write only accepts part of a buffer at a
+    /// time. The write isn't flushed, so callers using a buffered write must flush it themselves.
+    /// Returns the number of bytes written, including the header.
     ///
     /// # Errors
     /// <ul>
-    ///     <li> Returns an <a href="error/enum.ErrorKind.html">IoError</a> if the write produces an
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
     ///     std IoError during write.</li>
     /// </ul>
-    pub fn save<T: Write>(&self, write: &mut T) -> Result<()> {
-        write.write(b"farbfeld")
-            .and_then(|_| write.write_u32::<BigEndian>(self.width))
-            .and_then(|_| write.write_u32::<BigEndian>(self.height))
-            .and_then(|_| {
-                for pixel in self.pixels.iter().flat_map(Pixel::iter) {
-                    write.write_u16::<BigEndian>(*pixel)?;
-                };
-                Ok(())
-            })
-            .map(|_| ())
-            .map_err(|err| Error::from(ErrorKind::IoError(err)))
+    #[cfg(feature = "std")]
+    pub fn save<T: Write>(&self, write: &mut T) -> Result<u64> {
+        self.save_with_progress(write, |_, _| {})
+    }
+
+    /// Writes the image to the given write in the same way as [save](#method.save), calling
+    /// `progress` with the number of bytes written so far and the total size of the image in bytes
+    /// after the header and after every 64 KiB of pixels. The total is always 16 + 8 bytes per
+    /// pixel, and the final call reports the total as written. Returns the number of bytes written.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+    ///     std IoError during write.</li>
+    /// </ul>
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(1, 1, vec![Pixel::default()]).unwrap();
+    /// let mut last = (0, 0);
+    /// farb.save_with_progress(&mut Vec::new(), |written, total| last = (written, total)).unwrap();
+    /// assert_eq!((24, 24), last);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn save_with_progress<T, F>(&self, write: &mut T, mut progress: F) -> Result<u64>
+        where T: Write, F: FnMut(u64, u64) {
+        let total = 16 + self.pixels.len() as u64 * 8;
+        let header = write_header(write, self.width, self.height)?;
+        progress(header, total);
+        write_pixel_chunks(write, &self.pixels, |written| progress(header + written, total))
+            .map(|written| header + written)
+    }
+
+    /// Writes the rectangle of this image with its top left corner at (x, y) and the given
+    /// dimensions to the given write as a complete Farbfeld image. This produces the same bytes as
+    /// saving the result of [crop](#method.crop), without creating the cropped image. Returns the
+    /// number of bytes written.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">Error::RegionOutOfBounds</a> if
+    ///     the rectangle doesn't fit within the image, before anything is written.</li>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+    ///     std IoError during write.</li>
+    /// </ul>
+    #[cfg(feature = "std")]
+    pub fn save_region<T: Write>(&self, write: &mut T, x: u32, y: u32, width: u32, height: u32) -> Result<u64> {
+        self.check_region(x, y, width, height)?;
+        let mut written = write_header(write, width, height)?;
+        for row in y..y + height {
+            written += write_row_pixels(write, self.region_row(row, x, width))?;
+        }
+        Ok(written)
+    }
+
+    /// Writes only the big endian pixel payload of the image to the given write, without the
+    /// header. Combined with [write_header](fn.write_header.html) this produces the same bytes as
+    /// [save](#method.save). Returns the number of bytes written.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+    ///     std IoError during write.</li>
+    /// </ul>
+    #[cfg(feature = "std")]
+    pub fn write_pixels<T: Write>(&self, write: &mut T) -> Result<u64> {
+        write_pixel_chunks(write, &self.pixels, |_| {})
     }
 
     /// Writes the image to the file at the given path according to the
     /// [spec](http://tools.suckless.org/farbfeld/). The File is created if it doesn't exist or
     /// truncated if it does, as defined by
-    /// [File::create](https://doc.rust-lang.org/nightly/std/fs/struct.File.html#method.create)
+    /// [File::create](https://doc.rust-lang.org/nightly/std/fs/struct.File.html#method.create).
+    /// Returns the number of bytes written, including the header.
     ///
     /// # Errors
     /// <ul>
-    ///     <li> Returns an <a href="error/enum.ErrorKind.html">IoError</a> if the write produces an
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
     ///     std IoError during write.</li>
     /// </ul>
-    pub fn save_to_file<T: AsRef<Path>>(&self, path: T) -> Result<()> {
+    #[cfg(feature = "std")]
+    pub fn save_to_file<T: AsRef<Path>>(&self, path: T) -> Result<u64> {
         File::create(path)
             .map(BufWriter::new)
-            .map_err(|err| Error::from(ErrorKind::IoError(err)))
+            .map_err(Error::IoError)
             .and_then(|mut w| Farbfeld::save(self, &mut w)
-                .and_then(|_| w.flush()
-                    .map_err(|err| Error::from(ErrorKind::IoError(err)))
+                .and_then(|written| w.flush()
+                    .map(|_| written)
+                    .map_err(Error::IoError)
                 )
             )
     }
+
+    /// Atomically writes the image to the file at the given path according to the
+    /// [spec](http://tools.suckless.org/farbfeld/). The image is written to a uniquely named
+    /// temporary file in the same directory, synced to disk, then renamed over the destination.
+    /// Other processes therefore see either the old file or the complete new image, never a
+    /// partially written one.
+    ///
+    /// The temporary file is removed if anything fails. Because it is created next to the
+    /// destination, the rename never crosses filesystems. On Windows,
+    /// [rename](https://doc.rust-lang.org/std/fs/fn.rename.html) replaces an existing destination,
+    /// but fails if the destination is open in another process.
+    ///
+    /// Returns the number of bytes written, including the header.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the temporary file
+    ///     cannot be created, written, synced or renamed.</li>
+    /// </ul>
+    #[cfg(feature = "std")]
+    pub fn save_to_file_atomic<T: AsRef<Path>>(&self, path: T) -> Result<u64> {
+        write_atomic(path.as_ref(), |file| {
+            let mut w = BufWriter::new(file);
+            let written = self.save(&mut w)?;
+            w.flush().map_err(Error::IoError)?;
+            Ok(written)
+        })
+    }
+}
+
+/// Counter making temporary file names unique within this process.
+#[cfg(feature = "std")]
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Creates a new temporary file next to `path`, calls `write` with it, then syncs it and renames it
+/// over `path`, returning the result of `write`. The temporary file is removed if any step fails.
+#[cfg(feature = "std")]
+fn write_atomic<T, F>(path: &Path, write: F) -> Result<T> where F: FnOnce(&mut File) -> Result<T> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new(".")
+    };
+    let name = path.file_name()
+        .ok_or_else(|| Error::IoError(io::Error::new(io::ErrorKind::InvalidInput, "path has no file name")))?;
+
+    let (temp_path, mut file) = loop {
+        let mut temp_name = OsString::from(".");
+        temp_name.push(name);
+        temp_name.push(format!(".{}.{}.tmp", process::id(), TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)));
+        let temp_path = dir.join(temp_name);
+        match OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+            Ok(file) => break (temp_path, file),
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(Error::IoError(err))
+        }
+    };
+
+    let res = write(&mut file)
+        .and_then(|out| file.sync_all()
+            .map(|_| out)
+            .map_err(Error::IoError))
+        .and_then(|out| {
+            drop(file);
+            fs::rename(&temp_path, path)
+                .map(|_| out)
+                .map_err(Error::IoError)
+        });
+    if res.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    res
+}
+
+/// The largest width or height produced by the [Arbitrary](https://docs.rs/arbitrary) implementation
+/// of [Farbfeld](struct.Farbfeld.html), keeping generated images small enough for fuzzing.
+#[cfg(feature = "arbitrary")]
+pub const ARBITRARY_MAX_DIM: u32 = 64;
+
+#[cfg(feature = "arbitrary")]
+impl<'a> ::arbitrary::Arbitrary<'a> for Farbfeld {
+    fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+        let width = u.int_in_range(0..=ARBITRARY_MAX_DIM)?;
+        let height = u.int_in_range(0..=ARBITRARY_MAX_DIM)?;
+        let pixels = (0..width * height)
+            .map(|_| u.arbitrary::<Pixel>())
+            .collect::<::arbitrary::Result<Vec<Pixel>>>()?;
+        Ok(Farbfeld {
+            width,
+            height,
+            pixels: Arc::new(pixels)
+        })
+    }
+}
+
+/// The number of pixels serialized into the buffer used by save before each write, 64 KiB worth.
+#[cfg(feature = "std")]
+const SAVE_CHUNK_PIXELS: usize = 8192;
+
+/// Serializes the 16 byte header of a Farbfeld image with the given dimensions.
+pub(crate) fn header_bytes(width: u32, height: u32) -> [u8; 16] {
+    let mut header = [0_u8; 16];
+    header[..8].copy_from_slice(b"farbfeld");
+    header[8..12].copy_from_slice(&width.to_be_bytes());
+    header[12..].copy_from_slice(&height.to_be_bytes());
+    header
+}
+
+/// Writes the 16 byte header of a Farbfeld image with the given dimensions to the given write.
+/// Returns the number of bytes written, which is always 16.
+///
+/// Multiple images' pixels can follow a single header, as long as the header's dimensions describe
+/// all of them.
+///
+/// # Errors
+/// <ul>
+///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+///     std IoError during write.</li>
+/// </ul>
+///
+/// ```
+/// # use ::ruff::*;
+/// let farb = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2_u16, 3_u16, 4_u16)]).unwrap();
+/// let mut data = Vec::new();
+/// write_header(&mut data, 1, 2).unwrap();
+/// farb.write_pixels(&mut data).unwrap();
+/// farb.write_pixels(&mut data).unwrap();
+///
+/// assert_eq!(&2, Farbfeld::from_read(&data[..]).unwrap().height());
+/// ```
+#[cfg(feature = "std")]
+pub fn write_header<T: Write>(write: &mut T, width: u32, height: u32) -> Result<u64> {
+    write.write_all(&header_bytes(width, height))
+        .map(|_| 16)
+        .map_err(Error::IoError)
+}
+
+/// Writes the big endian serialization of the given pixels to the given write, such as a single
+/// row of an image following a header written by [write_header](fn.write_header.html). Returns the
+/// number of bytes written.
+///
+/// # Errors
+/// <ul>
+///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+///     std IoError during write.</li>
+/// </ul>
+#[cfg(feature = "std")]
+pub fn write_row_pixels<T: Write>(write: &mut T, row: &[Pixel]) -> Result<u64> {
+    write_pixel_chunks(write, row, |_| {})
+}
+
+/// Writes the pixels in chunks of 64 KiB through a single buffer, calling `progress` with the
+/// number of bytes written after each chunk. Returns the number of bytes written.
+#[cfg(feature = "std")]
+fn write_pixel_chunks<T, F>(write: &mut T, pixels: &[Pixel], mut progress: F) -> Result<u64>
+    where T: Write, F: FnMut(u64) {
+    let mut written = 0;
+    let mut buff = Vec::with_capacity(SAVE_CHUNK_PIXELS.min(pixels.len()) * 8);
+    for chunk in pixels.chunks(SAVE_CHUNK_PIXELS) {
+        buff.clear();
+        extend_row_bytes(&mut buff, chunk);
+        write.write_all(&buff).map_err(Error::IoError)?;
+        written += buff.len() as u64;
+        progress(written);
+    }
+    Ok(written)
+}
+
+/// Serializes a single pixel as its big endian components.
+#[cfg(feature = "std")]
+pub(crate) fn pixel_bytes(pixel: &Pixel) -> [u8; 8] {
+    let mut bytes = [0_u8; 8];
+    for (out, component) in bytes.chunks_mut(2).zip(pixel.iter()) {
+        out.copy_from_slice(&component.to_be_bytes());
+    }
+    bytes
+}
+
+/// Appends the big endian serialization of the given pixels to the buffer.
+pub(crate) fn extend_row_bytes(buff: &mut Vec<u8>, row: &[Pixel]) {
+    let start = buff.len();
+    buff.resize(start + row.len() * 8, 0);
+    endian::encode_channels(pixel::as_channels(row), &mut buff[start..]);
 }
 
 impl Index<usize> for Farbfeld {
@@ -196,31 +919,31 @@ impl Index<Range<usize>> for Farbfeld {
 
 impl IndexMut<usize> for Farbfeld {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.pixels[index]
+        &mut self.pixels_mut()[index]
     }
 }
 
 impl IndexMut<RangeFull> for Farbfeld {
     fn index_mut(&mut self, _: RangeFull) -> &mut Self::Output {
-        &mut self.pixels
+        self.pixels_mut()
     }
 }
 
 impl IndexMut<RangeTo<usize>> for Farbfeld {
     fn index_mut(&mut self, index: RangeTo<usize>) -> &mut Self::Output {
-        &mut self.pixels[index]
+        &mut self.pixels_mut()[index]
     }
 }
 
 impl IndexMut<RangeFrom<usize>> for Farbfeld {
     fn index_mut(&mut self, index: RangeFrom<usize>) -> &mut Self::Output {
-        &mut self.pixels[index]
+        &mut self.pixels_mut()[index]
     }
 }
 
 impl IndexMut<Range<usize>> for Farbfeld {
     fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
-        &mut self.pixels[index]
+        &mut self.pixels_mut()[index]
     }
 }
 
@@ -228,15 +951,100 @@ impl IndexMut<Range<usize>> for Farbfeld {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "std")]
     use std::env;
+    #[cfg(feature = "std")]
+    use std::io::Cursor;
+
+    #[test]
+    fn test_rgba8_round_trip() {
+        let data: Vec<u8> = (0..5 * 3 * 4).map(|i| (i * 37 % 256) as u8).collect();
+        let farb = Farbfeld::from_rgba8(5, 3, &data).unwrap();
+        assert_eq!(&Pixel::new(0_u16, 37 * 257, 74 * 257, 111 * 257), &farb[0]);
+        assert_eq!(data, farb.to_rgba8_vec());
+
+        match Farbfeld::from_rgba8(5, 3, &data[1..]) {
+            Err(Error::InvalidFarbfeldDimensions) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_argb32() {
+        let farb = Farbfeld::new(3, 1, vec![Pixel::new(65535_u16, 0, 0, 65535), Pixel::new(257_u16, 514, 771, 1028),
+                                            Pixel::new(0_u16, 0, 65535, 0)]).unwrap();
+        let packed = farb.to_argb32();
+        assert_eq!(vec![0xffff_0000, 0x0401_0203, 0x0000_00ff], packed);
 
-    use test::Bencher;
+        let round_trip = Farbfeld::from_argb32(3, 1, &packed).unwrap();
+        assert_eq!(farb.pixels(), round_trip.pixels());
+        assert!(Farbfeld::from_argb32(2, 1, &packed).is_err());
+    }
 
-    #[bench]
-    fn bench_from_file(b: &mut Bencher) {
-        b.iter(|| Farbfeld::from_file("test.ff").unwrap())
+    #[test]
+    fn test_0rgb32() {
+        let farb = Farbfeld::new(3, 1, vec![Pixel::new(65535_u16, 0, 0, 65535), Pixel::new(65535_u16, 0, 0, 32768),
+                                            Pixel::new(0_u16, 0, 65535, 0)]).unwrap();
+        assert_eq!(vec![0x00ff_0000, 0x00ff_7f7f, 0x00ff_ffff],
+                   farb.to_0rgb32(Pixel::new(65535_u16, 65535, 65535, 0)));
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_validates_dimensions() {
+        let json = r#"{"pixels":[{"red":1,"green":2,"blue":3,"alpha":4}],"width":1,"height":1}"#;
+        let farb: Farbfeld = serde_json::from_str(json).unwrap();
+        assert_eq!(&[Pixel::new(1_u16, 2, 3, 4)], farb.pixels());
+
+        let json = r#"{"pixels":[],"width":10,"height":10}"#;
+        let err = serde_json::from_str::<Farbfeld>(json).unwrap_err().to_string();
+        assert!(err.contains("10x10 image needs 100 pixels, found 0 pixels"), "{}", err);
+
+        let json = r#"{"pixels":[],"width":4294967295,"height":4294967295}"#;
+        let err = serde_json::from_str::<Farbfeld>(json).unwrap_err().to_string();
+        assert!(err.contains("4294967295x4294967295 image needs 18446744065119617025 pixels"), "{}", err);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_shared_pixels() {
+        let farb = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2, 3, 4)]).unwrap();
+        let clone = farb.clone();
+        let json = r#"{"pixels":[{"red":1,"green":2,"blue":3,"alpha":4}],"width":1,"height":1}"#;
+        assert_eq!(json, serde_json::to_string(&clone).unwrap());
+        assert_eq!(farb, serde_json::from_str::<Farbfeld>(&serde_json::to_string(&farb).unwrap()).unwrap());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_clones_share_pixels() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut clone = farb.clone();
+        assert_eq!(farb.pixels().as_ptr(), clone.pixels().as_ptr());
+        assert_eq!(2, Arc::strong_count(&farb.pixels));
+
+        clone[0] = Pixel::new(1_u16, 2, 3, 4);
+        assert_ne!(farb.pixels().as_ptr(), clone.pixels().as_ptr());
+        assert_eq!(Farbfeld::from_file("test.ff").unwrap(), farb);
+        assert_eq!(&Pixel::new(1_u16, 2, 3, 4), &clone[0]);
+        assert_eq!(farb.pixels()[1..], clone.pixels()[1..]);
+
+        // Changing an image which no longer shares its pixels doesn't copy them again.
+        let pixels = clone.pixels().as_ptr();
+        clone.pixels_mut()[1] = Pixel::default();
+        clone.for_each_pixel_mut(|pixel| *pixel.alpha_mut() = 0);
+        assert_eq!(pixels, clone.pixels().as_ptr());
+        assert_eq!(1, Arc::strong_count(&farb.pixels));
+
+        let mut shared = farb.clone();
+        shared.as_channels_mut()[0] = 7;
+        assert_eq!(7, *shared[0].red());
+        assert_ne!(farb[0], shared[0]);
+        let (_, _, data) = farb.clone().into_raw_u16();
+        assert_eq!(farb.as_channels(), &data[..]);
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn test_save_from_eq() {
         let mut test_file = env::temp_dir();
@@ -251,4 +1059,406 @@ mod tests {
         File::open(&test_file).unwrap().read_to_end(&mut test).unwrap();
         assert_eq!(org, test);
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_to_bytes_eq_save() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut saved = Cursor::new(Vec::new());
+        farb.save(&mut saved).unwrap();
+        let bytes = farb.to_bytes();
+
+        assert_eq!(16 + farb.pixels().len() * 8, bytes.len());
+        assert_eq!(bytes.len(), bytes.capacity());
+        assert_eq!(saved.into_inner(), bytes);
+
+        let mut appended = b"prefix".to_vec();
+        farb.save_to_vec(&mut appended);
+        assert_eq!(&b"prefix"[..], &appended[..6]);
+        assert_eq!(bytes, &appended[6..]);
+    }
+
+    #[test]
+    fn test_map_channel() {
+        let mut farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2, 3, 65535), Pixel::new(4_u16, 5, 6, 65535)]).unwrap();
+        assert!(!farb.has_transparency());
+
+        farb.map_channel(Channel::Alpha, |_| 0);
+        assert!(farb.has_transparency());
+        assert_eq!(&[Pixel::new(1_u16, 2, 3, 0), Pixel::new(4_u16, 5, 6, 0)], farb.pixels());
+    }
+
+    #[test]
+    fn test_map_channels() {
+        let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2, 3, 4)]).unwrap();
+        farb.map_channels(|channel, value| match channel {
+            Channel::Alpha => value,
+            _ => value + 100
+        });
+        assert_eq!(&Pixel::new(101_u16, 102, 103, 4), &farb[0]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_from_bytes_round_trip() {
+        let farb = Farbfeld::new(2, 2, (0..4_u16).map(|i| Pixel::new(i, i * 2, i * 3, 65535)).collect()).unwrap();
+        let parsed = Farbfeld::from_bytes(&farb.to_bytes()).unwrap();
+        assert_eq!((farb.width(), farb.height()), (parsed.width(), parsed.height()));
+        assert_eq!(farb.pixels(), parsed.pixels());
+
+        let file = fs::read("test.ff").unwrap();
+        assert_eq!(file, Farbfeld::from_bytes(&file).unwrap().to_bytes());
+    }
+
+    #[test]
+    fn test_from_bytes_errors() {
+        match Farbfeld::from_bytes(b"farbfelx") {
+            Err(Error::InvalidMagic) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+
+        let mut bytes = Farbfeld::new(1, 1, vec![Pixel::default()]).unwrap().to_bytes();
+        bytes.pop();
+        match Farbfeld::from_bytes(&bytes) {
+            Err(Error::UnexpectedEof { needed: Some(1) }) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    /// A write accepting at most 3 bytes per call, and interrupting every other call.
+    #[cfg(feature = "std")]
+    struct ShortWrite {
+        data: Vec<u8>,
+        interrupt: bool
+    }
+
+    #[cfg(feature = "std")]
+    impl Write for ShortWrite {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if !self.interrupt {
+                return Err(::std::io::Error::new(::std::io::ErrorKind::Interrupted, "interrupted"));
+            }
+            let len = buf.len().min(3);
+            self.data.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> ::std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_save_returns_len() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let expected = 16 + *farb.width() as u64 * *farb.height() as u64 * 8;
+        let mut data = Vec::new();
+        assert_eq!(expected, farb.save(&mut data).unwrap());
+        assert_eq!(expected, data.len() as u64);
+
+        let mut test_file = env::temp_dir();
+        test_file.push(format!("ruff-len-{}.ff", process::id()));
+        assert_eq!(expected, farb.save_to_file(&test_file).unwrap());
+        assert_eq!(expected, fs::metadata(&test_file).unwrap().len());
+        fs::remove_file(&test_file).unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_header_and_pixels_eq_to_bytes() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut data = Vec::new();
+        assert_eq!(16, write_header(&mut data, *farb.width(), *farb.height()).unwrap());
+        assert_eq!(farb.pixels().len() as u64 * 8, farb.write_pixels(&mut data).unwrap());
+
+        assert_eq!(farb.to_bytes(), data);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_concatenated_pixels() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut data = Vec::new();
+        write_header(&mut data, *farb.width(), farb.height() * 2).unwrap();
+        farb.write_pixels(&mut data).unwrap();
+        for row in 0..*farb.height() {
+            write_row_pixels(&mut data, farb.row(row).unwrap()).unwrap();
+        }
+        let double = Farbfeld::from_read(&data[..]).unwrap();
+
+        assert_eq!(farb.height() * 2, *double.height());
+        assert_eq!(farb.pixels(), &double[..farb.pixels().len()]);
+        assert_eq!(farb.pixels(), &double[farb.pixels().len()..]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_raw_u16_round_trip() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let raw = farb.as_raw_u16();
+        assert_eq!(farb.pixels().len() * 4, raw.len());
+
+        let (width, height, data) = Farbfeld::from_raw_u16(*farb.width(), *farb.height(), raw.clone())
+            .unwrap()
+            .into_raw_u16();
+        assert_eq!((*farb.width(), *farb.height()), (width, height));
+        assert_eq!(raw, data);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_as_channels() {
+        let mut farb = Farbfeld::from_file("test.ff").unwrap();
+        assert_eq!(farb.as_raw_u16(), farb.as_channels());
+        assert_eq!(farb.pixels().as_ptr() as *const u16, farb.as_channels().as_ptr());
+
+        let (width, height) = (*farb.width(), *farb.height());
+        for (i, channel) in farb.as_channels_mut().iter_mut().enumerate() {
+            *channel = i as u16;
+        }
+        assert_eq!((width, height), (*farb.width(), *farb.height()));
+        assert_eq!(&Pixel::new(4_u16, 5, 6, 7), &farb[1]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_reload_reuses_allocation() {
+        let original = Farbfeld::from_file("test.ff").unwrap();
+        let mut farb = original.clone();
+        let small = Farbfeld::new(3, 2, (0..6_u16).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+        farb.reload_from_read(&small.to_bytes()[..]).unwrap();
+        assert_eq!(small, farb);
+        assert_eq!(Farbfeld::from_file("test.ff").unwrap(), original);
+
+        let (pixels, capacity) = (farb.pixels().as_ptr(), farb.pixels.capacity());
+        let smaller = Farbfeld::new(1, 4, vec![Pixel::new(9_u16, 8, 7, 6); 4]).unwrap();
+        farb.reload_from_bytes(&smaller.to_bytes()).unwrap();
+        assert_eq!(smaller, farb);
+        assert_eq!((pixels, capacity), (farb.pixels().as_ptr(), farb.pixels.capacity()));
+
+        farb.reload_from_file("test.ff").unwrap();
+        assert_eq!(original, farb);
+        assert_eq!(Farbfeld::from_read(&original.to_bytes()[..]).unwrap(), farb);
+        assert!(farb.pixels.capacity() >= original.pixels().len());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_reload_errors_leave_image() {
+        let original = Farbfeld::from_file("test.ff").unwrap();
+        let mut farb = original.clone();
+        farb[0] = Pixel::default();
+        let expected = farb.clone();
+        let bytes = Farbfeld::new(2, 2, vec![Pixel::new(1_u16, 2, 3, 4); 4]).unwrap().to_bytes();
+
+        let mut wrong_dimensions = bytes.clone();
+        wrong_dimensions[15] = 3;
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'g';
+        for (data, message) in [(&bytes[..bytes.len() - 3], "truncated"), (&wrong_dimensions[..], "dimensions"),
+                                (&bad_magic[..], "magic"), (&bytes[..10], "header")] {
+            let (expected_err, err) = (Farbfeld::from_read(data).unwrap_err(), farb.reload_from_read(data).unwrap_err());
+            assert_eq!(format!("{:?}", expected_err), format!("{:?}", err), "{}", message);
+            assert_eq!(expected, farb, "{}", message);
+        }
+        match farb.reload_from_file("does-not-exist.ff") {
+            Err(Error::IoError(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        assert_eq!(expected, farb);
+        assert_eq!(Farbfeld::from_file("test.ff").unwrap(), original);
+    }
+
+    #[test]
+    fn test_raw_u16_reuses_allocation() {
+        let data: Vec<u16> = (0..24).collect();
+        let ptr = data.as_ptr();
+        let farb = Farbfeld::from_raw_u16(3, 2, data).unwrap();
+        assert_eq!(&Pixel::new(20_u16, 21, 22, 23), &farb[5]);
+
+        let (_, _, data) = farb.into_raw_u16();
+        assert_eq!(ptr, data.as_ptr());
+        assert_eq!((0..24).collect::<Vec<u16>>(), data);
+    }
+
+    #[test]
+    fn test_raw_u16_odd_capacity() {
+        let mut data = Vec::with_capacity(9);
+        data.extend(0..8_u16);
+        let farb = Farbfeld::from_raw_u16(1, 2, data).unwrap();
+        assert_eq!(&[Pixel::new(0_u16, 1, 2, 3), Pixel::new(4_u16, 5, 6, 7)], farb.pixels());
+    }
+
+    #[test]
+    fn test_raw_u16_wrong_length() {
+        for len in [0, 3, 7, 9, 16].iter() {
+            match Farbfeld::from_raw_u16(1, 2, vec![0; *len]) {
+                Err(Error::InvalidFarbfeldDimensions) => {},
+                res => panic!("Unexpected result {:?}", res)
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_save_region_eq_crop() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let (width, height) = (*farb.width(), *farb.height());
+        let regions = [(0, 0, width, height), (3, 5, 10, 7), (0, 0, 1, height), (width - 1, 0, 1, height),
+                       (0, height - 1, width, 1), (width - 1, height - 1, 1, 1), (2, 2, 0, 0)];
+        for &(x, y, w, h) in regions.iter() {
+            let mut data = Vec::new();
+            let written = farb.save_region(&mut data, x, y, w, h).unwrap();
+            assert_eq!(farb.crop(x, y, w, h).unwrap().to_bytes(), data);
+            assert_eq!(data.len() as u64, written);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_save_region_out_of_bounds() {
+        let farb = Farbfeld::new(2, 2, vec![Pixel::default(); 4]).unwrap();
+        for &(x, y, w, h) in [(0, 0, 3, 1), (1, 0, 2, 1), (0, 2, 1, 1), (u32::MAX, 0, 2, 1)].iter() {
+            let mut data = Vec::new();
+            match farb.save_region(&mut data, x, y, w, h) {
+                Err(Error::RegionOutOfBounds(..)) => {},
+                res => panic!("Unexpected result {:?}", res)
+            }
+            assert!(data.is_empty());
+            assert!(farb.crop(x, y, w, h).is_err());
+        }
+    }
+
+    #[test]
+    fn test_zip_pixels() {
+        let farb = Farbfeld::new(3, 2, (0..6_u16).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+        let other = Farbfeld::new(3, 2, (0..6_u16).map(|i| Pixel::new(i * 2, 0, 0, 0)).collect()).unwrap();
+        let zipped: Vec<_> = farb.zip_pixels(&other).unwrap().collect();
+        assert_eq!(6, zipped.len());
+        assert_eq!(6, farb.zip_pixels(&other).unwrap().len());
+        for (i, &(x, y, pixel, other_pixel)) in zipped.iter().enumerate() {
+            assert_eq!(((i % 3) as u32, (i / 3) as u32), (x, y));
+            assert_eq!((&farb[i], &other[i]), (pixel, other_pixel));
+        }
+
+        let mut combined = farb.clone();
+        for (pixel, other) in combined.zip_pixels_mut(&other).unwrap() {
+            *pixel.red_mut() += *other.red();
+        }
+        assert_eq!(vec![0, 3, 6, 9, 12, 15], combined.pixels().iter().map(|pixel| *pixel.red()).collect::<Vec<_>>());
+
+        let empty = Farbfeld::new(0, 4, Vec::new()).unwrap();
+        assert_eq!(0, empty.zip_pixels(&empty).unwrap().count());
+    }
+
+    #[test]
+    fn test_zip_pixels_mismatch() {
+        let mut farb = Farbfeld::new(3, 2, vec![Pixel::default(); 6]).unwrap();
+        let other = Farbfeld::new(2, 3, vec![Pixel::default(); 6]).unwrap();
+        match farb.zip_pixels(&other) {
+            Err(Error::DimensionMismatch(3, 2, 2, 3)) => {},
+            Err(err) => panic!("Unexpected error {:?}", err),
+            Ok(_) => panic!("Unexpected success")
+        }
+        let mismatch = match farb.zip_pixels_mut(&other) {
+            Err(err) => err,
+            Ok(_) => panic!("Unexpected success")
+        };
+        match mismatch {
+            Error::DimensionMismatch(3, 2, 2, 3) => {},
+            err => panic!("Unexpected error {:?}", err)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_save_short_writes() {
+        let farb = Farbfeld::new(3, 2, (0..6_u16)
+            .map(|i| Pixel::new(i, i + 1000, i + 2000, 65535 - i))
+            .collect()).unwrap();
+        let mut write = ShortWrite { data: Vec::new(), interrupt: false };
+        farb.save(&mut write).unwrap();
+
+        assert_eq!(16 + 6 * 8, write.data.len());
+        assert_eq!(&b"farbfeld"[..], &write.data[..8]);
+        assert_eq!(farb.to_bytes(), write.data);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_save_to_file_atomic() {
+        let mut dir = env::temp_dir();
+        dir.push(format!("ruff-atomic-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("atomic.ff");
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+
+        assert_eq!(16 + farb.pixels().len() as u64 * 8, farb.save_to_file_atomic(&path).unwrap());
+        assert_eq!(farb.to_bytes(), fs::read(&path).unwrap());
+
+        let failed = write_atomic(&path, |file| {
+            file.write_all(b"farbfeld\0\0").map_err(Error::IoError)?;
+            Err::<u64, _>(Error::IoError(io::Error::other("disk full")))
+        });
+        assert!(failed.is_err());
+        assert_eq!(farb.to_bytes(), fs::read(&path).unwrap());
+        assert_eq!(1, fs::read_dir(&dir).unwrap().count());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_save_to_file_atomic_failure_no_destination() {
+        let mut dir = env::temp_dir();
+        dir.push(format!("ruff-atomic-missing-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("missing.ff");
+
+        let failed = write_atomic(&path, |file| {
+            file.write_all(b"farb").map_err(Error::IoError)?;
+            Err::<u64, _>(Error::IoError(io::Error::other("disk full")))
+        });
+        assert!(failed.is_err());
+        assert!(!path.exists());
+        assert_eq!(0, fs::read_dir(&dir).unwrap().count());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_save_with_progress() {
+        let farb = Farbfeld::new(300, 100, vec![Pixel::new(1_u16, 2_u16, 3_u16, 4_u16); 300 * 100]).unwrap();
+        let total = 16 + 300 * 100 * 8;
+        let mut calls = Vec::new();
+        let mut data = Vec::new();
+        farb.save_with_progress(&mut data, |written, total| calls.push((written, total))).unwrap();
+
+        assert!(calls.len() > 2);
+        assert!(calls.iter().all(|&(_, t)| t == total));
+        assert!(calls.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(Some(&(total, total)), calls.last());
+        assert_eq!(total, data.len() as u64);
+    }
+
+    #[test]
+    fn test_new_overflowing_dimensions() {
+        assert!(Farbfeld::new(u32::MAX, u32::MAX, Vec::new()).is_err());
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary_coherent() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let data: Vec<u8> = (0..4096_u32).map(|i| (i * 31 % 251) as u8).collect();
+        let farb = Farbfeld::arbitrary(&mut Unstructured::new(&data)).unwrap();
+
+        assert!(farb.width() <= &ARBITRARY_MAX_DIM);
+        assert!(farb.height() <= &ARBITRARY_MAX_DIM);
+        assert_eq!((farb.width() * farb.height()) as usize, farb.pixels().len());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,231 @@
+//! A C interface to ruff, for loading and saving Farbfeld images from programs written in other
+//! languages. Requires the `ffi` feature.
+//!
+//! Pixels are passed as interleaved red, green, blue and alpha channels in row-major order, as
+//! native endian uint16_t values. Every function returns a [RuffError](enum.RuffError.html) code,
+//! and the message of the last error on the calling thread is available from
+//! [ruff_last_error_message](fn.ruff_last_error_message.html). Panics are caught and reported as
+//! `RUFF_ERROR_PANIC`, so they never unwind into the calling program.
+//!
+//! A C header can be generated with [cbindgen](https://github.com/eqrion/cbindgen) by running
+//! `cbindgen --config cbindgen.toml --output include/ruff.h` from the root of the crate. The
+//! `ffi` directory holds a crate which builds the interface as a shared library, so `ruff` itself
+//! stays an rlib which can be built without std.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::farbfeld::Farbfeld;
+use crate::error::*;
+
+/// The number of u16s before the pixels of an allocation returned by ruff_load_path, which store
+/// the number of u16s in the pixels so the allocation can be freed.
+const LEN_PREFIX: usize = 4;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The result of a call to a ruff function.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuffError {
+    /// The call succeeded.
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullPointer = 1,
+    /// The path wasn't valid UTF-8.
+    InvalidPath = 2,
+    /// Reading or writing failed.
+    Io = 3,
+    /// The file isn't a valid Farbfeld image.
+    Parse = 4,
+    /// The dimensions don't fit in memory.
+    InvalidDimensions = 5,
+    /// ruff panicked. This is a bug in ruff.
+    Panic = 6
+}
+
+impl<'a> From<&'a Error> for RuffError {
+    fn from(err: &'a Error) -> RuffError {
+        match *err {
+            Error::IoError(_) => RuffError::Io,
+            Error::InvalidFarbfeldDimensions => RuffError::InvalidDimensions,
+            _ => RuffError::Parse
+        }
+    }
+}
+
+/// Records the error message for ruff_last_error_message, returning the error.
+fn set_error(code: RuffError, message: String) -> RuffError {
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    code
+}
+
+/// Runs the function, converting its errors and any panic into an error code.
+fn catch<F: FnOnce() -> ::std::result::Result<(), RuffError>>(func: F) -> RuffError {
+    match panic::catch_unwind(AssertUnwindSafe(|| {
+        LAST_ERROR.with(|last| *last.borrow_mut() = None);
+        func()
+    })) {
+        Ok(Ok(())) => RuffError::Ok,
+        Ok(Err(code)) => code,
+        Err(_) => set_error(RuffError::Panic, "ruff panicked".to_string())
+    }
+}
+
+fn convert_error(err: Error) -> RuffError {
+    set_error(RuffError::from(&err), err.to_string())
+}
+
+unsafe fn path<'a>(path: *const c_char) -> ::std::result::Result<&'a str, RuffError> {
+    if path.is_null() {
+        return Err(set_error(RuffError::NullPointer, "path is null".to_string()));
+    }
+    CStr::from_ptr(path).to_str()
+        .map_err(|_| set_error(RuffError::InvalidPath, "path isn't valid UTF-8".to_string()))
+}
+
+/// Loads the Farbfeld image at the given path, storing its dimensions in width and height and a
+/// pointer to its pixels in pixels. The pixels contain width * height * 4 channels, and must be
+/// freed with [ruff_free](fn.ruff_free.html). Nothing is stored if an error is returned.
+///
+/// # Safety
+/// path must be a null terminated string, and width, height and pixels must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ruff_load_path(path: *const c_char, width: *mut u32, height: *mut u32,
+                                        pixels: *mut *mut u16) -> RuffError {
+    catch(|| {
+        let path = self::path(path)?;
+        if width.is_null() || height.is_null() || pixels.is_null() {
+            return Err(set_error(RuffError::NullPointer, "output pointer is null".to_string()));
+        }
+        let (w, h, data) = Farbfeld::from_file(path).map_err(convert_error)?.into_raw_u16();
+
+        let mut alloc = Vec::with_capacity(LEN_PREFIX + data.len());
+        alloc.extend((data.len() as u64).to_ne_bytes().chunks(2).map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]])));
+        alloc.extend_from_slice(&data);
+        let alloc = Box::into_raw(alloc.into_boxed_slice()) as *mut u16;
+
+        *width = w;
+        *height = h;
+        *pixels = alloc.add(LEN_PREFIX);
+        Ok(())
+    })
+}
+
+/// Saves an image with the given dimensions and pixels to the given path. The pixels must contain
+/// width * height * 4 channels.
+///
+/// # Safety
+/// path must be a null terminated string, and pixels must be valid for reads of
+/// width * height * 4 channels.
+#[no_mangle]
+pub unsafe extern "C" fn ruff_save_path(path: *const c_char, width: u32, height: u32,
+                                        pixels: *const u16) -> RuffError {
+    catch(|| {
+        let path = self::path(path)?;
+        if pixels.is_null() {
+            return Err(set_error(RuffError::NullPointer, "pixels is null".to_string()));
+        }
+        let len = (width as u64).checked_mul(height as u64 * 4)
+            .filter(|&len| len <= isize::MAX as u64 / 2)
+            .ok_or_else(|| set_error(RuffError::InvalidDimensions, "image is too large".to_string()))?;
+        let data = slice::from_raw_parts(pixels, len as usize).to_vec();
+        Farbfeld::from_raw_u16(width, height, data)
+            .and_then(|farb| farb.save_to_file(path))
+            .map(|_| ())
+            .map_err(convert_error)
+    })
+}
+
+/// Frees the pixels returned by [ruff_load_path](fn.ruff_load_path.html). Does nothing if pixels
+/// is null.
+///
+/// # Safety
+/// pixels must be null or a pointer returned by ruff_load_path which hasn't already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn ruff_free(pixels: *mut u16) {
+    if pixels.is_null() {
+        return;
+    }
+    let alloc = pixels.sub(LEN_PREFIX);
+    let mut len = [0_u8; 8];
+    for (i, bytes) in len.chunks_mut(2).enumerate() {
+        bytes.copy_from_slice(&(*alloc.add(i)).to_ne_bytes());
+    }
+    let len = LEN_PREFIX + u64::from_ne_bytes(len) as usize;
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(alloc, len)));
+}
+
+/// Returns the message of the last error returned by a ruff function on the calling thread, or
+/// null if the last call succeeded. The message is valid until the next call to a ruff function
+/// on the same thread.
+#[no_mangle]
+pub extern "C" fn ruff_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map(|message| message.as_ptr()).unwrap_or(ptr::null()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn test_load_save() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut test_file = env::temp_dir();
+        test_file.push(format!("ruff_ffi_{}.ff", process::id()));
+        let test_path = CString::new(test_file.to_str().unwrap()).unwrap();
+
+        unsafe {
+            let (mut width, mut height, mut pixels) = (0, 0, ptr::null_mut());
+            let path = CString::new("test.ff").unwrap();
+            assert_eq!(RuffError::Ok, ruff_load_path(path.as_ptr(), &mut width, &mut height, &mut pixels));
+            assert!(ruff_last_error_message().is_null());
+            assert_eq!((*farb.width(), *farb.height()), (width, height));
+            assert_eq!(&farb.as_raw_u16()[..], slice::from_raw_parts(pixels, farb.pixels().len() * 4));
+
+            assert_eq!(RuffError::Ok, ruff_save_path(test_path.as_ptr(), width, height, pixels));
+            ruff_free(pixels);
+        }
+
+        assert_eq!(farb.to_bytes(), fs::read(&test_file).unwrap());
+        fs::remove_file(&test_file).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            let (mut width, mut height, mut pixels) = (0, 0, ptr::null_mut());
+            assert_eq!(RuffError::NullPointer, ruff_load_path(ptr::null(), &mut width, &mut height, &mut pixels));
+            assert!(!ruff_last_error_message().is_null());
+
+            let path = CString::new("does_not_exist.ff").unwrap();
+            assert_eq!(RuffError::Io, ruff_load_path(path.as_ptr(), &mut width, &mut height, &mut pixels));
+            assert!(pixels.is_null());
+
+            let path = CString::new("Cargo.toml").unwrap();
+            assert_eq!(RuffError::Parse, ruff_load_path(path.as_ptr(), &mut width, &mut height, &mut pixels));
+            let message = CStr::from_ptr(ruff_last_error_message()).to_str().unwrap();
+            assert!(message.contains("farbfeld"));
+
+            assert_eq!(RuffError::NullPointer, ruff_save_path(path.as_ptr(), 1, 1, ptr::null()));
+            ruff_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_panic_caught() {
+        assert_eq!(RuffError::Panic, catch(|| panic!("boom")));
+        assert!(!ruff_last_error_message().is_null());
+    }
+}
@@ -0,0 +1,184 @@
+//! Running Farbfeld filters in pipelines, in the style of the suckless farbfeld tools such as
+//! `png2ff < in.png | filter | ff2png > out.png`.
+//!
+//! Errors are returned rather than printed, so programs can choose how to report them and which
+//! exit code to use.
+
+use std::io::{self, Read, Write, BufWriter};
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+use crate::encoder::FarbfeldEncoder;
+use crate::error::*;
+use crate::parser;
+use crate::stream;
+
+/// Parses an image from the given read, applies the filter and writes the result to the given
+/// write.
+///
+/// # Errors
+/// Returns the errors of [Farbfeld::from_read](../struct.Farbfeld.html#method.from_read), any
+/// error returned by the filter, or an [Error::IoError](../error/enum.Error.html) if the
+/// result cannot be written.
+///
+/// # Examples
+/// ```
+/// # use ::ruff::*;
+/// let farb = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2, 3, 4)]).unwrap();
+/// let mut out = Vec::new();
+/// filter_io(&farb.to_bytes()[..], &mut out, |farb| farb.crop(0, 0, 0, 0)).unwrap();
+///
+/// assert_eq!(Farbfeld::new(0, 0, Vec::new()).unwrap().to_bytes(), out);
+/// ```
+pub fn filter_io<R, W, F>(read: R, mut write: W, filter: F) -> Result<()>
+    where R: Read, W: Write, F: FnOnce(Farbfeld) -> Result<Farbfeld> {
+    let farb = Farbfeld::from_read(read).and_then(filter)?;
+    farb.save(&mut write)?;
+    write.flush().map_err(Error::IoError)
+}
+
+/// Parses an image from stdin, applies the filter and writes the result to stdout, as described
+/// by [filter_io](fn.filter_io.html).
+///
+/// # Errors
+/// Returns the errors of [filter_io](fn.filter_io.html).
+pub fn filter_stdio<F: FnOnce(Farbfeld) -> Result<Farbfeld>>(filter: F) -> Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    filter_io(stdin.lock(), BufWriter::new(stdout.lock()), filter)
+}
+
+/// Applies the filter to each row of the image from the given read, writing each row to the given
+/// write as soon as it is filtered, so only a single row is held in memory. The filter is given
+/// the index of the row and its pixels to modify in place, so the dimensions of the image are
+/// unchanged.
+///
+/// # Errors
+/// Returns any error returned by the filter, or one of the following errors.
+/// <ul>
+///     <li><a href="../error/enum.Error.html">Error::InvalidMagic</a> or
+///     <a href="../error/enum.Error.html">Error::UnexpectedEof</a> if the read doesn't
+///     contain a complete image.</li>
+///     <li><a href="../error/enum.Error.html">Error::IoError</a> if the read or write
+///     fails.</li>
+/// </ul>
+///
+/// # Examples
+/// ```
+/// # use ::ruff::*;
+/// let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2, 3, 4); 2]).unwrap();
+/// let mut out = Vec::new();
+/// filter_rows_io(&farb.to_bytes()[..], &mut out, |_, row| {
+///     for pixel in row {
+///         *pixel.alpha_mut() = 65535;
+///     }
+///     Ok(())
+/// }).unwrap();
+///
+/// assert_eq!(&[Pixel::new(1_u16, 2, 3, 65535); 2], Farbfeld::from_read(&out[..]).unwrap().pixels());
+/// ```
+pub fn filter_rows_io<R, W, F>(mut read: R, write: W, mut filter: F) -> Result<()>
+    where R: Read, W: Write, F: FnMut(u32, &mut [Pixel]) -> Result<()> {
+    let (width, height) = stream::read_header(&mut read)?
+        .ok_or(Error::UnexpectedEof { needed: Some(16) })?;
+    let row_len = width as u64 * 8;
+    let mut buff = Vec::new();
+    let mut encoder = FarbfeldEncoder::new(write, width, height)?;
+    for row in 0..height {
+        buff.clear();
+        (&mut read).take(row_len).read_to_end(&mut buff).map_err(Error::IoError)?;
+        if (buff.len() as u64) < row_len {
+            let missing = (row_len - buff.len() as u64) + (height - row - 1) as u64 * row_len;
+            return Err(Error::UnexpectedEof { needed: Some(missing) });
+        }
+        let mut pixels = parser::parse_pixels(&buff)?;
+        filter(row, &mut pixels)?;
+        encoder.write_row(&pixels)?;
+    }
+    encoder.finish().map(|_| ())
+}
+
+/// Applies the filter to each row of the image from stdin, writing the result to stdout, as
+/// described by [filter_rows_io](fn.filter_rows_io.html).
+///
+/// # Errors
+/// Returns the errors of [filter_rows_io](fn.filter_rows_io.html).
+pub fn filter_rows_stdio<F: FnMut(u32, &mut [Pixel]) -> Result<()>>(filter: F) -> Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    filter_rows_io(stdin.lock(), BufWriter::new(stdout.lock()), filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::farbfeld;
+
+    fn invert(pixel: &mut Pixel) {
+        *pixel = Pixel::new(65535 - *pixel.red(), 65535 - *pixel.green(), 65535 - *pixel.blue(), *pixel.alpha());
+    }
+
+    #[test]
+    fn test_filter_io_eq_filter_rows_io() {
+        let data = Farbfeld::from_file("test.ff").unwrap().to_bytes();
+        let mut whole = Vec::new();
+        filter_io(&data[..], &mut whole, |farb| {
+            let pixels = farb.pixels().iter().cloned().map(|mut pixel| {
+                invert(&mut pixel);
+                pixel
+            }).collect();
+            Farbfeld::new(*farb.width(), *farb.height(), pixels)
+        }).unwrap();
+
+        let mut rows = Vec::new();
+        filter_rows_io(&data[..], &mut rows, |_, row| {
+            row.iter_mut().for_each(invert);
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(whole, rows);
+        assert_ne!(data, rows);
+    }
+
+    #[test]
+    fn test_filter_error_returned() {
+        let data = Farbfeld::new(1, 2, vec![Pixel::default(); 2]).unwrap().to_bytes();
+        let mut out = Vec::new();
+        match filter_rows_io(&data[..], &mut out, |row, _| if row == 1 {
+            Err(Error::InvalidFarbfeldDimensions)
+        } else {
+            Ok(())
+        }) {
+            Err(Error::InvalidFarbfeldDimensions) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        assert_eq!(16 + 8, out.len());
+
+        match filter_io(&data[..], Vec::new(), |_| Err(Error::InvalidFarbfeldDimensions)) {
+            Err(Error::InvalidFarbfeldDimensions) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_filter_rows_truncated() {
+        let data = Farbfeld::new(2, 3, vec![Pixel::default(); 6]).unwrap().to_bytes();
+        match filter_rows_io(&data[..data.len() - 20], Vec::new(), |_, _| Ok(())) {
+            Err(Error::UnexpectedEof { needed: Some(20) }) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        match filter_rows_io(&b""[..], Vec::new(), |_, _| Ok(())) {
+            Err(Error::UnexpectedEof { needed: Some(16) }) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_filter_rows_huge_width() {
+        let header = farbfeld::header_bytes(u32::MAX, 1);
+        match filter_rows_io(&header[..], Vec::new(), |_, _| Ok(())) {
+            Err(Error::UnexpectedEof { needed: Some(needed) }) => assert_eq!(u32::MAX as u64 * 8, needed),
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+}
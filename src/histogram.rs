@@ -0,0 +1,153 @@
+//! Counting the values of each channel across a Farbfeld image.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Channel;
+
+/// The number of possible values of a channel.
+const BINS: usize = 65536;
+
+/// The number of pixels with each value of each channel of an image, created by
+/// [Farbfeld::histogram](../struct.Farbfeld.html#method.histogram).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [Vec<u64>; 4],
+    total: u64
+}
+
+impl Histogram {
+    /// Returns the number of pixels with each value of the given channel, indexed by value. The
+    /// slice always contains 65536 counts.
+    pub fn channel(&self, channel: Channel) -> &[u64] {
+        &self.counts[channel as usize]
+    }
+
+    /// Returns the number of pixels counted.
+    pub fn total(&self) -> &u64 {
+        &self.total
+    }
+
+    /// Returns the smallest value of the given channel which at least `percentile` percent of
+    /// pixels are less than or equal to, always counting at least one pixel. A percentile of 0
+    /// finds the smallest value in the image and a percentile of 100 finds the largest. Returns
+    /// None if the image has no pixels.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::pixel::Channel;
+    /// let farb = Farbfeld::new(4, 1, (1..5_u16).map(|i| Pixel::new(i, 0, 0, 0)).collect()).unwrap();
+    /// let histogram = farb.histogram();
+    ///
+    /// assert_eq!(Some(1), histogram.percentile(Channel::Red, 0.0));
+    /// assert_eq!(Some(2), histogram.percentile(Channel::Red, 50.0));
+    /// assert_eq!(Some(4), histogram.percentile(Channel::Red, 100.0));
+    /// ```
+    pub fn percentile(&self, channel: Channel, percentile: f32) -> Option<u16> {
+        percentile_of(self.channel(channel), self.total, percentile)
+    }
+}
+
+/// Finds the percentile of the given counts, as described by
+/// [Histogram::percentile](struct.Histogram.html#method.percentile).
+pub(crate) fn percentile_of(counts: &[u64], total: u64, percentile: f32) -> Option<u16> {
+    let exact = total as f64 * percentile.clamp(0.0, 100.0) as f64 / 100.0;
+    // Casting saturates, so a NaN percentile counts a single pixel.
+    let mut target = exact as u64;
+    if (target as f64) < exact {
+        target += 1;
+    }
+    let target = target.clamp(1, total.max(1));
+    let mut seen = 0;
+    for (value, &count) in counts.iter().enumerate() {
+        seen += count;
+        if seen >= target {
+            return Some(value as u16);
+        }
+    }
+    None
+}
+
+/// Returns the table mapping each value to its position in the cumulative distribution of the
+/// counts, scaled so the smallest counted value maps to 0 and the largest to 65535 and rounded to
+/// the nearest value. Returns None if fewer than two different values are counted.
+pub(crate) fn equalization_table(counts: &[u64]) -> Option<Vec<u16>> {
+    let total: u64 = counts.iter().sum();
+    let smallest = *counts.iter().find(|&&count| count != 0)?;
+    let span = (total - smallest) as u128;
+    if span == 0 {
+        return None;
+    }
+    let mut seen = 0;
+    Some(counts.iter().map(|&count| {
+        seen += count;
+        ((seen.saturating_sub(smallest) as u128 * u16::MAX as u128 + span / 2) / span) as u16
+    }).collect())
+}
+
+impl Farbfeld {
+    /// Counts the number of pixels with each value of each channel.
+    pub fn histogram(&self) -> Histogram {
+        let mut counts = [vec![0; BINS], vec![0; BINS], vec![0; BINS], vec![0; BINS]];
+        for pixel in self.pixels() {
+            for (counts, channel) in counts.iter_mut().zip(pixel.iter()) {
+                counts[*channel as usize] += 1;
+            }
+        }
+        Histogram {
+            counts,
+            total: self.pixels().len() as u64
+        }
+    }
+
+    /// Counts the number of pixels with each luminance.
+    pub(crate) fn luminance_counts(&self) -> Vec<u64> {
+        let mut counts = vec![0; BINS];
+        for pixel in self.pixels() {
+            counts[pixel.luminance() as usize] += 1;
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::pixel::Pixel;
+
+    #[test]
+    fn test_histogram() {
+        let farb = Farbfeld::new(3, 1, vec![
+            Pixel::new(1_u16, 2, 3, 65535),
+            Pixel::new(1_u16, 5, 6, 65535),
+            Pixel::new(7_u16, 2, 9, 0)
+        ]).unwrap();
+        let histogram = farb.histogram();
+
+        assert_eq!(&3, histogram.total());
+        assert_eq!(65536, histogram.channel(Channel::Red).len());
+        assert_eq!(2, histogram.channel(Channel::Red)[1]);
+        assert_eq!(1, histogram.channel(Channel::Red)[7]);
+        assert_eq!(2, histogram.channel(Channel::Green)[2]);
+        assert_eq!(1, histogram.channel(Channel::Blue)[9]);
+        assert_eq!(2, histogram.channel(Channel::Alpha)[65535]);
+        assert_eq!(3, histogram.channel(Channel::Blue).iter().sum::<u64>());
+    }
+
+    #[test]
+    fn test_percentile() {
+        let farb = Farbfeld::new(10, 1, (0..10_u16).map(|i| Pixel::new(i * 10, 0, 0, 0)).collect()).unwrap();
+        let histogram = farb.histogram();
+
+        assert_eq!(Some(0), histogram.percentile(Channel::Red, 0.0));
+        assert_eq!(Some(0), histogram.percentile(Channel::Red, 10.0));
+        assert_eq!(Some(10), histogram.percentile(Channel::Red, 10.1));
+        assert_eq!(Some(90), histogram.percentile(Channel::Red, 100.0));
+        assert_eq!(Some(0), histogram.percentile(Channel::Green, 100.0));
+
+        let empty = Farbfeld::new(0, 0, Vec::new()).unwrap().histogram();
+        assert_eq!(None, empty.percentile(Channel::Red, 50.0));
+    }
+}
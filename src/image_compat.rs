@@ -0,0 +1,184 @@
+//! Conversions between Farbfeld images and the [image](https://crates.io/crates/image) crate's
+//! RGBA image buffers, along with implementations of the image crate's GenericImageView and
+//! GenericImage for Farbfeld, using `Rgba<u16>` as the pixel type. Requires the `image` feature.
+//!
+//! Both Farbfeld and the image crate store pixels row by row with the red, green, blue and alpha
+//! channels in that order, and both use u32 dimensions, so the conversions only change the channel
+//! depth.
+//!
+//! Implementing GenericImage allows Farbfeld images to be used directly with the image crate's
+//! imageops, and crates such as imageproc, without converting them first.
+//!
+//! ```
+//! # use ::ruff::*;
+//! use image::GenericImageView;
+//!
+//! let farb = Farbfeld::new(2, 2, vec![Pixel::new(1_u16, 2, 3, 4); 4]).unwrap();
+//! assert_eq!(image::Rgba([1, 2, 3, 4]), farb.get_pixel(1, 1));
+//! ```
+
+use std::ops::Deref;
+
+use image::{GenericImage, GenericImageView, ImageBuffer, Pixel as ImagePixel, Rgba, RgbaImage};
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+
+impl From<Pixel> for Rgba<u16> {
+    fn from(pixel: Pixel) -> Rgba<u16> {
+        Rgba(pixel.into())
+    }
+}
+
+impl From<Rgba<u16>> for Pixel {
+    fn from(Rgba(channels): Rgba<u16>) -> Pixel {
+        Pixel::from(channels)
+    }
+}
+
+impl Farbfeld {
+    /// Creates a Farbfeld image from an 8 bit RGBA image, expanding each channel to 16 bits.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let image = image::RgbaImage::from_pixel(2, 1, image::Rgba([255, 0, 1, 255]));
+    /// let farb = Farbfeld::from_rgba8_image(&image);
+    ///
+    /// assert_eq!(&[Pixel::new(65535_u16, 0, 257, 65535); 2], farb.pixels());
+    /// ```
+    pub fn from_rgba8_image(image: &RgbaImage) -> Farbfeld {
+        let pixels = image.pixels()
+            .map(|&Rgba(channels)| Pixel::from_rgba8(channels))
+            .collect();
+        // The image holds exactly one pixel per position, so its dimensions are always valid.
+        Farbfeld::new(image.width(), image.height(), pixels).expect("image dimensions are valid")
+    }
+
+    /// Creates a Farbfeld image from a 16 bit RGBA image without any loss.
+    pub fn from_rgba16_image<C: Deref<Target = [u16]>>(image: &ImageBuffer<Rgba<u16>, C>) -> Farbfeld {
+        let pixels = image.pixels()
+            .map(|&Rgba(channels)| Pixel::from(channels))
+            .collect();
+        // The image holds exactly one pixel per position, so its dimensions are always valid.
+        Farbfeld::new(image.width(), image.height(), pixels).expect("image dimensions are valid")
+    }
+
+    /// Creates an 8 bit RGBA image from this image, rounding each channel to the nearest 8 bit
+    /// value.
+    ///
+    /// # Panics
+    /// Panics if the number of channels in the image overflows a usize, as the image crate
+    /// requires.
+    pub fn to_rgba8_image(&self) -> RgbaImage {
+        ImageBuffer::from_raw(*self.width(), *self.height(), self.to_rgba8_vec()).expect("image dimensions are valid")
+    }
+
+    /// Creates a 16 bit RGBA image from this image without any loss.
+    ///
+    /// # Panics
+    /// Panics if the number of channels in the image overflows a usize, as the image crate
+    /// requires.
+    pub fn to_rgba16_image(&self) -> ImageBuffer<Rgba<u16>, Vec<u16>> {
+        let raw = self.pixels().iter()
+            .flat_map(|pixel| pixel.iter().cloned())
+            .collect();
+        ImageBuffer::from_raw(*self.width(), *self.height(), raw).expect("image dimensions are valid")
+    }
+}
+
+impl Farbfeld {
+    /// Returns the index into pixels of the pixel at (x, y).
+    ///
+    /// # Panics
+    /// Panics if (x, y) is outside the image, as required by GenericImageView.
+    fn image_index(&self, x: u32, y: u32) -> usize {
+        if x >= *self.width() || y >= *self.height() {
+            panic!("Image index {:?} out of bounds {:?}", (x, y), (*self.width(), *self.height()));
+        }
+        y as usize * *self.width() as usize + x as usize
+    }
+}
+
+impl GenericImageView for Farbfeld {
+    type Pixel = Rgba<u16>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        (*self.width(), *self.height())
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Rgba<u16> {
+        self[self.image_index(x, y)].into()
+    }
+}
+
+impl GenericImage for Farbfeld {
+    fn get_pixel_mut(&mut self, x: u32, y: u32) -> &mut Rgba<u16> {
+        let index = self.image_index(x, y);
+        // Pixel and Rgba<u16> are both repr(C) structs of four u16 channels in the same order.
+        unsafe { &mut *(&mut self[index] as *mut Pixel as *mut Rgba<u16>) }
+    }
+
+    fn put_pixel(&mut self, x: u32, y: u32, pixel: Rgba<u16>) {
+        let index = self.image_index(x, y);
+        self[index] = pixel.into();
+    }
+
+    fn blend_pixel(&mut self, x: u32, y: u32, pixel: Rgba<u16>) {
+        let mut blended = self.get_pixel(x, y);
+        blended.blend(&pixel);
+        self.put_pixel(x, y, blended);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rgba16_round_trip() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let image = farb.to_rgba16_image();
+        assert_eq!((*farb.width(), *farb.height()), image.dimensions());
+
+        let round_trip = Farbfeld::from_rgba16_image(&image);
+        assert_eq!(farb.to_bytes(), round_trip.to_bytes());
+    }
+
+    #[test]
+    fn test_rgba8_round_trip() {
+        let image = RgbaImage::from_fn(7, 3, |x, y| Rgba([x as u8 * 36, y as u8 * 127, 255, (x * y) as u8]));
+        let farb = Farbfeld::from_rgba8_image(&image);
+        assert_eq!(&[Pixel::new(36_u16 * 257, 0, 65535, 0)], &farb.pixels()[1..2]);
+        assert_eq!(image, farb.to_rgba8_image());
+    }
+
+    #[test]
+    fn test_crop_imm_eq_crop() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let cropped = image::imageops::crop_imm(&farb, 3, 5, 10, 7).to_image();
+        assert_eq!(farb.crop(3, 5, 10, 7).unwrap().to_rgba16_image(), cropped);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_put_pixel() {
+        let mut farb = Farbfeld::new(2, 2, vec![Pixel::default(); 4]).unwrap();
+        farb.put_pixel(1, 0, Rgba([1, 2, 3, 4]));
+        farb.get_pixel_mut(0, 1).0[3] = 65535;
+        assert_eq!(&[Pixel::default(), Pixel::new(1_u16, 2, 3, 4), Pixel::new(0_u16, 0, 0, 65535),
+                     Pixel::default()], farb.pixels());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_get_pixel_out_of_bounds() {
+        let farb = Farbfeld::new(2, 2, vec![Pixel::default(); 4]).unwrap();
+        farb.get_pixel(2, 0);
+    }
+
+    #[test]
+    fn test_narrow_rounds() {
+        let farb = Farbfeld::new(1, 1, vec![Pixel::new(128_u16, 129_u16, 65535_u16, 385_u16)]).unwrap();
+        assert_eq!(&Rgba([0, 1, 255, 1]), farb.to_rgba8_image().get_pixel(0, 0));
+    }
+}
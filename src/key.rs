@@ -0,0 +1,122 @@
+//! Replacing and keying out colours of Farbfeld images, such as for green screens.
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+
+impl Farbfeld {
+    /// Replaces every pixel [similar](pixel/struct.Pixel.html#method.is_similar) to `target`
+    /// within `tolerance` with `replacement`. Returns the number of pixels changed, which doesn't
+    /// include similar pixels which were already equal to `replacement`.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let red = Pixel::new(65535_u16, 0, 0, 65535);
+    /// let blue = Pixel::new(0_u16, 0, 65535, 65535);
+    /// let mut farb = Farbfeld::new(2, 1, vec![red, Pixel::new(65000_u16, 0, 0, 65535)]).unwrap();
+    ///
+    /// assert_eq!(2, farb.replace_color(red, blue, 1000));
+    /// assert_eq!(&[blue, blue], farb.pixels());
+    /// ```
+    pub fn replace_color(&mut self, target: Pixel, replacement: Pixel, tolerance: u16) -> u64 {
+        let mut changed = 0;
+        for pixel in self[..].iter_mut() {
+            if pixel.is_similar(&target, tolerance) && *pixel != replacement {
+                *pixel = replacement;
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Makes every pixel [similar](pixel/struct.Pixel.html#method.is_similar) to `target` within
+    /// `tolerance` fully transparent, leaving its colour unchanged.
+    pub fn key_out_color(&mut self, target: Pixel, tolerance: u16) {
+        self.key_out_color_soft(target, tolerance, tolerance);
+    }
+
+    /// Keys out `target` with a soft edge. Pixels within `inner` of `target`, as found by
+    /// [Pixel::distance](pixel/struct.Pixel.html#method.distance), are made fully transparent,
+    /// pixels further than `outer` are left unchanged, and the alpha of pixels in between is
+    /// scaled by how far they are between `inner` and `outer`. This partially keys out
+    /// anti-aliased edges, which mix the key colour with the foreground. An `outer` no greater than
+    /// `inner` keys out as [key_out_color](#method.key_out_color) does with `inner`.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let green = Pixel::new(0_u16, 65535, 0, 65535);
+    /// let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(30000_u16, 35535, 0, 65535)]).unwrap();
+    /// farb.key_out_color_soft(green, 10000, 50000);
+    ///
+    /// assert_eq!(&Pixel::new(30000_u16, 35535, 0, 32768), &farb[0]);
+    /// ```
+    pub fn key_out_color_soft(&mut self, target: Pixel, inner: u16, outer: u16) {
+        for pixel in self[..].iter_mut() {
+            let distance = pixel.distance(&target);
+            if distance <= inner {
+                *pixel.alpha_mut() = 0;
+            } else if distance < outer {
+                let (position, span) = ((distance - inner) as u64, (outer - inner) as u64);
+                let alpha = pixel.alpha_mut();
+                *alpha = ((*alpha as u64 * position * 2 + span) / (span * 2)) as u16;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::patterns;
+
+    fn green() -> Pixel {
+        Pixel::new(0_u16, 65535, 0, 65535)
+    }
+
+    /// A green screen with a red square in the middle, anti-aliased by a half red, half green
+    /// ring.
+    fn green_screen() -> Farbfeld {
+        let mut farb = patterns::solid(8, 8, green());
+        farb.draw_filled_rect(2, 2, 4, 4, Pixel::new(32768_u16, 32767, 0, 65535));
+        farb.draw_filled_rect(3, 3, 2, 2, Pixel::new(65535_u16, 0, 0, 65535));
+        farb
+    }
+
+    #[test]
+    fn test_replace_color() {
+        let mut farb = green_screen();
+        let expected = farb.pixels().iter().filter(|pixel| pixel.is_similar(&green(), 40000)).count() as u64;
+        let blue = Pixel::new(0_u16, 0, 65535, 65535);
+        assert_eq!(expected, farb.replace_color(green(), blue, 40000));
+        assert_eq!(60, expected);
+        assert_eq!(0, farb.replace_color(green(), blue, 0));
+        assert_eq!(0, farb.replace_color(blue, blue, 0));
+        assert_eq!(&Pixel::new(65535_u16, 0, 0, 65535), &farb[3 * 8 + 3]);
+    }
+
+    #[test]
+    fn test_key_out_color() {
+        let mut farb = green_screen();
+        farb.key_out_color(green(), 0);
+        for (i, pixel) in farb.pixels().iter().enumerate() {
+            let (x, y) = (i % 8, i / 8);
+            let inside = (2..6).contains(&x) && (2..6).contains(&y);
+            assert_eq!(if inside { 65535 } else { 0 }, *pixel.alpha(), "({}, {})", x, y);
+        }
+        assert_eq!(green().red(), farb[0].red());
+    }
+
+    #[test]
+    fn test_key_out_color_soft() {
+        let mut farb = green_screen();
+        farb.key_out_color_soft(green(), 0, 65535);
+        assert_eq!(&0, farb[0].alpha());
+        assert_eq!(&32768, farb[2 * 8 + 2].alpha());
+        assert_eq!(&65535, farb[3 * 8 + 3].alpha());
+
+        let mut hard = green_screen();
+        hard.key_out_color_soft(green(), 40000, 10);
+        assert_eq!(&0, hard[2 * 8 + 2].alpha());
+        assert_eq!(&65535, hard[3 * 8 + 3].alpha());
+    }
+}
@@ -1,23 +1,129 @@
 //! Ruff is a library for parsing and writing Farbfeld files. Farbfeld is a simple image format
 //! defined by [suckless](https://suckless.org). This library follows the
 //! [spec](https://http://tools.suckless.org/farbfeld/) for parsing and writing Farbfeld files.
+//!
+//! The `std` feature is enabled by default. Without it ruff is `no_std`, only requiring `alloc`,
+//! and images can still be created, parsed from a byte slice with `Farbfeld::from_bytes` and
+//! encoded with `Farbfeld::to_bytes`. Everything built on `std::io`, and every other optional
+//! feature apart from `rgb` and `rand`, requires `std`.
 
-#![feature(test)]
-#![feature(fused)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 #[cfg(feature = "serde")]
-#[macro_use] extern crate serde;
-#[macro_use] extern crate nom;
-#[macro_use] extern crate error_chain;
+#[macro_use] extern crate serde as serde_crate;
+extern crate nom;
+#[cfg(feature = "std")]
 extern crate byteorder;
-extern crate test;
+#[cfg(feature = "arbitrary")]
+extern crate arbitrary;
+#[cfg(feature = "proptest")]
+extern crate proptest;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "flate2")]
+extern crate flate2;
+#[cfg(feature = "bzip2")]
+extern crate bzip2;
+#[cfg(any(feature = "sha2", feature = "digest"))]
+extern crate sha2;
+#[cfg(feature = "crc32fast")]
+extern crate crc32fast;
+#[cfg(feature = "image")]
+extern crate image;
+#[cfg(feature = "png")]
+extern crate png;
+#[cfg(feature = "base64")]
+extern crate base64;
+#[cfg(feature = "rgb")]
+extern crate rgb;
+#[cfg(feature = "rand")]
+extern crate rand;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "wasm")]
+extern crate js_sys;
 
 mod parser;
+mod endian;
 mod farbfeld;
+mod adjust;
+mod composite;
+mod key;
+mod threshold;
+mod trim;
+mod median;
+mod pixelate;
+pub mod ansi;
+pub mod channels;
+pub mod color_matrix;
+pub mod convolve;
+#[cfg(feature = "std")]
+pub mod bmp;
+#[cfg(feature = "std")]
+pub mod encoder;
+pub mod diff;
+pub mod draw;
 pub mod error;
+pub mod histogram;
+pub mod lut;
+pub mod mask;
+#[cfg(feature = "std")]
+pub mod filter;
 pub mod pixel;
+#[cfg(feature = "std")]
+pub mod netpbm;
+pub mod palette;
+pub mod patterns;
+pub mod pipeline;
+pub mod region;
+pub mod resize;
+#[cfg(feature = "std")]
+pub mod stream;
+pub mod texture;
+pub mod transform;
+pub mod window;
+#[cfg(feature = "serde")]
+pub mod serde;
+#[cfg(feature = "proptest")]
+pub mod testing;
+#[cfg(feature = "tokio")]
+pub mod async_io;
+#[cfg(any(feature = "flate2", feature = "bzip2"))]
+pub mod compression;
+#[cfg(any(feature = "sha2", feature = "crc32fast", feature = "digest"))]
+pub mod digest;
+#[cfg(feature = "image")]
+mod image_compat;
+#[cfg(feature = "png")]
+pub mod png_io;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "base64")]
+mod data_uri;
+#[cfg(feature = "rgb")]
+mod rgb_compat;
+#[cfg(feature = "rand")]
+mod rand_compat;
+#[cfg(feature = "rayon")]
+mod parallel;
 
 pub use self::pixel::Pixel;
 pub use self::farbfeld::Farbfeld;
+#[cfg(feature = "std")]
+pub use self::farbfeld::{write_header, write_row_pixels};
+#[cfg(feature = "std")]
+pub use self::encoder::{FarbfeldEncoder, EncodeBuffer, FarbfeldBytes, save_rows};
+#[cfg(feature = "std")]
+pub use self::stream::{FarbfeldStream, read_all_frames};
+#[cfg(feature = "std")]
+pub use self::filter::{filter_io, filter_stdio, filter_rows_io, filter_rows_stdio};
+#[cfg(feature = "arbitrary")]
+pub use self::farbfeld::ARBITRARY_MAX_DIM;
 
 
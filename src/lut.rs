@@ -0,0 +1,182 @@
+//! Remapping the channels of Farbfeld images with lookup tables, such as for gamma, curves and
+//! levels.
+
+use alloc::vec::Vec;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::{Pixel, Channel};
+use crate::error::*;
+
+/// A table for each channel mapping every possible value to its replacement, so applying it costs
+/// a single lookup per channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelLut {
+    tables: [Vec<u16>; 4]
+}
+
+/// Returns a table mapping every possible value with `map`.
+fn table<F: Fn(u16) -> u16>(map: F) -> Vec<u16> {
+    (0..=u16::MAX).map(map).collect()
+}
+
+impl ChannelLut {
+    /// Creates a lookup table which leaves every channel unchanged.
+    pub fn identity() -> ChannelLut {
+        ChannelLut {
+            tables: [table(|value| value), table(|value| value), table(|value| value), table(|value| value)]
+        }
+    }
+
+    /// Creates a lookup table which maps the given channel with `map`, which is called once for
+    /// every possible value, and leaves the other channels unchanged.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::lut::ChannelLut;
+    /// # use ::ruff::pixel::Channel;
+    /// let lut = ChannelLut::from_fn(Channel::Green, |value| value / 2);
+    ///
+    /// assert_eq!(Pixel::new(10_u16, 5, 10, 10), lut.apply(&Pixel::new(10_u16, 10, 10, 10)));
+    /// ```
+    pub fn from_fn<F: Fn(u16) -> u16>(channel: Channel, map: F) -> ChannelLut {
+        let mut lut = ChannelLut::identity();
+        lut.tables[channel as usize] = table(map);
+        lut
+    }
+
+    /// Creates a lookup table which maps the red, green and blue channels with `map`, which is
+    /// called once for every possible value, and leaves alpha unchanged.
+    pub(crate) fn from_colour_fn<F: Fn(u16) -> u16>(map: F) -> ChannelLut {
+        let colours = table(map);
+        ChannelLut {
+            tables: [colours.clone(), colours.clone(), colours, table(|value| value)]
+        }
+    }
+
+    /// Creates a lookup table which maps the red, green and blue channels through a curve passing
+    /// through the given `(input, output)` control points, in any order, and leaves alpha
+    /// unchanged. Values between two points are linearly interpolated and rounded to the nearest
+    /// value, and values beyond the first or last point take that point's output.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::lut::ChannelLut;
+    /// let lut = ChannelLut::from_points(&[(0, 0), (32768, 49152), (65535, 65535)]).unwrap();
+    ///
+    /// assert_eq!(Pixel::new(24576_u16, 49152, 65535, 7), lut.apply(&Pixel::new(16384_u16, 32768, 65535, 7)));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidAdjustment](../error/enum.Error.html) if there are no points or
+    /// two points have the same input.
+    pub fn from_points(points: &[(u16, u16)]) -> Result<ChannelLut> {
+        let mut points = points.to_vec();
+        points.sort_unstable();
+        if points.is_empty() {
+            return Err(Error::InvalidAdjustment("a curve needs at least one point"));
+        }
+        if points.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(Error::InvalidAdjustment("curve points must have distinct inputs"));
+        }
+        Ok(ChannelLut::from_colour_fn(|value| {
+            let after = points.partition_point(|&(input, _)| input < value);
+            match (points.get(after.wrapping_sub(1)), points.get(after)) {
+                (_, Some(&(input, output))) if input == value => output,
+                (Some(&(x0, y0)), Some(&(x1, y1))) => {
+                    let (x0, y0, x1, y1) = (x0 as i64, y0 as i64, x1 as i64, y1 as i64);
+                    let span = x1 - x0;
+                    // Rounds to the nearest value, with halves rounded up.
+                    (y0 + ((y1 - y0) * (value as i64 - x0) * 2 + span).div_euclid(span * 2)) as u16
+                },
+                (Some(&(_, output)), None) | (None, Some(&(_, output))) => output,
+                (None, None) => unreachable!("there's at least one point")
+            }
+        }))
+    }
+
+    /// Returns the table of the given channel, indexed by value. The slice always contains 65536
+    /// values.
+    pub fn table(&self, channel: Channel) -> &[u16] {
+        &self.tables[channel as usize]
+    }
+
+    /// Maps every channel of the pixel through its table.
+    pub fn apply(&self, pixel: &Pixel) -> Pixel {
+        let [red, green, blue, alpha] = &self.tables;
+        Pixel::new(red[*pixel.red() as usize], green[*pixel.green() as usize], blue[*pixel.blue() as usize],
+                   alpha[*pixel.alpha() as usize])
+    }
+}
+
+impl Default for ChannelLut {
+    fn default() -> ChannelLut {
+        ChannelLut::identity()
+    }
+}
+
+impl Farbfeld {
+    /// Maps every channel of every pixel through the lookup table.
+    pub fn apply_lut_in_place(&mut self, lut: &ChannelLut) {
+        for pixel in self[..].iter_mut() {
+            *pixel = lut.apply(pixel);
+        }
+    }
+
+    /// Returns a copy of the image mapped through the lookup table as described by
+    /// [apply_lut_in_place](#method.apply_lut_in_place).
+    pub fn apply_lut(&self, lut: &ChannelLut) -> Farbfeld {
+        let mut farb = self.clone();
+        farb.apply_lut_in_place(lut);
+        farb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp() -> Farbfeld {
+        Farbfeld::new(256, 256, (0..=u16::MAX).map(|i| Pixel::new(i, u16::MAX - i, i / 2, i)).collect()).unwrap()
+    }
+
+    #[test]
+    fn test_identity() {
+        assert_eq!(ramp(), ramp().apply_lut(&ChannelLut::identity()));
+        assert_eq!(ramp(), ramp().apply_lut(&ChannelLut::from_points(&[(0, 0), (65535, 65535)]).unwrap()));
+    }
+
+    #[test]
+    fn test_invert() {
+        let lut = ChannelLut::from_points(&[(65535, 0), (0, 65535)]).unwrap();
+        assert_eq!(ramp().invert(), ramp().apply_lut(&lut));
+
+        let mut farb = ramp();
+        farb.apply_lut_in_place(&ChannelLut::from_fn(Channel::Alpha, |value| u16::MAX - value));
+        assert_eq!(ramp().invert_channel(Channel::Alpha), farb);
+    }
+
+    #[test]
+    fn test_from_points() {
+        let lut = ChannelLut::from_points(&[(1000, 2000), (3000, 1000)]).unwrap();
+        let table = lut.table(Channel::Red);
+        assert_eq!(2000, table[0]);
+        assert_eq!(2000, table[1000]);
+        assert_eq!(1500, table[2000]);
+        assert_eq!(2000, table[1001]);
+        assert_eq!(1999, table[1002]);
+        assert_eq!(1000, table[3000]);
+        assert_eq!(1000, table[65535]);
+        assert_eq!(table, lut.table(Channel::Blue));
+        assert_eq!(ChannelLut::identity().table(Channel::Alpha), lut.table(Channel::Alpha));
+
+        let constant = ChannelLut::from_points(&[(5, 7)]).unwrap();
+        assert!(constant.table(Channel::Green).iter().all(|&value| value == 7));
+
+        for points in &[&[][..], &[(1, 2), (1, 3)][..]] {
+            match ChannelLut::from_points(points) {
+                Err(Error::InvalidAdjustment(_)) => {},
+                res => panic!("Unexpected result {:?}", res.map(|_| ()))
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Applying separate greyscale masks to the alpha channel of Farbfeld images.
+
+use crate::farbfeld::Farbfeld;
+use crate::error::*;
+
+/// How a mask is combined with the existing alpha channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskMode {
+    /// The mask replaces the alpha channel.
+    Replace,
+    /// The alpha channel is multiplied by the mask, so white leaves it unchanged and black makes
+    /// the pixel fully transparent.
+    Multiply
+}
+
+impl Farbfeld {
+    /// Sets the alpha channel of every pixel from the
+    /// [luminance](pixel/struct.Pixel.html#method.luminance) of the pixel at the same position in
+    /// `mask`, combined with the existing alpha as described by `mode`. The mask's own alpha is
+    /// ignored.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::mask::MaskMode;
+    /// let mut farb = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2, 3, 65535)]).unwrap();
+    /// let mask = Farbfeld::new(1, 1, vec![Pixel::new(32768_u16, 32768, 32768, 0)]).unwrap();
+    /// farb.apply_alpha_mask(&mask, MaskMode::Multiply).unwrap();
+    ///
+    /// assert_eq!(&Pixel::new(1_u16, 2, 3, 32768), &farb[0]);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::DimensionMismatch](error/enum.Error.html) containing both sizes if
+    /// the mask isn't the same size as the image.
+    pub fn apply_alpha_mask(&mut self, mask: &Farbfeld, mode: MaskMode) -> Result<()> {
+        for (pixel, mask) in self.zip_pixels_mut(mask)? {
+            let mask = mask.luminance();
+            let alpha = pixel.alpha_mut();
+            *alpha = match mode {
+                MaskMode::Replace => mask,
+                MaskMode::Multiply => ((*alpha as u32 * mask as u32 + 32767) / 65535) as u16
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    use crate::pixel::Pixel;
+
+    fn image() -> Farbfeld {
+        Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2, 3, 65535), Pixel::new(4_u16, 5, 6, 1000)]).unwrap()
+    }
+
+    fn mask(value: u16) -> Farbfeld {
+        Farbfeld::new(2, 1, vec![Pixel::new(value, value, value, 65535); 2]).unwrap()
+    }
+
+    #[test]
+    fn test_multiply_white_is_identity() {
+        let mut farb = image();
+        farb.apply_alpha_mask(&mask(65535), MaskMode::Multiply).unwrap();
+        assert_eq!(image(), farb);
+    }
+
+    #[test]
+    fn test_black_is_transparent() {
+        for &mode in &[MaskMode::Multiply, MaskMode::Replace] {
+            let mut farb = image();
+            farb.apply_alpha_mask(&mask(0), mode).unwrap();
+            assert_eq!(&[Pixel::new(1_u16, 2, 3, 0), Pixel::new(4_u16, 5, 6, 0)], farb.pixels());
+        }
+    }
+
+    #[test]
+    fn test_replace_ignores_alpha() {
+        let mut farb = image();
+        farb.apply_alpha_mask(&mask(20000), MaskMode::Replace).unwrap();
+        assert_eq!(&[Pixel::new(1_u16, 2, 3, 20000), Pixel::new(4_u16, 5, 6, 20000)], farb.pixels());
+    }
+
+    #[test]
+    fn test_dimension_mismatch() {
+        let mask = Farbfeld::new(1, 2, vec![Pixel::default(); 2]).unwrap();
+        match image().apply_alpha_mask(&mask, MaskMode::Replace) {
+            Err(Error::DimensionMismatch(2, 1, 1, 2)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Median filtering Farbfeld images, such as for removing salt and pepper noise from scans.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+
+/// The largest radius the median filter uses, so the number of pixels in a window fits in a u64.
+const MAX_MEDIAN_RADIUS: u32 = 1 << 23;
+
+/// The largest radius for which sorting each window is faster than keeping a histogram.
+const MAX_SORTING_RADIUS: u32 = 2;
+
+/// The number of times each value of a channel occurs in a window, with a coarse histogram of the
+/// top 8 bits of the values so the median can be found without visiting every value.
+struct Histogram {
+    coarse: Vec<u64>,
+    fine: Vec<u64>
+}
+
+impl Histogram {
+    fn new() -> Histogram {
+        Histogram { coarse: vec![0; 1 << 8], fine: vec![0; 1 << 16] }
+    }
+
+    fn add(&mut self, value: u16, count: u64) {
+        self.coarse[value as usize >> 8] += count;
+        self.fine[value as usize] += count;
+    }
+
+    fn remove(&mut self, value: u16, count: u64) {
+        self.coarse[value as usize >> 8] -= count;
+        self.fine[value as usize] -= count;
+    }
+
+    /// Returns the value at the given position in the sorted window, which must be smaller than
+    /// the number of values in the window.
+    fn nth(&self, mut rank: u64) -> u16 {
+        let mut start = 0;
+        for &count in &self.coarse {
+            if rank < count {
+                break;
+            }
+            rank -= count;
+            start += 1 << 8;
+        }
+        for (value, &count) in self.fine[start..start + (1 << 8)].iter().enumerate() {
+            if rank < count {
+                return (start + value) as u16;
+            }
+            rank -= count;
+        }
+        unreachable!("the rank is within the window")
+    }
+}
+
+/// Visits every index the span extending `radius` around `centre` covers once moved to the
+/// nearest of the `len` indices, together with the number of positions in the span moved to it.
+fn clamped_span<F: FnMut(usize, u64)>(centre: usize, radius: u64, len: usize, mut visit: F) {
+    let (centre, radius, last) = (centre as u64, radius, len as u64 - 1);
+    let (start, end) = (centre.saturating_sub(radius), (centre + radius).min(last));
+    for index in start..=end {
+        let mut count = 1;
+        if index == 0 {
+            count += radius.saturating_sub(centre);
+        }
+        if index == last {
+            count += (centre + radius).saturating_sub(last);
+        }
+        visit(index as usize, count);
+    }
+}
+
+impl Farbfeld {
+    /// Returns a copy of the image where the red, green and blue channels of each pixel are the
+    /// median of that channel over the square of pixels extending `radius` pixels around it,
+    /// clamping pixels outside the image to its edges. Alpha is left unchanged. A radius of 0
+    /// leaves the image unchanged, and radii above 2^23 are treated as 2^23.
+    ///
+    /// Unlike a blur, this removes isolated specks of noise entirely and keeps edges between
+    /// flat areas sharp. Each window is sorted for radii up to 2, and larger radii keep a
+    /// histogram of each channel which is updated as the window slides along each row, so the
+    /// time taken grows with the radius rather than its square.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(3, 3, vec![Pixel::new(0_u16, 0, 0, 65535); 9]).unwrap();
+    /// farb[4] = Pixel::new(65535_u16, 65535, 65535, 65535);
+    ///
+    /// assert_eq!(&[Pixel::new(0_u16, 0, 0, 65535); 9], farb.median_filter(1).pixels());
+    /// ```
+    pub fn median_filter(&self, radius: u32) -> Farbfeld {
+        if radius == 0 || self.pixels().is_empty() {
+            return self.clone();
+        }
+        let radius = radius.min(MAX_MEDIAN_RADIUS);
+        if radius <= MAX_SORTING_RADIUS {
+            self.median_sorting(radius)
+        } else {
+            self.median_histogram(radius)
+        }
+    }
+
+    /// Median filters the non-empty image by sorting every window.
+    fn median_sorting(&self, radius: u32) -> Farbfeld {
+        let radius = radius as i64;
+        let area = ((2 * radius + 1) * (2 * radius + 1)) as usize;
+        let mut channels = [Vec::with_capacity(area), Vec::with_capacity(area), Vec::with_capacity(area)];
+        let mut pixels = self.pixels().to_vec();
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let (x, y) = ((i % *self.width() as usize) as i64, (i / *self.width() as usize) as i64);
+            for channel in channels.iter_mut() {
+                channel.clear();
+            }
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let neighbour = self.get_pixel_clamped(x + dx, y + dy);
+                    for (channel, &value) in channels.iter_mut().zip(neighbour.iter()) {
+                        channel.push(value);
+                    }
+                }
+            }
+            let [red, green, blue] = channels.each_mut().map(|channel| *channel.select_nth_unstable(area / 2).1);
+            *pixel = Pixel::new(red, green, blue, *pixel.alpha());
+        }
+        Farbfeld::new(*self.width(), *self.height(), pixels).expect("filtering keeps the dimensions")
+    }
+
+    /// Median filters the non-empty image with a histogram of each channel in the window, which
+    /// slides along each row.
+    fn median_histogram(&self, radius: u32) -> Farbfeld {
+        let (width, height) = (*self.width() as usize, *self.height() as usize);
+        let radius = radius as u64;
+        let rank = (2 * radius + 1) * (2 * radius + 1) / 2;
+        let mut histograms = [Histogram::new(), Histogram::new(), Histogram::new()];
+        let mut rows = Vec::new();
+        let mut pixels = self.pixels().to_vec();
+        for y in 0..height {
+            rows.clear();
+            clamped_span(y, radius, height, |row, count| rows.push((row * width, count)));
+            let update_column = |histograms: &mut [Histogram; 3], column: usize, count: u64, add: bool| {
+                for &(row, row_count) in &rows {
+                    for (histogram, &value) in histograms.iter_mut().zip(self[row + column].iter()) {
+                        if add {
+                            histogram.add(value, count * row_count);
+                        } else {
+                            histogram.remove(value, count * row_count);
+                        }
+                    }
+                }
+            };
+
+            clamped_span(0, radius, width, |column, count| update_column(&mut histograms, column, count, true));
+            for x in 0..width {
+                if x > 0 {
+                    let removed = ((x - 1) as u64).saturating_sub(radius) as usize;
+                    let added = (x as u64 + radius).min(width as u64 - 1) as usize;
+                    if removed != added {
+                        update_column(&mut histograms, removed, 1, false);
+                        update_column(&mut histograms, added, 1, true);
+                    }
+                }
+                let pixel = &mut pixels[y * width + x];
+                let [red, green, blue] = histograms.each_ref().map(|histogram| histogram.nth(rank));
+                *pixel = Pixel::new(red, green, blue, *pixel.alpha());
+            }
+            clamped_span(width - 1, radius, width, |column, count| update_column(&mut histograms, column, count, false));
+        }
+        Farbfeld::new(*self.width(), *self.height(), pixels).expect("filtering keeps the dimensions")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A noisy image with channels from a xorshift generator.
+    fn noise(width: u32, height: u32) -> Farbfeld {
+        let mut state = 0x2545_f491_u32;
+        let pixels = (0..width * height).map(|_| {
+            let mut channels = [0; 4];
+            for channel in channels.iter_mut() {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                *channel = (state >> 16) as u16;
+            }
+            Pixel::from(channels)
+        }).collect();
+        Farbfeld::new(width, height, pixels).unwrap()
+    }
+
+    #[test]
+    fn test_identity() {
+        assert_eq!(noise(7, 5), noise(7, 5).median_filter(0));
+        let empty = Farbfeld::new(0, 3, Vec::new()).unwrap();
+        assert_eq!(empty, empty.median_filter(4));
+    }
+
+    #[test]
+    fn test_impulse() {
+        let black = Pixel::new(0_u16, 0, 0, 65535);
+        let mut farb = Farbfeld::new(9, 9, vec![black; 81]).unwrap();
+        farb[4 * 9 + 4] = Pixel::new(65535_u16, 65535, 65535, 100);
+        let mut expected = Farbfeld::new(9, 9, vec![black; 81]).unwrap();
+        expected[4 * 9 + 4] = Pixel::new(0_u16, 0, 0, 100);
+        assert_eq!(expected, farb.median_filter(1));
+        assert_eq!(expected, farb.median_filter(3));
+    }
+
+    #[test]
+    fn test_step_edge() {
+        let (dark, light) = (Pixel::new(1000_u16, 2000, 3000, 65535), Pixel::new(60000_u16, 50000, 40000, 65535));
+        let pixels = (0..12 * 8).map(|i| if i % 12 < 5 { dark } else { light }).collect();
+        let farb = Farbfeld::new(12, 8, pixels).unwrap();
+        for radius in 1..6 {
+            assert_eq!(farb, farb.median_filter(radius), "radius {}", radius);
+        }
+    }
+
+    #[test]
+    fn test_histogram_matches_sorting() {
+        for &(width, height) in &[(13, 9), (1, 6), (5, 1), (2, 2)] {
+            let farb = noise(width, height);
+            for radius in 1..8 {
+                assert_eq!(farb.median_sorting(radius), farb.median_histogram(radius),
+                           "{}x{} radius {}", width, height, radius);
+            }
+        }
+        let farb = noise(4, 3);
+        assert_eq!(farb.median_histogram(MAX_MEDIAN_RADIUS), farb.median_filter(u32::MAX));
+    }
+}
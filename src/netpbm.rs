@@ -0,0 +1,310 @@
+//! Converting between Farbfeld and the binary netpbm formats, PAM (`P7`) and PPM (`P6`).
+//!
+//! Images are written with a maxval of 65535, so no precision is lost. Images with any maxval up
+//! to 65535 can be read, and their samples are scaled to 16 bits, so a maxval of 255 is scaled by
+//! 257. Comments and any whitespace are accepted within headers, as described by the netpbm
+//! [spec](http://netpbm.sourceforge.net/doc/).
+
+use std::io::{Read, Write};
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::{Pixel, composite_channel};
+use crate::error::*;
+
+/// How the alpha channel is handled when writing a PPM image, which has no alpha channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpmAlpha {
+    /// Drop the alpha channel, keeping the red, green and blue channels unchanged.
+    Drop,
+    /// Composite each pixel onto the red, green and blue channels of the given background.
+    Composite(Pixel)
+}
+
+impl Farbfeld {
+    /// Writes the image to the given write as a 16 bit `RGB_ALPHA` PAM image.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+    ///     std IoError during write.</li>
+    /// </ul>
+    pub fn to_pam<W: Write>(&self, mut write: W) -> Result<()> {
+        let header = format!("P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 65535\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+                             self.width(), self.height());
+        let mut buff = header.into_bytes();
+        crate::farbfeld::extend_row_bytes(&mut buff, self.pixels());
+        write.write_all(&buff).map_err(Error::IoError)
+    }
+
+    /// Writes the image to the given write as a 16 bit PPM image, handling the alpha channel as
+    /// given.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the write produces an
+    ///     std IoError during write.</li>
+    /// </ul>
+    pub fn to_ppm<W: Write>(&self, mut write: W, alpha: PpmAlpha) -> Result<()> {
+        let mut buff = format!("P6\n{} {}\n65535\n", self.width(), self.height()).into_bytes();
+        buff.reserve(self.pixels().len() * 6);
+        for pixel in self.pixels() {
+            let channels = [*pixel.red(), *pixel.green(), *pixel.blue()];
+            let background = match alpha {
+                PpmAlpha::Drop => None,
+                PpmAlpha::Composite(background) => Some([*background.red(), *background.green(), *background.blue()])
+            };
+            for i in 0..3 {
+                let channel = match background {
+                    None => channels[i],
+                    Some(background) => composite_channel(channels[i], background[i], *pixel.alpha())
+                };
+                buff.extend_from_slice(&channel.to_be_bytes());
+            }
+        }
+        write.write_all(&buff).map_err(Error::IoError)
+    }
+
+    /// Parses the entire of the given read as a binary PAM image. Images with a depth of 1
+    /// (grayscale), 2 (grayscale with alpha), 3 (RGB) or 4 (RGB with alpha) are supported.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the read produces an
+    ///     std IoError.</li>
+    ///     <li> Returns an <a href="error/enum.Error.html">InvalidNetpbmHeader</a> if the header
+    ///     is malformed or describes an unsupported image.</li>
+    ///     <li> Returns an <a href="error/enum.Error.html">UnexpectedEof</a> if the data ends
+    ///     before every pixel is read.</li>
+    /// </ul>
+    pub fn from_pam<R: Read>(read: R) -> Result<Farbfeld> {
+        let data = read_data(read)?;
+        let mut header = Header { data: &data, pos: 0 };
+        header.magic(b"P7")?;
+        let (mut width, mut height, mut depth, mut maxval) = (None, None, None, None);
+        loop {
+            let line = header.line()?;
+            let mut fields = line.split(|b: &u8| b.is_ascii_whitespace()).filter(|field| !field.is_empty());
+            let (key, value) = (fields.next(), fields.next());
+            match key {
+                None => continue,
+                Some(b"ENDHDR") => break,
+                Some(b"TUPLTYPE") => continue,
+                Some(b"WIDTH") => width = value.and_then(parse_number),
+                Some(b"HEIGHT") => height = value.and_then(parse_number),
+                Some(b"DEPTH") => depth = value.and_then(parse_number),
+                Some(b"MAXVAL") => maxval = value.and_then(parse_number),
+                Some(_) => return Err(Error::InvalidNetpbmHeader("Unknown PAM header field"))
+            }
+        }
+        match (width, height, depth, maxval) {
+            (Some(width), Some(height), Some(depth @ 1..=4), Some(maxval)) =>
+                from_raster(&data[header.pos..], width, height, depth as usize, maxval),
+            (_, _, Some(_), _) => Err(Error::InvalidNetpbmHeader("Unsupported PAM depth")),
+            _ => Err(Error::InvalidNetpbmHeader("Missing PAM header field"))
+        }
+    }
+
+    /// Parses the entire of the given read as a binary PPM image. Every pixel is fully opaque.
+    ///
+    /// # Errors
+    /// Returns the errors of [from_pam](#method.from_pam).
+    pub fn from_ppm<R: Read>(read: R) -> Result<Farbfeld> {
+        let data = read_data(read)?;
+        let mut header = Header { data: &data, pos: 0 };
+        header.magic(b"P6")?;
+        let width = header.number()?;
+        let height = header.number()?;
+        let maxval = header.number()?;
+        // A single whitespace character separates the header from the raster.
+        if !data.get(header.pos).map(u8::is_ascii_whitespace).unwrap_or(false) {
+            return Err(Error::InvalidNetpbmHeader("Missing whitespace after PPM header"));
+        }
+        from_raster(&data[header.pos + 1..], width, height, 3, maxval)
+    }
+}
+
+fn read_data<R: Read>(mut read: R) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    read.read_to_end(&mut data).map_err(Error::IoError)?;
+    Ok(data)
+}
+
+fn parse_number(field: &[u8]) -> Option<u32> {
+    ::std::str::from_utf8(field).ok().and_then(|field| field.parse().ok())
+}
+
+/// Reads the raster of an image with the given depth and maxval, scaling each sample to 16 bits.
+fn from_raster(raster: &[u8], width: u32, height: u32, depth: usize, maxval: u32) -> Result<Farbfeld> {
+    if maxval == 0 || maxval > 65535 {
+        return Err(Error::InvalidNetpbmHeader("Maxval must be between 1 and 65535"));
+    }
+    let sample_size = if maxval < 256 { 1 } else { 2 };
+    let len = (width as u64 * height as u64).checked_mul(depth as u64 * sample_size)
+        .ok_or(Error::InvalidNetpbmHeader("Image dimensions are too large"))?;
+    if (raster.len() as u64) < len {
+        return Err(Error::UnexpectedEof { needed: Some(len - raster.len() as u64) });
+    }
+
+    let sample = |bytes: &[u8]| {
+        let value = if sample_size == 2 { u16::from_be_bytes([bytes[0], bytes[1]]) as u32 } else { bytes[0] as u32 };
+        ((value.min(maxval) * 65535 + maxval / 2) / maxval) as u16
+    };
+    let pixels = raster[..len as usize]
+        .chunks(depth * sample_size as usize)
+        .map(|tuple| {
+            let channels: Vec<u16> = tuple.chunks(sample_size as usize).map(sample).collect();
+            match channels.len() {
+                1 => Pixel::new(channels[0], channels[0], channels[0], 65535),
+                2 => Pixel::new(channels[0], channels[0], channels[0], channels[1]),
+                3 => Pixel::new(channels[0], channels[1], channels[2], 65535),
+                _ => Pixel::new(channels[0], channels[1], channels[2], channels[3])
+            }
+        })
+        .collect();
+    Farbfeld::new(width, height, pixels)
+}
+
+/// A position within a netpbm header.
+struct Header<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Header<'a> {
+    /// Consumes the two byte magic number, which must be followed by whitespace.
+    fn magic(&mut self, magic: &[u8]) -> Result<()> {
+        if !self.data.starts_with(magic) {
+            return Err(Error::InvalidMagic);
+        }
+        self.pos = magic.len();
+        Ok(())
+    }
+
+    /// Skips whitespace and comments, which run from a `#` to the end of the line.
+    fn skip_whitespace(&mut self) {
+        while let Some(&byte) = self.data.get(self.pos) {
+            if byte == b'#' {
+                while self.data.get(self.pos).map(|&b| b != b'\n').unwrap_or(false) {
+                    self.pos += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Consumes a decimal number preceded by whitespace and comments.
+    fn number(&mut self) -> Result<u32> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.data.get(self.pos).map(u8::is_ascii_digit).unwrap_or(false) {
+            self.pos += 1;
+        }
+        match parse_number(&self.data[start..self.pos]) {
+            Some(number) => Ok(number),
+            None if self.pos == self.data.len() => Err(Error::UnexpectedEof { needed: None }),
+            None => Err(Error::InvalidNetpbmHeader("Expected a number"))
+        }
+    }
+
+    /// Consumes the next line, returning it without its newline. Comment lines are returned empty.
+    fn line(&mut self) -> Result<&'a [u8]> {
+        let data = self.data;
+        let start = self.pos;
+        match data[start..].iter().position(|&b| b == b'\n') {
+            Some(len) => {
+                self.pos += len + 1;
+                let line = &data[start..start + len];
+                Ok(if line.trim_ascii_start().starts_with(b"#") { &[] } else { line })
+            },
+            None => Err(Error::UnexpectedEof { needed: None })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pam_round_trip() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut pam = Vec::new();
+        farb.to_pam(&mut pam).unwrap();
+        assert_eq!(farb.to_bytes(), Farbfeld::from_pam(&pam[..]).unwrap().to_bytes());
+    }
+
+    #[test]
+    fn test_ppm_round_trip_drops_alpha() {
+        let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2, 3, 4), Pixel::new(5_u16, 6, 7, 65535)]).unwrap();
+        let mut ppm = Vec::new();
+        farb.to_ppm(&mut ppm, PpmAlpha::Drop).unwrap();
+        assert_eq!(&[Pixel::new(1_u16, 2, 3, 65535), Pixel::new(5_u16, 6, 7, 65535)],
+                   Farbfeld::from_ppm(&ppm[..]).unwrap().pixels());
+    }
+
+    #[test]
+    fn test_ppm_composite() {
+        let farb = Farbfeld::new(3, 1, vec![Pixel::new(65535_u16, 0, 0, 0), Pixel::new(65535_u16, 0, 0, 65535),
+                                            Pixel::new(65535_u16, 0, 0, 32768)]).unwrap();
+        let mut ppm = Vec::new();
+        farb.to_ppm(&mut ppm, PpmAlpha::Composite(Pixel::new(0_u16, 0, 65535, 0))).unwrap();
+        assert_eq!(&[Pixel::new(0_u16, 0, 65535, 65535), Pixel::new(65535_u16, 0, 0, 65535),
+                     Pixel::new(32768_u16, 0, 32767, 65535)], Farbfeld::from_ppm(&ppm[..]).unwrap().pixels());
+    }
+
+    #[test]
+    fn test_ppm_maxval_255() {
+        let ppm = b"P6\n# a comment\n2 # width\n\t1\r\n255 \x00\x80\xff\x01\x02\x03";
+        let farb = Farbfeld::from_ppm(&ppm[..]).unwrap();
+        assert_eq!((&2, &1), (farb.width(), farb.height()));
+        assert_eq!(&[Pixel::new(0_u16, 128 * 257, 65535, 65535), Pixel::new(257_u16, 514, 771, 65535)],
+                   farb.pixels());
+    }
+
+    #[test]
+    fn test_pam_gray_maxval_255() {
+        let pam = b"P7\n# a comment\nWIDTH 2\n  HEIGHT\t1\nDEPTH 2\nMAXVAL 255\nTUPLTYPE GRAYSCALE_ALPHA\nENDHDR\n\x01\x02\x03\x04";
+        assert_eq!(&[Pixel::new(257_u16, 257, 257, 514), Pixel::new(771_u16, 771, 771, 1028)],
+                   Farbfeld::from_pam(&pam[..]).unwrap().pixels());
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        match Farbfeld::from_ppm(&b"P7\n1 1\n255\n\x00\x00\x00"[..]) {
+            Err(Error::InvalidMagic) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        match Farbfeld::from_ppm(&b"P6\n1 x\n255\n\x00\x00\x00"[..]) {
+            Err(Error::InvalidNetpbmHeader(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        match Farbfeld::from_pam(&b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 5\nMAXVAL 255\nENDHDR\n"[..]) {
+            Err(Error::InvalidNetpbmHeader(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_short_raster() {
+        match Farbfeld::from_ppm(&b"P6\n2 1\n65535\n\x00\x00\x00"[..]) {
+            Err(Error::UnexpectedEof { needed: Some(9) }) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_overflowing_dimensions() {
+        match Farbfeld::from_ppm(&b"P6\n4294967295 4294967295\n65535\n\x00"[..]) {
+            Err(Error::InvalidNetpbmHeader(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        let pam = b"P7\nWIDTH 4294967295\nHEIGHT 4294967295\nDEPTH 4\nMAXVAL 65535\nENDHDR\n\x00";
+        match Farbfeld::from_pam(&pam[..]) {
+            Err(Error::InvalidNetpbmHeader(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+}
@@ -0,0 +1,476 @@
+//! Reducing Farbfeld images to a limited palette of colours.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+use crate::error::*;
+
+/// A colour's red, green and blue channels.
+type Colour = [u16; 3];
+
+/// Returns the red, green and blue channels of the pixel.
+fn colour(pixel: &Pixel) -> Colour {
+    [*pixel.red(), *pixel.green(), *pixel.blue()]
+}
+
+/// The order pixels are visited in while dithering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanOrder {
+    /// Every row from left to right.
+    Raster,
+    /// Alternating rows from left to right and right to left, which avoids the diagonal artefacts
+    /// of always pushing error in the same direction.
+    Serpentine
+}
+
+/// Returns the squared distance between the colours.
+fn squared_distance(a: [i64; 3], b: [i64; 3]) -> u64 {
+    a.iter().zip(b.iter()).map(|(&a, &b)| (a - b).unsigned_abs().pow(2)).sum()
+}
+
+/// The largest range of a `PaletteIndex` which is searched by checking every entry, as that's
+/// quicker than descending the tree for a few entries.
+const LEAF_SIZE: usize = 8;
+
+/// An index over a palette answering which entry is nearest a colour, as used when quantizing and
+/// dithering, without comparing the colour against every entry.
+///
+/// Entries are compared by the squared distance between their red, green and blue channels, and
+/// alpha is ignored. When several entries are equally near, the one earliest in the palette is
+/// chosen, so the answers are the same as checking every entry in order.
+#[derive(Debug, Clone)]
+pub struct PaletteIndex {
+    palette: Vec<Pixel>,
+    /// A k-d tree of the palette's colours and indices. Each range larger than `LEAF_SIZE` is split
+    /// at its middle node, with the nodes before it no greater in its axis and the nodes after it
+    /// no smaller.
+    nodes: Vec<([i64; 3], usize)>,
+    /// The channel each node splits its range by.
+    axes: Vec<usize>
+}
+
+impl PaletteIndex {
+    /// Creates an index over the palette.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidPalette](../error/enum.Error.html) if the palette is empty.
+    pub fn new(palette: &[Pixel]) -> Result<PaletteIndex> {
+        if palette.is_empty() {
+            return Err(Error::InvalidPalette("can't index no colours"));
+        }
+        let mut nodes: Vec<([i64; 3], usize)> = palette.iter().enumerate()
+            .map(|(i, entry)| (colour(entry).map(i64::from), i))
+            .collect();
+        let mut axes = vec![0; nodes.len()];
+        PaletteIndex::build(&mut nodes, &mut axes);
+        Ok(PaletteIndex { palette: palette.to_vec(), nodes, axes })
+    }
+
+    /// Arranges the nodes into a k-d tree, splitting each range by its widest channel until it's
+    /// small enough to search every entry.
+    fn build(nodes: &mut [([i64; 3], usize)], axes: &mut [usize]) {
+        if nodes.len() <= LEAF_SIZE {
+            return;
+        }
+        let axis = (0..3).max_by_key(|&axis| {
+            let values = nodes.iter().map(|(colour, _)| colour[axis]);
+            values.clone().max().unwrap_or(0) - values.min().unwrap_or(0)
+        }).unwrap_or(0);
+        let middle = nodes.len() / 2;
+        nodes.select_nth_unstable_by_key(middle, |(colour, _)| colour[axis]);
+        axes[middle] = axis;
+        let (before, after) = nodes.split_at_mut(middle);
+        let (before_axes, after_axes) = axes.split_at_mut(middle);
+        PaletteIndex::build(before, before_axes);
+        PaletteIndex::build(&mut after[1..], &mut after_axes[1..]);
+    }
+
+    /// Returns the palette being indexed.
+    pub fn palette(&self) -> &[Pixel] {
+        &self.palette
+    }
+
+    /// Returns the index and value of the palette entry nearest the pixel's colour.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::palette::PaletteIndex;
+    /// let palette = [Pixel::new(0_u16, 0, 0, 65535), Pixel::new(65535_u16, 0, 0, 65535), Pixel::new(0_u16, 0, 0, 0)];
+    /// let index = PaletteIndex::new(&palette).unwrap();
+    ///
+    /// assert_eq!((1, palette[1]), index.nearest(Pixel::new(40000_u16, 0, 0, 0)));
+    /// assert_eq!((0, palette[0]), index.nearest(Pixel::new(0_u16, 0, 0, 0)));
+    /// ```
+    pub fn nearest(&self, pixel: Pixel) -> (usize, Pixel) {
+        let index = self.nearest_colour(colour(&pixel).map(i64::from));
+        (index, self.palette[index])
+    }
+
+    /// Returns the index of the palette entry nearest the colour, which may be outside the range
+    /// of a channel.
+    fn nearest_colour(&self, target: [i64; 3]) -> usize {
+        let mut best = (u64::MAX, usize::MAX);
+        self.search(0..self.nodes.len(), target, [0; 3], 0, &mut best);
+        best.1
+    }
+
+    /// Searches the range of the tree for a nearer entry than `best`, the distance and index of
+    /// the nearest entry so far. Every entry in the range is at least `bound` away, the sum of
+    /// `offsets`, the squared distances to the range along each channel.
+    fn search(&self, range: Range<usize>, target: [i64; 3], offsets: [u64; 3], bound: u64, best: &mut (u64, usize)) {
+        if range.len() <= LEAF_SIZE {
+            for &(colour, index) in &self.nodes[range] {
+                let candidate = (squared_distance(colour, target), index);
+                if candidate < *best {
+                    *best = candidate;
+                }
+            }
+            return;
+        }
+        let middle = range.start + range.len() / 2;
+        let (colour, index) = self.nodes[middle];
+        let candidate = (squared_distance(colour, target), index);
+        if candidate < *best {
+            *best = candidate;
+        }
+        let axis = self.axes[middle];
+        let offset = target[axis] - colour[axis];
+        let (near, far) = if offset < 0 {
+            (range.start..middle, middle + 1..range.end)
+        } else {
+            (middle + 1..range.end, range.start..middle)
+        };
+        self.search(near, target, offsets, bound, best);
+        let mut far_offsets = offsets;
+        far_offsets[axis] = offset.unsigned_abs().pow(2);
+        let far_bound = bound - offsets[axis] + far_offsets[axis];
+        // An entry on the far side as near as the best could still win the tie with an earlier
+        // index.
+        if far_bound <= best.0 {
+            self.search(far, target, far_offsets, far_bound, best);
+        }
+    }
+}
+
+/// Returns the channel with the widest range of values among the colours, and that range.
+fn widest_channel(colours: &[(Colour, u64)]) -> (usize, u16) {
+    (0..3).map(|channel| {
+        let values = colours.iter().map(|(colour, _)| colour[channel]);
+        (channel, values.clone().max().unwrap_or(0) - values.min().unwrap_or(0))
+    }).max_by_key(|&(_, range)| range).unwrap_or((0, 0))
+}
+
+/// Returns the mean of the colours weighted by their counts, rounded to the nearest value.
+fn mean(colours: &[(Colour, u64)]) -> Colour {
+    let total: u64 = colours.iter().map(|(_, count)| count).sum();
+    let mut mean = [0; 3];
+    for (channel, mean) in mean.iter_mut().enumerate() {
+        let sum: u128 = colours.iter().map(|(colour, count)| colour[channel] as u128 * *count as u128).sum();
+        *mean = ((sum + total as u128 / 2) / total as u128) as u16;
+    }
+    mean
+}
+
+impl Farbfeld {
+    /// Reduces the red, green and blue channels of the image to a palette of at most `max_colors`
+    /// colours with median cut quantization, returning the palette and the image with every pixel
+    /// replaced by its nearest palette entry. Each pixel keeps its own alpha, and every palette
+    /// entry is opaque.
+    ///
+    /// Starting with a box holding every colour in the image, the box with the widest range of
+    /// any one channel is repeatedly split at the median of that channel, weighted by the number
+    /// of pixels of each colour, until there are `max_colors` boxes. Each palette entry is the
+    /// weighted mean of a box. An image with no more than `max_colors` colours is returned
+    /// unchanged, with a palette of its colours in increasing order.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(3, 1, vec![
+    ///     Pixel::new(0_u16, 0, 0, 65535), Pixel::new(100_u16, 100, 100, 0), Pixel::new(65535_u16, 0, 0, 65535)
+    /// ]).unwrap();
+    /// let (palette, quantized) = farb.quantize(2).unwrap();
+    ///
+    /// assert_eq!(vec![Pixel::new(50_u16, 50, 50, 65535), Pixel::new(65535_u16, 0, 0, 65535)], palette);
+    /// assert_eq!(&Pixel::new(50_u16, 50, 50, 0), &quantized[1]);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidPalette](error/enum.Error.html) if `max_colors` is 0.
+    pub fn quantize(&self, max_colors: usize) -> Result<(Vec<Pixel>, Farbfeld)> {
+        if max_colors == 0 {
+            return Err(Error::InvalidPalette("can't quantize to no colours"));
+        }
+        let mut colours: Vec<(Colour, u64)> = Vec::new();
+        let mut sorted: Vec<Colour> = self.pixels().iter().map(colour).collect();
+        sorted.sort_unstable();
+        for colour in sorted {
+            match colours.last_mut() {
+                Some((last, count)) if *last == colour => *count += 1,
+                _ => colours.push((colour, 1))
+            }
+        }
+        let opaque = |[red, green, blue]: Colour| Pixel::new(red, green, blue, u16::MAX);
+        if colours.len() <= max_colors {
+            return Ok((colours.into_iter().map(|(colour, _)| opaque(colour)).collect(), self.clone()));
+        }
+
+        let mut boxes: Vec<Range<usize>> = Vec::with_capacity(max_colors);
+        boxes.push(0..colours.len());
+        while boxes.len() < max_colors {
+            let widest = boxes.iter().enumerate()
+                .filter(|(_, range)| range.len() > 1)
+                .map(|(i, range)| (i, widest_channel(&colours[range.clone()])))
+                .max_by_key(|&(_, (_, width))| width);
+            let (i, channel) = match widest {
+                Some((i, (channel, _))) => (i, channel),
+                None => break
+            };
+            let range = boxes.swap_remove(i);
+            let colours = &mut colours[range.clone()];
+            colours.sort_unstable_by_key(|(colour, _)| colour[channel]);
+            let half = colours.iter().map(|(_, count)| count).sum::<u64>().div_ceil(2);
+            let mut seen = 0;
+            let median = colours.iter().position(|(_, count)| {
+                seen += count;
+                seen >= half
+            }).unwrap_or(0);
+            let split = range.start + (median + 1).clamp(1, colours.len() - 1);
+            boxes.push(range.start..split);
+            boxes.push(split..range.end);
+        }
+
+        let palette: Vec<Pixel> = boxes.iter().map(|range| opaque(mean(&colours[range.clone()]))).collect();
+        // Map each distinct colour once, then look pixels up by colour.
+        colours.sort_unstable();
+        let index = PaletteIndex::new(&palette)?;
+        let mapped: Vec<Pixel> = colours.iter().map(|&(colour, _)| palette[index.nearest_colour(colour.map(i64::from))]).collect();
+        let pixels = self.pixels().iter().map(|pixel| {
+            let index = colours.binary_search_by_key(&colour(pixel), |&(colour, _)| colour)
+                .expect("every colour in the image was counted");
+            let mut quantized = mapped[index];
+            *quantized.alpha_mut() = *pixel.alpha();
+            quantized
+        }).collect();
+        Ok((palette, Farbfeld::new(*self.width(), *self.height(), pixels)?))
+    }
+
+    /// Reduces the red, green and blue channels of the image to the palette with Floyd-Steinberg
+    /// dithering, scanning every row from left to right, as described by
+    /// [dither_to_palette_with](#method.dither_to_palette_with).
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidPalette](error/enum.Error.html) if the palette is empty.
+    pub fn dither_to_palette(&self, palette: &[Pixel]) -> Result<Farbfeld> {
+        self.dither_to_palette_with(palette, ScanOrder::Raster)
+    }
+
+    /// Reduces the red, green and blue channels of the image to the palette with Floyd-Steinberg
+    /// dithering, visiting pixels in the given order. Each pixel is replaced by the palette entry
+    /// nearest its colour plus the error carried to it, and the difference between the two is
+    /// carried on to the unvisited neighbours: 7/16 to the next pixel in the row and 3/16, 5/16
+    /// and 1/16 to the pixels below, behind and ahead. Carried error isn't clamped, so it's never
+    /// lost. Each pixel keeps its own alpha, and the palette's alpha is ignored.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidPalette](error/enum.Error.html) if the palette is empty.
+    pub fn dither_to_palette_with(&self, palette: &[Pixel], order: ScanOrder) -> Result<Farbfeld> {
+        if palette.is_empty() {
+            return Err(Error::InvalidPalette("can't dither to no colours"));
+        }
+        let index = PaletteIndex::new(palette)?;
+        let width = *self.width() as usize;
+        let mut pixels = self.pixels().to_vec();
+        // The error carried to each pixel of this row and the next, with a pixel on either side so
+        // error carried past the edges needs no special case.
+        let (mut current, mut next) = (vec![[0_i64; 3]; width + 2], vec![[0_i64; 3]; width + 2]);
+        for (y, row) in pixels.chunks_mut(width.max(1)).enumerate() {
+            let reversed = order == ScanOrder::Serpentine && y % 2 == 1;
+            for step in 0..width {
+                let x = if reversed { width - 1 - step } else { step };
+                let mut wanted = colour(&row[x]).map(i64::from);
+                for (wanted, error) in wanted.iter_mut().zip(current[x + 1].iter()) {
+                    *wanted += error;
+                }
+                let chosen = palette[index.nearest_colour(wanted)];
+                let (ahead, behind) = if reversed { (x, x + 2) } else { (x + 2, x) };
+                for (channel, (wanted, chosen)) in wanted.iter().zip(colour(&chosen).iter()).enumerate() {
+                    let error = wanted - *chosen as i64;
+                    let (right, below_behind, below) = (error * 7 / 16, error * 3 / 16, error * 5 / 16);
+                    current[ahead][channel] += right;
+                    next[behind][channel] += below_behind;
+                    next[x + 1][channel] += below;
+                    next[ahead][channel] += error - right - below_behind - below;
+                }
+                row[x] = Pixel::new(*chosen.red(), *chosen.green(), *chosen.blue(), *row[x].alpha());
+            }
+            core::mem::swap(&mut current, &mut next);
+            next.iter_mut().for_each(|error| *error = [0; 3]);
+        }
+        Farbfeld::new(*self.width(), *self.height(), pixels)
+    }
+
+    /// Dithers the image to black and white as described by
+    /// [dither_to_palette](#method.dither_to_palette), such as for 1 bit displays.
+    pub fn dither_to_black_white(&self) -> Farbfeld {
+        self.dither_to_palette(&[Pixel::new(0_u16, 0, 0, u16::MAX), Pixel::new(u16::MAX, u16::MAX, u16::MAX, u16::MAX)])
+            .expect("the palette isn't empty")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::patterns::{self, linear_gradient, GradientDirection};
+
+    #[test]
+    fn test_quantize_few_colours() {
+        let (red, blue) = (Pixel::new(65535_u16, 0, 0, 65535), Pixel::new(0_u16, 0, 65535, 1000));
+        let farb = Farbfeld::new(3, 1, vec![red, blue, red]).unwrap();
+        let (palette, quantized) = farb.quantize(16).unwrap();
+        assert_eq!(vec![Pixel::new(0_u16, 0, 65535, 65535), red], palette);
+        assert_eq!(farb, quantized);
+    }
+
+    #[test]
+    fn test_quantize_gradient() {
+        let farb = linear_gradient(256, 3, Pixel::new(0_u16, 0, 0, 65535), Pixel::new(65535_u16, 32768, 0, 65535),
+                                   GradientDirection::Horizontal);
+        let (palette, quantized) = farb.quantize(4).unwrap();
+        assert_eq!(4, palette.len());
+        let mut unique: Vec<Pixel> = quantized.pixels().to_vec();
+        unique.sort_unstable_by_key(|pixel| *pixel.red());
+        unique.dedup();
+        assert_eq!(4, unique.len());
+        assert!(unique.iter().all(|pixel| palette.contains(pixel)));
+        for (i, pixel) in quantized.pixels().iter().enumerate().take(256) {
+            let target = colour(&farb[i]);
+            assert!(palette.iter().all(|entry| distance(colour(entry), target) >= distance(colour(pixel), target)));
+        }
+    }
+
+    #[test]
+    fn test_quantize_preserves_alpha() {
+        let farb = Farbfeld::new(4, 1, (0..4_u16).map(|i| Pixel::new(i * 20000, 0, 0, i)).collect()).unwrap();
+        let (palette, quantized) = farb.quantize(2).unwrap();
+        assert_eq!(2, palette.len());
+        assert!(palette.iter().all(|entry| entry.alpha() == &65535));
+        assert_eq!(vec![0, 1, 2, 3], quantized.pixels().iter().map(|pixel| *pixel.alpha()).collect::<Vec<_>>());
+    }
+
+    fn distance(a: Colour, b: Colour) -> u64 {
+        squared_distance(a.map(i64::from), b.map(i64::from))
+    }
+
+    /// Returns the index of the palette entry nearest the colour by checking every entry.
+    fn brute_force(palette: &[Pixel], target: [i64; 3]) -> usize {
+        (0..palette.len()).min_by_key(|&i| squared_distance(colour(&palette[i]).map(i64::from), target)).unwrap()
+    }
+
+    /// Returns pseudo-random values from a xorshift generator.
+    fn random_values(seed: u64) -> impl Iterator<Item = u64> {
+        let mut state = seed;
+        core::iter::repeat_with(move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        })
+    }
+
+    #[test]
+    fn test_palette_index_matches_brute_force() {
+        let mut values = random_values(0x2545_f491_4f6c_dd1d);
+        let mut pixel = || {
+            let value = values.next().unwrap();
+            Pixel::new(value as u16, (value >> 16) as u16, (value >> 32) as u16, (value >> 48) as u16)
+        };
+        for &size in &[1, 2, 3, 16, 256] {
+            let palette: Vec<Pixel> = (0..size).map(|_| pixel()).collect();
+            let index = PaletteIndex::new(&palette).unwrap();
+            assert_eq!(&palette[..], index.palette());
+            for _ in 0..2000 {
+                let target = pixel();
+                let expected = brute_force(&palette, colour(&target).map(i64::from));
+                assert_eq!((expected, palette[expected]), index.nearest(target));
+            }
+            for target in &[[-70000, 0, 0], [0, 140000, -5], [65535, 65535, 65535]] {
+                assert_eq!(brute_force(&palette, *target), index.nearest_colour(*target));
+            }
+        }
+    }
+
+    #[test]
+    fn test_palette_index_ties() {
+        // Coarse channels make equally near entries common, and every one is duplicated.
+        let mut values = random_values(99);
+        let mut palette: Vec<Pixel> = (0..64).map(|_| {
+            let value = values.next().unwrap();
+            Pixel::new((value % 4) as u16 * 1000, (value / 4 % 4) as u16 * 1000, (value / 16 % 4) as u16 * 1000, 0)
+        }).collect();
+        palette.extend_from_slice(&palette.clone());
+        let index = PaletteIndex::new(&palette).unwrap();
+        for red in (0..4000).step_by(250) {
+            for green in (0..4000).step_by(250) {
+                for blue in (0..4000).step_by(500) {
+                    let target = [red, green, blue];
+                    assert_eq!(brute_force(&palette, target), index.nearest_colour(target), "{:?}", target);
+                }
+            }
+        }
+
+        match PaletteIndex::new(&[]) {
+            Err(Error::InvalidPalette(_)) => {},
+            res => panic!("Unexpected result {:?}", res.map(|_| ()))
+        }
+    }
+
+    fn white_fraction(farb: &Farbfeld) -> f64 {
+        let white = Pixel::new(65535_u16, 65535, 65535, 65535);
+        farb.pixels().iter().filter(|&&pixel| pixel == white).count() as f64 / farb.pixels().len() as f64
+    }
+
+    #[test]
+    fn test_dither_grey() {
+        let grey = patterns::solid(64, 64, Pixel::new(32768_u16, 32768, 32768, 65535));
+        let dithered = grey.dither_to_black_white();
+        assert!(dithered.pixels().iter().all(|pixel| pixel.red() == &0 || pixel.red() == &65535));
+        assert!((white_fraction(&dithered) - 0.5).abs() < 0.02, "white {}", white_fraction(&dithered));
+
+        let quarter = patterns::solid(64, 64, Pixel::new(16384_u16, 16384, 16384, 65535));
+        let palette = [Pixel::new(0_u16, 0, 0, 0), Pixel::new(65535_u16, 65535, 65535, 0)];
+        let serpentine = quarter.dither_to_palette_with(&palette, ScanOrder::Serpentine).unwrap();
+        assert!((white_fraction(&serpentine) - 0.25).abs() < 0.02, "white {}", white_fraction(&serpentine));
+        assert_ne!(serpentine, quarter.dither_to_palette(&palette).unwrap());
+    }
+
+    #[test]
+    fn test_dither_palette_colours() {
+        let (red, green, blue) = (Pixel::new(65535_u16, 0, 0, 65535), Pixel::new(0_u16, 65535, 0, 100),
+                                  Pixel::new(0_u16, 0, 65535, 65535));
+        let farb = patterns::checkerboard(7, 5, 2, red, green);
+        let palette = [blue, Pixel::new(0_u16, 65535, 0, 65535), red];
+        assert_eq!(farb, farb.dither_to_palette(&palette).unwrap());
+        assert_eq!(farb, farb.dither_to_palette_with(&palette, ScanOrder::Serpentine).unwrap());
+
+        match farb.dither_to_palette(&[]) {
+            Err(Error::InvalidPalette(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        let empty = Farbfeld::new(0, 3, Vec::new()).unwrap();
+        assert_eq!(empty, empty.dither_to_black_white());
+    }
+
+    #[test]
+    fn test_quantize_errors() {
+        match Farbfeld::new(0, 0, Vec::new()).unwrap().quantize(0) {
+            Err(Error::InvalidPalette(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        let (palette, _) = Farbfeld::new(0, 0, Vec::new()).unwrap().quantize(3).unwrap();
+        assert!(palette.is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+//! Processing the pixels of Farbfeld images on several threads with the
+//! [rayon](https://crates.io/crates/rayon) crate. Requires the `rayon` feature.
+//!
+//! Work is split by rows, so each thread handles whole rows of neighbouring pixels, and every
+//! pixel is still processed independently, so the results are exactly the same as processing
+//! the pixels in order.
+
+use alloc::vec;
+
+use rayon::prelude::*;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+
+impl Farbfeld {
+    /// Returns a copy of the image with every pixel replaced by the result of `map`, as described
+    /// by [map_pixels](#method.map_pixels), with the rows shared between rayon's threads.
+    /// Requires the `rayon` feature.
+    ///
+    /// `map` is called from several threads at once, so it must be `Sync` and `Send`, and it
+    /// can't keep mutable state between pixels.
+    pub fn par_map_pixels<F>(&self, map: F) -> Farbfeld where F: Fn(&Pixel) -> Pixel + Sync + Send {
+        let width = (*self.width() as usize).max(1);
+        let mut pixels = vec![Pixel::default(); self.pixels().len()];
+        pixels.par_chunks_mut(width).zip(self.pixels().par_chunks(width)).for_each(|(mapped, row)| {
+            for (mapped, pixel) in mapped.iter_mut().zip(row) {
+                *mapped = map(pixel);
+            }
+        });
+        Farbfeld::new(*self.width(), *self.height(), pixels).expect("mapping keeps the dimensions")
+    }
+
+    /// Calls `f` with every pixel of the image so it can change them in place, as described by
+    /// [for_each_pixel_mut](#method.for_each_pixel_mut), with the rows shared between rayon's
+    /// threads. The pixels aren't visited in any particular order. Requires the `rayon` feature.
+    ///
+    /// `f` is called from several threads at once, so it must be `Sync` and `Send`, and it can't
+    /// keep mutable state between pixels.
+    pub fn par_for_each_pixel_mut<F>(&mut self, f: F) where F: Fn(&mut Pixel) + Sync + Send {
+        let width = (*self.width() as usize).max(1);
+        self[..].par_chunks_mut(width).for_each(|row| row.iter_mut().for_each(&f));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::patterns::{linear_gradient, GradientDirection};
+
+    fn image(width: u32, height: u32) -> Farbfeld {
+        let (from, to) = (Pixel::new(0_u16, 1000, 65535, 7), Pixel::new(65535_u16, 20000, 3, 60000));
+        linear_gradient(width, height, from, to, GradientDirection::Diagonal)
+    }
+
+    fn transform(pixel: &Pixel) -> Pixel {
+        let [red, green, blue, alpha] = <[u16; 4]>::from(*pixel);
+        Pixel::new(blue ^ 0x5a5a, red.wrapping_mul(3), green / 7, alpha.rotate_left(5))
+    }
+
+    #[test]
+    fn test_par_map_pixels() {
+        for &(width, height) in &[(1, 1), (257, 131), (1, 500), (500, 1), (0, 5)] {
+            let farb = image(width, height);
+            assert_eq!(farb.map_pixels(transform), farb.par_map_pixels(transform));
+        }
+    }
+
+    #[test]
+    fn test_par_for_each_pixel_mut() {
+        for &(width, height) in &[(1, 1), (257, 131), (1, 500), (500, 1), (0, 5)] {
+            let (mut serial, mut parallel) = (image(width, height), image(width, height));
+            serial.for_each_pixel_mut(|pixel| *pixel = transform(pixel));
+            parallel.par_for_each_pixel_mut(|pixel| *pixel = transform(pixel));
+            assert_eq!(serial, parallel);
+            assert_eq!(image(width, height).map_pixels(transform), parallel);
+        }
+    }
+}
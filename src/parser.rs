@@ -1,53 +1,211 @@
-use nom::{be_u32, be_u16, IResult};
+//! The nom parsers for Farbfeld images. Nom's results are translated into the crate's own
+//! [Error](../error/struct.Error.html) here, so no nom types escape the parser.
+
+use alloc::vec::Vec;
+
+use nom::{IResult, Needed, Err as NomErr};
+use nom::bytes::streaming::tag;
+use nom::error::{Error as NomError, ErrorKind as NomErrorKind};
+use nom::number::streaming::{be_u16, be_u32};
 
 use super::Pixel;
+use super::pixel;
+use super::endian;
 use super::Farbfeld;
 use super::error::*;
 
-named!(pub parse_pixel<Pixel>, do_parse!(
-    red: be_u16 >>
-    green: be_u16 >>
-    blue: be_u16 >>
-    alpha: be_u16 >>
-    (Pixel::new(red, green, blue, alpha))
-));
-
-named!(pub parse_farb<Farbfeld>, do_parse!(
-    tag!("farbfeld") >>
-    width: be_u32 >>
-    height: be_u32 >>
-    pixels: many0!(flat_map!(take!(8), parse_pixel)) >>
-    res: expr_res!(Farbfeld::new(width, height, pixels)) >>
-    (res)
-));
-
-pub fn i_to_res<I, O>(res: IResult<I, O, u32>) -> Result<O> {
+fn pixel(input: &[u8]) -> IResult<&[u8], Pixel> {
+    let (input, red) = be_u16(input)?;
+    let (input, green) = be_u16(input)?;
+    let (input, blue) = be_u16(input)?;
+    let (input, alpha) = be_u16(input)?;
+    Ok((input, Pixel::new(red, green, blue, alpha)))
+}
+
+fn header(input: &[u8]) -> IResult<&[u8], (u32, u32)> {
+    let (input, _) = tag(&b"farbfeld"[..])(input)?;
+    let (input, width) = be_u32(input)?;
+    let (input, height) = be_u32(input)?;
+    Ok((input, (width, height)))
+}
+
+/// Parses a single pixel from the start of the input.
+pub fn parse_pixel(input: &[u8]) -> Result<Pixel> {
+    i_to_res(input, pixel(input))
+}
+
+/// Parses the header at the start of the input into the image's width and height.
+pub fn parse_header(input: &[u8]) -> Result<(u32, u32)> {
+    i_to_res(input, header(input))
+}
+
+/// Parses every pixel in the input, which should contain 8 bytes per pixel.
+#[cfg(feature = "std")]
+pub fn parse_pixels(input: &[u8]) -> Result<Vec<Pixel>> {
+    check_pixels(input)?;
+    let mut pixels = Vec::new();
+    decode_pixels(input, &mut pixels);
+    Ok(pixels)
+}
+
+/// Parses a complete Farbfeld image from the input.
+pub fn parse_farb(input: &[u8]) -> Result<Farbfeld> {
+    let mut pixels = Vec::new();
+    let (width, height) = parse_farb_into(input, &mut pixels)?;
+    Farbfeld::new(width, height, pixels)
+}
+
+/// Parses a complete Farbfeld image from the input into `pixels`, replacing its contents and
+/// reusing its allocation, and returns the image's width and height. `pixels` is left unchanged
+/// if parsing fails.
+pub fn parse_farb_into(input: &[u8], pixels: &mut Vec<Pixel>) -> Result<(u32, u32)> {
+    let (width, height) = parse_header(input)?;
+    let count = check_pixels(&input[16..])?;
+    if width as u64 * height as u64 != count as u64 {
+        return Err(Error::InvalidFarbfeldDimensions);
+    }
+    decode_pixels(&input[16..], pixels);
+    Ok((width, height))
+}
+
+/// Checks the input contains only whole pixels, returning the number of pixels.
+fn check_pixels(input: &[u8]) -> Result<usize> {
+    let partial = &input[input.len() / 8 * 8..];
+    if !partial.is_empty() {
+        // Only a partial pixel can fail, so nom describes the missing bytes.
+        parse_pixel(partial)?;
+    }
+    Ok(input.len() / 8)
+}
+
+/// Decodes the input, which must contain only whole pixels, into `pixels`, replacing its
+/// contents.
+fn decode_pixels(input: &[u8], pixels: &mut Vec<Pixel>) {
+    // Every pixel is overwritten, so only pixels beyond the current length need initialising.
+    pixels.resize(input.len() / 8, Pixel::default());
+    endian::decode_channels(input, pixel::as_channels_mut(pixels));
+}
+
+/// Translates a nom result into a crate result. `input` must be the input given to the parser
+/// which produced `res`, so offsets can be calculated.
+fn i_to_res<O>(input: &[u8], res: IResult<&[u8], O>) -> Result<O> {
     match res {
-        IResult::Incomplete(need) => Err(Error::from(ErrorKind::NotEnoughDataError(need))),
-        IResult::Done(_, farb) => Ok(farb),
-        IResult::Error(err) => Err(Error::from(ErrorKind::NomError(err)))
+        Ok((_, out)) => Ok(out),
+        Err(NomErr::Incomplete(Needed::Size(size))) =>
+            Err(Error::UnexpectedEof { needed: Some(size.get() as u64) }),
+        Err(NomErr::Incomplete(Needed::Unknown)) => Err(Error::UnexpectedEof { needed: None }),
+        Err(NomErr::Error(ref err)) | Err(NomErr::Failure(ref err))
+            if err.code == NomErrorKind::Tag && err.input.len() == input.len() =>
+            Err(Error::InvalidMagic),
+        Err(NomErr::Error(err)) | Err(NomErr::Failure(err)) =>
+            Err(Error::ParseFailure { offset: error_offset(input, &err) })
     }
 }
 
+/// Finds the offset into `input` of the position recorded by a nom error.
+fn error_offset(input: &[u8], err: &NomError<&[u8]>) -> u64 {
+    (input.len() - err.input.len()) as u64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn kind<T>(res: Result<T>) -> Error {
+        match res {
+            Err(err) => err,
+            Ok(_) => panic!("Expected an error")
+        }
+    }
+
     #[test]
     fn test_parse_pixel() {
         let data = [0_u8, 1_u8, 0_u8, 2_u8, 0_u8, 3_u8, 0_u8, 4_u8];
-        assert_eq!(Pixel::new(1_u16, 2_u16, 3_u16, 4_u16), i_to_res(parse_pixel(&data)).unwrap());
+        assert_eq!(Pixel::new(1_u16, 2_u16, 3_u16, 4_u16), parse_pixel(&data).unwrap());
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let mut data = b"farbfeld".to_vec();
+        data.extend([0,0,0,2, 0,0,1,0].iter());
+        assert_eq!((2, 256), parse_header(&data).unwrap());
     }
 
     #[test]
     fn test_parse_farb() {
         let mut data = "farbfeld".as_bytes().to_vec();
         data.extend([0,0,0,1, 0,0,0,1,    0,1, 0,1, 0,1, 0,1].iter());
-        let correct = Farbfeld::new(1_u32, 1_u32, Vec::from([Pixel::new(1_u16, 1_u16, 1_u16, 1_u16)].to_vec())).unwrap();
-        let test = i_to_res(parse_farb(&data)).unwrap();
+        let correct = Farbfeld::new(1_u32, 1_u32, [Pixel::new(1_u16, 1_u16, 1_u16, 1_u16)].to_vec()).unwrap();
+        let test = parse_farb(&data).unwrap();
 
         assert_eq!(correct.width(), test.width());
         assert_eq!(correct.height(), test.height());
         assert_eq!(correct.pixels(), test.pixels());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_invalid_magic() {
+        match kind(parse_farb(b"farbfelt\0\0\0\0\0\0\0\0")) {
+            Error::InvalidMagic => {},
+            kind => panic!("Unexpected error {:?}", kind)
+        }
+        match kind(parse_header(b"PNG")) {
+            Error::InvalidMagic => {},
+            kind => panic!("Unexpected error {:?}", kind)
+        }
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        match kind(parse_farb(b"farb")) {
+            Error::UnexpectedEof { needed: Some(4) } => {},
+            kind => panic!("Unexpected error {:?}", kind)
+        }
+        match kind(parse_farb(b"farbfeld\0\0\0\x01\0\0")) {
+            Error::UnexpectedEof { needed: Some(2) } => {},
+            kind => panic!("Unexpected error {:?}", kind)
+        }
+
+        let mut data = b"farbfeld\0\0\0\x01\0\0\0\x02".to_vec();
+        data.extend([1_u8; 11].iter());
+        match kind(parse_farb(&data)) {
+            Error::UnexpectedEof { needed: Some(1) } => {},
+            kind => panic!("Unexpected error {:?}", kind)
+        }
+        match kind(parse_pixel(&[0, 1, 2])) {
+            Error::UnexpectedEof { needed: Some(1) } => {},
+            kind => panic!("Unexpected error {:?}", kind)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_parse_pixels() {
+        let data: Vec<u8> = (0..24).collect();
+        let expected: Vec<Pixel> = data.chunks(8).map(|chunk| parse_pixel(chunk).unwrap()).collect();
+        assert_eq!(expected, parse_pixels(&data).unwrap());
+        assert_eq!(Pixel::new(0x1011_u16, 0x1213, 0x1415, 0x1617), parse_pixels(&data).unwrap()[2]);
+        assert!(parse_pixels(&[]).unwrap().is_empty());
+        match kind(parse_pixels(&data[..21])) {
+            Error::UnexpectedEof { needed: Some(1) } => {},
+            kind => panic!("Unexpected error {:?}", kind)
+        }
+    }
+
+    #[test]
+    fn test_invalid_dimensions() {
+        let mut data = b"farbfeld\0\0\0\x01\0\0\0\x02".to_vec();
+        data.extend([1_u8; 24].iter());
+        match kind(parse_farb(&data)) {
+            Error::InvalidFarbfeldDimensions => {},
+            kind => panic!("Unexpected error {:?}", kind)
+        }
+    }
+
+    #[test]
+    fn test_error_offset() {
+        let input = b"0123456789";
+        assert_eq!(4, error_offset(input, &NomError::new(&input[4..], NomErrorKind::Tag)));
+        assert_eq!(0, error_offset(input, &NomError::new(&input[..], NomErrorKind::Tag)));
+    }
+}
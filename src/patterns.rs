@@ -0,0 +1,322 @@
+//! Generating reference images, such as test patterns for checking viewers and pipelines.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+
+/// 75% of full intensity, used by the upper bars of [smpte_bars](fn.smpte_bars.html).
+const BAR: u16 = 49151;
+
+/// The direction a [linear_gradient](fn.linear_gradient.html) runs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientDirection {
+    /// From the leftmost column to the rightmost.
+    Horizontal,
+    /// From the top row to the bottom.
+    Vertical,
+    /// From the top left corner to the bottom right.
+    Diagonal
+}
+
+/// Returns the colour at `t` along a gradient with the given stops, as described by
+/// [linear_gradient_stops](fn.linear_gradient_stops.html).
+fn sample_stops(stops: &[(f32, Pixel)], t: f64) -> Pixel {
+    match stops.iter().position(|&(position, _)| t < position as f64) {
+        None => stops.last().map_or(Pixel::default(), |&(_, pixel)| pixel),
+        Some(0) => stops[0].1,
+        Some(i) => {
+            let ((start, from), (end, to)) = (stops[i - 1], stops[i]);
+            from.lerp_f64(to, (t - start as f64) / (end as f64 - start as f64))
+        }
+    }
+}
+
+/// Returns how far along a linear gradient in the given direction the pixel is, from 0 to 1.
+fn linear_position(width: u32, height: u32, x: u32, y: u32, direction: GradientDirection) -> f64 {
+    let (along, len) = match direction {
+        GradientDirection::Horizontal => (x as u64, width.saturating_sub(1) as u64),
+        GradientDirection::Vertical => (y as u64, height.saturating_sub(1) as u64),
+        GradientDirection::Diagonal =>
+            (x as u64 + y as u64, width.saturating_sub(1) as u64 + height.saturating_sub(1) as u64)
+    };
+    if len == 0 { 0.0 } else { along as f64 / len as f64 }
+}
+
+/// Returns how far the pixel is from the centre of a radial gradient, as a fraction of its radius.
+#[cfg(feature = "std")]
+fn radial_position(x: u32, y: u32, (cx, cy): (f32, f32), radius: f32) -> f64 {
+    if radius.is_nan() || radius <= 0.0 {
+        return 1.0;
+    }
+    (x as f64 - cx as f64).hypot(y as f64 - cy as f64) / radius as f64
+}
+
+/// Creates an image of the given dimensions by calling `pixel` with the coordinates of each pixel
+/// in row-major order.
+fn generate<F: FnMut(u32, u32) -> Pixel>(width: u32, height: u32, mut pixel: F) -> Farbfeld {
+    let pixels: Vec<Pixel> = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| pixel(x, y))
+        .collect();
+    Farbfeld::new(width, height, pixels).expect("generated images match their dimensions")
+}
+
+/// Returns an image where every pixel is `pixel`.
+pub fn solid(width: u32, height: u32, pixel: Pixel) -> Farbfeld {
+    Farbfeld::new(width, height, vec![pixel; width as usize * height as usize])
+        .expect("generated images match their dimensions")
+}
+
+/// Returns a checkerboard of square cells `cell` pixels wide, starting with a cell of `a` in the
+/// top left corner and alternating with `b`. A cell size of 0 is treated as 1, and cells larger
+/// than the image leave it entirely `a`.
+///
+/// ```
+/// # use ::ruff::*;
+/// # use ::ruff::patterns::checkerboard;
+/// let (a, b) = (Pixel::new(0_u16, 0, 0, 65535), Pixel::new(65535_u16, 65535, 65535, 65535));
+/// let farb = checkerboard(4, 2, 2, a, b);
+///
+/// assert_eq!(&[a, a, b, b, a, a, b, b], farb.pixels());
+/// ```
+pub fn checkerboard(width: u32, height: u32, cell: u32, a: Pixel, b: Pixel) -> Farbfeld {
+    let cell = cell.max(1);
+    generate(width, height, |x, y| if (x / cell + y / cell).is_multiple_of(2) { a } else { b })
+}
+
+/// Returns SMPTE color bars. The top two thirds hold seven bars of 75% white, yellow, cyan,
+/// green, magenta, red and blue. Below them, a twelfth of the height holds blue, black, magenta,
+/// black, cyan, black and 75% white bars. The bottom quarter holds -I, 100% white, +Q and black,
+/// each a bar and a quarter wide, then the PLUGE bars of black, black and 4% white, each a third of
+/// a bar wide, then black. Black is 0, so the PLUGE bar below black is black too.
+pub fn smpte_bars(width: u32, height: u32) -> Farbfeld {
+    let colour = |red, green, blue| Pixel::new(red, green, blue, u16::MAX);
+    let black = colour(0, 0, 0);
+    let top = [
+        colour(BAR, BAR, BAR), colour(BAR, BAR, 0), colour(0, BAR, BAR), colour(0, BAR, 0),
+        colour(BAR, 0, BAR), colour(BAR, 0, 0), colour(0, 0, BAR)
+    ];
+    let middle = [colour(0, 0, BAR), black, colour(BAR, 0, BAR), black, colour(0, BAR, BAR), black, colour(BAR, BAR, BAR)];
+    let plus_four = colour(2621, 2621, 2621);
+    generate(width, height, |x, y| {
+        let row = y as u64 * 12 / height as u64;
+        match row {
+            0..=7 => top[(x as u64 * 7 / width as u64) as usize],
+            8 => middle[(x as u64 * 7 / width as u64) as usize],
+            // Measured in twelfths of a bar.
+            _ => match x as u64 * 84 / width as u64 {
+                0..=14 => colour(0, 8481, 19532),
+                15..=29 => colour(u16::MAX, u16::MAX, u16::MAX),
+                30..=44 => colour(12850, 0, 27242),
+                68..=71 => plus_four,
+                _ => black
+            }
+        }
+    })
+}
+
+/// Returns white with alpha rising evenly from fully transparent in the leftmost column to opaque
+/// in the rightmost, rounded to the nearest value. An image one pixel wide is opaque.
+///
+/// ```
+/// # use ::ruff::*;
+/// # use ::ruff::patterns::alpha_wedge;
+/// let farb = alpha_wedge(3, 1);
+///
+/// assert_eq!(&[0, 32768, 65535], &[*farb[0].alpha(), *farb[1].alpha(), *farb[2].alpha()]);
+/// ```
+pub fn alpha_wedge(width: u32, height: u32) -> Farbfeld {
+    let last = width.saturating_sub(1) as u64;
+    generate(width, height, |x, _| {
+        let alpha = match last {
+            0 => u16::MAX,
+            last => ((x as u64 * u16::MAX as u64 + last / 2) / last) as u16
+        };
+        Pixel::new(u16::MAX, u16::MAX, u16::MAX, alpha)
+    })
+}
+
+/// Returns a gradient running from `start` to `end` in the given direction, interpolating each
+/// pixel with [Pixel::lerp](../struct.Pixel.html#method.lerp). The first and last rows or columns
+/// are exactly `start` and `end`, so a horizontal gradient from black to white 65536 pixels wide
+/// holds every value once. The diagonal gradient is constant along lines parallel to the
+/// bottom-left-to-top-right diagonal.
+///
+/// ```
+/// # use ::ruff::*;
+/// # use ::ruff::patterns::{linear_gradient, GradientDirection};
+/// let (black, white) = (Pixel::new(0_u16, 0, 0, 65535), Pixel::new(65535_u16, 65535, 65535, 65535));
+/// let farb = linear_gradient(3, 1, black, white, GradientDirection::Horizontal);
+///
+/// assert_eq!(&[black, Pixel::new(32768_u16, 32768, 32768, 65535), white], farb.pixels());
+/// ```
+pub fn linear_gradient(width: u32, height: u32, start: Pixel, end: Pixel, direction: GradientDirection) -> Farbfeld {
+    linear_gradient_stops(width, height, &[(0.0, start), (1.0, end)], direction)
+}
+
+/// Returns a gradient in the given direction through a list of stops, each a position from 0 at
+/// the start of the gradient to 1 at its end paired with the colour there, in increasing order of
+/// position. Pixels between two stops are interpolated with
+/// [Pixel::lerp](../struct.Pixel.html#method.lerp), pixels before the first stop or after the
+/// last take its colour, and no stops at all leave the image transparent black.
+///
+/// ```
+/// # use ::ruff::*;
+/// # use ::ruff::patterns::{linear_gradient_stops, GradientDirection};
+/// let (red, green) = (Pixel::new(65535_u16, 0, 0, 65535), Pixel::new(0_u16, 65535, 0, 65535));
+/// let farb = linear_gradient_stops(1, 5, &[(0.25, red), (0.5, green), (0.75, red)], GradientDirection::Vertical);
+///
+/// assert_eq!(&[red, red, green, red, red], farb.pixels());
+/// ```
+pub fn linear_gradient_stops(width: u32, height: u32, stops: &[(f32, Pixel)], direction: GradientDirection) -> Farbfeld {
+    generate(width, height, |x, y| sample_stops(stops, linear_position(width, height, x, y, direction)))
+}
+
+/// Returns a circular gradient from `inner` at `center` to `outer` at `radius` pixels from it,
+/// measured to the top left corner of each pixel and interpolated with
+/// [Pixel::lerp](../struct.Pixel.html#method.lerp). Pixels beyond the radius are `outer`, as is
+/// every pixel if the radius isn't positive. Requires the `std` feature.
+#[cfg(feature = "std")]
+pub fn radial_gradient(width: u32, height: u32, center: (f32, f32), radius: f32, inner: Pixel, outer: Pixel) -> Farbfeld {
+    radial_gradient_stops(width, height, center, radius, &[(0.0, inner), (1.0, outer)])
+}
+
+/// Returns a circular gradient around `center` through a list of stops as described by
+/// [linear_gradient_stops](fn.linear_gradient_stops.html), where 0 is the centre and 1 is
+/// `radius` pixels from it. Requires the `std` feature.
+#[cfg(feature = "std")]
+pub fn radial_gradient_stops(width: u32, height: u32, center: (f32, f32), radius: f32, stops: &[(f32, Pixel)]) -> Farbfeld {
+    generate(width, height, |x, y| sample_stops(stops, radial_position(x, y, center, radius)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(farb: &Farbfeld, x: u32, y: u32) -> Pixel {
+        farb[(y * *farb.width() + x) as usize]
+    }
+
+    #[test]
+    fn test_solid() {
+        let pixel = Pixel::new(1_u16, 2, 3, 4);
+        let farb = solid(3, 2, pixel);
+        assert_eq!((&3, &2), (farb.width(), farb.height()));
+        assert!(farb.pixels().iter().all(|&p| p == pixel));
+        assert!(solid(0, 5, pixel).pixels().is_empty());
+    }
+
+    #[test]
+    fn test_checkerboard() {
+        let (a, b) = (Pixel::new(1_u16, 0, 0, 65535), Pixel::new(0_u16, 1, 0, 65535));
+        let farb = checkerboard(10, 7, 3, a, b);
+        assert_eq!(a, at(&farb, 0, 0));
+        assert_eq!(a, at(&farb, 2, 2));
+        assert_eq!(b, at(&farb, 3, 0));
+        assert_eq!(b, at(&farb, 0, 3));
+        assert_eq!(a, at(&farb, 3, 3));
+        assert_eq!(b, at(&farb, 9, 6));
+
+        assert!(checkerboard(4, 4, 100, a, b).pixels().iter().all(|&p| p == a));
+        assert_eq!(&[a, b, b, a], checkerboard(2, 2, 0, a, b).pixels());
+        assert!(checkerboard(0, 0, 3, a, b).pixels().is_empty());
+    }
+
+    #[test]
+    fn test_smpte_bars() {
+        let farb = smpte_bars(84, 12);
+        let colour = |red: u16, green: u16, blue: u16| Pixel::new(red, green, blue, 65535);
+        assert_eq!(colour(49151, 49151, 49151), at(&farb, 0, 0));
+        assert_eq!(colour(49151, 49151, 0), at(&farb, 12, 7));
+        assert_eq!(colour(0, 0, 49151), at(&farb, 83, 0));
+        assert_eq!(colour(0, 0, 49151), at(&farb, 0, 8));
+        assert_eq!(colour(0, 0, 0), at(&farb, 12, 8));
+        assert_eq!(colour(0, 8481, 19532), at(&farb, 0, 9));
+        assert_eq!(colour(65535, 65535, 65535), at(&farb, 15, 11));
+        assert_eq!(colour(12850, 0, 27242), at(&farb, 44, 10));
+        assert_eq!(colour(0, 0, 0), at(&farb, 45, 9));
+        assert_eq!(colour(2621, 2621, 2621), at(&farb, 68, 9));
+        assert_eq!(colour(0, 0, 0), at(&farb, 83, 11));
+
+        assert!(smpte_bars(0, 10).pixels().is_empty());
+        assert_eq!(1, smpte_bars(1, 1).pixels().len());
+    }
+
+    fn black() -> Pixel {
+        Pixel::new(0_u16, 0, 0, 65535)
+    }
+
+    fn white() -> Pixel {
+        Pixel::new(65535_u16, 65535, 65535, 65535)
+    }
+
+    #[test]
+    fn test_linear_gradient() {
+        let farb = linear_gradient(65536, 1, black(), white(), GradientDirection::Horizontal);
+        assert!(farb.pixels().iter().enumerate().all(|(i, pixel)| *pixel.red() as usize == i));
+
+        let farb = linear_gradient(7, 300, white(), black(), GradientDirection::Vertical);
+        assert_eq!(white(), at(&farb, 3, 0));
+        assert_eq!(black(), at(&farb, 6, 299));
+        for y in 1..300 {
+            assert!(at(&farb, 0, y).red() < at(&farb, 0, y - 1).red());
+            assert_eq!(at(&farb, 0, y), at(&farb, 6, y));
+        }
+
+        let farb = linear_gradient(3, 3, black(), white(), GradientDirection::Diagonal);
+        assert_eq!((black(), white()), (at(&farb, 0, 0), at(&farb, 2, 2)));
+        assert_eq!(at(&farb, 2, 0), at(&farb, 0, 2));
+        assert_eq!(at(&farb, 1, 1), at(&farb, 2, 0));
+        assert_eq!(Pixel::new(16384_u16, 16384, 16384, 65535), at(&farb, 1, 0));
+
+        assert_eq!(&[black()], linear_gradient(1, 1, black(), white(), GradientDirection::Diagonal).pixels());
+        assert!(linear_gradient(0, 4, black(), white(), GradientDirection::Vertical).pixels().is_empty());
+    }
+
+    #[test]
+    fn test_linear_gradient_stops() {
+        let red = Pixel::new(65535_u16, 0, 0, 65535);
+        let farb = linear_gradient_stops(5, 1, &[(0.0, black()), (0.5, red), (1.0, white())], GradientDirection::Horizontal);
+        assert_eq!(&[black(), Pixel::new(32768_u16, 0, 0, 65535), red,
+                     Pixel::new(65535_u16, 32768, 32768, 65535), white()], farb.pixels());
+        let transparent = linear_gradient_stops(2, 2, &[], GradientDirection::Horizontal);
+        assert!(transparent.pixels().iter().all(|&pixel| pixel == Pixel::default()));
+        let single = linear_gradient_stops(3, 1, &[(0.5, red)], GradientDirection::Horizontal);
+        assert!(single.pixels().iter().all(|&pixel| pixel == red));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_radial_gradient() {
+        let farb = radial_gradient(11, 11, (5.0, 5.0), 5.0, white(), black());
+        assert_eq!(white(), at(&farb, 5, 5));
+        assert_eq!(black(), at(&farb, 0, 5));
+        assert_eq!(black(), at(&farb, 0, 0));
+        for &(x, y) in &[(1_i32, 0_i32), (2, 3), (4, 1)] {
+            let expected = at(&farb, (5 + x) as u32, (5 + y) as u32);
+            for &(x, y) in &[(x, y), (y, x), (-x, y), (x, -y), (-x, -y), (-y, -x)] {
+                assert_eq!(expected, at(&farb, (5 + x) as u32, (5 + y) as u32));
+            }
+        }
+        assert!(at(&farb, 5, 4).red() > at(&farb, 5, 3).red());
+        assert_eq!(Pixel::new(52428_u16, 52428, 52428, 65535), at(&farb, 6, 5));
+
+        let degenerate = radial_gradient(2, 2, (0.0, 0.0), 0.0, white(), black());
+        assert!(degenerate.pixels().iter().all(|&pixel| pixel == black()));
+    }
+
+    #[test]
+    fn test_alpha_wedge() {
+        let farb = alpha_wedge(65536, 1);
+        assert_eq!(0, *at(&farb, 0, 0).alpha());
+        assert_eq!(65535, *at(&farb, 65535, 0).alpha());
+        assert!(farb.pixels().iter().enumerate().all(|(i, pixel)| *pixel.alpha() as usize == i));
+        assert!(farb.pixels().iter().all(|pixel| pixel.red() == &65535));
+
+        let farb = alpha_wedge(5, 2);
+        assert_eq!(16384, *at(&farb, 1, 1).alpha());
+        assert_eq!(65535, *alpha_wedge(1, 3)[2].alpha());
+        assert!(alpha_wedge(0, 3).pixels().is_empty());
+    }
+}
@@ -0,0 +1,467 @@
+//! Chaining transforms into a pipeline which can be applied to many Farbfeld images, such as by a
+//! batch converter.
+//!
+//! Each step is a [Transform](trait.Transform.html). Steps which change every pixel
+//! independently of the others also implement [PixelTransform](trait.PixelTransform.html), and a
+//! [Pipeline](struct.Pipeline.html) fuses neighbouring pixel transforms into a single pass over
+//! the image.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::{Pixel, GrayscaleMethod};
+use crate::resize::ResizeFilter;
+use crate::error::*;
+
+/// A step which transforms an image.
+pub trait Transform {
+    /// Returns the name of the transform, which is attached to its errors when it's part of a
+    /// [Pipeline](struct.Pipeline.html).
+    fn name(&self) -> &'static str;
+
+    /// Transforms the image.
+    fn apply(&self, farb: Farbfeld) -> Result<Farbfeld>;
+
+    /// Returns this transform as a pixel transform if it changes every pixel independently of
+    /// the others, so it can be fused with its neighbours. Returns None by default.
+    fn as_pixel_transform(&self) -> Option<&dyn PixelTransform> {
+        None
+    }
+}
+
+/// A transform which changes every pixel independently of the others, so several can be applied
+/// in a single pass over the image. Implementations should also return themselves from
+/// [Transform::as_pixel_transform](trait.Transform.html#method.as_pixel_transform).
+pub trait PixelTransform: Transform {
+    /// Transforms a single pixel.
+    fn map_pixel(&self, pixel: &Pixel) -> Pixel;
+}
+
+/// Applies a pixel transform to every pixel of the image.
+fn map_every_pixel<T: PixelTransform + ?Sized>(transform: &T, mut farb: Farbfeld) -> Farbfeld {
+    farb.for_each_pixel_mut(|pixel| *pixel = transform.map_pixel(pixel));
+    farb
+}
+
+/// Resizes images as described by [Farbfeld::resize](../struct.Farbfeld.html#method.resize).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resize {
+    width: u32,
+    height: u32,
+    filter: ResizeFilter
+}
+
+impl Resize {
+    /// Creates a transform resizing images to the given dimensions with the filter.
+    pub fn new(width: u32, height: u32, filter: ResizeFilter) -> Resize {
+        Resize { width, height, filter }
+    }
+}
+
+impl Transform for Resize {
+    fn name(&self) -> &'static str {
+        "resize"
+    }
+
+    fn apply(&self, farb: Farbfeld) -> Result<Farbfeld> {
+        Ok(farb.resize(self.width, self.height, self.filter))
+    }
+}
+
+/// Crops images as described by [Farbfeld::crop](../struct.Farbfeld.html#method.crop).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32
+}
+
+impl Crop {
+    /// Creates a transform cropping images to the rectangle with its top left corner at (x, y)
+    /// and the given dimensions.
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Crop {
+        Crop { x, y, width, height }
+    }
+}
+
+impl Transform for Crop {
+    fn name(&self) -> &'static str {
+        "crop"
+    }
+
+    /// # Errors
+    /// Returns an [Error::RegionOutOfBounds](../error/enum.Error.html) if the rectangle doesn't
+    /// fit within the image.
+    fn apply(&self, farb: Farbfeld) -> Result<Farbfeld> {
+        farb.crop(self.x, self.y, self.width, self.height)
+    }
+}
+
+/// Trims transparent borders from images as described by
+/// [Farbfeld::trim](../struct.Farbfeld.html#method.trim).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Trim;
+
+impl Transform for Trim {
+    fn name(&self) -> &'static str {
+        "trim"
+    }
+
+    fn apply(&self, farb: Farbfeld) -> Result<Farbfeld> {
+        Ok(farb.trim())
+    }
+}
+
+/// Sharpens images as described by [Farbfeld::sharpen](../struct.Farbfeld.html#method.sharpen).
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sharpen {
+    amount: f32,
+    sigma: f32,
+    threshold: u16
+}
+
+#[cfg(feature = "std")]
+impl Sharpen {
+    /// Creates a transform sharpening images with the given amount, blur and threshold.
+    pub fn new(amount: f32, sigma: f32, threshold: u16) -> Sharpen {
+        Sharpen { amount, sigma, threshold }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Transform for Sharpen {
+    fn name(&self) -> &'static str {
+        "sharpen"
+    }
+
+    fn apply(&self, farb: Farbfeld) -> Result<Farbfeld> {
+        Ok(farb.sharpen(self.amount, self.sigma, self.threshold))
+    }
+}
+
+/// Flattens images onto a solid background as described by
+/// [Farbfeld::flatten](../struct.Farbfeld.html#method.flatten).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flatten {
+    background: Pixel
+}
+
+impl Flatten {
+    /// Creates a transform flattening images onto the background, whose alpha is ignored.
+    pub fn new(mut background: Pixel) -> Flatten {
+        *background.alpha_mut() = u16::MAX;
+        Flatten { background }
+    }
+}
+
+impl Transform for Flatten {
+    fn name(&self) -> &'static str {
+        "flatten"
+    }
+
+    fn apply(&self, farb: Farbfeld) -> Result<Farbfeld> {
+        Ok(map_every_pixel(self, farb))
+    }
+
+    fn as_pixel_transform(&self) -> Option<&dyn PixelTransform> {
+        Some(self)
+    }
+}
+
+impl PixelTransform for Flatten {
+    fn map_pixel(&self, pixel: &Pixel) -> Pixel {
+        pixel.over(self.background)
+    }
+}
+
+/// Converts images to greyscale as described by
+/// [Farbfeld::grayscale_with](../struct.Farbfeld.html#method.grayscale_with).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grayscale {
+    method: GrayscaleMethod
+}
+
+impl Grayscale {
+    /// Creates a transform converting images to greyscale with the method.
+    pub fn new(method: GrayscaleMethod) -> Grayscale {
+        Grayscale { method }
+    }
+}
+
+impl Default for Grayscale {
+    fn default() -> Grayscale {
+        Grayscale::new(GrayscaleMethod::Luminance709)
+    }
+}
+
+impl Transform for Grayscale {
+    fn name(&self) -> &'static str {
+        "grayscale"
+    }
+
+    fn apply(&self, farb: Farbfeld) -> Result<Farbfeld> {
+        Ok(map_every_pixel(self, farb))
+    }
+
+    fn as_pixel_transform(&self) -> Option<&dyn PixelTransform> {
+        Some(self)
+    }
+}
+
+impl PixelTransform for Grayscale {
+    fn map_pixel(&self, pixel: &Pixel) -> Pixel {
+        let grey = pixel.grey(self.method);
+        Pixel::new(grey, grey, grey, *pixel.alpha())
+    }
+}
+
+/// Inverts the red, green and blue channels of images as described by
+/// [Farbfeld::invert](../struct.Farbfeld.html#method.invert).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Invert;
+
+impl Transform for Invert {
+    fn name(&self) -> &'static str {
+        "invert"
+    }
+
+    fn apply(&self, farb: Farbfeld) -> Result<Farbfeld> {
+        Ok(map_every_pixel(self, farb))
+    }
+
+    fn as_pixel_transform(&self) -> Option<&dyn PixelTransform> {
+        Some(self)
+    }
+}
+
+impl PixelTransform for Invert {
+    fn map_pixel(&self, pixel: &Pixel) -> Pixel {
+        pixel.invert()
+    }
+}
+
+/// Replaces every pixel of images with the result of a function, as described by
+/// [Farbfeld::map_pixels](../struct.Farbfeld.html#method.map_pixels).
+#[derive(Debug, Clone, Copy)]
+pub struct MapPixels<F> {
+    map: F
+}
+
+impl<F: Fn(&Pixel) -> Pixel> MapPixels<F> {
+    /// Creates a transform replacing every pixel with the result of `map`.
+    pub fn new(map: F) -> MapPixels<F> {
+        MapPixels { map }
+    }
+}
+
+impl<F: Fn(&Pixel) -> Pixel> Transform for MapPixels<F> {
+    fn name(&self) -> &'static str {
+        "map pixels"
+    }
+
+    fn apply(&self, farb: Farbfeld) -> Result<Farbfeld> {
+        Ok(map_every_pixel(self, farb))
+    }
+
+    fn as_pixel_transform(&self) -> Option<&dyn PixelTransform> {
+        Some(self)
+    }
+}
+
+impl<F: Fn(&Pixel) -> Pixel> PixelTransform for MapPixels<F> {
+    fn map_pixel(&self, pixel: &Pixel) -> Pixel {
+        (self.map)(pixel)
+    }
+}
+
+/// A sequence of transforms applied to images in order.
+///
+/// Neighbouring steps which are [pixel transforms](trait.PixelTransform.html) are fused, so each
+/// pixel goes through all of them before the next pixel, in a single pass over the image without
+/// an intermediate copy. The result is the same as applying each step in turn.
+///
+/// ```
+/// # use ::ruff::*;
+/// # use ::ruff::pipeline::{Pipeline, Crop, Invert, Flatten};
+/// let pipeline = Pipeline::new()
+///     .then(Crop::new(1, 0, 1, 1))
+///     .then(Flatten::new(Pixel::new(0_u16, 0, 0, 65535)))
+///     .then(Invert);
+/// let farb = Farbfeld::new(2, 1, vec![Pixel::default(), Pixel::new(1_u16, 2, 3, 65535)]).unwrap();
+///
+/// assert_eq!(&[Pixel::new(65534_u16, 65533, 65532, 65535)], pipeline.run(farb).unwrap().pixels());
+/// ```
+#[derive(Default)]
+pub struct Pipeline {
+    steps: Vec<Box<dyn Transform>>
+}
+
+impl Pipeline {
+    /// Creates a pipeline with no steps, which leaves images unchanged.
+    pub fn new() -> Pipeline {
+        Pipeline { steps: Vec::new() }
+    }
+
+    /// Adds the transform to the end of the pipeline.
+    pub fn then<T: Transform + 'static>(mut self, step: T) -> Pipeline {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    /// Returns the number of steps in the pipeline.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Returns true if the pipeline has no steps.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Applies every step of the pipeline to the image in order.
+    ///
+    /// # Errors
+    /// Returns an [Error::TransformFailed](../error/enum.Error.html) containing the name of the
+    /// step and its error if a step fails, without applying the steps after it.
+    pub fn run(&self, mut farb: Farbfeld) -> Result<Farbfeld> {
+        let mut fused: Vec<&dyn PixelTransform> = Vec::new();
+        for step in &self.steps {
+            if let Some(pixel_transform) = step.as_pixel_transform() {
+                fused.push(pixel_transform);
+                continue;
+            }
+            farb = Pipeline::run_fused(&mut fused, farb);
+            farb = step.apply(farb).map_err(|err| Error::TransformFailed(step.name(), Box::new(err)))?;
+        }
+        Ok(Pipeline::run_fused(&mut fused, farb))
+    }
+
+    /// Applies the pixel transforms to the image in a single pass, leaving none left to apply.
+    fn run_fused(fused: &mut Vec<&dyn PixelTransform>, mut farb: Farbfeld) -> Farbfeld {
+        match fused.len() {
+            0 => {},
+            1 => farb = map_every_pixel(fused[0], farb),
+            _ => farb.for_each_pixel_mut(|pixel| {
+                for step in fused.iter() {
+                    *pixel = step.map_pixel(pixel);
+                }
+            })
+        }
+        fused.clear();
+        farb
+    }
+
+    /// Reads the image at `input`, applies every step of the pipeline to it and saves the result
+    /// to `output`, returning the number of bytes written. Requires the `std` feature.
+    ///
+    /// # Errors
+    /// Returns the errors of [Farbfeld::from_file](../struct.Farbfeld.html#method.from_file)
+    /// and [Farbfeld::save_to_file](../struct.Farbfeld.html#method.save_to_file), or an
+    /// [Error::TransformFailed](../error/enum.Error.html) if a step fails, in which case `output`
+    /// isn't written.
+    #[cfg(feature = "std")]
+    pub fn run_file<P: AsRef<Path>, Q: AsRef<Path>>(&self, input: P, output: Q) -> Result<u64> {
+        self.run(Farbfeld::from_file(input)?)?.save_to_file(output)
+    }
+}
+
+impl Transform for Pipeline {
+    fn name(&self) -> &'static str {
+        "pipeline"
+    }
+
+    fn apply(&self, farb: Farbfeld) -> Result<Farbfeld> {
+        self.run(farb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::patterns::{self, linear_gradient, GradientDirection};
+
+    fn image() -> Farbfeld {
+        let (from, to) = (Pixel::new(0_u16, 1000, 65535, 0), Pixel::new(65535_u16, 20000, 3, 65535));
+        let mut farb = patterns::solid(40, 30, Pixel::default());
+        let gradient = linear_gradient(30, 20, from, to, GradientDirection::Diagonal);
+        for (i, pixel) in gradient.pixels().iter().enumerate() {
+            farb[(i / 30 + 5) * 40 + i % 30 + 4] = *pixel;
+        }
+        farb
+    }
+
+    fn swap_red_blue(pixel: &Pixel) -> Pixel {
+        Pixel::new(*pixel.blue(), *pixel.green(), *pixel.red(), *pixel.alpha())
+    }
+
+    #[test]
+    fn test_fused_matches_individual() {
+        let background = Pixel::new(1000_u16, 2000, 3000, 0);
+        let pipeline = Pipeline::new()
+            .then(Trim)
+            .then(Resize::new(15, 10, ResizeFilter::Bilinear))
+            .then(Flatten::new(background))
+            .then(MapPixels::new(swap_red_blue))
+            .then(Grayscale::new(GrayscaleMethod::Lightness))
+            .then(Crop::new(2, 1, 10, 8))
+            .then(Invert);
+        assert_eq!(7, pipeline.len());
+
+        let expected = image().trim().resize(15, 10, ResizeFilter::Bilinear).flatten(background)
+            .map_pixels(swap_red_blue).grayscale_with(GrayscaleMethod::Lightness)
+            .crop(2, 1, 10, 8).unwrap().invert();
+        assert_eq!(expected, pipeline.run(image()).unwrap());
+
+        let mut individually = image();
+        for step in &pipeline.steps {
+            individually = step.apply(individually).unwrap();
+        }
+        assert_eq!(expected, individually);
+        assert_eq!(expected, Pipeline::new().then(pipeline).apply(image()).unwrap());
+    }
+
+    #[test]
+    fn test_empty_pipeline() {
+        assert!(Pipeline::new().is_empty());
+        assert_eq!(image(), Pipeline::default().run(image()).unwrap());
+        assert_eq!(image().grayscale().invert(), Pipeline::new().then(Grayscale::default()).then(Invert).run(image()).unwrap());
+    }
+
+    #[test]
+    fn test_error_names_step() {
+        let pipeline = Pipeline::new()
+            .then(Trim)
+            .then(Crop::new(0, 0, 100, 100))
+            .then(MapPixels::new(|_: &Pixel| -> Pixel { panic!("steps after a failure aren't applied") }));
+        match pipeline.run(image()) {
+            Err(Error::TransformFailed("crop", ref err)) => match **err {
+                Error::RegionOutOfBounds(0, 0, 100, 100) => {},
+                ref res => panic!("Unexpected result {:?}", res)
+            },
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_run_file() {
+        let dir = std::env::temp_dir().join(format!("ruff-pipeline-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("input.ff"), dir.join("output.ff"));
+        image().save_to_file(&input).unwrap();
+
+        let pipeline = Pipeline::new().then(Trim).then(Sharpen::new(1.0, 1.0, 0)).then(Flatten::new(Pixel::default()));
+        let written = pipeline.run_file(&input, &output).unwrap();
+        let expected = image().trim().sharpen(1.0, 1.0, 0).flatten(Pixel::default());
+        assert_eq!(expected.to_bytes().len() as u64, written);
+        assert_eq!(expected, Farbfeld::from_file(&output).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
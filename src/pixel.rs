@@ -1,11 +1,18 @@
 //! Pixels within a Farbfeld image as defined by the [spec](http://tools.suckless.org/farbfeld/).
 
-use std::iter::{ExactSizeIterator, FusedIterator};
+use core::fmt;
+use core::slice;
+use core::iter::{ExactSizeIterator, FusedIterator};
 
 /// A single pixel in a Farbfeld image as defined by the [spec](http://tools.suckless.org/farbfeld/)
 /// by Suckless.
+///
+/// Pixels are guaranteed to be laid out as four native endian u16 channels in the order red,
+/// green, blue, alpha, with no padding, so a slice of pixels has the same layout as a slice of four
+/// times as many u16s, as used by [Farbfeld::as_channels](../struct.Farbfeld.html#method.as_channels).
 #[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
 pub struct Pixel {
     red: u16,
     green: u16,
@@ -13,6 +20,45 @@ pub struct Pixel {
     alpha: u16
 }
 
+/// A channel of a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// The red channel.
+    Red,
+    /// The green channel.
+    Green,
+    /// The blue channel.
+    Blue,
+    /// The alpha channel.
+    Alpha
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Channel::Red => "red",
+            Channel::Green => "green",
+            Channel::Blue => "blue",
+            Channel::Alpha => "alpha"
+        })
+    }
+}
+
+/// How the red, green and blue components of a pixel are combined into a single grey value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GrayscaleMethod {
+    /// The relative luminance with the Rec. 709 coefficients, as found by
+    /// [Pixel::luminance](struct.Pixel.html#method.luminance).
+    Luminance709,
+    /// The luma with the Rec. 601 coefficients 0.299, 0.587 and 0.114, as used by analogue
+    /// television and JPEG.
+    Luminance601,
+    /// The mean of the components.
+    Average,
+    /// The mean of the largest and smallest components, as used by HSL.
+    Lightness
+}
+
 /// A consuming iterator implementation for [Pixel](struct.Pixel.html). It produces the red, green,
 /// blue then alpha values of a pixel in that order, then returns None.
 ///
@@ -105,13 +151,220 @@ impl Pixel {
         &mut self.alpha
     }
 
+    /// Returns a reference to the given component of this pixel.
+    pub fn channel(&self, channel: Channel) -> &u16 {
+        match channel {
+            Channel::Red => &self.red,
+            Channel::Green => &self.green,
+            Channel::Blue => &self.blue,
+            Channel::Alpha => &self.alpha
+        }
+    }
+
+    /// Returns a mutable reference to the given component of this pixel.
+    pub fn channel_mut(&mut self, channel: Channel) -> &mut u16 {
+        match channel {
+            Channel::Red => &mut self.red,
+            Channel::Green => &mut self.green,
+            Channel::Blue => &mut self.blue,
+            Channel::Alpha => &mut self.alpha
+        }
+    }
+
+    /// Creates a new Pixel from 8 bit red, green, blue and alpha components in that order, expanding
+    /// each so that 255 becomes 65535.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// assert_eq!(Pixel::new(65535_u16, 0, 257, 32896), Pixel::from_rgba8([255, 0, 1, 128]));
+    /// ```
+    pub fn from_rgba8(rgba: [u8; 4]) -> Pixel {
+        Pixel {
+            red: expand_channel(rgba[0]),
+            green: expand_channel(rgba[1]),
+            blue: expand_channel(rgba[2]),
+            alpha: expand_channel(rgba[3])
+        }
+    }
+
+    /// Returns the red, green, blue and alpha components of this pixel in that order, each rounded
+    /// to the nearest 8 bit value.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// assert_eq!([255, 0, 1, 0], Pixel::new(65535_u16, 128, 129, 127).to_rgba8());
+    /// ```
+    pub fn to_rgba8(&self) -> [u8; 4] {
+        [narrow_channel(self.red), narrow_channel(self.green), narrow_channel(self.blue), narrow_channel(self.alpha)]
+    }
+
+    /// Returns the relative luminance of this pixel's red, green and blue components, weighted by
+    /// the Rec. 709 coefficients 0.2126, 0.7152 and 0.0722 and rounded to the nearest value. The
+    /// components are treated as linear and alpha is ignored.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// assert_eq!(65535, Pixel::new(65535_u16, 65535, 65535, 0).luminance());
+    /// assert_eq!(46870, Pixel::new(0_u16, 65535, 0, 65535).luminance());
+    /// ```
+    pub fn luminance(&self) -> u16 {
+        // The coefficients scaled to sum to 65536.
+        ((self.red as u32 * 13933 + self.green as u32 * 46871 + self.blue as u32 * 4732 + 32768) >> 16) as u16
+    }
+
+    /// Returns the grey value of this pixel's red, green and blue components combined as described
+    /// by `method`, rounded to the nearest value. Alpha is ignored.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::pixel::GrayscaleMethod;
+    /// let pixel = Pixel::new(0_u16, 65535, 1, 65535);
+    ///
+    /// assert_eq!(pixel.luminance(), pixel.grey(GrayscaleMethod::Luminance709));
+    /// assert_eq!(21845, pixel.grey(GrayscaleMethod::Average));
+    /// assert_eq!(32768, pixel.grey(GrayscaleMethod::Lightness));
+    /// ```
+    pub fn grey(&self, method: GrayscaleMethod) -> u16 {
+        let (red, green, blue) = (self.red as u32, self.green as u32, self.blue as u32);
+        match method {
+            GrayscaleMethod::Luminance709 => self.luminance(),
+            // The coefficients scaled to sum to 65536.
+            GrayscaleMethod::Luminance601 => ((red * 19595 + green * 38470 + blue * 7471 + 32768) >> 16) as u16,
+            GrayscaleMethod::Average => ((red + green + blue + 1) / 3) as u16,
+            GrayscaleMethod::Lightness => (red.max(green).max(blue) + red.min(green).min(blue)).div_ceil(2) as u16
+        }
+    }
+
+    /// Returns the largest difference between any channel of this pixel and the same channel of
+    /// `other`, including alpha.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// assert_eq!(300, Pixel::new(100_u16, 200, 300, 400).distance(&Pixel::new(200_u16, 0, 600, 400)));
+    /// ```
+    pub fn distance(&self, other: &Pixel) -> u16 {
+        self.iter().zip(other.iter()).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0)
+    }
+
+    /// Returns whether every channel of this pixel, including alpha, is within `tolerance` of the
+    /// same channel of `other`, as found by [distance](#method.distance).
+    pub fn is_similar(&self, other: &Pixel, tolerance: u16) -> bool {
+        self.distance(other) <= tolerance
+    }
+
+    /// Returns this pixel with its red, green and blue components inverted, so each becomes 65535
+    /// minus its value. Alpha is unchanged.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// assert_eq!(Pixel::new(65535_u16, 65534, 0, 7), Pixel::new(0_u16, 1, 65535, 7).invert());
+    /// ```
+    pub fn invert(&self) -> Pixel {
+        Pixel {
+            red: u16::MAX - self.red,
+            green: u16::MAX - self.green,
+            blue: u16::MAX - self.blue,
+            alpha: self.alpha
+        }
+    }
+
+    /// Linearly interpolates every channel, including alpha, between this pixel and `other`,
+    /// rounding to the nearest value. A `t` of 0 returns this pixel and 1 returns `other`, and
+    /// values outside 0 to 1 are clamped, with NaN treated as 0.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let black = Pixel::new(0_u16, 0, 0, 65535);
+    /// let white = Pixel::new(65535_u16, 65535, 65535, 65535);
+    ///
+    /// assert_eq!(Pixel::new(32768_u16, 32768, 32768, 65535), black.lerp(white, 0.5));
+    /// assert_eq!(white, black.lerp(white, 2.0));
+    /// ```
+    pub fn lerp(&self, other: Pixel, t: f32) -> Pixel {
+        self.lerp_f64(other, t as f64)
+    }
+
+    /// Interpolates between the pixels as described by [lerp](#method.lerp), with the precision of
+    /// an f64.
+    pub(crate) fn lerp_f64(&self, other: Pixel, t: f64) -> Pixel {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let channel = |from: u16, to: u16| (from as f64 + (to as f64 - from as f64) * t + 0.5) as u16;
+        Pixel {
+            red: channel(self.red, other.red),
+            green: channel(self.green, other.green),
+            blue: channel(self.blue, other.blue),
+            alpha: channel(self.alpha, other.alpha)
+        }
+    }
+
+    /// Composites this pixel over the backdrop with the source-over operator, treating both as
+    /// straight (non-premultiplied) alpha and rounding each channel to the nearest value. Opaque
+    /// pixels replace the backdrop and fully transparent pixels leave it unchanged.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let half_red = Pixel::new(65535_u16, 0, 0, 32768);
+    /// let blue = Pixel::new(0_u16, 0, 65535, 65535);
+    ///
+    /// assert_eq!(Pixel::new(32768_u16, 0, 32767, 65535), half_red.over(blue));
+    /// assert_eq!(blue, Pixel::new(1_u16, 2, 3, 0).over(blue));
+    /// ```
+    pub fn over(&self, backdrop: Pixel) -> Pixel {
+        const MAX: u64 = u16::MAX as u64;
+        if self.alpha == 0 {
+            return backdrop;
+        }
+        let source_alpha = self.alpha as u64;
+        // The alpha the backdrop contributes, scaled by 65535.
+        let backdrop_weight = backdrop.alpha as u64 * (MAX - source_alpha);
+        let alpha = source_alpha * MAX + backdrop_weight;
+        let channel = |source: u16, backdrop: u16|
+            ((source as u64 * source_alpha * MAX + backdrop as u64 * backdrop_weight + alpha / 2) / alpha) as u16;
+        Pixel {
+            red: channel(self.red, backdrop.red),
+            green: channel(self.green, backdrop.green),
+            blue: channel(self.blue, backdrop.blue),
+            alpha: ((alpha + MAX / 2) / MAX) as u16
+        }
+    }
+
     /// Creates an iterator over a reference to the slice. The iterator produces a reference to the
     /// red, green, blue then alpha component of this pixel, then returns None.
-    pub fn iter(&self) -> Iter {
+    pub fn iter(&self) -> Iter<'_> {
         Iter {pixel: self, curr: 0}
     }
 }
 
+/// Returns the interleaved red, green, blue and alpha channels of the pixels, without copying.
+pub(crate) fn as_channels(pixels: &[Pixel]) -> &[u16] {
+    // Pixel is a repr(C) struct of four u16 channels, so it has the same alignment as u16 and the
+    // slice covers exactly four times as many u16s as pixels.
+    unsafe { slice::from_raw_parts(pixels.as_ptr() as *const u16, pixels.len() * 4) }
+}
+
+/// Returns the interleaved red, green, blue and alpha channels of the pixels, without copying.
+pub(crate) fn as_channels_mut(pixels: &mut [Pixel]) -> &mut [u16] {
+    // The layout is the same as for as_channels, and every u16 is a valid channel value.
+    unsafe { slice::from_raw_parts_mut(pixels.as_mut_ptr() as *mut u16, pixels.len() * 4) }
+}
+
+/// Expands an 8 bit channel to 16 bits, mapping 255 to 65535.
+pub(crate) fn expand_channel(channel: u8) -> u16 {
+    channel as u16 * 257
+}
+
+/// Narrows a 16 bit channel to 8 bits, rounding to the nearest value.
+pub(crate) fn narrow_channel(channel: u16) -> u8 {
+    ((channel as u32 + 128) / 257) as u8
+}
+
+/// Composites the channel with the given alpha onto the background channel, rounding to the
+/// nearest value.
+pub(crate) fn composite_channel(channel: u16, background: u16, alpha: u16) -> u16 {
+    let (channel, background, alpha) = (channel as u32, background as u32, alpha as u32);
+    ((channel * alpha + background * (65535 - alpha) + 32767) / 65535) as u16
+}
+
 impl From<[u16; 4]> for Pixel {
     fn from(i: [u16; 4]) -> Self {
         Pixel {
@@ -123,9 +376,20 @@ impl From<[u16; 4]> for Pixel {
     }
 }
 
-impl Into<[u16; 4]> for Pixel {
-    fn into(self) -> [u16; 4] {
-        [self.red, self.green, self.blue, self.alpha]
+impl From<Pixel> for [u16; 4] {
+    fn from(p: Pixel) -> Self {
+        [p.red, p.green, p.blue, p.alpha]
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> ::arbitrary::Arbitrary<'a> for Pixel {
+    fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+        u.arbitrary::<[u16; 4]>().map(Pixel::from)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        <[u16; 4] as ::arbitrary::Arbitrary>::size_hint(depth)
     }
 }
 
@@ -191,4 +455,60 @@ mod test {
         assert_eq!(Pixel::from([10_u16, 20_u16, 30_u16, 40_u16]),
             Pixel::new(10_u16, 20_u16, 30_u16, 40_u16));
     }
+
+    #[test]
+    fn test_grey() {
+        let methods = [GrayscaleMethod::Luminance709, GrayscaleMethod::Luminance601, GrayscaleMethod::Average,
+                       GrayscaleMethod::Lightness];
+        for &value in &[0_u16, 1, 32768, 65535] {
+            for &method in &methods {
+                assert_eq!(value, Pixel::new(value, value, value, 3).grey(method));
+            }
+        }
+        let pixel = Pixel::new(1000_u16, 50000, 20000, 0);
+        assert_eq!(31929, pixel.grey(GrayscaleMethod::Luminance601));
+        assert_eq!(23667, pixel.grey(GrayscaleMethod::Average));
+        assert_eq!(25500, pixel.grey(GrayscaleMethod::Lightness));
+    }
+
+    #[test]
+    fn test_distance() {
+        let pixel = Pixel::new(1000_u16, 2000, 3000, 4000);
+        assert_eq!(0, pixel.distance(&pixel));
+        assert_eq!(65535, Pixel::new(0_u16, 0, 0, 0).distance(&Pixel::new(0_u16, 0, 0, 65535)));
+        assert_eq!(10, pixel.distance(&Pixel::new(1005_u16, 1990, 3000, 4001)));
+        assert!(pixel.is_similar(&Pixel::new(1005_u16, 1990, 3000, 4001), 10));
+        assert!(!pixel.is_similar(&Pixel::new(1005_u16, 1990, 3000, 4001), 9));
+    }
+
+    #[test]
+    fn test_lerp() {
+        let (from, to) = (Pixel::new(0_u16, 65535, 100, 7), Pixel::new(65535_u16, 0, 100, 8));
+        assert_eq!(from, from.lerp(to, 0.0));
+        assert_eq!(to, from.lerp(to, 1.0));
+        assert_eq!(from, from.lerp(to, -3.0));
+        assert_eq!(from, from.lerp(to, f32::NAN));
+        assert_eq!(Pixel::new(16384_u16, 49151, 100, 7), from.lerp(to, 0.25));
+    }
+
+    #[test]
+    fn test_over() {
+        let backdrop = Pixel::new(1000_u16, 2000, 3000, 65535);
+        let opaque = Pixel::new(10_u16, 20, 30, 65535);
+        assert_eq!(opaque, opaque.over(backdrop));
+        assert_eq!(backdrop, Pixel::new(10_u16, 20, 30, 0).over(backdrop));
+        assert_eq!(Pixel::new(1_u16, 2, 3, 0), Pixel::new(10_u16, 20, 30, 0).over(Pixel::new(1_u16, 2, 3, 0)));
+
+        let half = Pixel::new(65535_u16, 0, 0, 32768);
+        let over_opaque = half.over(backdrop);
+        assert_eq!(65535, *over_opaque.alpha());
+        assert_eq!(composite_channel(65535, 1000, 32768), *over_opaque.red());
+        assert_eq!(composite_channel(0, 3000, 32768), *over_opaque.blue());
+
+        let over_transparent = half.over(Pixel::new(0_u16, 65535, 0, 0));
+        assert_eq!(half, over_transparent);
+
+        let over_half = half.over(Pixel::new(0_u16, 0, 65535, 32768));
+        assert_eq!(Pixel::new(43690_u16, 0, 21845, 49152), over_half);
+    }
 }
\ No newline at end of file
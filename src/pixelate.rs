@@ -0,0 +1,153 @@
+//! Pixelating Farbfeld images, such as for mosaic effects or hiding faces and text.
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+use crate::error::*;
+
+impl Farbfeld {
+    /// Returns a copy of the image divided into square cells `block` pixels wide, starting from
+    /// the top left corner, with every pixel of each cell replaced by the average of the cell.
+    /// Cells along the right and bottom edges which don't fit within the image are averaged over
+    /// just the pixels they cover. Every channel, including alpha, is averaged separately and
+    /// rounded to the nearest value. A block of 1 leaves the image unchanged.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(3, 1, vec![Pixel::new(1_u16, 2, 3, 4), Pixel::new(3_u16, 4, 5, 6),
+    ///                                     Pixel::new(9_u16, 9, 9, 9)]).unwrap();
+    ///
+    /// assert_eq!(&[Pixel::new(2_u16, 3, 4, 5), Pixel::new(2_u16, 3, 4, 5), Pixel::new(9_u16, 9, 9, 9)],
+    ///            farb.pixelate(2).unwrap().pixels());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidAdjustment](error/enum.Error.html) if block is 0.
+    pub fn pixelate(&self, block: u32) -> Result<Farbfeld> {
+        let mut farb = self.clone();
+        farb.pixelate_region(0, 0, *self.width(), *self.height(), block)?;
+        Ok(farb)
+    }
+
+    /// Pixelates the rectangle of the image with its top left corner at (x, y) and the given
+    /// dimensions as described by [pixelate](#method.pixelate), with the cells starting from the
+    /// top left corner of the rectangle. Pixels outside the rectangle are left unchanged, so this
+    /// can redact part of an image.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidAdjustment](error/enum.Error.html) if block is 0, or an
+    /// [Error::RegionOutOfBounds](error/enum.Error.html) if the rectangle doesn't fit within
+    /// the image. The image is left unchanged if an error is returned.
+    pub fn pixelate_region(&mut self, x: u32, y: u32, width: u32, height: u32, block: u32) -> Result<()> {
+        if block == 0 {
+            return Err(Error::InvalidAdjustment("pixelate blocks must be at least 1 pixel wide"));
+        }
+        self.check_region(x, y, width, height)?;
+        let image_width = *self.width() as usize;
+        for cell_y in (y..y + height).step_by(block as usize) {
+            let rows = cell_y as usize..cell_y.saturating_add(block).min(y + height) as usize;
+            for cell_x in (x..x + width).step_by(block as usize) {
+                let columns = cell_x as usize..cell_x.saturating_add(block).min(x + width) as usize;
+                let count = (rows.len() * columns.len()) as u64;
+                let mut sums = [0_u64; 4];
+                for row in rows.clone() {
+                    for pixel in &self[row * image_width + columns.start..row * image_width + columns.end] {
+                        for (sum, &value) in sums.iter_mut().zip(pixel.iter()) {
+                            *sum += value as u64;
+                        }
+                    }
+                }
+                let average = Pixel::from(sums.map(|sum| ((sum + count / 2) / count) as u16));
+                for row in rows.clone() {
+                    for pixel in &mut self[row * image_width + columns.start..row * image_width + columns.end] {
+                        *pixel = average;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    use crate::patterns::{linear_gradient, GradientDirection};
+
+    fn image() -> Farbfeld {
+        let (from, to) = (Pixel::new(0_u16, 1000, 65535, 7), Pixel::new(65535_u16, 20000, 3, 60000));
+        linear_gradient(11, 7, from, to, GradientDirection::Diagonal)
+    }
+
+    /// Returns the rounded average of the pixels.
+    fn average<'a, I: Iterator<Item = &'a Pixel>>(pixels: I) -> Pixel {
+        let pixels: Vec<&Pixel> = pixels.collect();
+        let count = pixels.len() as u64;
+        let mut sums = [0_u64; 4];
+        for pixel in pixels {
+            for (sum, &value) in sums.iter_mut().zip(pixel.iter()) {
+                *sum += value as u64;
+            }
+        }
+        Pixel::from(sums.map(|sum| ((sum + count / 2) / count) as u16))
+    }
+
+    #[test]
+    fn test_identity_and_solid() {
+        assert_eq!(image(), image().pixelate(1).unwrap());
+        let expected = average(image().pixels().iter());
+        for block in [11, 12, u32::MAX] {
+            let farb = image().pixelate(block).unwrap();
+            assert!(farb.pixels().iter().all(|pixel| pixel == &expected), "block {}", block);
+        }
+        let empty = Farbfeld::new(0, 0, Vec::new()).unwrap();
+        assert_eq!(empty, empty.pixelate(3).unwrap());
+    }
+
+    #[test]
+    fn test_edge_cells() {
+        let farb = image().pixelate(4).unwrap();
+        for (i, pixel) in farb.pixels().iter().enumerate() {
+            let (cell_x, cell_y) = (i as u32 % 11 / 4 * 4, i as u32 / 11 / 4 * 4);
+            let (width, height) = ((cell_x + 4).min(11) - cell_x, (cell_y + 4).min(7) - cell_y);
+            let cell = image().crop(cell_x, cell_y, width, height).unwrap();
+            assert_eq!(&average(cell.pixels().iter()), pixel, "pixel {}", i);
+        }
+        let corner = image().crop(8, 4, 3, 3).unwrap();
+        assert_eq!(average(corner.pixels().iter()), farb[76]);
+    }
+
+    #[test]
+    fn test_pixelate_region() {
+        let mut farb = image();
+        farb.pixelate_region(2, 1, 7, 5, 3).unwrap();
+        let expected = image().crop(2, 1, 7, 5).unwrap().pixelate(3).unwrap();
+        let mut inside = expected.pixels().iter();
+        for (i, pixel) in farb.pixels().iter().enumerate() {
+            let (x, y) = (i as u32 % 11, i as u32 / 11);
+            if (2..9).contains(&x) && (1..6).contains(&y) {
+                assert_eq!(inside.next(), Some(pixel));
+            } else {
+                assert_eq!(&image()[i], pixel);
+            }
+        }
+        assert_eq!(None, inside.next());
+    }
+
+    #[test]
+    fn test_errors() {
+        match image().pixelate(0) {
+            Err(Error::InvalidAdjustment(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        let mut farb = image();
+        match farb.pixelate_region(5, 5, 7, 1, 2) {
+            Err(Error::RegionOutOfBounds(5, 5, 7, 1)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        assert!(farb.pixelate_region(0, 0, 1, 1, 0).is_err());
+        assert_eq!(image(), farb);
+    }
+}
@@ -0,0 +1,187 @@
+//! Converting between Farbfeld and PNG images. Requires the `png` feature.
+//!
+//! PNG is the format Farbfeld images are most often converted from and to, as done by the
+//! `png2ff` and `ff2png` tools from suckless.
+
+use std::io::{Read, Write, Cursor};
+
+use png::{self, BitDepth, ColorType, Decoder, Encoder, Transformations};
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::{Pixel, expand_channel, narrow_channel};
+use crate::error::*;
+
+/// The channel depth of a written PNG image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngDepth {
+    /// 8 bits per channel. Each channel is rounded to the nearest 8 bit value.
+    Eight,
+    /// 16 bits per channel, which is lossless.
+    Sixteen
+}
+
+/// The color type of a written PNG image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngColor {
+    /// Always write red, green, blue and alpha channels.
+    Rgba,
+    /// Write only red, green and blue channels if every pixel is fully opaque, otherwise write
+    /// red, green, blue and alpha channels.
+    RgbIfOpaque
+}
+
+impl Farbfeld {
+    /// Decodes the PNG image from the given read into a Farbfeld image. Grayscale, RGB and
+    /// paletted images with or without transparency are expanded to 16 bit RGBA, and 8 bit
+    /// channels are scaled up so that 255 becomes 65535. Only the first frame of an animated PNG is
+    /// decoded.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">IoError</a> if the read produces an
+    ///     std IoError.</li>
+    ///     <li> Returns an <a href="error/enum.Error.html">PngDecodingError</a> if the data
+    ///     isn't a valid PNG image.</li>
+    /// </ul>
+    pub fn from_png<R: Read>(mut read: R) -> Result<Farbfeld> {
+        let mut data = Vec::new();
+        read.read_to_end(&mut data).map_err(Error::IoError)?;
+        let mut decoder = Decoder::new(Cursor::new(data));
+        decoder.set_transformations(Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let mut buff = vec![0_u8; reader.output_buffer_size().ok_or(png::DecodingError::LimitsExceeded)?];
+        let info = reader.next_frame(&mut buff)?;
+
+        let sample_size = if info.bit_depth == BitDepth::Sixteen { 2 } else { 1 };
+        let samples = info.color_type.samples();
+        let pixels = buff[..info.buffer_size()]
+            .chunks(info.line_size)
+            .flat_map(|line| line[..info.width as usize * samples * sample_size].chunks(samples * sample_size))
+            .map(|chunk| {
+                let channel = |i: usize| if sample_size == 2 {
+                    u16::from_be_bytes([chunk[i * 2], chunk[i * 2 + 1]])
+                } else {
+                    expand_channel(chunk[i])
+                };
+                match info.color_type {
+                    ColorType::Grayscale => Pixel::new(channel(0), channel(0), channel(0), 65535),
+                    ColorType::GrayscaleAlpha => Pixel::new(channel(0), channel(0), channel(0), channel(1)),
+                    ColorType::Rgb => Pixel::new(channel(0), channel(1), channel(2), 65535),
+                    ColorType::Rgba => Pixel::new(channel(0), channel(1), channel(2), channel(3)),
+                    ColorType::Indexed => unreachable!("Indexed colors are expanded by the decoder")
+                }
+            })
+            .collect();
+        Farbfeld::new(info.width, info.height, pixels)
+    }
+
+    /// Encodes the image as an RGBA PNG image with the given depth, writing it to the given write.
+    ///
+    /// # Errors
+    /// <ul>
+    ///     <li> Returns an <a href="error/enum.Error.html">PngEncodingError</a> if the image
+    ///     cannot be encoded, such as if either dimension is zero, or if the write produces an std
+    ///     IoError.</li>
+    /// </ul>
+    pub fn to_png<W: Write>(&self, write: W, depth: PngDepth) -> Result<()> {
+        self.to_png_with_color(write, depth, PngColor::Rgba)
+    }
+
+    /// Encodes the image as a PNG image with the given depth and color type, writing it to the
+    /// given write.
+    ///
+    /// # Errors
+    /// Returns the errors of [to_png](#method.to_png).
+    pub fn to_png_with_color<W: Write>(&self, write: W, depth: PngDepth, color: PngColor) -> Result<()> {
+        let alpha = color == PngColor::Rgba || self.has_transparency();
+        let mut encoder = Encoder::new(write, *self.width(), *self.height());
+        encoder.set_color(if alpha { ColorType::Rgba } else { ColorType::Rgb });
+        encoder.set_depth(match depth {
+            PngDepth::Eight => BitDepth::Eight,
+            PngDepth::Sixteen => BitDepth::Sixteen
+        });
+
+        let channels = if alpha { 4 } else { 3 };
+        let mut data = Vec::with_capacity(self.pixels().len() * channels * 2);
+        for pixel in self.pixels() {
+            for &channel in pixel.iter().take(channels) {
+                match depth {
+                    PngDepth::Eight => data.push(narrow_channel(channel)),
+                    PngDepth::Sixteen => data.extend_from_slice(&channel.to_be_bytes())
+                }
+            }
+        }
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(width: u32, height: u32, color: ColorType, depth: BitDepth, palette: Option<&[u8]>, data: &[u8]) -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut encoder = Encoder::new(&mut png, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        if let Some(palette) = palette {
+            encoder.set_palette(palette);
+        }
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        png
+    }
+
+    #[test]
+    fn test_sixteen_round_trip() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let mut png = Vec::new();
+        farb.to_png(&mut png, PngDepth::Sixteen).unwrap();
+        assert_eq!(farb.to_bytes(), Farbfeld::from_png(&png[..]).unwrap().to_bytes());
+    }
+
+    #[test]
+    fn test_eight_bit_paletted() {
+        let palette = [255, 0, 0, 0, 128, 255];
+        let png = encode(3, 1, ColorType::Indexed, BitDepth::Eight, Some(&palette), &[1, 0, 1]);
+        let farb = Farbfeld::from_png(&png[..]).unwrap();
+        assert_eq!(&[Pixel::new(0_u16, 128 * 257, 65535, 65535), Pixel::new(65535_u16, 0, 0, 65535),
+                     Pixel::new(0_u16, 128 * 257, 65535, 65535)], farb.pixels());
+    }
+
+    #[test]
+    fn test_gray_alpha() {
+        let png = encode(2, 1, ColorType::GrayscaleAlpha, BitDepth::Sixteen, None, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        let farb = Farbfeld::from_png(&png[..]).unwrap();
+        assert_eq!(&[Pixel::new(0x0102_u16, 0x0102, 0x0102, 0x0304), Pixel::new(0x0506_u16, 0x0506, 0x0506, 0x0708)],
+                   farb.pixels());
+    }
+
+    #[test]
+    fn test_rgb_if_opaque() {
+        let opaque = Farbfeld::new(1, 1, vec![Pixel::new(257_u16, 514, 771, 65535)]).unwrap();
+        let mut png = Vec::new();
+        opaque.to_png_with_color(&mut png, PngDepth::Eight, PngColor::RgbIfOpaque).unwrap();
+        let reader = Decoder::new(Cursor::new(&png[..])).read_info().unwrap();
+        assert_eq!(ColorType::Rgb, reader.info().color_type);
+        assert_eq!(opaque.to_bytes(), Farbfeld::from_png(&png[..]).unwrap().to_bytes());
+
+        let transparent = Farbfeld::new(1, 1, vec![Pixel::new(257_u16, 514, 771, 0)]).unwrap();
+        png.clear();
+        transparent.to_png_with_color(&mut png, PngDepth::Eight, PngColor::RgbIfOpaque).unwrap();
+        assert_eq!(transparent.to_bytes(), Farbfeld::from_png(&png[..]).unwrap().to_bytes());
+    }
+
+    #[test]
+    fn test_invalid_png() {
+        match Farbfeld::from_png(&b"farbfeld"[..]) {
+            Err(Error::PngDecodingError(_)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Generating random images and adding noise to existing ones with the
+//! [rand](https://crates.io/crates/rand) crate, such as for fuzzing and benchmarking without
+//! checking fixtures into a repository. Requires the `rand` feature.
+//!
+//! Every channel is drawn in turn from the generator, so a generator seeded with the same value
+//! always produces the same image.
+
+use alloc::vec::Vec;
+
+use rand::Rng;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::{Pixel, Channel};
+use crate::convolve::AlphaMode;
+
+impl Pixel {
+    /// Creates a pixel with every channel, including alpha, drawn uniformly from the generator.
+    /// Requires the `rand` feature.
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Pixel {
+        Pixel::from(rng.random::<[u16; 4]>())
+    }
+}
+
+impl Farbfeld {
+    /// Creates an image of the given dimensions where every channel of every pixel, including
+    /// alpha, is drawn uniformly from the generator. Requires the `rand` feature.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// use rand::SeedableRng;
+    ///
+    /// let first = Farbfeld::random(4, 4, &mut rand_chacha::ChaCha8Rng::seed_from_u64(7));
+    /// let second = Farbfeld::random(4, 4, &mut rand_chacha::ChaCha8Rng::seed_from_u64(7));
+    ///
+    /// assert_eq!(first, second);
+    /// ```
+    pub fn random<R: Rng + ?Sized>(width: u32, height: u32, rng: &mut R) -> Farbfeld {
+        Farbfeld::random_with(width, height, rng, Pixel::random)
+    }
+
+    /// Creates an image as described by [random](#method.random), but with every pixel opaque.
+    /// Requires the `rand` feature.
+    pub fn random_opaque<R: Rng + ?Sized>(width: u32, height: u32, rng: &mut R) -> Farbfeld {
+        Farbfeld::random_with(width, height, rng, |rng| {
+            let [red, green, blue] = rng.random::<[u16; 3]>();
+            Pixel::new(red, green, blue, u16::MAX)
+        })
+    }
+
+    /// Adds noise drawn uniformly from `-amplitude` to `amplitude` to the red, green and blue
+    /// channels of every pixel, saturating at 0 and 65535. Requires the `rand` feature.
+    pub fn add_noise<R: Rng + ?Sized>(&mut self, amplitude: u16, rng: &mut R) {
+        self.add_noise_with(amplitude, rng, AlphaMode::Preserve);
+    }
+
+    /// Adds noise drawn uniformly from `-amplitude` to `amplitude` to every channel of every pixel,
+    /// saturating at 0 and 65535, with the alpha channel treated as described by `alpha`. Each
+    /// channel gets its own noise. Requires the `rand` feature.
+    pub fn add_noise_with<R: Rng + ?Sized>(&mut self, amplitude: u16, rng: &mut R, alpha: AlphaMode) {
+        if amplitude == 0 {
+            return;
+        }
+        let amplitude = amplitude as i32;
+        self.map_noisy_channels(alpha, |value| {
+            (value as i32 + rng.random_range(-amplitude..=amplitude)).clamp(0, u16::MAX as i32) as u16
+        });
+    }
+
+    /// Adds normally distributed noise with a standard deviation of `sigma` to the red, green and
+    /// blue channels of every pixel, rounding to the nearest value and saturating at 0 and 65535.
+    /// Requires the `rand` and `std` features.
+    #[cfg(feature = "std")]
+    pub fn add_gaussian_noise<R: Rng + ?Sized>(&mut self, sigma: f32, rng: &mut R) {
+        self.add_gaussian_noise_with(sigma, rng, AlphaMode::Preserve);
+    }
+
+    /// Adds normally distributed noise with a standard deviation of `sigma` to every channel of
+    /// every pixel, as described by [add_gaussian_noise](#method.add_gaussian_noise), with the
+    /// alpha channel treated as described by `alpha`. A sigma which isn't positive leaves the
+    /// image unchanged. Requires the `rand` and `std` features.
+    #[cfg(feature = "std")]
+    pub fn add_gaussian_noise_with<R: Rng + ?Sized>(&mut self, sigma: f32, rng: &mut R, alpha: AlphaMode) {
+        if sigma.is_nan() || sigma <= 0.0 {
+            return;
+        }
+        self.map_noisy_channels(alpha, |value| {
+            // The Box-Muller transform, with the first sample moved into (0, 1] to avoid ln(0).
+            let (first, second) = (1.0 - rng.random::<f64>(), rng.random::<f64>());
+            let normal = (-2.0 * first.ln()).sqrt() * (2.0 * core::f64::consts::PI * second).cos();
+            // Casting saturates, clamping the channel to 0 and 65535.
+            (value as f64 + normal * sigma as f64 + 0.5) as u16
+        });
+    }
+
+    /// Replaces each channel with the result of `noisy`, leaving alpha unchanged unless `alpha` is
+    /// [AlphaMode::Process](convolve/enum.AlphaMode.html).
+    fn map_noisy_channels<F: FnMut(u16) -> u16>(&mut self, alpha: AlphaMode, mut noisy: F) {
+        self.map_channels(|channel, value| match (channel, alpha) {
+            (Channel::Alpha, AlphaMode::Preserve) => value,
+            _ => noisy(value)
+        });
+    }
+
+    /// Creates an image of the given dimensions with each pixel created by `pixel` in row-major
+    /// order.
+    fn random_with<R, F>(width: u32, height: u32, rng: &mut R, mut pixel: F) -> Farbfeld
+        where R: Rng + ?Sized, F: FnMut(&mut R) -> Pixel {
+        let pixels: Vec<Pixel> = (0..width as u64 * height as u64).map(|_| pixel(rng)).collect();
+        Farbfeld::new(width, height, pixels).expect("generated images match their dimensions")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_seeded_is_deterministic() {
+        let first = Farbfeld::random(16, 9, &mut ChaCha8Rng::seed_from_u64(42));
+        assert_eq!(first, Farbfeld::random(16, 9, &mut ChaCha8Rng::seed_from_u64(42)));
+        assert_ne!(first, Farbfeld::random(16, 9, &mut ChaCha8Rng::seed_from_u64(43)));
+        assert!(first.pixels().windows(2).any(|pair| pair[0] != pair[1]));
+
+        let first = Farbfeld::random_opaque(5, 5, &mut ChaCha8Rng::seed_from_u64(1));
+        assert_eq!(first, Farbfeld::random_opaque(5, 5, &mut ChaCha8Rng::seed_from_u64(1)));
+        assert_eq!(Pixel::random(&mut ChaCha8Rng::seed_from_u64(9)), Pixel::random(&mut ChaCha8Rng::seed_from_u64(9)));
+    }
+
+    fn grey(value: u16) -> Farbfeld {
+        Farbfeld::new(256, 256, vec![Pixel::new(value, value, value, 40000); 65536]).unwrap()
+    }
+
+    /// Returns the mean of the red channel across the image.
+    fn mean_red(farb: &Farbfeld) -> f64 {
+        farb.pixels().iter().map(|pixel| *pixel.red() as f64).sum::<f64>() / farb.pixels().len() as f64
+    }
+
+    #[test]
+    fn test_add_noise() {
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let mut farb = grey(32768);
+        farb.add_noise(0, &mut rng);
+        assert_eq!(grey(32768), farb);
+
+        farb.add_noise(1000, &mut rng);
+        assert!((mean_red(&farb) - 32768.0).abs() < 10.0, "mean {}", mean_red(&farb));
+        assert!(farb.pixels().iter().all(|pixel| pixel.alpha() == &40000));
+        assert!(farb.pixels().iter().all(|pixel| (31768..=33768).contains(pixel.green())));
+        assert!(farb.pixels().iter().any(|pixel| pixel.red() != &32768));
+
+        let mut alpha = grey(32768);
+        alpha.add_noise_with(1000, &mut rng, AlphaMode::Process);
+        assert!(alpha.pixels().iter().any(|pixel| pixel.alpha() != &40000));
+    }
+
+    #[test]
+    fn test_add_noise_saturates() {
+        let mut rng = ChaCha8Rng::seed_from_u64(6);
+        let mut black = grey(0);
+        black.add_noise(1000, &mut rng);
+        assert!(black.pixels().iter().all(|pixel| pixel.red() <= &1000));
+        let mut white = grey(65535);
+        white.add_noise(1000, &mut rng);
+        assert!(white.pixels().iter().all(|pixel| pixel.blue() >= &64535));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_add_gaussian_noise() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut farb = grey(32768);
+        farb.add_gaussian_noise(0.0, &mut rng);
+        assert_eq!(grey(32768), farb);
+
+        farb.add_gaussian_noise(500.0, &mut rng);
+        let mean = mean_red(&farb);
+        let deviation = (farb.pixels().iter().map(|pixel| (*pixel.red() as f64 - mean).powi(2)).sum::<f64>()
+                         / farb.pixels().len() as f64).sqrt();
+        assert!((mean - 32768.0).abs() < 10.0, "mean {}", mean);
+        assert!((deviation - 500.0).abs() < 10.0, "deviation {}", deviation);
+        assert!(farb.pixels().iter().all(|pixel| pixel.alpha() == &40000));
+
+        let mut white = grey(65535);
+        white.add_gaussian_noise_with(5000.0, &mut rng, AlphaMode::Process);
+        assert!(white.pixels().iter().any(|pixel| pixel.red() == &65535));
+        assert!(white.pixels().iter().any(|pixel| pixel.alpha() != &40000));
+    }
+
+    #[test]
+    fn test_random_opaque() {
+        let farb = Farbfeld::random_opaque(8, 8, &mut ChaCha8Rng::seed_from_u64(3));
+        assert!(farb.pixels().iter().all(|pixel| pixel.alpha() == &65535));
+        assert!(Farbfeld::random(0, 8, &mut ChaCha8Rng::seed_from_u64(3)).pixels().is_empty());
+    }
+}
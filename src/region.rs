@@ -0,0 +1,266 @@
+//! Visiting the pixels of a rectangle of a Farbfeld image without copying it.
+
+use core::iter::{ExactSizeIterator, FusedIterator};
+use core::slice;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+use crate::error::*;
+
+/// An iterator over the pixels of a rectangle of a Farbfeld image, created by
+/// [Farbfeld::region_pixels](../struct.Farbfeld.html#method.region_pixels). It produces the
+/// x and y coordinates of each pixel within the image, together with a reference to the pixel,
+/// row by row.
+#[derive(Debug, Clone)]
+pub struct RegionIter<'a> {
+    rows: slice::Chunks<'a, Pixel>,
+    row: slice::Iter<'a, Pixel>,
+    x: u32,
+    y: u32,
+    width: u32,
+    total: usize,
+    remaining: usize
+}
+
+/// An iterator over the pixels of a rectangle of a Farbfeld image, created by
+/// [Farbfeld::region_pixels_mut](../struct.Farbfeld.html#method.region_pixels_mut). It
+/// produces the x and y coordinates of each pixel within the image, together with a mutable
+/// reference to the pixel, row by row.
+#[derive(Debug)]
+pub struct RegionIterMut<'a> {
+    rows: slice::ChunksMut<'a, Pixel>,
+    row: slice::IterMut<'a, Pixel>,
+    x: u32,
+    y: u32,
+    width: u32,
+    total: usize,
+    remaining: usize
+}
+
+impl<'a> RegionIter<'a> {
+    /// Returns the coordinates of the next pixel, which must exist.
+    fn coordinates(&self) -> (u32, u32) {
+        let index = self.total - self.remaining;
+        (self.x + (index % self.width as usize) as u32, self.y + (index / self.width as usize) as u32)
+    }
+}
+
+impl<'a> RegionIterMut<'a> {
+    /// Returns the coordinates of the next pixel, which must exist.
+    fn coordinates(&self) -> (u32, u32) {
+        let index = self.total - self.remaining;
+        (self.x + (index % self.width as usize) as u32, self.y + (index / self.width as usize) as u32)
+    }
+}
+
+impl<'a> Iterator for RegionIter<'a> {
+    type Item = (u32, u32, &'a Pixel);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (x, y) = self.coordinates();
+        let pixel = match self.row.next() {
+            Some(pixel) => pixel,
+            None => {
+                let row = self.rows.next()?;
+                self.row = row[self.x as usize..(self.x + self.width) as usize].iter();
+                self.row.next()?
+            }
+        };
+        self.remaining -= 1;
+        Some((x, y, pixel))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for RegionIter<'a> {
+    fn len(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl<'a> FusedIterator for RegionIter<'a> {}
+
+impl<'a> Iterator for RegionIterMut<'a> {
+    type Item = (u32, u32, &'a mut Pixel);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (x, y) = self.coordinates();
+        let pixel = match self.row.next() {
+            Some(pixel) => pixel,
+            None => {
+                let row = self.rows.next()?;
+                self.row = row[self.x as usize..(self.x + self.width) as usize].iter_mut();
+                self.row.next()?
+            }
+        };
+        self.remaining -= 1;
+        Some((x, y, pixel))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for RegionIterMut<'a> {
+    fn len(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl<'a> FusedIterator for RegionIterMut<'a> {}
+
+impl Farbfeld {
+    /// Returns the rows of the image containing the rectangle, which must already have been
+    /// checked by check_region.
+    fn region_rows(&self, y: u32, height: u32) -> core::ops::Range<usize> {
+        let width = *self.width() as usize;
+        y as usize * width..(y + height) as usize * width
+    }
+
+    /// Returns an iterator over the pixels of the rectangle of the image with its top left corner
+    /// at (x, y) and the given dimensions, without copying them. It produces the coordinates of
+    /// each pixel within the image and a reference to it, row by row.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(3, 2, (0..6_u16).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+    /// let mut iter = farb.region_pixels(1, 0, 2, 2).unwrap();
+    ///
+    /// assert_eq!(4, iter.len());
+    /// assert_eq!(Some((1, 0, &farb[1])), iter.next());
+    /// assert_eq!(Some((2, 0, &farb[2])), iter.next());
+    /// assert_eq!(Some((1, 1, &farb[4])), iter.next());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::RegionOutOfBounds](error/enum.Error.html) if the rectangle doesn't fit
+    /// within the image.
+    pub fn region_pixels(&self, x: u32, y: u32, width: u32, height: u32) -> Result<RegionIter<'_>> {
+        self.check_region(x, y, width, height)?;
+        let rows = self.region_rows(y, height);
+        let total = width as usize * height as usize;
+        Ok(RegionIter {
+            rows: self.pixels()[rows].chunks((*self.width() as usize).max(1)),
+            row: [].iter(),
+            x,
+            y,
+            width,
+            total,
+            remaining: total
+        })
+    }
+
+    /// Returns an iterator over the pixels of the rectangle of the image with its top left corner
+    /// at (x, y) and the given dimensions, producing the coordinates of each pixel within the
+    /// image and a mutable reference to it, row by row. Pixels outside the rectangle can't be
+    /// changed.
+    ///
+    /// # Errors
+    /// Returns an [Error::RegionOutOfBounds](error/enum.Error.html) if the rectangle doesn't fit
+    /// within the image.
+    pub fn region_pixels_mut(&mut self, x: u32, y: u32, width: u32, height: u32) -> Result<RegionIterMut<'_>> {
+        self.check_region(x, y, width, height)?;
+        let rows = self.region_rows(y, height);
+        let total = width as usize * height as usize;
+        let image_width = (*self.width() as usize).max(1);
+        Ok(RegionIterMut {
+            rows: self[rows].chunks_mut(image_width),
+            row: [].iter_mut(),
+            x,
+            y,
+            width,
+            total,
+            remaining: total
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    use crate::patterns::{linear_gradient, GradientDirection};
+
+    fn image() -> Farbfeld {
+        let (from, to) = (Pixel::new(0_u16, 1000, 65535, 7), Pixel::new(65535_u16, 20000, 3, 60000));
+        linear_gradient(13, 9, from, to, GradientDirection::Diagonal)
+    }
+
+    fn sum<'a, I: Iterator<Item = &'a Pixel>>(pixels: I) -> [u64; 4] {
+        let mut sum = [0; 4];
+        for pixel in pixels {
+            for (sum, &value) in sum.iter_mut().zip(pixel.iter()) {
+                *sum += value as u64;
+            }
+        }
+        sum
+    }
+
+    #[test]
+    fn test_region_pixels() {
+        let farb = image();
+        for &(x, y, width, height) in &[(0, 0, 13, 9), (3, 2, 4, 5), (9, 4, 4, 5), (12, 8, 1, 1), (5, 5, 0, 3), (13, 9, 0, 0)] {
+            let crop = farb.crop(x, y, width, height).unwrap();
+            let iter = farb.region_pixels(x, y, width, height).unwrap();
+            assert_eq!((width * height) as usize, iter.len());
+            assert_eq!(sum(crop.pixels().iter()), sum(iter.clone().map(|(_, _, pixel)| pixel)));
+            for (i, (px, py, pixel)) in iter.enumerate() {
+                let (cx, cy) = (i as u32 % width, i as u32 / width);
+                assert_eq!((x + cx, y + cy), (px, py));
+                assert_eq!(&crop[(cy * width + cx) as usize], pixel);
+                assert_eq!(&farb[(py * 13 + px) as usize], pixel);
+            }
+        }
+
+        let mut iter = farb.region_pixels(11, 7, 2, 2).unwrap();
+        assert_eq!(4, iter.by_ref().count());
+        assert_eq!(None, iter.next());
+        assert_eq!(0, iter.len());
+    }
+
+    #[test]
+    fn test_region_pixels_mut() {
+        let mut farb = image();
+        let mut iter = farb.region_pixels_mut(10, 3, 3, 6).unwrap();
+        assert_eq!(18, iter.len());
+        iter.next();
+        assert_eq!((17, Some(17)), iter.size_hint());
+        for (x, y, pixel) in farb.region_pixels_mut(10, 3, 3, 6).unwrap() {
+            *pixel = Pixel::new(x as u16, y as u16, 0, 0);
+        }
+        for (i, pixel) in farb.pixels().iter().enumerate() {
+            let (x, y) = (i as u32 % 13, i as u32 / 13);
+            if x >= 10 && y >= 3 {
+                assert_eq!(&Pixel::new(x as u16, y as u16, 0, 0), pixel);
+            } else {
+                assert_eq!(&image()[i], pixel);
+            }
+        }
+    }
+
+    #[test]
+    fn test_region_out_of_bounds() {
+        let mut farb = image();
+        for &(x, y, width, height) in &[(0, 0, 14, 1), (10, 0, 4, 1), (0, 9, 1, 1), (0, 5, 1, 5), (u32::MAX, 0, 2, 1)] {
+            match farb.region_pixels(x, y, width, height) {
+                Err(Error::RegionOutOfBounds(..)) => {},
+                res => panic!("Unexpected result {:?}", res)
+            }
+            assert!(farb.region_pixels_mut(x, y, width, height).is_err());
+        }
+        let empty = Farbfeld::new(0, 0, Vec::new()).unwrap();
+        assert_eq!(0, empty.region_pixels(0, 0, 0, 0).unwrap().len());
+    }
+}
@@ -0,0 +1,493 @@
+//! Resizing Farbfeld images with separable resampling filters.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::iter::{ExactSizeIterator, FusedIterator};
+
+use crate::farbfeld::Farbfeld;
+use crate::patterns;
+use crate::pixel::Pixel;
+
+/// How pixels are resampled when resizing an image.
+///
+/// When downscaling, every filter except `Nearest` is stretched to cover the source pixels which
+/// fall within each destination pixel, so fine detail is averaged rather than aliased.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeFilter {
+    /// Use the source pixel under the centre of each destination pixel. This is the fastest
+    /// filter and keeps hard edges, but aliases when downscaling.
+    Nearest,
+    /// Linearly interpolate between the nearest source pixels, with a triangle filter.
+    Bilinear,
+    /// Average the source pixels each destination pixel covers, weighted by how much of each is
+    /// covered, which is the most accurate filter for large reductions such as thumbnails. When
+    /// upscaling, each destination pixel is smaller than a source pixel and this is the same as
+    /// `Bilinear`.
+    Box,
+    /// Windowed sinc interpolation over 3 source pixels in each direction, which keeps the most
+    /// detail but can ring slightly around hard edges. Requires the `std` feature.
+    #[cfg(feature = "std")]
+    Lanczos3
+}
+
+impl ResizeFilter {
+    /// Returns how many source pixels either side of the centre the filter covers before it's
+    /// stretched.
+    fn support(self) -> f64 {
+        match self {
+            ResizeFilter::Nearest => 0.5,
+            ResizeFilter::Bilinear | ResizeFilter::Box => 1.0,
+            #[cfg(feature = "std")]
+            ResizeFilter::Lanczos3 => 3.0
+        }
+    }
+
+    /// Returns the weight of a source pixel `distance` pixels from the centre of the
+    /// destination pixel, with the filter stretched by `stretch`.
+    fn weight(self, distance: f64, stretch: f64) -> f64 {
+        let scaled = (distance / stretch).abs();
+        match self {
+            ResizeFilter::Nearest => if scaled < 0.5 { 1.0 } else { 0.0 },
+            ResizeFilter::Bilinear => (1.0 - scaled).max(0.0),
+            // The overlap of the source pixel with the destination pixel, both in source pixels.
+            ResizeFilter::Box => ((distance + 0.5).min(stretch / 2.0) - (distance - 0.5).max(-stretch / 2.0)).max(0.0),
+            #[cfg(feature = "std")]
+            ResizeFilter::Lanczos3 => lanczos3(scaled)
+        }
+    }
+}
+
+/// Returns the Lanczos kernel with a window of 3, `sinc(x) * sinc(x / 3)`, which is 0 outside
+/// -3 to 3.
+#[cfg(feature = "std")]
+fn lanczos3(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else if x.abs() < 3.0 {
+        let pi_x = core::f64::consts::PI * x;
+        3.0 * pi_x.sin() * (pi_x / 3.0).sin() / (pi_x * pi_x)
+    } else {
+        0.0
+    }
+}
+
+/// The source pixels contributing to one destination pixel, starting at `start`, and their
+/// weights, which sum to 1.
+#[derive(Debug)]
+struct Contributions {
+    start: usize,
+    weights: Vec<f64>
+}
+
+/// Finds the contributions of the source pixels to each destination pixel along one axis, when
+/// resizing from `source` pixels to `destination`. Source pixels outside the image are clamped
+/// to its edges. `source` must not be 0.
+fn contributions(source: u32, destination: u32, filter: ResizeFilter) -> Vec<Contributions> {
+    let scale = source as f64 / destination as f64;
+    let last = source as i64 - 1;
+    let stretch = if filter == ResizeFilter::Nearest { 1.0 } else { scale.max(1.0) };
+    let support = filter.support() * stretch;
+    (0..destination).map(|i| {
+        // Pixel i covers i to i + 1, so its centre is i + 0.5.
+        let centre = (i as f64 + 0.5) * scale;
+        if filter == ResizeFilter::Nearest {
+            return Contributions { start: (centre as i64).min(last) as usize, weights: vec![1.0] };
+        }
+        // Casting truncates towards 0, so widen the range by a pixel either side.
+        let (first, end) = ((centre - support) as i64 - 1, (centre + support) as i64 + 1);
+        let start = first.clamp(0, last);
+        let mut weights = vec![0.0; (end.clamp(0, last) - start + 1) as usize];
+        for j in first..=end {
+            weights[(j.clamp(0, last) - start) as usize] += filter.weight(j as f64 + 0.5 - centre, stretch);
+        }
+        let total: f64 = weights.iter().sum();
+        for weight in &mut weights {
+            *weight /= total;
+        }
+        Contributions { start: start as usize, weights }
+    }).collect()
+}
+
+/// An iterator over the mipmap levels of a Farbfeld image, created by
+/// [Farbfeld::mipmaps_iter](../struct.Farbfeld.html#method.mipmaps_iter). Each level is only
+/// produced when it's needed, so every level isn't held at once.
+#[derive(Debug, Clone)]
+pub struct Mipmaps {
+    next: Option<Farbfeld>
+}
+
+impl Iterator for Mipmaps {
+    type Item = Farbfeld;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let level = self.next.take()?;
+        let (width, height) = (*level.width(), *level.height());
+        if (width > 1 || height > 1) && !level.pixels().is_empty() {
+            self.next = Some(level.downscale_box((width / 2).max(1), (height / 2).max(1)));
+        }
+        Some(level)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Mipmaps {
+    fn len(&self) -> usize {
+        match self.next {
+            Some(ref level) if level.pixels().is_empty() => 1,
+            Some(ref level) => (32 - (*level.width()).max(*level.height()).leading_zeros()) as usize,
+            None => 0
+        }
+    }
+}
+
+impl FusedIterator for Mipmaps {}
+
+impl Farbfeld {
+    /// Returns a copy of the image resized to the given dimensions with the filter. Each
+    /// dimension is resampled separately, with every channel including alpha resampled
+    /// independently. Pixels outside the image are clamped to its edges, and channels are rounded
+    /// to the nearest value and clamped to 0 and 65535. Resizing an empty image gives a
+    /// transparent black image.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::resize::ResizeFilter;
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(0_u16, 0, 0, 65535), Pixel::new(65535_u16, 65535, 65535, 65535)]).unwrap();
+    /// let resized = farb.resize(4, 1, ResizeFilter::Bilinear);
+    ///
+    /// assert_eq!(&[0, 16384, 49151, 65535], &resized.pixels().iter().map(|pixel| *pixel.red()).collect::<Vec<_>>()[..]);
+    /// ```
+    pub fn resize(&self, width: u32, height: u32, filter: ResizeFilter) -> Farbfeld {
+        let len = width as usize * height as usize;
+        if self.pixels().is_empty() || len == 0 {
+            return Farbfeld::new(width, height, vec![Pixel::default(); len]).expect("the pixels fill the dimensions");
+        }
+        let source_width = *self.width() as usize;
+        // Resize each row, then each column of the result.
+        let mut rows = Vec::with_capacity(width as usize * *self.height() as usize);
+        let columns = contributions(*self.width(), width, filter);
+        for row in self.pixels().chunks(source_width) {
+            for column in &columns {
+                let mut sums = [0.0; 4];
+                for (pixel, weight) in row[column.start..].iter().zip(&column.weights) {
+                    for (sum, channel) in sums.iter_mut().zip(pixel.iter()) {
+                        *sum += *channel as f64 * weight;
+                    }
+                }
+                rows.push(sums);
+            }
+        }
+        let mut pixels = Vec::with_capacity(len);
+        for row in contributions(*self.height(), height, filter) {
+            for x in 0..width as usize {
+                let mut sums = [0.0; 4];
+                for (i, weight) in row.weights.iter().enumerate() {
+                    for (sum, channel) in sums.iter_mut().zip(rows[(row.start + i) * width as usize + x].iter()) {
+                        *sum += channel * weight;
+                    }
+                }
+                // Casting saturates, clamping any overshoot to 0 and 65535.
+                pixels.push(Pixel::from(sums.map(|sum| (sum + 0.5) as u16)));
+            }
+        }
+        Farbfeld::new(width, height, pixels).expect("the pixels fill the dimensions")
+    }
+
+    /// Returns a copy of the image downscaled to the given dimensions with the
+    /// [Box](resize/enum.ResizeFilter.html) filter, so each pixel is the exact average of the
+    /// rectangle of the source it covers, as described by [resize](#method.resize). Any dimension
+    /// which is larger than the source is upscaled with bilinear interpolation instead.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(3, 1, vec![Pixel::new(0_u16, 0, 0, 0), Pixel::new(3000_u16, 0, 0, 0), Pixel::new(6000_u16, 0, 0, 0)]).unwrap();
+    ///
+    /// assert_eq!(&[Pixel::new(1000_u16, 0, 0, 0), Pixel::new(5000_u16, 0, 0, 0)], farb.downscale_box(2, 1).pixels());
+    /// ```
+    pub fn downscale_box(&self, width: u32, height: u32) -> Farbfeld {
+        self.resize(width, height, ResizeFilter::Box)
+    }
+
+    /// Returns a copy of the image scaled down to fit within `max_width` by `max_height`, keeping
+    /// its aspect ratio. An image which already fits is returned unscaled, as images are never
+    /// scaled up. The side which fills the box takes its size, and the other side is rounded to
+    /// the nearest pixel, with halves rounded up, but never below 1. An empty image, or a box with
+    /// a zero dimension, gives an empty image.
+    ///
+    /// Images reduced to half their size or less are downscaled with the
+    /// [Box](resize/enum.ResizeFilter.html) filter, and smaller reductions use `Bilinear`.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(1000, 500, vec![Pixel::default(); 500000]).unwrap();
+    /// let thumbnail = farb.thumbnail(100, 100);
+    ///
+    /// assert_eq!((100, 50), (*thumbnail.width(), *thumbnail.height()));
+    /// ```
+    pub fn thumbnail(&self, max_width: u32, max_height: u32) -> Farbfeld {
+        let (width, height) = (*self.width(), *self.height());
+        if width <= max_width && height <= max_height {
+            return self.clone();
+        }
+        if self.pixels().is_empty() || max_width == 0 || max_height == 0 {
+            return Farbfeld::new(0, 0, Vec::new()).expect("an empty image has no pixels");
+        }
+        // Rounds `side * numerator / denominator` to the nearest pixel, with halves rounded up.
+        let fit = |side: u32, numerator: u32, denominator: u32| {
+            let (side, numerator, denominator) = (side as u64, numerator as u64, denominator as u64);
+            ((side * numerator * 2 + denominator) / (denominator * 2)).max(1) as u32
+        };
+        let (fitted_width, fitted_height) = if width as u64 * max_height as u64 >= height as u64 * max_width as u64 {
+            (max_width, fit(height, max_width, width))
+        } else {
+            (fit(width, max_height, height), max_height)
+        };
+        let filter = if fitted_width * 2 <= width || fitted_height * 2 <= height {
+            ResizeFilter::Box
+        } else {
+            ResizeFilter::Bilinear
+        };
+        self.resize(fitted_width, fitted_height, filter)
+    }
+
+    /// Returns an image of exactly `width` by `height` containing the
+    /// [thumbnail](#method.thumbnail) of the image, centred and surrounded by `fill`. When the
+    /// thumbnail can't be centred exactly, the extra pixel of space is on its right or below it.
+    /// The thumbnail's pixels replace the fill rather than being blended with it.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 1, 1, 1); 2]).unwrap();
+    /// let fill = Pixel::new(0_u16, 0, 0, 65535);
+    /// let thumbnail = farb.thumbnail_exact(2, 4, fill);
+    ///
+    /// assert_eq!(&[fill, fill, Pixel::new(1_u16, 1, 1, 1), Pixel::new(1_u16, 1, 1, 1), fill, fill, fill, fill], thumbnail.pixels());
+    /// ```
+    pub fn thumbnail_exact(&self, width: u32, height: u32, fill: Pixel) -> Farbfeld {
+        let thumbnail = self.thumbnail(width, height);
+        let mut canvas = patterns::solid(width, height, fill);
+        let (x, y) = ((width - *thumbnail.width()) / 2, (height - *thumbnail.height()) / 2);
+        for row in 0..*thumbnail.height() {
+            let start = ((y + row) as usize) * width as usize + x as usize;
+            canvas[start..start + *thumbnail.width() as usize]
+                .copy_from_slice(thumbnail.row(row).expect("the row is within the thumbnail"));
+        }
+        canvas
+    }
+
+    /// Returns the mipmap chain of the image, starting with a copy of the image itself. Each
+    /// following level halves the dimensions of the previous one, rounding down but never below 1,
+    /// and is downscaled from it with the [Box](resize/enum.ResizeFilter.html) filter, until the
+    /// final level is 1x1. There are `1 + floor(log2(max(width, height)))` levels, and an empty
+    /// image only has itself.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let sizes: Vec<_> = Farbfeld::new(5, 12, vec![Pixel::default(); 60]).unwrap().mipmaps().iter()
+    ///     .map(|level| (*level.width(), *level.height()))
+    ///     .collect();
+    ///
+    /// assert_eq!(vec![(5, 12), (2, 6), (1, 3), (1, 1)], sizes);
+    /// ```
+    pub fn mipmaps(&self) -> Vec<Farbfeld> {
+        self.mipmaps_iter().collect()
+    }
+
+    /// Returns an iterator over the mipmap chain of the image, as described by
+    /// [mipmaps](#method.mipmaps), which only produces each level when it's needed.
+    pub fn mipmaps_iter(&self) -> Mipmaps {
+        Mipmaps { next: Some(self.clone()) }
+    }
+
+    /// Returns a copy of the image resized to the given dimensions with the
+    /// [Lanczos3](resize/enum.ResizeFilter.html) filter, as described by
+    /// [resize](#method.resize). Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn resize_lanczos3(&self, width: u32, height: u32) -> Farbfeld {
+        self.resize(width, height, ResizeFilter::Lanczos3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters() -> Vec<ResizeFilter> {
+        vec![ResizeFilter::Nearest, ResizeFilter::Bilinear, ResizeFilter::Box, #[cfg(feature = "std")] ResizeFilter::Lanczos3]
+    }
+
+    /// A smooth image sampled at the centre of each pixel, scaled so each pixel covers `scale`
+    /// units.
+    #[cfg(feature = "std")]
+    fn smooth(size: u32, scale: f64) -> Farbfeld {
+        let pixels = (0..size * size).map(|i| {
+            let (x, y) = (((i % size) as f64 + 0.5) * scale, ((i / size) as f64 + 0.5) * scale);
+            let value = (0.5 + 0.25 * (x / 7.0).sin() + 0.25 * (y / 5.0).cos()) * 65535.0;
+            Pixel::new(value as u16, (65535.0 - value) as u16, 30000, 65535)
+        }).collect();
+        Farbfeld::new(size, size, pixels).unwrap()
+    }
+
+    #[test]
+    fn test_resize_identity() {
+        let farb = patterns::checkerboard(7, 5, 1, Pixel::new(0_u16, 1, 2, 3), Pixel::new(65535_u16, 40000, 2, 0));
+        for filter in filters() {
+            let resized = farb.resize(7, 5, filter);
+            for (pixel, expected) in resized.pixels().iter().zip(farb.pixels()) {
+                assert!(pixel.distance(expected) <= 1, "{:?} isn't close to {:?} with {:?}", pixel, expected, filter);
+            }
+        }
+    }
+
+    #[test]
+    fn test_resize_empty() {
+        let empty = Farbfeld::new(0, 3, Vec::new()).unwrap();
+        assert_eq!(patterns::solid(2, 2, Pixel::default()), empty.resize(2, 2, ResizeFilter::Bilinear));
+        let farb = patterns::solid(3, 3, Pixel::new(1_u16, 2, 3, 4));
+        assert_eq!(Farbfeld::new(0, 5, Vec::new()).unwrap(), farb.resize(0, 5, ResizeFilter::Nearest));
+    }
+
+    #[test]
+    fn test_resize_constant() {
+        let farb = patterns::solid(5, 3, Pixel::new(100_u16, 20000, 65535, 7));
+        for filter in filters() {
+            assert_eq!(patterns::solid(11, 2, Pixel::new(100_u16, 20000, 65535, 7)), farb.resize(11, 2, filter));
+        }
+    }
+
+    #[test]
+    fn test_resize_nearest() {
+        let farb = Farbfeld::new(4, 1, (0..4_u16).map(|i| Pixel::new(i, 0, 0, 0)).collect()).unwrap();
+        let reds = |farb: &Farbfeld| farb.pixels().iter().map(|pixel| *pixel.red()).collect::<Vec<_>>();
+        assert_eq!(vec![0, 0, 1, 1, 2, 2, 3, 3], reds(&farb.resize(8, 1, ResizeFilter::Nearest)));
+        assert_eq!(vec![1, 3], reds(&farb.resize(2, 1, ResizeFilter::Nearest)));
+        assert_eq!(vec![2, 2, 2], reds(&farb.resize(1, 3, ResizeFilter::Nearest)));
+    }
+
+    #[test]
+    fn test_downscale_box() {
+        let black = Pixel::new(0_u16, 0, 0, 65535);
+        let white = Pixel::new(65535_u16, 65535, 65535, 65535);
+        let checker = patterns::checkerboard(2, 2, 1, black, white);
+        assert_eq!(&[Pixel::new(32768_u16, 32768, 32768, 65535)], checker.downscale_box(1, 1).pixels());
+
+        let fine = patterns::checkerboard(60, 40, 1, black, white);
+        assert!(fine.downscale_box(7, 3).pixels().iter().all(|pixel| pixel.red().abs_diff(32768) <= 2000));
+
+        let farb = Farbfeld::new(2, 1, vec![black, white]).unwrap();
+        assert_eq!(farb.resize(5, 3, ResizeFilter::Bilinear), farb.downscale_box(5, 3));
+    }
+
+    #[test]
+    fn test_downscale_box_energy() {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let pixels = (0..30 * 20).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            Pixel::new(state as u16, (state >> 16) as u16, (state >> 32) as u16, 65535)
+        }).collect();
+        let farb = Farbfeld::new(30, 20, pixels).unwrap();
+        let energy = |farb: &Farbfeld, area: f64| farb.pixels().iter().map(|pixel| pixel.luminance() as f64).sum::<f64>() * area;
+
+        let downscaled = farb.downscale_box(7, 9);
+        let (before, after) = (energy(&farb, 1.0), energy(&downscaled, (30.0 / 7.0) * (20.0 / 9.0)));
+        // Each pixel's luminance is rounded, as is each downscaled channel.
+        assert!((before - after).abs() <= 1000.0, "{} isn't close to {}", after, before);
+    }
+
+    #[test]
+    fn test_mipmaps() {
+        for &(width, height) in &[(1, 1), (2, 2), (256, 256), (300, 17), (1, 1000), (7, 3)] {
+            let farb = Farbfeld::new(width, height, vec![Pixel::new(1_u16, 2, 3, 4); (width * height) as usize]).unwrap();
+            let levels = farb.mipmaps();
+            let max = width.max(height) as f64;
+            assert_eq!(1 + max.log2().floor() as usize, levels.len(), "{}x{}", width, height);
+            assert_eq!(levels.len(), farb.mipmaps_iter().len());
+            assert_eq!(farb, levels[0]);
+            assert_eq!((1, 1), (*levels[levels.len() - 1].width(), *levels[levels.len() - 1].height()));
+            assert!(levels.iter().all(|level| level.pixels().iter().all(|pixel| pixel == &Pixel::new(1_u16, 2, 3, 4))));
+        }
+
+        let black = Pixel::new(0_u16, 0, 0, 65535);
+        let white = Pixel::new(65535_u16, 65535, 65535, 65535);
+        let levels = patterns::checkerboard(2, 2, 1, black, white).mipmaps();
+        assert_eq!(&[Pixel::new(32768_u16, 32768, 32768, 65535)], levels[1].pixels());
+
+        let mut iter = Farbfeld::new(0, 4, Vec::new()).unwrap().mipmaps_iter();
+        assert_eq!(1, iter.len());
+        assert_eq!(Some((0, 4)), iter.next().map(|level| (*level.width(), *level.height())));
+        assert_eq!(None, iter.next());
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let dimensions = |farb: &Farbfeld| (*farb.width(), *farb.height());
+        let wide = Farbfeld::new(1000, 500, vec![Pixel::new(5_u16, 6, 7, 8); 500000]).unwrap();
+        assert_eq!((100, 50), dimensions(&wide.thumbnail(100, 100)));
+        assert_eq!((40, 20), dimensions(&wide.thumbnail(100, 20)));
+        assert!(wide.thumbnail(100, 100).pixels().iter().all(|pixel| pixel == &Pixel::new(5_u16, 6, 7, 8)));
+        assert_eq!((750, 375), dimensions(&wide.thumbnail(750, 1000)));
+
+        let small = patterns::checkerboard(30, 20, 3, Pixel::new(0_u16, 0, 0, 0), Pixel::new(1_u16, 2, 3, 4));
+        assert_eq!(small, small.thumbnail(30, 20));
+        assert_eq!(small, small.thumbnail(500, 500));
+
+        let thin = Farbfeld::new(1, 1000, vec![Pixel::default(); 1000]).unwrap();
+        assert_eq!((1, 10), dimensions(&thin.thumbnail(10, 10)));
+        assert_eq!((1, 100), dimensions(&Farbfeld::new(3, 1000, vec![Pixel::default(); 3000]).unwrap().thumbnail(100, 100)));
+        assert_eq!((0, 0), dimensions(&wide.thumbnail(0, 10)));
+    }
+
+    #[test]
+    fn test_thumbnail_exact() {
+        let colour = Pixel::new(5_u16, 6, 7, 8);
+        let fill = Pixel::new(65535_u16, 0, 0, 65535);
+        let wide = Farbfeld::new(1000, 500, vec![colour; 500000]).unwrap();
+        let thumbnail = wide.thumbnail_exact(100, 100, fill);
+        assert_eq!((100, 100), (*thumbnail.width(), *thumbnail.height()));
+        for y in 0..100 {
+            let expected = if (25..75).contains(&y) { colour } else { fill };
+            assert!(thumbnail.row(y).unwrap().iter().all(|pixel| pixel == &expected), "row {}", y);
+        }
+
+        let tall = Farbfeld::new(2, 5, vec![colour; 10]).unwrap();
+        let thumbnail = tall.thumbnail_exact(5, 5, fill);
+        for y in 0..5 {
+            assert_eq!(&[fill, colour, colour, fill, fill], thumbnail.row(y).unwrap());
+        }
+        assert_eq!(patterns::solid(3, 2, fill), Farbfeld::new(0, 0, Vec::new()).unwrap().thumbnail_exact(3, 2, fill));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_lanczos3_checkerboard() {
+        let black = Pixel::new(0_u16, 0, 0, 65535);
+        let white = Pixel::new(65535_u16, 65535, 65535, 65535);
+        let farb = patterns::checkerboard(64, 64, 1, black, white);
+        let resized = farb.resize_lanczos3(16, 16);
+        for pixel in resized.pixels() {
+            assert!(pixel.red().abs_diff(32768) < 1000, "{:?} isn't mid-grey", pixel);
+        }
+        let nearest = farb.resize(16, 16, ResizeFilter::Nearest);
+        assert!(nearest.pixels().iter().all(|pixel| *pixel == black || *pixel == white));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_lanczos3_beats_nearest() {
+        let reference = smooth(40, 4.0);
+        let source = smooth(160, 1.0);
+        let lanczos = source.resize_lanczos3(40, 40).psnr(&reference).unwrap();
+        let nearest = source.resize(40, 40, ResizeFilter::Nearest).psnr(&reference).unwrap();
+        assert!(lanczos > nearest, "lanczos {} dB isn't better than nearest {} dB", lanczos, nearest);
+
+        let upscaled = reference.resize_lanczos3(160, 160).psnr(&source).unwrap();
+        let nearest = reference.resize(160, 160, ResizeFilter::Nearest).psnr(&source).unwrap();
+        assert!(upscaled > nearest, "lanczos {} dB isn't better than nearest {} dB", upscaled, nearest);
+    }
+}
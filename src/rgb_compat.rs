@@ -0,0 +1,97 @@
+//! Conversions between ruff's pixels and the [rgb](https://crates.io/crates/rgb) crate's RGBA
+//! types, used by much of the Rust imaging ecosystem. Requires the `rgb` feature.
+//!
+//! [Pixel](../pixel/struct.Pixel.html) and `RGBA<u16>` are both repr(C) structs of four u16
+//! channels in red, green, blue, alpha order, so slices of one can be viewed as slices of the
+//! other without copying.
+
+use core::mem;
+use core::slice;
+
+use rgb::{RGBA, RGBA16, RGBA8};
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::{Pixel, expand_channel};
+use crate::error::*;
+
+// The slice casts below rely on Pixel and RGBA16 having the same layout.
+const _: () = assert!(mem::size_of::<Pixel>() == mem::size_of::<RGBA16>());
+const _: () = assert!(mem::align_of::<Pixel>() == mem::align_of::<RGBA16>());
+
+impl From<RGBA16> for Pixel {
+    fn from(rgba: RGBA16) -> Pixel {
+        Pixel::new(rgba.r, rgba.g, rgba.b, rgba.a)
+    }
+}
+
+impl From<Pixel> for RGBA16 {
+    fn from(pixel: Pixel) -> RGBA16 {
+        RGBA::new(*pixel.red(), *pixel.green(), *pixel.blue(), *pixel.alpha())
+    }
+}
+
+impl From<RGBA8> for Pixel {
+    /// Expands each channel so that 255 becomes 65535.
+    fn from(rgba: RGBA8) -> Pixel {
+        Pixel::new(expand_channel(rgba.r), expand_channel(rgba.g), expand_channel(rgba.b),
+                   expand_channel(rgba.a))
+    }
+}
+
+impl Farbfeld {
+    /// Returns all the pixels in the image in row-major order as the rgb crate's RGBA16, without
+    /// copying.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2, 3, 4)]).unwrap();
+    ///
+    /// assert_eq!(&[rgb::RGBA::new(1, 2, 3, 4)], farb.as_rgba16_slice());
+    /// ```
+    pub fn as_rgba16_slice(&self) -> &[RGBA16] {
+        let pixels = self.pixels();
+        // Pixel and RGBA16 are repr(C) structs of four u16 channels in the same order, as checked
+        // by the assertions above.
+        unsafe { slice::from_raw_parts(pixels.as_ptr() as *const RGBA16, pixels.len()) }
+    }
+
+    /// Creates a new Farbfeld object with the given dimensions, copying the given pixels.
+    ///
+    /// # Errors
+    /// Returns an [Error::InvalidFarbfeldDimensions](error/enum.Error.html)
+    /// if width * height != pixels.len().
+    pub fn from_rgba16_slice(width: u32, height: u32, pixels: &[RGBA16]) -> Result<Farbfeld> {
+        Farbfeld::new(width, height, pixels.iter().map(|&rgba| Pixel::from(rgba)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixel_round_trip() {
+        let pixel = Pixel::new(1_u16, 2, 3, 4);
+        assert_eq!(RGBA::new(1, 2, 3, 4), RGBA16::from(pixel));
+        assert_eq!(pixel, Pixel::from(RGBA16::from(pixel)));
+        assert_eq!(Pixel::new(0_u16, 257, 65535, 32896), Pixel::from(RGBA8::new(0, 1, 255, 128)));
+    }
+
+    #[test]
+    fn test_slice_round_trip() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let rgba = farb.as_rgba16_slice();
+        assert_eq!(farb.pixels().len(), rgba.len());
+        assert!(farb.pixels().iter().zip(rgba).all(|(&pixel, &rgba)| RGBA16::from(pixel) == rgba));
+
+        let round_trip = Farbfeld::from_rgba16_slice(*farb.width(), *farb.height(), rgba).unwrap();
+        assert_eq!(farb.to_bytes(), round_trip.to_bytes());
+        assert!(Farbfeld::from_rgba16_slice(1, 1, rgba).is_err());
+    }
+
+    #[test]
+    fn test_slice_zero_copy() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        assert_eq!(farb.pixels().as_ptr() as *const u8, farb.as_rgba16_slice().as_ptr() as *const u8);
+    }
+}
@@ -0,0 +1,8 @@
+//! Alternative serde representations of Farbfeld images. Requires the `serde` feature.
+//!
+//! The derived representation of [Farbfeld](../struct.Farbfeld.html) serializes every pixel as a
+//! separate struct, which is very large for formats such as JSON. The modules here can be used
+//! with `#[serde(with = "...")]` on fields holding a Farbfeld image to use a smaller
+//! representation.
+
+pub mod compact;
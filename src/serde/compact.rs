@@ -0,0 +1,155 @@
+//! Serializes a Farbfeld image as a struct containing its `width`, `height` and `pixels`, with the
+//! pixels stored as a single byte buffer in the big endian format used by Farbfeld files.
+//! Deserializing checks the length of the buffer matches the dimensions.
+//!
+//! ```
+//! # extern crate serde_derive;
+//! # extern crate serde_json;
+//! # use ::ruff::*;
+//! #[derive(serde_derive::Serialize, serde_derive::Deserialize)]
+//! struct Sprite {
+//!     name: String,
+//!     #[serde(with = "ruff::serde::compact")]
+//!     image: Farbfeld
+//! }
+//!
+//! let sprite = Sprite { name: "dot".to_string(), image: Farbfeld::new(1, 1, vec![Pixel::default()]).unwrap() };
+//! let json = serde_json::to_string(&sprite).unwrap();
+//!
+//! assert_eq!(r#"{"name":"dot","image":{"width":1,"height":1,"pixels":[0,0,0,0,0,0,0,0]}}"#, json);
+//! ```
+
+use std::fmt;
+
+use ::serde::ser::{Serialize, Serializer, SerializeStruct};
+use ::serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+
+use crate::farbfeld::{self, Farbfeld};
+use crate::parser;
+
+/// Serializes the image as described by the [module](index.html).
+pub fn serialize<S: Serializer>(image: &Farbfeld, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("Farbfeld", 3)?;
+    state.serialize_field("width", image.width())?;
+    state.serialize_field("height", image.height())?;
+    state.serialize_field("pixels", &PixelBytes(image))?;
+    state.end()
+}
+
+/// Deserializes an image as described by the [module](index.html).
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Farbfeld, D::Error> {
+    let compact = Compact::deserialize(deserializer)?;
+    let expected = compact.width as u64 * compact.height as u64 * 8;
+    if compact.pixels.0.len() as u64 != expected {
+        return Err(de::Error::invalid_length(compact.pixels.0.len(), &&*format!("{} bytes", expected)));
+    }
+    parser::parse_pixels(&compact.pixels.0)
+        .and_then(|pixels| Farbfeld::new(compact.width, compact.height, pixels))
+        .map_err(de::Error::custom)
+}
+
+/// The pixels of an image, serialized as bytes.
+struct PixelBytes<'a>(&'a Farbfeld);
+
+impl<'a> Serialize for PixelBytes<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut bytes = Vec::with_capacity(self.0.pixels().len() * 8);
+        farbfeld::extend_row_bytes(&mut bytes, self.0.pixels());
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Farbfeld")]
+struct Compact {
+    width: u32,
+    height: u32,
+    pixels: ByteBuf
+}
+
+/// A byte buffer deserialized from either bytes or a sequence of u8s, as formats such as JSON
+/// produce.
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ByteBuf, D::Error> {
+        deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+}
+
+struct ByteBufVisitor;
+
+impl<'de> Visitor<'de> for ByteBufVisitor {
+    type Value = ByteBuf;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a byte buffer")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(bytes.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(bytes))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(1 << 20));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(ByteBuf(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::pixel::Pixel;
+
+    #[derive(Serialize, Deserialize)]
+    struct Compact(#[serde(with = "crate::serde::compact")] Farbfeld);
+
+    #[test]
+    fn test_json_round_trip() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let json = serde_json::to_string(&Compact(farb)).unwrap();
+        let Compact(round_trip) = serde_json::from_str(&json).unwrap();
+        assert_eq!(Farbfeld::from_file("test.ff").unwrap().to_bytes(), round_trip.to_bytes());
+    }
+
+    #[test]
+    fn test_bincode_round_trip() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let bytes = bincode::serialize(&Compact(farb)).unwrap();
+        let Compact(round_trip) = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(Farbfeld::from_file("test.ff").unwrap().to_bytes(), round_trip.to_bytes());
+    }
+
+    #[test]
+    fn test_smaller_than_derived() {
+        let farb = Farbfeld::from_file("test.ff").unwrap();
+        let derived_json = serde_json::to_string(&farb).unwrap();
+        let derived_bincode = bincode::serialize(&farb).unwrap();
+        let farb = Compact(farb);
+        let compact_json = serde_json::to_string(&farb).unwrap();
+        let compact_bincode = bincode::serialize(&farb).unwrap();
+
+        assert!(compact_json.len() * 2 < derived_json.len());
+        // 4 bytes each for the width and height, then 8 bytes for the length of the pixels.
+        assert_eq!(farb.0.pixels().len() * 8 + 16, compact_bincode.len());
+        assert!(compact_bincode.len() <= derived_bincode.len());
+    }
+
+    #[test]
+    fn test_wrong_length() {
+        let json = r#"{"width":1,"height":1,"pixels":[0,0,0,0,0,0,0]}"#;
+        assert!(serde_json::from_str::<Compact>(json).is_err());
+        let json = r#"{"width":2,"height":1,"pixels":[0,0,0,0,0,0,0,0]}"#;
+        assert!(serde_json::from_str::<Compact>(json).is_err());
+        let json = r#"{"width":1,"height":1,"pixels":[0,1,0,2,0,3,0,4]}"#;
+        assert_eq!(&[Pixel::new(1_u16, 2, 3, 4)], serde_json::from_str::<Compact>(json).unwrap().0.pixels());
+    }
+}
@@ -0,0 +1,216 @@
+//! Reading streams of consecutive Farbfeld images, such as animations written as back to back
+//! frames.
+
+use std::io::{self, Read};
+
+use crate::farbfeld::Farbfeld;
+use crate::error::*;
+use crate::parser;
+
+/// An iterator parsing consecutive Farbfeld images from a reader. Each image may have different
+/// dimensions.
+///
+/// The iterator ends when the reader reaches EOF exactly at the end of an image. If the reader
+/// ends part way through an image, or any other error occurs, the error is produced and the
+/// iterator ends.
+///
+/// # Examples
+/// ```
+/// # use ::ruff::*;
+/// let frame = Farbfeld::new(1, 1, vec![Pixel::new(1_u16, 2_u16, 3_u16, 4_u16)]).unwrap();
+/// let mut data = Vec::new();
+/// frame.save(&mut data).unwrap();
+/// frame.save(&mut data).unwrap();
+///
+/// let frames = FarbfeldStream::new(&data[..]).collect::<Vec<_>>();
+/// assert_eq!(2, frames.len());
+/// ```
+#[derive(Debug)]
+pub struct FarbfeldStream<R> {
+    reader: R,
+    done: bool
+}
+
+impl<R: Read> FarbfeldStream<R> {
+    /// Creates a new stream reading images from the given reader.
+    pub fn new(reader: R) -> FarbfeldStream<R> {
+        FarbfeldStream {
+            reader,
+            done: false
+        }
+    }
+
+    /// Consumes the stream, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the next image, returning None if the reader is at EOF before the first byte of the
+    /// header.
+    fn read_frame(&mut self) -> Result<Option<Farbfeld>> {
+        let (width, height) = match read_header(&mut self.reader)? {
+            Some(dimensions) => dimensions,
+            None => return Ok(None)
+        };
+
+        let len = (width as u64 * height as u64).checked_mul(8).ok_or(Error::InvalidFarbfeldDimensions)?;
+        let mut buff = Vec::new();
+        (&mut self.reader).take(len).read_to_end(&mut buff).map_err(Error::IoError)?;
+        if (buff.len() as u64) < len {
+            return Err(Error::UnexpectedEof { needed: Some(len - buff.len() as u64) });
+        }
+        parser::parse_pixels(&buff)
+            .and_then(|pixels| Farbfeld::new(width, height, pixels))
+            .map(Some)
+    }
+}
+
+impl<R: Read> Iterator for FarbfeldStream<R> {
+    type Item = Result<Farbfeld>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = self.read_frame();
+        match res {
+            Ok(Some(farb)) => Some(Ok(farb)),
+            Ok(None) => {
+                self.done = true;
+                None
+            },
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Parses every image in the given reader, as described by
+/// [FarbfeldStream](struct.FarbfeldStream.html).
+///
+/// # Errors
+/// Returns the first error produced while parsing the images.
+pub fn read_all_frames<R: Read>(reader: R) -> Result<Vec<Farbfeld>> {
+    FarbfeldStream::new(reader).collect()
+}
+
+/// Reads and parses a header from the reader, returning None if the reader is at EOF before the
+/// first byte of the header.
+pub(crate) fn read_header<R: Read>(reader: &mut R) -> Result<Option<(u32, u32)>> {
+    let mut header = [0_u8; 16];
+    let read = read_fully(reader, &mut header)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if read < header.len() {
+        return Err(match parser::parse_header(&header[..read]) {
+            Err(err @ Error::InvalidMagic) => err,
+            _ => Error::UnexpectedEof { needed: Some((header.len() - read) as u64) }
+        });
+    }
+    parser::parse_header(&header).map(Some)
+}
+
+/// Reads into `buff` until it is full or the reader reaches EOF, returning the number of bytes
+/// read.
+pub(crate) fn read_fully<R: Read>(reader: &mut R, buff: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+    while read < buff.len() {
+        match reader.read(&mut buff[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+            Err(err) => return Err(Error::IoError(err))
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::pixel::Pixel;
+
+    fn frames() -> (Farbfeld, Farbfeld) {
+        let first = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2_u16, 3_u16, 4_u16),
+                                             Pixel::new(5_u16, 6_u16, 7_u16, 8_u16)]).unwrap();
+        let second = Farbfeld::new(1, 3, vec![Pixel::new(9_u16, 10_u16, 11_u16, 12_u16); 3]).unwrap();
+        (first, second)
+    }
+
+    #[test]
+    fn test_two_frames() {
+        let (first, second) = frames();
+        let mut data = Vec::new();
+        first.save(&mut data).unwrap();
+        second.save(&mut data).unwrap();
+
+        let parsed = read_all_frames(&data[..]).unwrap();
+        assert_eq!(2, parsed.len());
+        for (org, test) in [first, second].iter().zip(parsed.iter()) {
+            assert_eq!(org.width(), test.width());
+            assert_eq!(org.height(), test.height());
+            assert_eq!(org.pixels(), test.pixels());
+        }
+    }
+
+    #[test]
+    fn test_empty_stream() {
+        assert!(read_all_frames(&[][..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_truncated_second_frame() {
+        let (first, second) = frames();
+        let mut data = Vec::new();
+        first.save(&mut data).unwrap();
+        second.save(&mut data).unwrap();
+        data.truncate(data.len() - 5);
+
+        let mut stream = FarbfeldStream::new(&data[..]);
+        assert_eq!(first.pixels(), stream.next().unwrap().unwrap().pixels());
+        match stream.next() {
+            Some(Err(Error::UnexpectedEof { needed: Some(5) })) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        assert!(stream.next().is_none());
+        assert!(read_all_frames(&data[..]).is_err());
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        let (first, _) = frames();
+        let mut data = Vec::new();
+        first.save(&mut data).unwrap();
+        data.extend_from_slice(b"farbfeld\0\0");
+
+        let mut stream = FarbfeldStream::new(&data[..]);
+        assert!(stream.next().unwrap().is_ok());
+        match stream.next() {
+            Some(Err(Error::UnexpectedEof { needed: Some(6) })) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_invalid_magic() {
+        let mut stream = FarbfeldStream::new(&b"farbfelt\0\0\0\0\0\0\0\0"[..]);
+        match stream.next() {
+            Some(Err(Error::InvalidMagic)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_overflowing_dimensions() {
+        let mut stream = FarbfeldStream::new(&b"farbfeld\xff\xff\xff\xff\xff\xff\xff\xff"[..]);
+        match stream.next() {
+            Some(Err(Error::InvalidFarbfeldDimensions)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        assert!(stream.next().is_none());
+    }
+}
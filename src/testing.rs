@@ -0,0 +1,67 @@
+//! [Proptest](https://docs.rs/proptest) strategies for generating [Pixels](../pixel/struct.Pixel.html)
+//! and [Farbfelds](../struct.Farbfeld.html). Only available with the `proptest` feature.
+
+use proptest::prelude::*;
+use proptest::collection::vec;
+
+use crate::pixel::Pixel;
+use crate::farbfeld::Farbfeld;
+
+/// Creates a strategy producing pixels with uniformly distributed components.
+pub fn pixel_strategy() -> impl Strategy<Value = Pixel> {
+    any::<[u16; 4]>().prop_map(Pixel::from)
+}
+
+/// Creates a strategy producing valid Farbfeld images whose width and height are both at most
+/// `max_dim`. Zero sized images are included.
+pub fn farbfeld_strategy(max_dim: u32) -> impl Strategy<Value = Farbfeld> {
+    (0..=max_dim, 0..=max_dim).prop_flat_map(|(width, height)| {
+        vec(pixel_strategy(), width as usize * height as usize)
+            .prop_map(move |pixels| Farbfeld::new(width, height, pixels)
+                .expect("pixel count generated from the dimensions"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use byteorder::{WriteBytesExt, BigEndian};
+
+    proptest! {
+        #[test]
+        fn test_save_parse_round_trip(farb in farbfeld_strategy(16)) {
+            let mut data = Vec::new();
+            farb.save(&mut data).unwrap();
+            let parsed = Farbfeld::from_read(&data[..]).unwrap();
+
+            prop_assert_eq!(farb.width(), parsed.width());
+            prop_assert_eq!(farb.height(), parsed.height());
+            prop_assert_eq!(farb.pixels(), parsed.pixels());
+        }
+
+        #[test]
+        fn test_double_invert(farb in farbfeld_strategy(16)) {
+            let inverted = farb.invert();
+            for (pixel, original) in inverted.pixels().iter().zip(farb.pixels()) {
+                prop_assert_eq!(pixel.alpha(), original.alpha());
+            }
+            prop_assert_eq!(&farb, &inverted.invert());
+        }
+
+        #[test]
+        fn test_parse_arbitrary_bytes(data in vec(any::<u8>(), 0..256)) {
+            let _ = Farbfeld::from_read(&data[..]);
+        }
+
+        #[test]
+        fn test_parse_arbitrary_body(width in any::<u32>(), height in any::<u32>(),
+                                     body in vec(any::<u8>(), 0..256)) {
+            let mut data = b"farbfeld".to_vec();
+            data.write_u32::<BigEndian>(width).unwrap();
+            data.write_u32::<BigEndian>(height).unwrap();
+            data.extend(body);
+            let _ = Farbfeld::from_read(&data[..]);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Converting Farbfeld images to and from the tightly packed 16 bit per channel byte layouts used
+//! when uploading textures to a GPU, such as wgpu's `Rgba16Uint` and `Rgba16Unorm` formats.
+//!
+//! Rows may be padded to a multiple of an alignment, as wgpu requires the rows of buffers copied
+//! to and from textures to be aligned to 256 bytes.
+
+use alloc::vec::Vec;
+
+use crate::farbfeld::Farbfeld;
+use crate::error::*;
+
+/// The byte order of each channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    /// Least significant byte first, as expected by GPUs.
+    Little,
+    /// Most significant byte first, as used by Farbfeld files.
+    Big
+}
+
+/// The order of the channels within each pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelOrder {
+    /// Red, green, blue then alpha.
+    Rgba,
+    /// Blue, green, red then alpha.
+    Bgra
+}
+
+/// The layout of the bytes of a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureLayout {
+    endianness: Endianness,
+    channel_order: ChannelOrder,
+    bytes_per_row_alignment: u32
+}
+
+/// The row alignment wgpu requires for copies between buffers and textures.
+const WGPU_ROW_ALIGNMENT: u32 = 256;
+
+impl TextureLayout {
+    /// Creates a new layout, padding each row to a multiple of `bytes_per_row_alignment` bytes.
+    /// An alignment of 0 is treated as 1, leaving rows unpadded.
+    pub fn new(endianness: Endianness, channel_order: ChannelOrder, bytes_per_row_alignment: u32) -> TextureLayout {
+        TextureLayout {
+            endianness,
+            channel_order,
+            bytes_per_row_alignment: bytes_per_row_alignment.max(1)
+        }
+    }
+
+    /// Returns the layout expected by wgpu for `Rgba16Uint` and `Rgba16Unorm` textures: little
+    /// endian RGBA channels with rows aligned to 256 bytes.
+    pub fn wgpu() -> TextureLayout {
+        TextureLayout::new(Endianness::Little, ChannelOrder::Rgba, WGPU_ROW_ALIGNMENT)
+    }
+
+    /// Returns the byte order of each channel.
+    pub fn endianness(&self) -> &Endianness {
+        &self.endianness
+    }
+
+    /// Returns the order of the channels within each pixel.
+    pub fn channel_order(&self) -> &ChannelOrder {
+        &self.channel_order
+    }
+
+    /// Returns the alignment of each row in bytes.
+    pub fn bytes_per_row_alignment(&self) -> &u32 {
+        &self.bytes_per_row_alignment
+    }
+
+    /// Returns the number of bytes in each row of an image with the given width, including
+    /// padding.
+    ///
+    /// ```
+    /// # use ruff::texture::TextureLayout;
+    /// assert_eq!(256, TextureLayout::wgpu().bytes_per_row(3));
+    /// assert_eq!(512, TextureLayout::wgpu().bytes_per_row(33));
+    /// ```
+    pub fn bytes_per_row(&self, width: u32) -> u64 {
+        (width as u64 * 8).div_ceil(self.bytes_per_row_alignment as u64) * self.bytes_per_row_alignment as u64
+    }
+
+    /// Converts a channel to bytes with this layout's endianness.
+    fn channel_bytes(&self, channel: u16) -> [u8; 2] {
+        match self.endianness {
+            Endianness::Little => channel.to_le_bytes(),
+            Endianness::Big => channel.to_be_bytes()
+        }
+    }
+
+    /// Converts bytes with this layout's endianness to a channel.
+    fn channel_from_bytes(&self, bytes: [u8; 2]) -> u16 {
+        match self.endianness {
+            Endianness::Little => u16::from_le_bytes(bytes),
+            Endianness::Big => u16::from_be_bytes(bytes)
+        }
+    }
+
+    /// Swaps the red and blue channels if the channel order is BGRA. Swapping is its own inverse,
+    /// so this converts in both directions.
+    fn order_channels(&self, [first, green, third, alpha]: [u16; 4]) -> [u16; 4] {
+        match self.channel_order {
+            ChannelOrder::Rgba => [first, green, third, alpha],
+            ChannelOrder::Bgra => [third, green, first, alpha]
+        }
+    }
+}
+
+impl Default for TextureLayout {
+    /// Returns the layout of unpadded, little endian RGBA channels.
+    fn default() -> TextureLayout {
+        TextureLayout::new(Endianness::Little, ChannelOrder::Rgba, 1)
+    }
+}
+
+impl Farbfeld {
+    /// Returns the pixels of the image in row-major order in the given layout. Each row is followed
+    /// by zeroed padding up to the layout's
+    /// [bytes_per_row](texture/struct.TextureLayout.html#method.bytes_per_row), including the
+    /// last.
+    pub fn to_texture_bytes(&self, layout: TextureLayout) -> Vec<u8> {
+        let bytes_per_row = layout.bytes_per_row(*self.width()) as usize;
+        let mut bytes = Vec::with_capacity(bytes_per_row * *self.height() as usize);
+        if *self.width() == 0 {
+            return bytes;
+        }
+        for row in self.pixels().chunks(*self.width() as usize) {
+            let row_start = bytes.len();
+            for pixel in row {
+                for channel in layout.order_channels((*pixel).into()).iter() {
+                    bytes.extend_from_slice(&layout.channel_bytes(*channel));
+                }
+            }
+            bytes.resize(row_start + bytes_per_row, 0);
+        }
+        bytes
+    }
+
+    /// Creates an image from pixels in the given layout, such as a texture read back from a GPU,
+    /// skipping any row padding. The padding after the last row may be omitted.
+    ///
+    /// # Errors
+    /// Returns an [Error::BufferTooSmall](error/enum.Error.html) if the data is too short to
+    /// hold an image with the given dimensions.
+    pub fn from_texture_bytes(width: u32, height: u32, data: &[u8], layout: TextureLayout) -> Result<Farbfeld> {
+        let bytes_per_row = layout.bytes_per_row(width);
+        // Dimensions too large to count the bytes of can't fit in any slice.
+        let needed = match height {
+            0 => Some(0),
+            height => bytes_per_row.checked_mul(height as u64 - 1)
+                .and_then(|rows| rows.checked_add(width as u64 * 8))
+        }.unwrap_or(u64::MAX);
+        if (data.len() as u64) < needed {
+            return Err(Error::BufferTooSmall(needed, data.len()));
+        }
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        if width > 0 {
+            for row in data.chunks(bytes_per_row as usize).take(height as usize) {
+                for pixel in row[..width as usize * 8].chunks(8) {
+                    let mut channels = [0_u16; 4];
+                    for (channel, bytes) in channels.iter_mut().zip(pixel.chunks(2)) {
+                        *channel = layout.channel_from_bytes([bytes[0], bytes[1]]);
+                    }
+                    pixels.push(layout.order_channels(channels).into());
+                }
+            }
+        }
+        Farbfeld::new(width, height, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    use crate::pixel::Pixel;
+
+    fn image() -> Farbfeld {
+        Farbfeld::new(2, 2, vec![
+            Pixel::new(0x0102_u16, 0x0304, 0x0506, 0x0708),
+            Pixel::new(0x1112_u16, 0x1314, 0x1516, 0x1718),
+            Pixel::new(0x2122_u16, 0x2324, 0x2526, 0x2728),
+            Pixel::new(0x3132_u16, 0x3334, 0x3536, 0x3738)
+        ]).unwrap()
+    }
+
+    #[test]
+    fn test_bytes_per_row() {
+        assert_eq!(8, TextureLayout::default().bytes_per_row(1));
+        assert_eq!(0, TextureLayout::wgpu().bytes_per_row(0));
+        assert_eq!(256, TextureLayout::wgpu().bytes_per_row(1));
+        assert_eq!(256, TextureLayout::wgpu().bytes_per_row(32));
+        assert_eq!(512, TextureLayout::wgpu().bytes_per_row(33));
+        assert_eq!(24, TextureLayout::new(Endianness::Little, ChannelOrder::Rgba, 12).bytes_per_row(2));
+        assert_eq!(16, TextureLayout::new(Endianness::Little, ChannelOrder::Rgba, 0).bytes_per_row(2));
+    }
+
+    #[test]
+    fn test_to_texture_bytes() {
+        let bytes = image().to_texture_bytes(TextureLayout::default());
+        assert_eq!(vec![
+            0x02, 0x01, 0x04, 0x03, 0x06, 0x05, 0x08, 0x07, 0x12, 0x11, 0x14, 0x13, 0x16, 0x15, 0x18, 0x17,
+            0x22, 0x21, 0x24, 0x23, 0x26, 0x25, 0x28, 0x27, 0x32, 0x31, 0x34, 0x33, 0x36, 0x35, 0x38, 0x37
+        ], bytes);
+
+        let bytes = image().to_texture_bytes(TextureLayout::new(Endianness::Big, ChannelOrder::Bgra, 1));
+        assert_eq!(&[0x05, 0x06, 0x03, 0x04, 0x01, 0x02, 0x07, 0x08], &bytes[..8]);
+    }
+
+    #[test]
+    fn test_to_texture_bytes_padding() {
+        let bytes = image().to_texture_bytes(TextureLayout::wgpu());
+        assert_eq!(512, bytes.len());
+        assert_eq!(&[0x22, 0x21], &bytes[256..258]);
+        assert!(bytes[16..256].iter().chain(&bytes[272..]).all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_from_texture_bytes_round_trip() {
+        let layouts = [
+            TextureLayout::default(),
+            TextureLayout::wgpu(),
+            TextureLayout::new(Endianness::Big, ChannelOrder::Bgra, 12)
+        ];
+        for layout in layouts.iter() {
+            let bytes = image().to_texture_bytes(*layout);
+            let farb = Farbfeld::from_texture_bytes(2, 2, &bytes, *layout).unwrap();
+            assert_eq!(image().pixels(), farb.pixels());
+
+            let last_row = layout.bytes_per_row(2) as usize + 16;
+            let farb = Farbfeld::from_texture_bytes(2, 2, &bytes[..last_row], *layout).unwrap();
+            assert_eq!(image().pixels(), farb.pixels());
+        }
+    }
+
+    #[test]
+    fn test_from_texture_bytes_too_small() {
+        match Farbfeld::from_texture_bytes(2, 2, &[0; 271], TextureLayout::wgpu()) {
+            Err(Error::BufferTooSmall(272, 271)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        match Farbfeld::from_texture_bytes(u32::MAX, u32::MAX, &[], TextureLayout::wgpu()) {
+            Err(Error::BufferTooSmall(u64::MAX, 0)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        let empty = Farbfeld::from_texture_bytes(0, 3, &[], TextureLayout::wgpu()).unwrap();
+        assert!(empty.to_texture_bytes(TextureLayout::wgpu()).is_empty());
+    }
+}
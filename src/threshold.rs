@@ -0,0 +1,181 @@
+//! Binarizing Farbfeld images by the luminance or alpha of each pixel.
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+
+impl Farbfeld {
+    /// Returns a copy of the image where every pixel with a
+    /// [luminance](pixel/struct.Pixel.html#method.luminance) of at least `cutoff` is opaque white
+    /// and every other pixel is opaque black.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(40000_u16, 40000, 40000, 0), Pixel::new(100_u16, 0, 0, 0)]).unwrap();
+    ///
+    /// assert_eq!(&[Pixel::new(65535_u16, 65535, 65535, 65535), Pixel::new(0_u16, 0, 0, 65535)],
+    ///            farb.threshold(32768).pixels());
+    /// ```
+    pub fn threshold(&self, cutoff: u16) -> Farbfeld {
+        self.threshold_with(cutoff, Pixel::new(u16::MAX, u16::MAX, u16::MAX, u16::MAX), Pixel::new(0, 0, 0, u16::MAX))
+    }
+
+    /// Returns a copy of the image where every pixel with a luminance of at least `cutoff` is
+    /// replaced by `above` and every other pixel is replaced by `below`.
+    pub fn threshold_with(&self, cutoff: u16, above: Pixel, below: Pixel) -> Farbfeld {
+        let pixels = self.pixels().iter()
+            .map(|pixel| if pixel.luminance() >= cutoff { above } else { below })
+            .collect();
+        Farbfeld::new(*self.width(), *self.height(), pixels).expect("thresholding keeps the dimensions")
+    }
+
+    /// Picks a cutoff for [threshold](#method.threshold) from the luminance histogram with Otsu's
+    /// method, which splits the pixels into the two classes with the least variance within each
+    /// class. Returns 0, so every pixel is above the cutoff, if every pixel has the same luminance.
+    pub fn otsu_cutoff(&self) -> u16 {
+        let counts = self.luminance_counts();
+        let total = self.pixels().len() as f64;
+        let sum: f64 = counts.iter().enumerate().map(|(value, &count)| value as f64 * count as f64).sum();
+        let (mut below_count, mut below_sum) = (0.0, 0.0);
+        let (mut best, mut best_variance) = (0, 0.0);
+        // Every value up to and including `value` is below the cutoff.
+        for (value, &count) in counts.iter().enumerate().take(counts.len() - 1) {
+            below_count += count as f64;
+            below_sum += value as f64 * count as f64;
+            let above_count = total - below_count;
+            if below_count == 0.0 || above_count == 0.0 {
+                continue;
+            }
+            let difference = below_sum / below_count - (sum - below_sum) / above_count;
+            let variance = below_count * above_count * difference * difference;
+            if variance > best_variance {
+                best = value + 1;
+                best_variance = variance;
+            }
+        }
+        best as u16
+    }
+
+    /// Returns a copy of the image thresholded with the cutoff picked by
+    /// [otsu_cutoff](#method.otsu_cutoff).
+    pub fn auto_threshold(&self) -> Farbfeld {
+        self.threshold(self.otsu_cutoff())
+    }
+
+    /// Makes every pixel with an alpha of at least `cutoff` opaque and every other pixel fully
+    /// transparent, leaving the colours unchanged, such as for formats which only support 1 bit
+    /// transparency. A cutoff of 0 makes every pixel opaque.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 2, 3, 40000), Pixel::new(4_u16, 5, 6, 20000)]).unwrap();
+    /// farb.threshold_alpha(32768);
+    ///
+    /// assert_eq!(&[Pixel::new(1_u16, 2, 3, 65535), Pixel::new(4_u16, 5, 6, 0)], farb.pixels());
+    /// ```
+    pub fn threshold_alpha(&mut self, cutoff: u16) {
+        for pixel in self[..].iter_mut() {
+            let alpha = pixel.alpha_mut();
+            *alpha = if *alpha >= cutoff { u16::MAX } else { 0 };
+        }
+    }
+
+    /// Thresholds the alpha channel as described by [threshold_alpha](#method.threshold_alpha),
+    /// and also makes every fully transparent pixel transparent black, so the hidden colours don't
+    /// hinder compression.
+    pub fn threshold_alpha_clear(&mut self, cutoff: u16) {
+        self.threshold_alpha(cutoff);
+        for pixel in self[..].iter_mut() {
+            if pixel.alpha() == &0 {
+                *pixel = Pixel::default();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    fn grey(value: u16) -> Pixel {
+        Pixel::new(value, value, value, 65535)
+    }
+
+    #[test]
+    fn test_threshold_gradient() {
+        let farb = Farbfeld::new(256, 1, (0..256_u32).map(|i| grey((i * 257) as u16)).collect()).unwrap();
+        let binary = farb.threshold(32768);
+
+        assert!(binary.pixels()[..128].iter().all(|pixel| *pixel == Pixel::new(0_u16, 0, 0, 65535)));
+        assert!(binary.pixels()[128..].iter().all(|pixel| *pixel == Pixel::new(65535_u16, 65535, 65535, 65535)));
+    }
+
+    #[test]
+    fn test_threshold_with() {
+        let farb = Farbfeld::new(2, 1, vec![grey(10), grey(20)]).unwrap();
+        let above = Pixel::new(1_u16, 2, 3, 4);
+        let below = Pixel::new(5_u16, 6, 7, 8);
+        assert_eq!(&[below, above], farb.threshold_with(20, above, below).pixels());
+    }
+
+    #[test]
+    fn test_auto_threshold_bimodal() {
+        let pixels = (0..100_u16).map(|i| if i % 2 == 0 { grey(10000 + i) } else { grey(50000 + i) }).collect();
+        let farb = Farbfeld::new(10, 10, pixels).unwrap();
+        let cutoff = farb.otsu_cutoff();
+
+        assert!(cutoff > 10098 && cutoff <= 50001, "cutoff {} isn't between the modes", cutoff);
+        let binary = farb.auto_threshold();
+        assert_eq!(50, binary.pixels().iter().filter(|pixel| pixel.red() == &65535).count());
+    }
+
+    /// A red circle whose alpha falls from opaque at the centre to transparent at a radius of 8.
+    fn soft_circle() -> Farbfeld {
+        let pixels = (0..17 * 17_u64).map(|i| {
+            let (x, y) = ((i % 17).abs_diff(8), (i / 17).abs_diff(8));
+            let distance = (x * x + y * y).isqrt().min(8);
+            Pixel::new(65535_u16, 0, 0, (65535 - distance * 65535 / 8) as u16)
+        }).collect();
+        Farbfeld::new(17, 17, pixels).unwrap()
+    }
+
+    #[test]
+    fn test_threshold_alpha() {
+        let soft = soft_circle();
+        let mut farb = soft.clone();
+        farb.threshold_alpha(32768);
+        for (pixel, original) in farb.pixels().iter().zip(soft.pixels()) {
+            let expected = if *original.alpha() >= 32768 { 65535 } else { 0 };
+            assert_eq!(&Pixel::new(65535_u16, 0, 0, expected), pixel);
+        }
+        assert_eq!(&0, farb[0].alpha());
+        assert_eq!(&65535, farb[8 * 17 + 8].alpha());
+
+        let mut opaque = soft.clone();
+        opaque.threshold_alpha(0);
+        assert!(opaque.pixels().iter().all(|pixel| pixel.alpha() == &65535));
+    }
+
+    #[test]
+    fn test_threshold_alpha_clear() {
+        let mut thresholded = soft_circle();
+        thresholded.threshold_alpha(20000);
+        let mut cleared = soft_circle();
+        cleared.threshold_alpha_clear(20000);
+        for (pixel, thresholded) in cleared.pixels().iter().zip(thresholded.pixels()) {
+            if thresholded.alpha() == &0 {
+                assert_eq!(&Pixel::default(), pixel);
+            } else {
+                assert_eq!(thresholded, pixel);
+            }
+        }
+        assert!(cleared.pixels().iter().any(|pixel| pixel.alpha() == &65535));
+    }
+
+    #[test]
+    fn test_otsu_cutoff_constant() {
+        let farb = Farbfeld::new(2, 2, vec![grey(1234); 4]).unwrap();
+        assert_eq!(0, farb.otsu_cutoff());
+    }
+}
@@ -0,0 +1,607 @@
+//! Rotating and otherwise transforming the geometry of Farbfeld images.
+
+use alloc::vec::Vec;
+
+use crate::convolve::EdgeMode;
+use crate::farbfeld::Farbfeld;
+use crate::patterns;
+use crate::pixel::Pixel;
+use crate::resize::ResizeFilter;
+use crate::error::*;
+
+/// A 2D affine transform, made of a 2 by 3 matrix `[a, b, c, d, e, f]` which moves the point
+/// `(x, y)` to `(a * x + b * y + c, d * x + e * y + f)`. Coordinates are in pixels from the top
+/// left corner of the image, with y increasing downwards, so the centre of the pixel at `(0, 0)`
+/// is `(0.5, 0.5)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    matrix: [f32; 6]
+}
+
+impl Affine {
+    /// Creates a transform from its matrix.
+    pub fn new(matrix: [f32; 6]) -> Affine {
+        Affine { matrix }
+    }
+
+    /// Creates a transform which leaves points unchanged.
+    pub fn identity() -> Affine {
+        Affine::new([1.0, 0.0, 0.0, 0.0, 1.0, 0.0])
+    }
+
+    /// Creates a transform which moves points by `x` and `y`.
+    pub fn translate(x: f32, y: f32) -> Affine {
+        Affine::new([1.0, 0.0, x, 0.0, 1.0, y])
+    }
+
+    /// Creates a transform which scales points away from the origin by `x` and `y`.
+    pub fn scale(x: f32, y: f32) -> Affine {
+        Affine::new([x, 0.0, 0.0, 0.0, y, 0.0])
+    }
+
+    /// Creates a transform which rotates points clockwise by `degrees` about the origin. Requires
+    /// the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn rotate(degrees: f32) -> Affine {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Affine::new([cos, -sin, 0.0, sin, cos, 0.0])
+    }
+
+    /// Creates a transform which shears points, moving them horizontally by `x` times their y
+    /// coordinate and vertically by `y` times their x coordinate.
+    pub fn shear(x: f32, y: f32) -> Affine {
+        Affine::new([1.0, x, 0.0, y, 1.0, 0.0])
+    }
+
+    /// Returns the transform which applies this transform, followed by `next`.
+    ///
+    /// ```
+    /// # use ::ruff::transform::Affine;
+    /// let transform = Affine::scale(2.0, 3.0).compose(&Affine::translate(1.0, 1.0));
+    ///
+    /// assert_eq!((3.0, 4.0), transform.apply(1.0, 1.0));
+    /// ```
+    pub fn compose(&self, next: &Affine) -> Affine {
+        let [a, b, c, d, e, f] = self.matrix;
+        let [na, nb, nc, nd, ne, nf] = next.matrix;
+        Affine::new([
+            na * a + nb * d, na * b + nb * e, na * c + nb * f + nc,
+            nd * a + ne * d, nd * b + ne * e, nd * c + ne * f + nf
+        ])
+    }
+
+    /// Returns the transform which undoes this transform, or None if it can't be undone because
+    /// it flattens points onto a line or a single point.
+    pub fn inverse(&self) -> Option<Affine> {
+        let [a, b, c, d, e, f] = self.matrix;
+        let determinant = a as f64 * e as f64 - b as f64 * d as f64;
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        let (a, b, c, d, e, f) = (a as f64, b as f64, c as f64, d as f64, e as f64, f as f64);
+        let inverse = [e, -b, b * f - c * e, -d, a, c * d - a * f].map(|value| (value / determinant) as f32);
+        Some(Affine::new(inverse))
+    }
+
+    /// Returns the matrix of the transform.
+    pub fn matrix(&self) -> &[f32; 6] {
+        &self.matrix
+    }
+
+    /// Returns where the transform moves the point `(x, y)`.
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let [a, b, c, d, e, f] = self.matrix;
+        (a * x + b * y + c, d * x + e * y + f)
+    }
+}
+
+impl Default for Affine {
+    fn default() -> Affine {
+        Affine::identity()
+    }
+}
+
+/// Rounds `value` down to an integer, saturating at the limits of i64.
+fn floor(value: f64) -> i64 {
+    let truncated = value as i64;
+    if (truncated as f64) > value { truncated - 1 } else { truncated }
+}
+
+/// Returns an error unless the filter can sample single points of an image, which only
+/// `Nearest` and `Bilinear` can.
+fn check_filter(filter: ResizeFilter) -> Result<()> {
+    match filter {
+        ResizeFilter::Nearest | ResizeFilter::Bilinear => Ok(()),
+        filter => Err(Error::UnsupportedFilter(filter))
+    }
+}
+
+/// Samples the image at the given coordinates, where the centre of the pixel at `(x, y)` is at
+/// `(x + 0.5, y + 0.5)`, with pixels outside the image sampled as described by `edge`. The filter
+/// must have been checked by check_filter.
+fn sample(farb: &Farbfeld, x: f64, y: f64, filter: ResizeFilter, edge: EdgeMode) -> Pixel {
+    match filter {
+        ResizeFilter::Nearest => farb.get_pixel_edge(floor(x), floor(y), edge),
+        _ => bilinear(farb, x - 0.5, y - 0.5, edge)
+    }
+}
+
+/// Returns the coordinate as one which can be sampled, with NaN replaced by 0 and infinities
+/// moved to the limits of i64.
+fn sanitise(value: f64) -> f64 {
+    if value.is_nan() { 0.0 } else { value.clamp(i64::MIN as f64, i64::MAX as f64) }
+}
+
+/// Linearly interpolates between the four pixels around the given coordinates, where the centre
+/// of each pixel is at its integer coordinates, with pixels outside the image sampled as
+/// described by `edge`.
+fn bilinear(farb: &Farbfeld, x: f64, y: f64, edge: EdgeMode) -> Pixel {
+    let (x, y) = (sanitise(x), sanitise(y));
+    let (left, top) = (floor(x), floor(y));
+    let (right_weight, bottom_weight) = (x - left as f64, y - top as f64);
+    let mut channels = [0.0; 4];
+    for &(dx, dy, weight) in &[
+        (0, 0, (1.0 - right_weight) * (1.0 - bottom_weight)),
+        (1, 0, right_weight * (1.0 - bottom_weight)),
+        (0, 1, (1.0 - right_weight) * bottom_weight),
+        (1, 1, right_weight * bottom_weight)
+    ] {
+        let pixel = farb.get_pixel_edge(left.saturating_add(dx), top.saturating_add(dy), edge);
+        for (channel, &value) in channels.iter_mut().zip(pixel.iter()) {
+            *channel += value as f64 * weight;
+        }
+    }
+    let mut pixel = [0_u16; 4];
+    for (pixel, &channel) in pixel.iter_mut().zip(channels.iter()) {
+        // Casting saturates, so the sum can't overflow.
+        *pixel = (channel + 0.5) as u16;
+    }
+    Pixel::from(pixel)
+}
+
+/// Returns the smallest length at least `needed` with the same parity as `original`, so the
+/// image grows by the same number of pixels on either side.
+#[cfg(feature = "std")]
+fn expanded(needed: f64, original: u32) -> u32 {
+    // Allows for the rounding of the sine and cosine, so right angles don't grow a pixel.
+    let length = (needed - 1e-6).ceil().max(0.0) as u32;
+    if length % 2 == original % 2 { length } else { length + 1 }
+}
+
+impl Farbfeld {
+    /// Returns the pixel nearest the given coordinates, which may be anywhere, where the centre of
+    /// each pixel is at its integer coordinates, so coordinates are rounded to the nearest
+    /// integer with halves rounded up. Pixels outside the image are sampled as described by
+    /// `edge`, and NaN coordinates are treated as 0.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::convolve::EdgeMode;
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 1, 1, 1), Pixel::new(2_u16, 2, 2, 2)]).unwrap();
+    ///
+    /// assert_eq!(farb[0], farb.sample_nearest(0.49, 0.0, EdgeMode::Clamp));
+    /// assert_eq!(farb[1], farb.sample_nearest(0.5, -0.2, EdgeMode::Clamp));
+    /// assert_eq!(farb[1], farb.sample_nearest(7.0, 3.0, EdgeMode::Clamp));
+    /// ```
+    pub fn sample_nearest(&self, x: f32, y: f32, edge: EdgeMode) -> Pixel {
+        let (x, y) = (sanitise(x as f64 + 0.5), sanitise(y as f64 + 0.5));
+        self.get_pixel_edge(floor(x), floor(y), edge)
+    }
+
+    /// Returns the pixel at the given coordinates, which may be anywhere, linearly interpolated
+    /// between the four pixels around them, where the centre of each pixel is at its integer
+    /// coordinates. Each channel, including alpha, is interpolated separately and rounded to the
+    /// nearest value. Pixels outside the image are sampled as described by `edge`, and NaN
+    /// coordinates are treated as 0.
+    ///
+    /// This is the sampling used by [rotate](#method.rotate) and
+    /// [warp_affine](#method.warp_affine), which place pixel centres half a pixel further right
+    /// and down.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::convolve::EdgeMode;
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 10, 0, 0), Pixel::new(2_u16, 20, 0, 0)]).unwrap();
+    ///
+    /// assert_eq!(farb[1], farb.sample_bilinear(1.0, 0.0, EdgeMode::Clamp));
+    /// assert_eq!(Pixel::new(2_u16, 15, 0, 0), farb.sample_bilinear(0.5, 0.0, EdgeMode::Clamp));
+    /// assert_eq!(Pixel::new(1_u16, 5, 0, 0), farb.sample_bilinear(-0.5, 0.0, EdgeMode::Fill(Pixel::default())));
+    /// ```
+    pub fn sample_bilinear(&self, x: f32, y: f32, edge: EdgeMode) -> Pixel {
+        bilinear(self, x as f64, y as f64, edge)
+    }
+
+    /// Returns a copy of the image rotated a quarter turn clockwise.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 1, 1, 1), Pixel::new(2_u16, 2, 2, 2)]).unwrap();
+    ///
+    /// assert_eq!(&[Pixel::new(1_u16, 1, 1, 1), Pixel::new(2_u16, 2, 2, 2)], farb.rotate90().pixels());
+    /// assert_eq!((1, 2), (*farb.rotate90().width(), *farb.rotate90().height()));
+    /// ```
+    pub fn rotate90(&self) -> Farbfeld {
+        let (width, height) = (*self.width() as usize, *self.height() as usize);
+        let pixels: Vec<Pixel> = (0..width * height)
+            .map(|i| self[(height - 1 - i % height) * width + i / height])
+            .collect();
+        Farbfeld::new(height as u32, width as u32, pixels).expect("the pixels fill the dimensions")
+    }
+
+    /// Rotates the square image a quarter turn clockwise without allocating, producing the same
+    /// result as [rotate90](#method.rotate90). Each pixel is moved once, swapped around a cycle of
+    /// four pixels in the corners of a ring of the image.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(2, 2, (0..4_u16).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+    /// let expected = farb.rotate90();
+    /// farb.rotate90_in_place().unwrap();
+    ///
+    /// assert_eq!(expected, farb);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::NotSquare](error/enum.Error.html) if the width and height of the image
+    /// differ, as rotating it changes its dimensions and so the layout of its pixels.
+    pub fn rotate90_in_place(&mut self) -> Result<()> {
+        if self.width() != self.height() {
+            return Err(Error::NotSquare(*self.width(), *self.height()));
+        }
+        let size = *self.width() as usize;
+        let pixels = &mut self[..];
+        for y in 0..size / 2 {
+            for x in y..size - 1 - y {
+                let (opposite_x, opposite_y) = (size - 1 - x, size - 1 - y);
+                let top = pixels[y * size + x];
+                pixels[y * size + x] = pixels[opposite_x * size + y];
+                pixels[opposite_x * size + y] = pixels[opposite_y * size + opposite_x];
+                pixels[opposite_y * size + opposite_x] = pixels[x * size + opposite_y];
+                pixels[x * size + opposite_y] = top;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a copy of the image rotated a half turn.
+    pub fn rotate180(&self) -> Farbfeld {
+        let pixels = self.pixels().iter().rev().cloned().collect();
+        Farbfeld::new(*self.width(), *self.height(), pixels).expect("the pixels fill the dimensions")
+    }
+
+    /// Returns a copy of the image rotated a quarter turn anticlockwise.
+    pub fn rotate270(&self) -> Farbfeld {
+        let (width, height) = (*self.width() as usize, *self.height() as usize);
+        let pixels: Vec<Pixel> = (0..width * height)
+            .map(|i| self[(i % height) * width + width - 1 - i / height])
+            .collect();
+        Farbfeld::new(height as u32, width as u32, pixels).expect("the pixels fill the dimensions")
+    }
+
+    /// Returns a copy of the image rotated clockwise by `degrees` about its centre. Requires the
+    /// `std` feature.
+    ///
+    /// The image is grown to contain all of the rotated image, by the same number of pixels on
+    /// opposite sides, with the uncovered area filled with `background`. Each pixel is sampled
+    /// from the source with the `Nearest` or `Bilinear`
+    /// [ResizeFilter](resize/enum.ResizeFilter.html), with `Bilinear` blending the edges of the
+    /// image into the background. Multiples of 90 degrees give exactly the same image as
+    /// [rotate90](#method.rotate90), [rotate180](#method.rotate180) and
+    /// [rotate270](#method.rotate270), and a copy of the image if the angle isn't finite.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::resize::ResizeFilter;
+    /// let farb = Farbfeld::new(10, 10, vec![Pixel::new(1_u16, 1, 1, 65535); 100]).unwrap();
+    /// let rotated = farb.rotate(45.0, ResizeFilter::Bilinear, Pixel::default()).unwrap();
+    ///
+    /// assert_eq!((16, 16), (*rotated.width(), *rotated.height()));
+    /// assert_eq!(&Pixel::default(), &rotated[0]);
+    /// assert_eq!(&Pixel::new(1_u16, 1, 1, 65535), &rotated[8 * 16 + 8]);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::UnsupportedFilter](error/enum.Error.html) if the filter isn't `Nearest`
+    /// or `Bilinear`.
+    #[cfg(feature = "std")]
+    pub fn rotate(&self, degrees: f32, filter: ResizeFilter, background: Pixel) -> Result<Farbfeld> {
+        check_filter(filter)?;
+        if !degrees.is_finite() {
+            return Ok(self.clone());
+        }
+        let turns = degrees / 90.0;
+        if turns == turns.round() {
+            return Ok(match (turns as i64).rem_euclid(4) {
+                0 => self.clone(),
+                1 => self.rotate90(),
+                2 => self.rotate180(),
+                _ => self.rotate270()
+            });
+        }
+
+        let (sin, cos) = (degrees as f64).to_radians().sin_cos();
+        let (width, height) = (*self.width() as f64, *self.height() as f64);
+        let rotated_width = expanded(width * cos.abs() + height * sin.abs(), *self.width());
+        let rotated_height = expanded(width * sin.abs() + height * cos.abs(), *self.height());
+        let mut rotated = patterns::solid(rotated_width, rotated_height, background);
+        let edge = EdgeMode::Fill(background);
+        for (i, pixel) in rotated[..].iter_mut().enumerate() {
+            let x = (i % rotated_width as usize) as f64 + 0.5 - rotated_width as f64 / 2.0;
+            let y = (i / rotated_width as usize) as f64 + 0.5 - rotated_height as f64 / 2.0;
+            // Rotates anticlockwise to find where the pixel came from.
+            let (source_x, source_y) = (x * cos + y * sin + width / 2.0, y * cos - x * sin + height / 2.0);
+            *pixel = sample(self, source_x, source_y, filter, edge);
+        }
+        Ok(rotated)
+    }
+
+    /// Returns an image of `output_size` containing the image transformed by the 2 by 3 affine
+    /// `matrix`, as described by [Affine](transform/struct.Affine.html), which can build one.
+    ///
+    /// Each pixel is sampled from the source at the centre of the pixel moved by the inverse of
+    /// the matrix, with the `Nearest` or `Bilinear` [ResizeFilter](resize/enum.ResizeFilter.html),
+    /// and pixels outside the source sampled as described by `edge`. An integer translation with
+    /// either filter moves the pixels exactly. If the matrix can't be inverted, so the image is
+    /// flattened onto a line or a point, none of the source is visible and every pixel is `edge`'s
+    /// fill pixel, or transparent black.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::convolve::EdgeMode;
+    /// # use ::ruff::resize::ResizeFilter;
+    /// # use ::ruff::transform::Affine;
+    /// let farb = Farbfeld::new(2, 1, vec![Pixel::new(1_u16, 1, 1, 1), Pixel::new(2_u16, 2, 2, 2)]).unwrap();
+    /// let matrix = *Affine::scale(2.0, 1.0).compose(&Affine::translate(1.0, 0.0)).matrix();
+    /// let warped = farb.warp_affine(matrix, (6, 1), ResizeFilter::Nearest, EdgeMode::Fill(Pixel::default())).unwrap();
+    ///
+    /// assert_eq!(&[Pixel::default(), Pixel::new(1_u16, 1, 1, 1), Pixel::new(1_u16, 1, 1, 1),
+    ///              Pixel::new(2_u16, 2, 2, 2), Pixel::new(2_u16, 2, 2, 2), Pixel::default()], warped.pixels());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an [Error::UnsupportedFilter](error/enum.Error.html) if the filter isn't `Nearest`
+    /// or `Bilinear`.
+    pub fn warp_affine(&self, matrix: [f32; 6], output_size: (u32, u32), filter: ResizeFilter,
+                       edge: EdgeMode) -> Result<Farbfeld> {
+        check_filter(filter)?;
+        let (width, height) = output_size;
+        let background = match edge {
+            EdgeMode::Fill(pixel) => pixel,
+            _ => Pixel::default()
+        };
+        let mut warped = patterns::solid(width, height, background);
+        let inverse = match Affine::new(matrix).inverse() {
+            Some(inverse) => inverse,
+            None => return Ok(warped)
+        };
+        let [a, b, c, d, e, f] = inverse.matrix.map(|value| value as f64);
+        for (i, pixel) in warped[..].iter_mut().enumerate() {
+            let x = (i % width as usize) as f64 + 0.5;
+            let y = (i / width as usize) as f64 + 0.5;
+            *pixel = sample(self, a * x + b * y + c, d * x + e * y + f, filter, edge);
+        }
+        Ok(warped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    use crate::patterns::{linear_gradient, GradientDirection};
+
+    fn image() -> Farbfeld {
+        let from = Pixel::new(65535_u16, 1000, 0, 65535);
+        let to = Pixel::new(3_u16, 50000, 65535, 20000);
+        linear_gradient(31, 20, from, to, GradientDirection::Diagonal)
+    }
+
+    /// Returns whether every channel of every pixel differs by at most `tolerance`.
+    fn approx_eq(farb: &Farbfeld, other: &Farbfeld, tolerance: u16) -> bool {
+        (farb.width(), farb.height()) == (other.width(), other.height())
+            && farb.pixels().iter().zip(other.pixels()).all(|(pixel, other)| pixel.distance(other) <= tolerance)
+    }
+
+    #[test]
+    fn test_affine() {
+        let transform = Affine::translate(3.0, -2.0).compose(&Affine::scale(2.0, 4.0)).compose(&Affine::shear(0.5, 0.0));
+        assert_eq!((2.0, -8.0), transform.apply(0.0, 0.0));
+        assert_eq!((6.0, 4.0), transform.apply(-1.0, 3.0));
+        let (x, y) = transform.inverse().unwrap().apply(6.0, 4.0);
+        assert!((x + 1.0).abs() < 1e-5 && (y - 3.0).abs() < 1e-5, "({}, {})", x, y);
+
+        assert_eq!(Affine::identity(), Affine::default());
+        assert_eq!(Affine::identity(), Affine::scale(2.0, 0.5).compose(&Affine::scale(0.5, 2.0)));
+        assert_eq!(Some(Affine::identity()), Affine::identity().inverse());
+        assert_eq!(None, Affine::scale(1.0, 0.0).inverse());
+        assert_eq!(None, Affine::new([1.0, 2.0, 5.0, 2.0, 4.0, 1.0]).inverse());
+    }
+
+    #[test]
+    fn test_warp_identity() {
+        for &filter in &[ResizeFilter::Nearest, ResizeFilter::Bilinear] {
+            for &edge in &[EdgeMode::Clamp, EdgeMode::Mirror, EdgeMode::Fill(Pixel::new(1_u16, 2, 3, 4))] {
+                assert_eq!(image(), image().warp_affine(*Affine::identity().matrix(), (31, 20), filter, edge).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_warp_translate() {
+        for &(dx, dy) in &[(3, 0), (-5, 2), (40, -1), (0, -19)] {
+            for &filter in &[ResizeFilter::Nearest, ResizeFilter::Bilinear] {
+                for &edge in &[EdgeMode::Clamp, EdgeMode::Wrap, EdgeMode::Fill(Pixel::new(1_u16, 2, 3, 4))] {
+                    let matrix = *Affine::translate(dx as f32, dy as f32).matrix();
+                    let warped = image().warp_affine(matrix, (25, 30), filter, edge).unwrap();
+                    for (i, pixel) in warped.pixels().iter().enumerate() {
+                        let (x, y) = ((i % 25) as i64, (i / 25) as i64);
+                        assert_eq!(&image().get_pixel_edge(x - dx, y - dy, edge), pixel, "({}, {}) by ({}, {})", x, y, dx, dy);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_warp_scale_round_trip() {
+        let up = image().warp_affine(*Affine::scale(2.0, 3.0).matrix(), (62, 60), ResizeFilter::Bilinear, EdgeMode::Clamp).unwrap();
+        let down = up.warp_affine(*Affine::scale(0.5, 1.0 / 3.0).matrix(), (31, 20), ResizeFilter::Bilinear, EdgeMode::Clamp).unwrap();
+        // The edges are clamped when upscaling, so only the inside round trips.
+        let inner = |farb: &Farbfeld| farb.crop(1, 0, 29, 20).unwrap();
+        assert!(approx_eq(&inner(&image()), &inner(&down), 2));
+
+        let sheared = image().warp_affine(*Affine::shear(0.25, 0.0).matrix(), (40, 20), ResizeFilter::Nearest, EdgeMode::Fill(Pixel::default())).unwrap();
+        assert_eq!(image().row(0).unwrap(), &sheared.row(0).unwrap()[..31]);
+        assert_eq!(&Pixel::default(), &sheared[31]);
+
+        let flat = image().warp_affine(*Affine::scale(0.0, 1.0).matrix(), (3, 3), ResizeFilter::Nearest, EdgeMode::Clamp).unwrap();
+        assert_eq!(patterns::solid(3, 3, Pixel::default()), flat);
+    }
+
+    #[test]
+    fn test_rotate_right_angles() {
+        let farb = Farbfeld::new(3, 2, (0..6_u16).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+        let values = |farb: &Farbfeld| farb.pixels().iter().map(|pixel| *pixel.red()).collect::<Vec<_>>();
+        assert_eq!(vec![3, 0, 4, 1, 5, 2], values(&farb.rotate90()));
+        assert_eq!(vec![5, 4, 3, 2, 1, 0], values(&farb.rotate180()));
+        assert_eq!(vec![2, 5, 1, 4, 0, 3], values(&farb.rotate270()));
+        assert_eq!((2, 3), (*farb.rotate270().width(), *farb.rotate270().height()));
+
+        assert_eq!(image(), image().rotate90().rotate90().rotate90().rotate90());
+        assert_eq!(image().rotate180(), image().rotate90().rotate90());
+        assert_eq!(image().rotate270(), image().rotate180().rotate90());
+        let empty = Farbfeld::new(0, 4, Vec::new()).unwrap();
+        assert_eq!((4, 0), (*empty.rotate90().width(), *empty.rotate90().height()));
+    }
+
+    #[test]
+    fn test_sample_integer_coordinates() {
+        let farb = image();
+        let fill = Pixel::new(1_u16, 2, 3, 4);
+        for edge in [EdgeMode::Clamp, EdgeMode::Wrap, EdgeMode::Mirror, EdgeMode::Fill(fill)] {
+            for y in -3..*farb.height() as i64 + 3 {
+                for x in -3..*farb.width() as i64 + 3 {
+                    let expected = farb.get_pixel_edge(x, y, edge);
+                    assert_eq!(expected, farb.sample_bilinear(x as f32, y as f32, edge), "({}, {})", x, y);
+                    assert_eq!(expected, farb.sample_nearest(x as f32, y as f32, edge), "({}, {})", x, y);
+                    assert_eq!(expected, farb.sample_nearest(x as f32 + 0.4, y as f32 - 0.5, edge), "({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_midway() {
+        let farb = image();
+        let average = |a: &Pixel, b: &Pixel| {
+            let mut channels = [0; 4];
+            for (channel, (&a, &b)) in channels.iter_mut().zip(a.iter().zip(b.iter())) {
+                *channel = (a as u32 + b as u32).div_ceil(2) as u16;
+            }
+            Pixel::from(channels)
+        };
+        for i in 0..farb.pixels().len() - 1 {
+            let (x, y) = ((i % 31) as f32, (i / 31) as f32);
+            if x < 30.0 {
+                assert_eq!(average(&farb[i], &farb[i + 1]), farb.sample_bilinear(x + 0.5, y, EdgeMode::Clamp));
+            }
+            if i + 31 < farb.pixels().len() {
+                assert_eq!(average(&farb[i], &farb[i + 31]), farb.sample_bilinear(x, y + 0.5, EdgeMode::Clamp));
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_non_finite() {
+        let farb = image();
+        for edge in [EdgeMode::Clamp, EdgeMode::Fill(Pixel::new(1_u16, 2, 3, 4))] {
+            assert_eq!(farb[0], farb.sample_bilinear(f32::NAN, f32::NAN, edge));
+            assert_eq!(farb[0], farb.sample_nearest(f32::NAN, f32::NAN, edge));
+            assert_eq!(farb.get_pixel_edge(1, 0, edge), farb.sample_bilinear(1.0, f32::NAN, edge));
+            assert_eq!(farb.get_pixel_edge(i64::MAX, 0, edge), farb.sample_bilinear(f32::INFINITY, 0.0, edge));
+            assert_eq!(farb.get_pixel_edge(0, i64::MIN, edge), farb.sample_nearest(0.0, f32::NEG_INFINITY, edge));
+        }
+    }
+
+    #[test]
+    fn test_rotate90_in_place() {
+        for size in 0..10_u32 {
+            let pixels = (0..size * size).map(|i| Pixel::new(i as u16, (i * 7) as u16, 0, 1)).collect();
+            let mut farb = Farbfeld::new(size, size, pixels).unwrap();
+            let expected = farb.rotate90();
+            farb.rotate90_in_place().unwrap();
+            assert_eq!(expected, farb, "size {}", size);
+        }
+
+        let mut farb = image();
+        match farb.rotate90_in_place() {
+            Err(Error::NotSquare(..)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        assert_eq!(image(), farb);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_rotate_matches_right_angles() {
+        let background = Pixel::new(1_u16, 2, 3, 4);
+        for &filter in &[ResizeFilter::Nearest, ResizeFilter::Bilinear] {
+            for (degrees, expected) in [
+                (0.0, image()),
+                (90.0, image().rotate90()),
+                (180.0, image().rotate180()),
+                (270.0, image().rotate270()),
+                (-90.0, image().rotate270()),
+                (450.0, image().rotate90()),
+                (-720.0, image())
+            ] {
+                assert_eq!(expected, image().rotate(degrees, filter, background).unwrap(), "{}", degrees);
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_rotate_round_trip() {
+        let background = Pixel::new(0_u16, 0, 0, 0);
+        for &degrees in &[30.0, -12.5, 135.0] {
+            let rotated = image().rotate(degrees, ResizeFilter::Bilinear, background).unwrap();
+            assert!(*rotated.width() > 31 && *rotated.height() > 20);
+            let back = rotated.rotate(-degrees, ResizeFilter::Bilinear, background).unwrap();
+            let (x, y) = ((*back.width() - 31) / 2, (*back.height() - 20) / 2);
+            // The edges are blended with the background on the way.
+            let inner = |farb: &Farbfeld| farb.crop(2, 2, 27, 16).unwrap();
+            let back = back.crop(x, y, 31, 20).unwrap();
+            assert!(approx_eq(&inner(&image()), &inner(&back), 8), "{}", degrees);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_rotate_nearest() {
+        let background = Pixel::new(9_u16, 9, 9, 9);
+        let rotated = image().rotate(60.0, ResizeFilter::Nearest, background).unwrap();
+        assert_eq!(&background, &rotated[0]);
+        assert!(rotated.pixels().iter().all(|pixel| pixel == &background || image().pixels().contains(pixel)));
+
+        let farb = Farbfeld::new(10, 10, vec![Pixel::new(1_u16, 1, 1, 65535); 100]).unwrap();
+        let rotated = farb.rotate(45.0, ResizeFilter::Nearest, background).unwrap();
+        assert_eq!((16, 16), (*rotated.width(), *rotated.height()));
+        assert_eq!(image(), image().rotate(f32::NAN, ResizeFilter::Nearest, background).unwrap());
+    }
+
+    #[test]
+    fn test_unsupported_filter() {
+        match image().warp_affine(*Affine::identity().matrix(), (31, 20), ResizeFilter::Box, EdgeMode::Clamp) {
+            Err(Error::UnsupportedFilter(ResizeFilter::Box)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+        #[cfg(feature = "std")]
+        match image().rotate(f32::NAN, ResizeFilter::Lanczos3, Pixel::default()) {
+            Err(Error::UnsupportedFilter(ResizeFilter::Lanczos3)) => {},
+            res => panic!("Unexpected result {:?}", res)
+        }
+    }
+}
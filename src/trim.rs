@@ -0,0 +1,115 @@
+//! Trimming uniform borders from Farbfeld images, such as for packing sprites.
+
+use alloc::vec::Vec;
+
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+
+impl Farbfeld {
+    /// Returns the x and y coordinates of the top left corner, the width and the height of the
+    /// smallest rectangle containing every pixel which isn't fully transparent. Returns None if
+    /// every pixel is fully transparent or the image is empty.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// let mut farb = Farbfeld::new(4, 3, vec![Pixel::default(); 12]).unwrap();
+    /// farb.draw_filled_rect(1, 1, 2, 1, Pixel::new(1_u16, 2, 3, 4));
+    ///
+    /// assert_eq!(Some((1, 1, 2, 1)), farb.content_bounds());
+    /// ```
+    pub fn content_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        self.bounds_where(|pixel| pixel.alpha() == &0)
+    }
+
+    /// Returns the bounds of the content as described by
+    /// [content_bounds](#method.content_bounds), where the border is every pixel
+    /// [similar](pixel/struct.Pixel.html#method.is_similar) to `border` within `tolerance`
+    /// instead of every fully transparent pixel.
+    pub fn content_bounds_color(&self, border: Pixel, tolerance: u16) -> Option<(u32, u32, u32, u32)> {
+        self.bounds_where(|pixel| pixel.is_similar(&border, tolerance))
+    }
+
+    /// Returns a copy of the image cropped to its [content_bounds](#method.content_bounds),
+    /// removing every outer row and column which is fully transparent. Returns a 0x0 image if
+    /// every pixel is fully transparent.
+    pub fn trim(&self) -> Farbfeld {
+        self.crop_to(self.content_bounds())
+    }
+
+    /// Returns a copy of the image cropped to its
+    /// [content_bounds_color](#method.content_bounds_color), removing every outer row and column
+    /// where every pixel is similar to `border`. Returns a 0x0 image if every pixel is similar.
+    pub fn trim_color(&self, border: Pixel, tolerance: u16) -> Farbfeld {
+        self.crop_to(self.content_bounds_color(border, tolerance))
+    }
+
+    /// Crops the image to the given bounds, or to 0x0 if there are none.
+    fn crop_to(&self, bounds: Option<(u32, u32, u32, u32)>) -> Farbfeld {
+        let (x, y, width, height) = bounds.unwrap_or((0, 0, 0, 0));
+        self.crop(x, y, width, height).expect("the bounds are within the image")
+    }
+
+    /// Finds the bounds of every pixel which isn't part of the border.
+    fn bounds_where<F: Fn(&Pixel) -> bool>(&self, is_border: F) -> Option<(u32, u32, u32, u32)> {
+        let width = *self.width() as usize;
+        let rows: Vec<&[Pixel]> = self.pixels().chunks(width.max(1)).collect();
+        let is_content = |row: &&[Pixel]| !row.iter().all(&is_border);
+        let top = rows.iter().position(is_content)?;
+        let bottom = rows.iter().rposition(is_content).expect("a row has content");
+        let rows = &rows[top..=bottom];
+        let left = (0..width).find(|&x| rows.iter().any(|row| !is_border(&row[x])))?;
+        let right = (0..width).rfind(|&x| rows.iter().any(|row| !is_border(&row[x])))?;
+        Some((left as u32, top as u32, (right - left + 1) as u32, (bottom - top + 1) as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    use crate::patterns;
+
+    #[test]
+    fn test_trim_margin() {
+        let content = patterns::checkerboard(4, 2, 1, Pixel::new(1_u16, 2, 3, 65535), Pixel::new(4_u16, 5, 6, 1));
+        let mut farb = Farbfeld::new(10, 8, vec![Pixel::new(7_u16, 8, 9, 0); 80]).unwrap();
+        for (i, pixel) in content.pixels().iter().enumerate() {
+            farb[(3 + i / 4) * 10 + 3 + i % 4] = *pixel;
+        }
+        assert_eq!(Some((3, 3, 4, 2)), farb.content_bounds());
+        assert_eq!(content, farb.trim());
+        assert_eq!(content, farb.trim().trim());
+    }
+
+    #[test]
+    fn test_trim_everything() {
+        let farb = Farbfeld::new(3, 2, vec![Pixel::new(1_u16, 2, 3, 0); 6]).unwrap();
+        assert_eq!(None, farb.content_bounds());
+        assert_eq!(Farbfeld::new(0, 0, Vec::new()).unwrap(), farb.trim());
+        assert_eq!(None, Farbfeld::new(0, 5, Vec::new()).unwrap().content_bounds());
+        assert_eq!(None, Farbfeld::new(5, 0, Vec::new()).unwrap().content_bounds());
+    }
+
+    #[test]
+    fn test_trim_nothing() {
+        let farb = patterns::checkerboard(5, 3, 1, Pixel::new(0_u16, 0, 0, 0), Pixel::new(1_u16, 1, 1, 1));
+        assert_eq!(farb, farb.trim_color(Pixel::new(0_u16, 0, 0, 0), 0));
+        let corners = patterns::checkerboard(5, 3, 1, Pixel::new(1_u16, 1, 1, 1), Pixel::new(0_u16, 0, 0, 0));
+        assert_eq!(corners, corners.trim());
+    }
+
+    #[test]
+    fn test_trim_color() {
+        let white = Pixel::new(65535_u16, 65535, 65535, 65535);
+        let mut farb = patterns::solid(6, 5, white);
+        farb[6] = Pixel::new(65000_u16, 65535, 65535, 65535);
+        farb[2 * 6 + 4] = Pixel::new(0_u16, 0, 0, 65535);
+        assert_eq!(Some((0, 1, 5, 2)), farb.content_bounds_color(white, 0));
+        assert_eq!(Some((4, 2, 1, 1)), farb.content_bounds_color(white, 1000));
+        assert_eq!(&[Pixel::new(0_u16, 0, 0, 65535)], farb.trim_color(white, 1000).pixels());
+        assert_eq!(Some((0, 0, 6, 5)), farb.content_bounds());
+        assert_eq!(patterns::solid(0, 0, white), farb.trim_color(white, 65535));
+    }
+}
@@ -0,0 +1,76 @@
+//! JavaScript bindings generated by wasm-bindgen, for decoding and encoding Farbfeld images in the
+//! browser. Requires the `wasm` feature, and only makes sense when building for
+//! `wasm32-unknown-unknown`.
+//!
+//! Functions returning an error throw a JavaScript `Error` containing ruff's error message.
+
+use js_sys::Uint16Array;
+use wasm_bindgen::prelude::*;
+
+use crate::farbfeld::Farbfeld;
+
+/// A decoded Farbfeld image, as seen from JavaScript.
+#[wasm_bindgen]
+pub struct JsFarbfeld {
+    inner: Farbfeld
+}
+
+#[wasm_bindgen]
+impl JsFarbfeld {
+    /// The width of the image.
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        *self.inner.width()
+    }
+
+    /// The height of the image.
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        *self.inner.height()
+    }
+
+    /// The pixels of the image as interleaved red, green, blue and alpha channels in row-major
+    /// order. The array is a copy, as a view of the WebAssembly memory would be invalidated as soon
+    /// as the memory grows.
+    #[wasm_bindgen(getter)]
+    pub fn pixels(&self) -> Uint16Array {
+        Uint16Array::from(&self.inner.as_raw_u16()[..])
+    }
+}
+
+/// Parses a complete Farbfeld file, throwing an error if it isn't valid.
+#[wasm_bindgen]
+pub fn decode(bytes: &[u8]) -> Result<JsFarbfeld, JsError> {
+    Ok(JsFarbfeld { inner: Farbfeld::from_bytes(bytes)? })
+}
+
+/// Encodes interleaved red, green, blue and alpha channels in row-major order as a Farbfeld file,
+/// throwing an error if the number of channels doesn't match the dimensions.
+#[wasm_bindgen]
+pub fn encode(width: u32, height: u32, pixels: &[u16]) -> Result<Vec<u8>, JsError> {
+    Ok(Farbfeld::from_raw_u16(width, height, pixels.to_vec())?.to_bytes())
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_round_trip() {
+        let pixels = [1_u16, 2, 3, 4, 65535, 0, 32768, 65535];
+        let bytes = encode(2, 1, &pixels).unwrap();
+        let decoded = decode(&bytes).unwrap();
+
+        assert_eq!(2, decoded.width());
+        assert_eq!(1, decoded.height());
+        assert_eq!(pixels.to_vec(), decoded.pixels().to_vec());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_errors() {
+        assert!(decode(b"farbfelx").is_err());
+        assert!(encode(2, 2, &[0; 4]).is_err());
+    }
+}
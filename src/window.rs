@@ -0,0 +1,234 @@
+//! Visiting the neighbourhood around every pixel of a Farbfeld image, such as for median filters
+//! and morphology.
+
+use core::iter::{ExactSizeIterator, FusedIterator};
+
+use crate::convolve::EdgeMode;
+use crate::farbfeld::Farbfeld;
+use crate::pixel::Pixel;
+
+/// The square of pixels extending `radius` pixels around a pixel of a Farbfeld image, produced
+/// by [WindowIter](struct.WindowIter.html). Pixels outside the image are sampled as described by
+/// the window's [EdgeMode](../convolve/enum.EdgeMode.html) when they're read, so creating a window
+/// doesn't copy or allocate anything.
+#[derive(Debug, Clone, Copy)]
+pub struct Window<'a> {
+    farb: &'a Farbfeld,
+    x: u32,
+    y: u32,
+    radius: u32,
+    edge: EdgeMode
+}
+
+/// An iterator over the windows around every pixel of a Farbfeld image in row-major order,
+/// created by [Farbfeld::windows](../struct.Farbfeld.html#method.windows).
+#[derive(Debug, Clone)]
+pub struct WindowIter<'a> {
+    farb: &'a Farbfeld,
+    radius: u32,
+    edge: EdgeMode,
+    next: usize
+}
+
+/// An iterator over the pixels of a window in row-major order, created by
+/// [Window::iter](struct.Window.html#method.iter).
+#[derive(Debug, Clone)]
+pub struct WindowPixels<'a> {
+    window: Window<'a>,
+    next: u64
+}
+
+impl<'a> Window<'a> {
+    /// Returns the x coordinate of the pixel at the centre of the window.
+    pub fn x(&self) -> &u32 {
+        &self.x
+    }
+
+    /// Returns the y coordinate of the pixel at the centre of the window.
+    pub fn y(&self) -> &u32 {
+        &self.y
+    }
+
+    /// Returns the number of pixels the window extends on each side of its centre.
+    pub fn radius(&self) -> &u32 {
+        &self.radius
+    }
+
+    /// Returns the length of each side of the window, `2 * radius + 1`.
+    pub fn size(&self) -> u64 {
+        2 * self.radius as u64 + 1
+    }
+
+    /// Returns the pixel `dx` pixels right of and `dy` pixels below the centre of the window,
+    /// sampled as described by the window's edge mode if it's outside the image. Offsets beyond the
+    /// radius are sampled the same way, even though they're outside the window.
+    pub fn get(&self, dx: i32, dy: i32) -> Pixel {
+        self.farb.get_pixel_edge(self.x as i64 + dx as i64, self.y as i64 + dy as i64, self.edge)
+    }
+
+    /// Returns the pixel at the centre of the window.
+    pub fn centre(&self) -> Pixel {
+        self.get(0, 0)
+    }
+
+    /// Returns an iterator over the pixels of the window in row-major order, starting from the
+    /// top left corner.
+    pub fn iter(&self) -> WindowPixels<'a> {
+        WindowPixels { window: *self, next: 0 }
+    }
+}
+
+impl<'a> IntoIterator for Window<'a> {
+    type Item = Pixel;
+    type IntoIter = WindowPixels<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> Iterator for WindowPixels<'a> {
+    type Item = Pixel;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let size = self.window.size();
+        if self.next >= size * size {
+            return None;
+        }
+        let radius = self.window.radius as i64;
+        let (dx, dy) = ((self.next % size) as i64 - radius, (self.next / size) as i64 - radius);
+        self.next += 1;
+        let window = &self.window;
+        Some(window.farb.get_pixel_edge(window.x as i64 + dx, window.y as i64 + dy, window.edge))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for WindowPixels<'a> {
+    fn len(&self) -> usize {
+        let size = self.window.size();
+        (size * size - self.next) as usize
+    }
+}
+
+impl<'a> FusedIterator for WindowPixels<'a> {}
+
+impl<'a> Iterator for WindowIter<'a> {
+    type Item = Window<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.farb.pixels().len() {
+            return None;
+        }
+        let width = *self.farb.width() as usize;
+        let window = Window {
+            farb: self.farb,
+            x: (self.next % width) as u32,
+            y: (self.next / width) as u32,
+            radius: self.radius,
+            edge: self.edge
+        };
+        self.next += 1;
+        Some(window)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for WindowIter<'a> {
+    fn len(&self) -> usize {
+        self.farb.pixels().len() - self.next
+    }
+}
+
+impl<'a> FusedIterator for WindowIter<'a> {}
+
+impl Farbfeld {
+    /// Returns an iterator over the square of pixels extending `radius` pixels around every pixel
+    /// of the image, in row-major order, with pixels outside the image sampled as described by
+    /// `edge`. Each [Window](window/struct.Window.html) borrows the image rather than copying its
+    /// pixels.
+    ///
+    /// ```
+    /// # use ::ruff::*;
+    /// # use ::ruff::convolve::EdgeMode;
+    /// let farb = Farbfeld::new(3, 1, (0..3_u16).map(|i| Pixel::new(i, i, i, i)).collect()).unwrap();
+    /// let maxima: Vec<u16> = farb.windows(1, EdgeMode::Clamp)
+    ///     .map(|window| window.iter().map(|pixel| *pixel.red()).max().unwrap())
+    ///     .collect();
+    ///
+    /// assert_eq!(vec![1, 2, 2], maxima);
+    /// ```
+    pub fn windows(&self, radius: u32, edge: EdgeMode) -> WindowIter<'_> {
+        WindowIter { farb: self, radius, edge, next: 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    use crate::patterns::{linear_gradient, GradientDirection};
+
+    fn image() -> Farbfeld {
+        let (from, to) = (Pixel::new(0_u16, 1000, 65535, 7), Pixel::new(65535_u16, 20000, 3, 60000));
+        let mut farb = linear_gradient(11, 7, from, to, GradientDirection::Diagonal);
+        farb[40] = Pixel::new(65535_u16, 65535, 65535, 65535);
+        farb
+    }
+
+    #[test]
+    fn test_box_blur() {
+        for radius in 0..4 {
+            let area = (2 * radius as u64 + 1).pow(2);
+            let pixels = image().windows(radius, EdgeMode::Clamp).map(|window| {
+                let mut sums = [0_u64; 4];
+                for pixel in window {
+                    for (sum, &value) in sums.iter_mut().zip(pixel.iter()) {
+                        *sum += value as u64;
+                    }
+                }
+                Pixel::from(sums.map(|sum| ((sum + area / 2) / area) as u16))
+            }).collect();
+            assert_eq!(image().box_blur(radius), Farbfeld::new(11, 7, pixels).unwrap(), "radius {}", radius);
+        }
+    }
+
+    #[test]
+    fn test_window() {
+        let farb = image();
+        let mut windows = farb.windows(2, EdgeMode::Mirror);
+        assert_eq!(77, windows.len());
+        let window = windows.nth(12).unwrap();
+        assert_eq!((1, 1, 2, 5), (*window.x(), *window.y(), *window.radius(), window.size()));
+        assert_eq!(64, windows.len());
+        assert_eq!(farb[12], window.centre());
+        assert_eq!(farb[11 + 2], window.get(1, 0));
+        assert_eq!(farb[3 * 11 + 1], window.get(0, 2));
+        assert_eq!(farb.get_pixel_mirrored(0, 1), window.get(-1, 0));
+        assert_eq!(farb.get_pixel_mirrored(-1, -1), window.get(-2, -2));
+
+        let mut pixels = window.iter();
+        assert_eq!(25, pixels.len());
+        assert_eq!(Some(farb.get_pixel_mirrored(-1, -1)), pixels.next());
+        assert_eq!(Some(farb.get_pixel_mirrored(0, -1)), pixels.next());
+        assert_eq!(Some(farb[3 * 11 + 3]), pixels.last());
+
+        let fill = Pixel::new(1_u16, 2, 3, 4);
+        let corner = farb.windows(1, EdgeMode::Fill(fill)).last().unwrap();
+        assert_eq!((10, 6), (*corner.x(), *corner.y()));
+        assert_eq!(5, corner.iter().filter(|pixel| pixel == &fill).count());
+
+        let empty = Farbfeld::new(0, 5, Vec::new()).unwrap();
+        assert_eq!(0, empty.windows(1, EdgeMode::Clamp).count());
+    }
+}